@@ -0,0 +1,165 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Completion of attribute names in policies
+
+use cedar_policy_validator::types::{Attributes, EntityRecordKind, Type};
+use cedar_policy_validator::ValidatorSchema;
+use lsp_types::{CompletionItem, CompletionItemKind};
+use std::collections::BTreeMap;
+
+/// Attribute names to complete at the byte offset `offset` in the policies
+/// in `text`.
+///
+/// Completion is offered after an attribute access on `principal`,
+/// `resource` or `context`, like `resource.owner.`. The policy being edited
+/// usually does not parse yet, so the access is read from the text, and
+/// `principal` (for example) may have any principal type in the schema.
+pub(crate) fn completions(
+    text: &str,
+    offset: usize,
+    schema: &ValidatorSchema,
+) -> Vec<CompletionItem> {
+    let Some((root, path)) = access_before(text.get(..offset).unwrap_or(text)) else {
+        return Vec::new();
+    };
+    let mut attributes: Vec<&Attributes> = match root {
+        "principal" => schema
+            .principals()
+            .filter_map(|ty| schema.get_entity_type(ty))
+            .map(|ty| ty.attributes())
+            .collect(),
+        "resource" => schema
+            .resources()
+            .filter_map(|ty| schema.get_entity_type(ty))
+            .map(|ty| ty.attributes())
+            .collect(),
+        "context" => schema
+            .action_ids()
+            .filter_map(|action| attributes_of(schema, action.context_type()))
+            .collect(),
+        _ => return Vec::new(),
+    };
+    for attr in path {
+        attributes = attributes
+            .into_iter()
+            .filter_map(|attrs| attrs.get_attr(attr))
+            .filter_map(|attr| attributes_of(schema, &attr.attr_type))
+            .collect();
+    }
+    // the same attribute may have a different type on different entity
+    // types or in different contexts
+    let mut types: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (name, attr) in attributes.iter().flat_map(|attrs| attrs.iter()) {
+        let ty = attr.attr_type.to_string();
+        let types = types.entry(name.as_str()).or_default();
+        if !types.contains(&ty) {
+            types.push(ty);
+        }
+    }
+    types
+        .into_iter()
+        .map(|(name, types)| CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::FIELD),
+            detail: Some(types.join(" | ")),
+            ..CompletionItem::default()
+        })
+        .collect()
+}
+
+/// The attributes of values of type `ty`, if it is a record or entity type
+fn attributes_of<'a>(schema: &'a ValidatorSchema, ty: &'a Type) -> Option<&'a Attributes> {
+    match ty {
+        Type::EntityOrRecord(EntityRecordKind::Record { attrs, .. })
+        | Type::EntityOrRecord(EntityRecordKind::ActionEntity { attrs, .. }) => Some(attrs),
+        Type::EntityOrRecord(EntityRecordKind::Entity(lub)) => lub
+            .get_single_entity()
+            .and_then(|ty| schema.get_entity_type(ty))
+            .map(|ty| ty.attributes()),
+        _ => None,
+    }
+}
+
+/// If `text` ends with an attribute access, like `resource.owner.` or
+/// `resource.owner.na`, the variable accessed and the attributes between it
+/// and the attribute being completed
+fn access_before(text: &str) -> Option<(&str, Vec<&str>)> {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    // the partial attribute name being completed
+    let mut rest = text.trim_end_matches(is_ident).strip_suffix('.')?;
+    let mut path = Vec::new();
+    loop {
+        let start = rest.trim_end_matches(is_ident);
+        let ident = rest.get(start.len()..)?;
+        if ident.is_empty() {
+            return None;
+        }
+        match start.strip_suffix('.') {
+            Some(start) => {
+                path.push(ident);
+                rest = start;
+            }
+            None => {
+                path.reverse();
+                return Some((ident, path));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schema::test::schema;
+
+    fn labels(text: &str) -> Vec<String> {
+        completions(text, text.len(), &schema())
+            .into_iter()
+            .map(|item| item.label)
+            .collect()
+    }
+
+    #[test]
+    fn accesses() {
+        assert_eq!(
+            access_before("when { principal."),
+            Some(("principal", vec![]))
+        );
+        assert_eq!(
+            access_before("when { resource.owner.addr"),
+            Some(("resource", vec!["owner"]))
+        );
+        assert_eq!(access_before("when { principal"), None);
+        assert_eq!(access_before("when { principal..a"), None);
+    }
+
+    #[test]
+    fn attribute_names() {
+        assert_eq!(labels("when { principal."), ["address", "level", "manager"]);
+        assert_eq!(
+            labels("when { resource.owner.address.c"),
+            ["city", "street"]
+        );
+        assert_eq!(
+            labels("when { resource.owner.manager."),
+            ["address", "level", "manager"]
+        );
+        assert_eq!(labels("when { context."), ["authenticated", "ip"]);
+        assert_eq!(labels("when { resource.tags."), Vec::<String>::new());
+        assert_eq!(labels("when { action."), Vec::<String>::new());
+    }
+}
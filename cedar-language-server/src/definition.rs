@@ -0,0 +1,134 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Go-to-definition from policies to the schema
+
+use crate::document::contains;
+use crate::policies::{parse_policies, policy_at};
+use crate::schema::Declaration;
+use cedar_policy_core::ast::{EntityType, EntityUID, ExprKind, Literal};
+use cedar_policy_core::parser::Loc;
+use smol_str::SmolStr;
+
+/// The schema declaration of the entity type or action named at the byte
+/// offset `offset` in the policies in `text`
+pub(crate) fn reference_at(text: &str, offset: usize) -> Option<Declaration> {
+    let policies = parse_policies(text);
+    let policy = policy_at(&policies, offset)?;
+    let mut references: Vec<(&Loc, Declaration)> = Vec::new();
+    for constraint in [
+        policy.principal_constraint().as_inner(),
+        policy.resource_constraint().as_inner(),
+    ] {
+        if let Some(uid) = constraint.get_euid() {
+            references.extend(uid.loc().map(|loc| (loc, uid_declaration(uid))));
+        }
+        references.extend(
+            constraint
+                .iter_entity_type_names()
+                .filter_map(|ty| Some((ty.loc()?, type_declaration(ty)))),
+        );
+    }
+    references.extend(
+        policy
+            .action_constraint()
+            .iter_euids()
+            .filter_map(|uid| Some((uid.loc()?, uid_declaration(uid)))),
+    );
+    for expr in policy.non_scope_constraints().subexpressions() {
+        match expr.expr_kind() {
+            ExprKind::Lit(Literal::EntityUID(uid)) => {
+                references.extend(
+                    uid.loc()
+                        .or_else(|| expr.source_loc())
+                        .map(|loc| (loc, uid_declaration(uid))),
+                );
+            }
+            ExprKind::Is { entity_type, .. } => {
+                references.extend(
+                    entity_type
+                        .loc()
+                        .map(|loc| (loc, type_declaration(entity_type))),
+                );
+            }
+            _ => (),
+        }
+    }
+    references
+        .into_iter()
+        .filter(|(loc, _)| contains(loc, offset))
+        .min_by_key(|(loc, _)| loc.span.len())
+        .map(|(_, declaration)| declaration)
+}
+
+fn uid_declaration(uid: &EntityUID) -> Declaration {
+    if uid.is_action() {
+        let eid: &SmolStr = uid.eid().as_ref();
+        Declaration::Action(uid.entity_type().to_string(), eid.clone())
+    } else {
+        type_declaration(uid.entity_type())
+    }
+}
+
+fn type_declaration(ty: &EntityType) -> Declaration {
+    Declaration::EntityType(ty.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const POLICY: &str = r#"permit(
+  principal in Photos::Group::"friends",
+  action == Photos::Action::"view",
+  resource is Photos::Photo
+)
+when { resource.owner == Photos::User::"alice" || principal is Photos::User };"#;
+
+    fn reference(needle: &str) -> Option<Declaration> {
+        reference_at(POLICY, POLICY.find(needle).unwrap() + 1)
+    }
+
+    #[test]
+    fn scope_references() {
+        assert_eq!(
+            reference("Group"),
+            Some(Declaration::EntityType("Photos::Group".into()))
+        );
+        assert_eq!(
+            reference("\"view"),
+            Some(Declaration::Action("Photos::Action".into(), "view".into()))
+        );
+        assert_eq!(
+            reference("Photos::Photo"),
+            Some(Declaration::EntityType("Photos::Photo".into()))
+        );
+        assert_eq!(reference("permit"), None);
+    }
+
+    #[test]
+    fn condition_references() {
+        assert_eq!(
+            reference("Photos::User::\"alice"),
+            Some(Declaration::EntityType("Photos::User".into()))
+        );
+        assert_eq!(
+            reference("Photos::User }"),
+            Some(Declaration::EntityType("Photos::User".into()))
+        );
+        assert_eq!(reference("owner"), None);
+    }
+}
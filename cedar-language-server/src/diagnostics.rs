@@ -0,0 +1,70 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Conversion of Cedar errors and warnings to LSP diagnostics
+
+use crate::document::Document;
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Range};
+
+/// Convert `diagnostic` to an LSP diagnostic in `doc`, located at its first
+/// label, or at the start of the document if it has none
+pub(crate) fn to_lsp(
+    doc: &Document,
+    diagnostic: &dyn miette::Diagnostic,
+    severity: DiagnosticSeverity,
+) -> Diagnostic {
+    let range = diagnostic
+        .labels()
+        .and_then(|mut labels| labels.next())
+        .map_or_else(Range::default, |label| {
+            doc.range(label.offset(), label.offset() + label.len())
+        });
+    let mut message = diagnostic.to_string();
+    if let Some(help) = diagnostic.help() {
+        message.push_str("\nhelp: ");
+        message.push_str(&help.to_string());
+    }
+    Diagnostic {
+        range,
+        severity: Some(severity),
+        code: diagnostic
+            .code()
+            .map(|code| NumberOrString::String(code.to_string())),
+        source: Some("cedar".into()),
+        message,
+        ..Diagnostic::default()
+    }
+}
+
+/// Convert `diagnostic`, and the diagnostics related to it, to LSP
+/// diagnostics in `doc`. Collections of errors, like
+/// [`cedar_policy_core::parser::err::ParseErrors`], report their first error
+/// directly and the others as related diagnostics.
+pub(crate) fn to_lsp_with_related(
+    doc: &Document,
+    diagnostic: &dyn miette::Diagnostic,
+    severity: DiagnosticSeverity,
+) -> Vec<Diagnostic> {
+    std::iter::once(to_lsp(doc, diagnostic, severity))
+        .chain(
+            diagnostic
+                .related()
+                .into_iter()
+                .flatten()
+                .map(|d| to_lsp(doc, d, severity)),
+        )
+        .collect()
+}
@@ -0,0 +1,180 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Text documents and conversion between byte offsets and LSP positions
+
+use cedar_policy_core::parser::Loc;
+use lsp_types::{Position, Range, Url};
+
+/// The kinds of file the server handles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DocumentKind {
+    /// A `.cedar` policy file
+    Policies,
+    /// A `.cedarschema` schema file
+    Schema,
+}
+
+impl DocumentKind {
+    /// The kind of the file `uri` names, if the server handles it
+    pub(crate) fn of(uri: &Url) -> Option<Self> {
+        let path = uri.path();
+        if path.ends_with(".cedarschema") {
+            Some(Self::Schema)
+        } else if path.ends_with(".cedar") {
+            Some(Self::Policies)
+        } else {
+            None
+        }
+    }
+}
+
+/// The text of a document, indexed by line.
+///
+/// Positions in the LSP count UTF-16 code units, while source locations in
+/// the Cedar ASTs are byte offsets.
+#[derive(Debug, Clone)]
+pub(crate) struct Document {
+    pub(crate) text: String,
+    pub(crate) version: i32,
+    pub(crate) kind: DocumentKind,
+    /// Byte offset at which each line starts
+    line_starts: Vec<usize>,
+}
+
+impl Document {
+    pub(crate) fn new(text: String, version: i32, kind: DocumentKind) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            text,
+            version,
+            kind,
+            line_starts,
+        }
+    }
+
+    /// The byte offset of `position`, clamped to the document, and to the end
+    /// of its line (before any `\n` or `\r\n`)
+    pub(crate) fn offset_at(&self, position: Position) -> usize {
+        let line = position.line as usize;
+        let Some(&start) = self.line_starts.get(line) else {
+            return self.text.len();
+        };
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        let text = self.text.get(start..end).unwrap_or_default();
+        let text = text
+            .strip_suffix('\n')
+            .map_or(text, |text| text.strip_suffix('\r').unwrap_or(text));
+        let mut units = 0;
+        for (i, c) in text.char_indices() {
+            if units >= position.character as usize {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        start + text.len()
+    }
+
+    /// The position of the byte offset `offset`
+    pub(crate) fn position_at(&self, offset: usize) -> Position {
+        let line = self
+            .line_starts
+            .partition_point(|&start| start <= offset)
+            .saturating_sub(1);
+        let start = self.line_starts.get(line).copied().unwrap_or_default();
+        let character = self
+            .text
+            .get(start..offset.min(self.text.len()))
+            .map_or(0, |s| s.encode_utf16().count());
+        Position::new(to_u32(line), to_u32(character))
+    }
+
+    /// The range of the bytes from `start` to `end`
+    pub(crate) fn range(&self, start: usize, end: usize) -> Range {
+        Range::new(self.position_at(start), self.position_at(end))
+    }
+
+    /// The range `loc` covers
+    pub(crate) fn loc_range(&self, loc: &Loc) -> Range {
+        self.range(loc.start(), loc.end())
+    }
+
+    /// The range of the whole document
+    pub(crate) fn full_range(&self) -> Range {
+        self.range(0, self.text.len())
+    }
+}
+
+/// Does `loc` contain the byte offset `offset`? A location contains the
+/// offset just past its end, so that a cursor placed right after a token
+/// still refers to it.
+pub(crate) fn contains(loc: &Loc, offset: usize) -> bool {
+    loc.start() <= offset && offset <= loc.end()
+}
+
+fn to_u32(n: usize) -> u32 {
+    u32::try_from(n).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let doc = Document::new(
+            "permit(\n  principal == User::\"ö𝄞\",\n);".into(),
+            0,
+            DocumentKind::Policies,
+        );
+        // `ö` is two bytes and one UTF-16 unit, `𝄞` is four bytes and two
+        // UTF-16 units
+        let quote = doc.text.rfind('"').unwrap();
+        assert_eq!(doc.position_at(quote), Position::new(1, 25));
+        assert_eq!(doc.offset_at(Position::new(1, 25)), quote);
+        assert_eq!(doc.position_at(0), Position::new(0, 0));
+        assert_eq!(doc.offset_at(Position::new(2, 0)), quote + 3);
+        assert_eq!(doc.position_at(doc.text.len()), Position::new(2, 2));
+        assert_eq!(doc.offset_at(Position::new(7, 0)), doc.text.len());
+    }
+
+    #[test]
+    fn past_end_of_line() {
+        let doc = Document::new("ab\ncd\r\nef".into(), 0, DocumentKind::Policies);
+        assert_eq!(doc.offset_at(Position::new(0, 2)), 2);
+        assert_eq!(doc.offset_at(Position::new(0, 9)), 2);
+        assert_eq!(doc.offset_at(Position::new(1, 2)), 5);
+        assert_eq!(doc.offset_at(Position::new(1, 9)), 5);
+        assert_eq!(doc.offset_at(Position::new(2, 9)), doc.text.len());
+    }
+
+    #[test]
+    fn kinds() {
+        let kind = |s| DocumentKind::of(&Url::parse(s).unwrap());
+        assert_eq!(
+            kind("file:///a/policies.cedar"),
+            Some(DocumentKind::Policies)
+        );
+        assert_eq!(kind("file:///a/b.cedarschema"), Some(DocumentKind::Schema));
+        assert_eq!(kind("file:///a/b.cedarschema.json"), None);
+    }
+}
@@ -0,0 +1,137 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Hover with the types of policy expressions
+
+use crate::document::Document;
+use crate::policies::{parse_policies, policy_at};
+use cedar_policy_core::ast::{Expr, ExprKind};
+use cedar_policy_validator::typecheck::{PolicyCheck, Typechecker};
+use cedar_policy_validator::{ValidationMode, ValidatorSchema};
+use lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind};
+
+/// The type of the innermost expression at the byte offset `offset` in the
+/// policies in `doc`.
+///
+/// A policy is typechecked once for each request environment it applies to,
+/// so an expression may have different types in different environments, for
+/// example `principal` in a policy that applies to several principal types.
+/// All of the types are shown.
+pub(crate) fn hover(doc: &Document, offset: usize, schema: &ValidatorSchema) -> Option<Hover> {
+    let policies = parse_policies(&doc.text);
+    let policy = policy_at(&policies, offset)?;
+    let policy_span = policy.loc().map(|loc| (loc.start(), loc.end()))?;
+    let typechecker = Typechecker::new(schema, ValidationMode::Strict);
+    let mut typed = Vec::new();
+    for (_, check) in typechecker.typecheck_by_request_env(policy) {
+        let expr = match check {
+            PolicyCheck::Success(expr) | PolicyCheck::Irrelevant(_, expr) => expr,
+            PolicyCheck::Fail(_) => continue,
+        };
+        typed.extend(expr.subexpressions().filter_map(|e| {
+            let span @ (start, end) = span(e, &doc.text)?;
+            // the scope and conditions of a policy are joined into an
+            // expression located at the whole policy, which is not
+            // interesting to hover over
+            if offset < start || end < offset || span == policy_span {
+                return None;
+            }
+            Some((span, e.data().as_ref()?.to_string()))
+        }));
+    }
+    let innermost = typed
+        .iter()
+        .map(|(span, _)| *span)
+        .min_by_key(|(start, end)| end - start)?;
+    let mut types = Vec::new();
+    for (span, ty) in typed {
+        if span == innermost && !types.contains(&ty) {
+            types.push(ty);
+        }
+    }
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("```cedarschema\n{}\n```", types.join(" | ")),
+        }),
+        range: Some(doc.range(innermost.0, innermost.1)),
+    })
+}
+
+/// The byte offsets at which `expr` starts and ends in `text`.
+///
+/// The parser locates every access in a chain like `resource.owner.level` at
+/// the whole chain, so the end of an attribute access is found in the text
+/// following the expression it accesses.
+fn span<T>(expr: &Expr<T>, text: &str) -> Option<(usize, usize)> {
+    let loc = expr.source_loc()?;
+    if let ExprKind::GetAttr {
+        expr: accessed,
+        attr,
+    } = expr.expr_kind()
+    {
+        if let Some((start, accessed_end)) = span(accessed, text) {
+            let access = text.get(accessed_end..loc.end()).unwrap_or_default();
+            let trimmed = access.trim_start();
+            let end = if trimmed.starts_with('.') {
+                trimmed.find(attr.as_str()).map(|i| i + attr.len())
+            } else {
+                trimmed.find(']').map(|i| i + 1)
+            };
+            if let (true, Some(end)) = (start == loc.start(), end) {
+                return Some((start, accessed_end + access.len() - trimmed.len() + end));
+            }
+        }
+    }
+    Some((loc.start(), loc.end()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::document::DocumentKind;
+    use crate::schema::test::schema;
+    use lsp_types::{Position, Range};
+
+    fn hover_at(text: &str, needle: &str) -> Option<(String, Range)> {
+        let doc = Document::new(text.into(), 0, DocumentKind::Policies);
+        let offset = text.find(needle).unwrap() + 1;
+        let hover = hover(&doc, offset, &schema())?;
+        match hover.contents {
+            HoverContents::Markup(markup) => Some((markup.value, hover.range.unwrap())),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn attribute_types() {
+        let text = r#"permit(principal, action == Photos::Action::"view", resource)
+when { resource.owner.level > 3 && context.ip.isLoopback() };"#;
+        let (value, range) = hover_at(text, "level").unwrap();
+        assert_eq!(value, "```cedarschema\nLong\n```");
+        assert_eq!(range, Range::new(Position::new(1, 7), Position::new(1, 27)));
+        let (value, _) = hover_at(text, ".owner").unwrap();
+        assert!(value.contains("Photos::User"), "{value}");
+        let (value, _) = hover_at(text, ".ip").unwrap();
+        assert!(value.contains("ipaddr"), "{value}");
+    }
+
+    #[test]
+    fn outside_expressions() {
+        let text = "permit(principal, action, resource);\n\n";
+        assert_eq!(hover_at(text, "\n\n"), None);
+    }
+}
@@ -0,0 +1,50 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Language Server Protocol server for Cedar policies and schemas.
+//!
+//! The server communicates over stdio and handles `.cedar` policy files and
+//! `.cedarschema` schema files. It publishes parse and validation
+//! diagnostics, and offers hover with the types of policy expressions,
+//! go-to-definition for entity types, actions and common types, completion
+//! of attribute names, formatting of policies and a document outline.
+//!
+//! Policies are validated against the schema file named by the `schema`
+//! initialization option, which is relative to the workspace root. Without
+//! that option, a policy file is validated against the only `.cedarschema`
+//! file in its directory, if there is exactly one.
+#![deny(
+    missing_docs,
+    rustdoc::broken_intra_doc_links,
+    rustdoc::private_intra_doc_links,
+    rustdoc::invalid_codeblock_attributes,
+    rustdoc::invalid_html_tags,
+    rustdoc::invalid_rust_codeblocks,
+    rustdoc::bare_urls,
+    clippy::doc_markdown
+)]
+
+mod completion;
+mod definition;
+mod diagnostics;
+mod document;
+mod hover;
+mod policies;
+mod schema;
+mod server;
+mod symbols;
+
+pub use server::{run, InitializationOptions};
@@ -0,0 +1,155 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Parsing and validation of policy documents
+
+use crate::diagnostics::to_lsp;
+use crate::document::{contains, Document};
+use cedar_policy_core::ast::{PolicyID, PolicySet, StaticPolicy, Template};
+use cedar_policy_core::parser::{parse_policyset, text_to_cst};
+use cedar_policy_validator::{ValidationMode, Validator, ValidatorSchema};
+use lsp_types::{Diagnostic, DiagnosticSeverity};
+
+/// Parse each policy in `text` on its own, so that a policy with errors does
+/// not hide the others. Policies with errors are omitted. Without the
+/// `tolerant-ast` feature, there is no error recovery, so a syntax error
+/// hides every policy.
+///
+/// Like the CLI, policies are identified by their `@id` annotation if they
+/// have one, and by their position (`policy0`, `policy1`, ...) otherwise.
+pub(crate) fn parse_policies(text: &str) -> Vec<Template> {
+    #[cfg(feature = "tolerant-ast")]
+    let cst = text_to_cst::parse_policies_tolerant(text);
+    #[cfg(not(feature = "tolerant-ast"))]
+    let cst = text_to_cst::parse_policies(text);
+    let Ok(cst) = cst else {
+        return Vec::new();
+    };
+    let Ok(policies) = cst.with_generated_policyids() else {
+        return Vec::new();
+    };
+    policies
+        .filter_map(|(id, policy)| policy.to_template(id).ok())
+        .map(|template| {
+            let id = template
+                .annotations()
+                .find(|(key, _)| key.as_ref() == "id")
+                .map(|(_, id)| PolicyID::from_smolstr(id.val.clone()));
+            match id {
+                Some(id) => template.new_id(id),
+                None => template,
+            }
+        })
+        .collect()
+}
+
+/// The policy in `policies` whose source contains the byte offset `offset`
+pub(crate) fn policy_at(policies: &[Template], offset: usize) -> Option<&Template> {
+    policies
+        .iter()
+        .find(|p| p.loc().is_some_and(|loc| contains(loc, offset)))
+}
+
+/// Parse errors in `doc`, and if `schema` is given, validation errors and
+/// warnings for the policies in `doc` which parse
+pub(crate) fn diagnostics(doc: &Document, schema: Option<&ValidatorSchema>) -> Vec<Diagnostic> {
+    let mut diagnostics = match parse_policyset(&doc.text) {
+        Ok(_) => Vec::new(),
+        Err(errs) => errs
+            .iter()
+            .map(|e| to_lsp(doc, e, DiagnosticSeverity::ERROR))
+            .collect(),
+    };
+    if let Some(schema) = schema {
+        let mut policies = PolicySet::new();
+        for template in parse_policies(&doc.text) {
+            // policies with duplicate ids are reported by the parser
+            let _ = match StaticPolicy::try_from(template.clone()) {
+                Ok(policy) => policies.add_static(policy),
+                Err(_) => policies.add_template(template),
+            };
+        }
+        let result = Validator::new(schema.clone()).validate(&policies, ValidationMode::Strict);
+        diagnostics.extend(
+            result
+                .validation_errors()
+                .map(|e| to_lsp(doc, e, DiagnosticSeverity::ERROR)),
+        );
+        diagnostics.extend(
+            result
+                .validation_warnings()
+                .map(|w| to_lsp(doc, w, DiagnosticSeverity::WARNING)),
+        );
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+// PANIC SAFETY: unit tests
+#[allow(clippy::indexing_slicing)]
+mod test {
+    use super::*;
+    use crate::document::DocumentKind;
+    use crate::schema::test::schema;
+    use lsp_types::{Position, Range};
+
+    #[test]
+    #[cfg(feature = "tolerant-ast")]
+    fn recovers_from_errors() {
+        let policies = parse_policies(
+            r#"
+            @id("viewers")
+            permit(principal, action == Action::"view", resource);
+            permit(principal, action, resource) when { principal. };
+            forbid(principal, action, resource);
+            "#,
+        );
+        let ids = policies
+            .iter()
+            .map(|p| p.id().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["viewers", "policy2"]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let doc = Document::new(
+            "permit(principal, action, resource)\nwhen { principal.level > };".into(),
+            0,
+            DocumentKind::Policies,
+        );
+        let diagnostics = diagnostics(&doc, None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 25), Position::new(1, 26))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    }
+
+    #[test]
+    fn validation_errors() {
+        let doc = Document::new(
+            "permit(principal, action, resource)\nwhen { principal.levl > 3 };".into(),
+            0,
+            DocumentKind::Policies,
+        );
+        let diagnostics = diagnostics(&doc, Some(&schema()));
+        assert!(!diagnostics.is_empty());
+        assert!(diagnostics.iter().all(|d| d.range.start.line == 1));
+        assert!(diagnostics[0].message.contains("levl"));
+    }
+}
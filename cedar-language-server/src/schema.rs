@@ -0,0 +1,318 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Schema documents: their diagnostics, and the declarations and type
+//! references in them
+
+use crate::diagnostics::{to_lsp, to_lsp_with_related};
+use crate::document::{contains, Document};
+use cedar_policy_core::ast::Name;
+use cedar_policy_core::extensions::Extensions;
+use cedar_policy_core::parser::Loc;
+use cedar_policy_validator::json_schema::{EntityTypeKind, Fragment, Type, TypeVariant};
+use cedar_policy_validator::{RawName, ValidatorSchema};
+use lsp_types::{Diagnostic, DiagnosticSeverity, Location, Url};
+use smol_str::SmolStr;
+
+/// Something declared in a schema, identified by its fully qualified name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Declaration {
+    EntityType(String),
+    /// An action, identified by its entity type (`Action` or `NS::Action`)
+    /// and id
+    Action(String, SmolStr),
+    CommonType(String),
+}
+
+/// A schema document, and what could be parsed from it
+#[derive(Debug)]
+pub(crate) struct SchemaDocument {
+    pub(crate) uri: Url,
+    pub(crate) doc: Document,
+    /// The schema as written, with source locations, if it parses
+    pub(crate) fragment: Option<Fragment<RawName>>,
+    /// The schema, if it parses and is well formed
+    pub(crate) schema: Option<ValidatorSchema>,
+}
+
+impl SchemaDocument {
+    pub(crate) fn new(uri: Url, doc: Document) -> Self {
+        let fragment = Fragment::from_cedarschema_str(&doc.text, Extensions::all_available())
+            .ok()
+            .map(|(fragment, _)| fragment);
+        let schema = ValidatorSchema::from_cedarschema_str(&doc.text, Extensions::all_available())
+            .ok()
+            .map(|(schema, _)| schema);
+        Self {
+            uri,
+            doc,
+            fragment,
+            schema,
+        }
+    }
+
+    /// Everything declared in the schema, with the location of its
+    /// declaration
+    pub(crate) fn declarations(&self) -> Vec<(Declaration, &Loc)> {
+        let mut declarations = Vec::new();
+        for (ns, def) in self.fragment.iter().flat_map(|f| &f.0) {
+            for (id, ty) in &def.common_types {
+                if let Some(loc) = &ty.loc {
+                    declarations.push((Declaration::CommonType(qualify(ns.as_ref(), id)), loc));
+                }
+            }
+            for (id, ty) in &def.entity_types {
+                if let Some(loc) = &ty.loc {
+                    declarations.push((Declaration::EntityType(qualify(ns.as_ref(), id)), loc));
+                }
+            }
+            for (id, action) in &def.actions {
+                if let Some(loc) = &action.loc {
+                    declarations.push((
+                        Declaration::Action(qualify(ns.as_ref(), "Action"), id.clone()),
+                        loc,
+                    ));
+                }
+            }
+        }
+        declarations
+    }
+
+    /// The location of the declaration of `declaration`
+    pub(crate) fn location_of(&self, declaration: &Declaration) -> Option<Location> {
+        self.declarations()
+            .into_iter()
+            .find(|(d, _)| d == declaration)
+            .map(|(_, loc)| Location::new(self.uri.clone(), self.doc.loc_range(loc)))
+    }
+
+    /// The declaration referred to by the type name at the byte offset
+    /// `offset` in the schema
+    pub(crate) fn reference_at(&self, offset: usize) -> Option<Declaration> {
+        let mut references = Vec::new();
+        for (ns, def) in self.fragment.iter().flat_map(|f| &f.0) {
+            let mut add = |loc: &Loc, name: &RawName, kind| {
+                if contains(loc, offset) {
+                    references.push((loc.end() - loc.start(), ns, name.clone(), kind));
+                }
+            };
+            for ty in def.common_types.values() {
+                type_references(&ty.ty, &mut add);
+            }
+            for ty in def.entity_types.values() {
+                if let EntityTypeKind::Standard(ty) = &ty.kind {
+                    for name in &ty.member_of_types {
+                        if let Some(loc) = name.loc() {
+                            add(loc, name, ReferenceKind::Entity);
+                        }
+                    }
+                    type_references(&ty.shape.0, &mut add);
+                    if let Some(tags) = &ty.tags {
+                        type_references(tags, &mut add);
+                    }
+                }
+            }
+            for action in def.actions.values() {
+                if let Some(applies_to) = &action.applies_to {
+                    for name in applies_to
+                        .principal_types
+                        .iter()
+                        .chain(&applies_to.resource_types)
+                    {
+                        if let Some(loc) = name.loc() {
+                            add(loc, name, ReferenceKind::Entity);
+                        }
+                    }
+                    type_references(&applies_to.context.0, &mut add);
+                }
+            }
+        }
+        let (_, ns, name, kind) = references.into_iter().min_by_key(|(len, ..)| *len)?;
+        self.resolve(ns.as_ref(), &name, kind)
+    }
+
+    /// Resolve the type name `name` referenced in the namespace `ns`. Common
+    /// types shadow entity types, and names in `ns` shadow names in the empty
+    /// namespace.
+    fn resolve(
+        &self,
+        ns: Option<&Name>,
+        name: &RawName,
+        kind: ReferenceKind,
+    ) -> Option<Declaration> {
+        let name = name.to_string();
+        let candidates = match ns {
+            Some(ns) if !name.contains("::") => vec![format!("{ns}::{name}"), name],
+            _ => vec![name],
+        };
+        let declarations = self.declarations();
+        candidates.into_iter().find_map(|name| {
+            let common = Declaration::CommonType(name.clone());
+            let entity = Declaration::EntityType(name);
+            let options = match kind {
+                ReferenceKind::Entity => vec![entity],
+                ReferenceKind::Common => vec![common],
+                ReferenceKind::EntityOrCommon => vec![common, entity],
+            };
+            options
+                .into_iter()
+                .find(|d| declarations.iter().any(|(declared, _)| declared == d))
+        })
+    }
+}
+
+/// Parse and validation errors and warnings for the schema in `doc`
+pub(crate) fn diagnostics(doc: &Document) -> Vec<Diagnostic> {
+    match ValidatorSchema::from_cedarschema_str(&doc.text, Extensions::all_available()) {
+        Ok((_, warnings)) => warnings
+            .map(|w| to_lsp(doc, &w, DiagnosticSeverity::WARNING))
+            .collect(),
+        Err(e) => to_lsp_with_related(doc, &e, DiagnosticSeverity::ERROR),
+    }
+}
+
+/// What a type name in a schema may refer to
+#[derive(Debug, Clone, Copy)]
+enum ReferenceKind {
+    Entity,
+    Common,
+    EntityOrCommon,
+}
+
+/// Call `f` on every type name referenced in `ty`
+fn type_references(ty: &Type<RawName>, f: &mut impl FnMut(&Loc, &RawName, ReferenceKind)) {
+    match ty {
+        Type::CommonTypeRef { type_name, loc } => {
+            if let Some(loc) = type_name.loc().or(loc.as_ref()) {
+                f(loc, type_name, ReferenceKind::Common);
+            }
+        }
+        Type::Type { ty, loc } => match ty {
+            TypeVariant::Entity { name } => {
+                if let Some(loc) = name.loc().or(loc.as_ref()) {
+                    f(loc, name, ReferenceKind::Entity);
+                }
+            }
+            TypeVariant::EntityOrCommon { type_name } => {
+                if let Some(loc) = type_name.loc().or(loc.as_ref()) {
+                    f(loc, type_name, ReferenceKind::EntityOrCommon);
+                }
+            }
+            TypeVariant::Set { element } => type_references(element, f),
+            TypeVariant::Record(record) => {
+                for attr in record.attributes.values() {
+                    type_references(&attr.ty, f);
+                }
+            }
+            _ => (),
+        },
+    }
+}
+
+/// The fully qualified name of `id` in the namespace `ns`
+fn qualify(ns: Option<&Name>, id: impl std::fmt::Display) -> String {
+    match ns {
+        Some(ns) => format!("{ns}::{id}"),
+        None => id.to_string(),
+    }
+}
+
+#[cfg(test)]
+// PANIC SAFETY: unit tests
+#[allow(clippy::indexing_slicing)]
+pub(crate) mod test {
+    use super::*;
+    use crate::document::DocumentKind;
+
+    pub(crate) const SCHEMA: &str = r#"namespace Photos {
+  type Address = { street: String, city: String };
+  entity Group;
+  entity User in [Group] { level: Long, address: Address, manager: User };
+  entity Photo { owner: User, tags: Set<String> };
+  action view appliesTo {
+    principal: [User],
+    resource: [Photo],
+    context: { ip: ipaddr, authenticated: Bool }
+  };
+}
+"#;
+
+    pub(crate) fn schema_document() -> SchemaDocument {
+        SchemaDocument::new(
+            Url::parse("file:///photos/schema.cedarschema").unwrap(),
+            Document::new(SCHEMA.into(), 0, DocumentKind::Schema),
+        )
+    }
+
+    pub(crate) fn schema() -> ValidatorSchema {
+        schema_document().schema.unwrap()
+    }
+
+    #[test]
+    fn declarations() {
+        let schema = schema_document();
+        let location = schema
+            .location_of(&Declaration::EntityType("Photos::Photo".into()))
+            .unwrap();
+        assert_eq!(location.range.start.line, 4);
+        let location = schema
+            .location_of(&Declaration::Action("Photos::Action".into(), "view".into()))
+            .unwrap();
+        assert_eq!(location.range.start.line, 5);
+        assert_eq!(
+            schema.location_of(&Declaration::EntityType("Photo".into())),
+            None
+        );
+    }
+
+    #[test]
+    fn references() {
+        let schema = schema_document();
+        let offset = |line: usize, needle: &str| {
+            let start = SCHEMA
+                .split_inclusive('\n')
+                .take(line)
+                .map(str::len)
+                .sum::<usize>();
+            start + SCHEMA.lines().nth(line).unwrap().find(needle).unwrap() + 1
+        };
+        assert_eq!(
+            schema.reference_at(offset(3, "Address")),
+            Some(Declaration::CommonType("Photos::Address".into()))
+        );
+        assert_eq!(
+            schema.reference_at(offset(3, "Group")),
+            Some(Declaration::EntityType("Photos::Group".into()))
+        );
+        assert_eq!(
+            schema.reference_at(offset(6, "User")),
+            Some(Declaration::EntityType("Photos::User".into()))
+        );
+        assert_eq!(schema.reference_at(offset(4, "Photo")), None);
+    }
+
+    #[test]
+    fn schema_errors() {
+        let doc = Document::new(
+            "entity User { manager: Usr };".into(),
+            0,
+            DocumentKind::Schema,
+        );
+        let diagnostics = diagnostics(&doc);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("Usr"));
+    }
+}
@@ -0,0 +1,379 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The server: its state, and the dispatch of LSP messages
+
+use crate::document::{Document, DocumentKind};
+use crate::schema::SchemaDocument;
+use crate::{completion, definition, hover, policies, schema, symbols};
+use cedar_policy_formatter::{policies_str_to_pretty, Config};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, LogMessage,
+    Notification as LspNotification, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, Formatting, GotoDefinition, HoverRequest,
+    Request as LspRequest,
+};
+use lsp_types::{
+    CompletionOptions, CompletionParams, CompletionResponse, DocumentFormattingParams,
+    DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverParams, HoverProviderCapability, InitializeParams, LogMessageParams, MessageType,
+    OneOf, PublishDiagnosticsParams, ServerCapabilities, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+
+/// Options a client may pass in the `initializationOptions` of its
+/// `initialize` request
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializationOptions {
+    /// The schema to validate policies against, relative to the workspace
+    /// root
+    #[serde(default)]
+    pub schema: Option<PathBuf>,
+}
+
+/// Serve the language server protocol on `connection` until the client
+/// shuts the server down
+pub fn run(connection: &Connection) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".into()]),
+            ..CompletionOptions::default()
+        }),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    let params: InitializeParams =
+        serde_json::from_value(connection.initialize(serde_json::to_value(capabilities)?)?)?;
+    let mut server = Server::new(connection, &params);
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    return Ok(());
+                }
+                let response = server.request(req);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(not) => server.notification(not)?,
+            Message::Response(_) => (),
+        }
+    }
+    Ok(())
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    /// The workspace root, if the client opened a folder
+    root: Option<PathBuf>,
+    options: InitializationOptions,
+    /// The documents open in the client, whose contents take precedence over
+    /// the files on disk
+    documents: HashMap<Url, Document>,
+}
+
+impl<'a> Server<'a> {
+    fn new(connection: &'a Connection, params: &InitializeParams) -> Self {
+        #[allow(deprecated)]
+        let root_uri = params.root_uri.as_ref();
+        let root = params
+            .workspace_folders
+            .iter()
+            .flatten()
+            .map(|folder| &folder.uri)
+            .chain(root_uri)
+            .find_map(|uri| uri.to_file_path().ok());
+        let options = params
+            .initialization_options
+            .clone()
+            .and_then(|options| serde_json::from_value(options).ok())
+            .unwrap_or_default();
+        Self {
+            connection,
+            root,
+            options,
+            documents: HashMap::new(),
+        }
+    }
+
+    fn request(&self, req: Request) -> Response {
+        match req.method.as_str() {
+            HoverRequest::METHOD => self.respond::<HoverRequest>(req, Self::hover),
+            GotoDefinition::METHOD => self.respond::<GotoDefinition>(req, Self::definition),
+            Completion::METHOD => self.respond::<Completion>(req, Self::completion),
+            Formatting::METHOD => self.respond::<Formatting>(req, Self::format),
+            DocumentSymbolRequest::METHOD => {
+                self.respond::<DocumentSymbolRequest>(req, Self::symbols)
+            }
+            _ => Response::new_err(
+                req.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request: {}", req.method),
+            ),
+        }
+    }
+
+    fn respond<R: LspRequest>(
+        &self,
+        req: Request,
+        handler: impl FnOnce(&Self, R::Params) -> R::Result,
+    ) -> Response
+    where
+        R::Params: DeserializeOwned,
+    {
+        let id: RequestId = req.id.clone();
+        match req.extract::<R::Params>(R::METHOD) {
+            Ok((id, params)) => Response::new_ok(id, handler(self, params)),
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn notification(&mut self, not: Notification) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Some(params) = self.params::<lsp_types::DidOpenTextDocumentParams>(not)? else {
+                    return Ok(());
+                };
+                let doc = params.text_document;
+                if let Some(kind) = DocumentKind::of(&doc.uri) {
+                    self.documents
+                        .insert(doc.uri.clone(), Document::new(doc.text, doc.version, kind));
+                    self.document_changed(&doc.uri)?;
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                let Some(params) = self.params::<lsp_types::DidChangeTextDocumentParams>(not)?
+                else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                // the server asks for full text synchronization, so the last
+                // change holds the whole text
+                if let (Some(doc), Some(change)) = (
+                    self.documents.get(&uri),
+                    params.content_changes.into_iter().last(),
+                ) {
+                    let doc = Document::new(change.text, params.text_document.version, doc.kind);
+                    self.documents.insert(uri.clone(), doc);
+                    self.document_changed(&uri)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let Some(params) = self.params::<lsp_types::DidCloseTextDocumentParams>(not)?
+                else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                if let Some(doc) = self.documents.remove(&uri) {
+                    self.publish(uri, Vec::new(), None)?;
+                    // policies are now validated against the schema on disk
+                    if doc.kind == DocumentKind::Schema {
+                        self.republish_policies()?;
+                    }
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Publish diagnostics for the open document `uri`, and if it is a
+    /// schema, for the open policy documents validated against it
+    fn document_changed(&self, uri: &Url) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let Some(doc) = self.documents.get(uri) else {
+            return Ok(());
+        };
+        match doc.kind {
+            DocumentKind::Policies => self.publish_policies(uri, doc),
+            DocumentKind::Schema => {
+                self.publish(uri.clone(), schema::diagnostics(doc), Some(doc.version))?;
+                self.republish_policies()
+            }
+        }
+    }
+
+    fn republish_policies(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for (uri, doc) in &self.documents {
+            if doc.kind == DocumentKind::Policies {
+                self.publish_policies(uri, doc)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn publish_policies(
+        &self,
+        uri: &Url,
+        doc: &Document,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let schema = self.schema_for(uri);
+        let schema = schema.as_ref().and_then(|s| s.schema.as_ref());
+        let diagnostics = policies::diagnostics(doc, schema);
+        self.publish(uri.clone(), diagnostics, Some(doc.version))
+    }
+
+    /// The parameters of `not`. If they are malformed, the notification is
+    /// ignored rather than stopping the server: the error is logged to the
+    /// client and `None` is returned.
+    fn params<P: DeserializeOwned>(
+        &self,
+        not: Notification,
+    ) -> Result<Option<P>, Box<dyn Error + Send + Sync>> {
+        let method = not.method.clone();
+        match not.extract::<P>(&method) {
+            Ok(params) => Ok(Some(params)),
+            Err(e) => {
+                let params = LogMessageParams {
+                    typ: MessageType::ERROR,
+                    message: format!("ignoring malformed `{method}` notification: {e}"),
+                };
+                let not = Notification::new(LogMessage::METHOD.into(), params);
+                self.connection.sender.send(Message::Notification(not))?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn publish(
+        &self,
+        uri: Url,
+        diagnostics: Vec<lsp_types::Diagnostic>,
+        version: Option<i32>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, version);
+        let not = Notification::new(PublishDiagnostics::METHOD.into(), params);
+        self.connection.sender.send(Message::Notification(not))?;
+        Ok(())
+    }
+
+    /// The schema that the policies in `uri` are validated against: the one
+    /// named in the initialization options, or else the only schema in the
+    /// same directory as the policies
+    fn schema_for(&self, uri: &Url) -> Option<SchemaDocument> {
+        let path = match (&self.options.schema, &self.root) {
+            (Some(schema), Some(root)) => root.join(schema),
+            (Some(schema), None) => schema.clone(),
+            (None, _) => {
+                let policies = uri.to_file_path().ok()?;
+                let mut schemas = std::fs::read_dir(policies.parent()?)
+                    .ok()?
+                    .filter_map(|entry| Some(entry.ok()?.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "cedarschema"));
+                let schema = schemas.next()?;
+                if schemas.next().is_some() {
+                    return None;
+                }
+                schema
+            }
+        };
+        let uri = Url::from_file_path(&path).ok()?;
+        let doc = match self.documents.get(&uri) {
+            Some(doc) => doc.clone(),
+            None => Document::new(
+                std::fs::read_to_string(&path).ok()?,
+                0,
+                DocumentKind::Schema,
+            ),
+        };
+        Some(SchemaDocument::new(uri, doc))
+    }
+
+    /// The open document at `params`, and the byte offset of its position
+    fn position(&self, params: &TextDocumentPositionParams) -> Option<(&Document, usize)> {
+        let doc = self.documents.get(&params.text_document.uri)?;
+        Some((doc, doc.offset_at(params.position)))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let params = params.text_document_position_params;
+        let (doc, offset) = self.position(&params)?;
+        if doc.kind != DocumentKind::Policies {
+            return None;
+        }
+        let schema = self.schema_for(&params.text_document.uri)?;
+        hover::hover(doc, offset, schema.schema.as_ref()?)
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let params = params.text_document_position_params;
+        let (doc, offset) = self.position(&params)?;
+        let uri = &params.text_document.uri;
+        let (schema, declaration) = match doc.kind {
+            DocumentKind::Policies => (
+                self.schema_for(uri)?,
+                definition::reference_at(&doc.text, offset)?,
+            ),
+            DocumentKind::Schema => {
+                let schema = SchemaDocument::new(uri.clone(), doc.clone());
+                let declaration = schema.reference_at(offset)?;
+                (schema, declaration)
+            }
+        };
+        schema
+            .location_of(&declaration)
+            .map(GotoDefinitionResponse::Scalar)
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let params = params.text_document_position;
+        let (doc, offset) = self.position(&params)?;
+        if doc.kind != DocumentKind::Policies {
+            return None;
+        }
+        let schema = self.schema_for(&params.text_document.uri)?;
+        let items = completion::completions(&doc.text, offset, schema.schema.as_ref()?);
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn format(&self, params: DocumentFormattingParams) -> Option<Vec<TextEdit>> {
+        let DocumentFormattingParams {
+            text_document,
+            options,
+            ..
+        } = params;
+        let doc = self.documents.get(&text_document.uri)?;
+        if doc.kind != DocumentKind::Policies {
+            return None;
+        }
+        let config = Config {
+            indent_width: options.tab_size.try_into().unwrap_or(2),
+            ..Config::default()
+        };
+        let formatted = policies_str_to_pretty(&doc.text, &config).ok()?;
+        Some(vec![TextEdit::new(doc.full_range(), formatted)])
+    }
+
+    fn symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let uri = params.text_document.uri;
+        let doc = self.documents.get(&uri)?;
+        let symbols = match doc.kind {
+            DocumentKind::Policies => symbols::policy_symbols(doc),
+            DocumentKind::Schema => symbols::schema_symbols(&SchemaDocument::new(uri, doc.clone())),
+        };
+        Some(DocumentSymbolResponse::Nested(symbols))
+    }
+}
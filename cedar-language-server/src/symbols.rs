@@ -0,0 +1,136 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Document outlines
+
+use crate::document::Document;
+use crate::policies::parse_policies;
+use crate::schema::{Declaration, SchemaDocument};
+use lsp_types::{DocumentSymbol, SymbolKind};
+
+/// The policies in `doc`, named by their ids
+pub(crate) fn policy_symbols(doc: &Document) -> Vec<DocumentSymbol> {
+    parse_policies(&doc.text)
+        .iter()
+        .filter_map(|policy| {
+            let range = doc.loc_range(policy.loc()?);
+            let detail = if policy.is_static() {
+                policy.effect().to_string()
+            } else {
+                format!("{} (template)", policy.effect())
+            };
+            Some(symbol(
+                policy.id().to_string(),
+                detail,
+                SymbolKind::OBJECT,
+                range,
+            ))
+        })
+        .collect()
+}
+
+/// The entity types, actions and common types declared in `schema`
+pub(crate) fn schema_symbols(schema: &SchemaDocument) -> Vec<DocumentSymbol> {
+    schema
+        .declarations()
+        .into_iter()
+        .map(|(declaration, loc)| {
+            let range = schema.doc.loc_range(loc);
+            match declaration {
+                Declaration::EntityType(name) => {
+                    symbol(name, "entity".into(), SymbolKind::CLASS, range)
+                }
+                Declaration::Action(ty, id) => symbol(
+                    format!("{ty}::\"{}\"", id.escape_debug()),
+                    "action".into(),
+                    SymbolKind::EVENT,
+                    range,
+                ),
+                Declaration::CommonType(name) => {
+                    symbol(name, "type".into(), SymbolKind::STRUCT, range)
+                }
+            }
+        })
+        .collect()
+}
+
+fn symbol(
+    name: String,
+    detail: String,
+    kind: SymbolKind,
+    range: lsp_types::Range,
+) -> DocumentSymbol {
+    // `deprecated` is deprecated in favour of `tags`, but has no default
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name,
+        detail: Some(detail),
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: range,
+        children: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::document::DocumentKind;
+    use crate::schema::test::schema_document;
+
+    #[test]
+    fn policies() {
+        let doc = Document::new(
+            r#"@id("viewers")
+permit(principal, action, resource);
+forbid(principal == ?principal, action, resource);"#
+                .into(),
+            0,
+            DocumentKind::Policies,
+        );
+        let symbols = policy_symbols(&doc)
+            .into_iter()
+            .map(|s| (s.name, s.detail.unwrap(), s.range.start.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            symbols,
+            [
+                ("viewers".into(), "permit".into(), 0),
+                ("policy1".into(), "forbid (template)".into(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn schema() {
+        let names = schema_symbols(&schema_document())
+            .into_iter()
+            .map(|s| s.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "Photos::Address",
+                "Photos::Group",
+                "Photos::Photo",
+                "Photos::User",
+                "Photos::Action::\"view\""
+            ]
+        );
+    }
+}
@@ -0,0 +1,216 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tests of the server over an in-memory connection
+
+// PANIC SAFETY tests
+#![allow(clippy::unwrap_used, clippy::indexing_slicing, clippy::panic)]
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized, LogMessage, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{
+    Formatting, GotoDefinition, HoverRequest, Initialize, Request as _, Shutdown,
+};
+use lsp_types::{
+    DidOpenTextDocumentParams, DocumentFormattingParams, FormattingOptions, GotoDefinitionResponse,
+    Hover, HoverContents, InitializeParams, LogMessageParams, MessageType, Position,
+    PublishDiagnosticsParams, TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
+    TextEdit, Url,
+};
+use serde_json::{json, Value};
+use std::thread::JoinHandle;
+
+const SCHEMA: &str = r#"entity User { level: Long };
+entity Photo { owner: User };
+action view appliesTo { principal: User, resource: Photo };
+"#;
+
+struct Client {
+    connection: Connection,
+    server: Option<JoinHandle<()>>,
+    next_id: i32,
+}
+
+impl Client {
+    fn start(options: Value) -> Self {
+        let (server, connection) = Connection::memory();
+        let server = std::thread::spawn(move || cedar_language_server::run(&server).unwrap());
+        let mut client = Self {
+            connection,
+            server: Some(server),
+            next_id: 0,
+        };
+        client.request(
+            Initialize::METHOD,
+            InitializeParams {
+                initialization_options: Some(options),
+                ..InitializeParams::default()
+            },
+        );
+        client.notify(Initialized::METHOD, json!({}));
+        client
+    }
+
+    fn request(&mut self, method: &str, params: impl serde::Serialize) -> Value {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let req = Request::new(id.clone(), method.into(), params);
+        self.connection.sender.send(Message::Request(req)).unwrap();
+        loop {
+            if let Message::Response(response) = self.connection.receiver.recv().unwrap() {
+                assert_eq!(response.id, id);
+                assert!(response.error.is_none(), "{:?}", response.error);
+                return response.result.unwrap();
+            }
+        }
+    }
+
+    fn notify(&self, method: &str, params: impl serde::Serialize) {
+        let not = Notification::new(method.into(), params);
+        self.connection
+            .sender
+            .send(Message::Notification(not))
+            .unwrap();
+    }
+
+    fn open(&self, uri: &Url, text: &str) -> PublishDiagnosticsParams {
+        self.notify(
+            DidOpenTextDocument::METHOD,
+            DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(uri.clone(), "cedar".into(), 1, text.into()),
+            },
+        );
+        loop {
+            if let Message::Notification(not) = self.connection.receiver.recv().unwrap() {
+                let params: PublishDiagnosticsParams =
+                    not.extract(PublishDiagnostics::METHOD).unwrap();
+                if &params.uri == uri {
+                    return params;
+                }
+            }
+        }
+    }
+
+    fn shutdown(mut self) {
+        self.request(Shutdown::METHOD, ());
+        self.notify(Exit::METHOD, ());
+        self.server.take().unwrap().join().unwrap();
+    }
+}
+
+fn position(uri: &Url, line: u32, character: u32) -> TextDocumentPositionParams {
+    TextDocumentPositionParams::new(
+        TextDocumentIdentifier::new(uri.clone()),
+        Position::new(line, character),
+    )
+}
+
+#[test]
+fn policies_with_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("photos.cedarschema"), SCHEMA).unwrap();
+    let uri = Url::from_file_path(dir.path().join("policies.cedar")).unwrap();
+    let mut client = Client::start(json!({}));
+
+    let invalid = Url::from_file_path(dir.path().join("invalid.cedar")).unwrap();
+    let diagnostics = client.open(
+        &invalid,
+        "permit(principal, action, resource)\nwhen { resource.owner.levl > 3 };",
+    );
+    assert_eq!(diagnostics.diagnostics.len(), 1);
+    assert_eq!(diagnostics.diagnostics[0].range.start.line, 1);
+
+    let diagnostics = client.open(
+        &uri,
+        "permit(principal, action, resource is Photo)\nwhen { resource.owner.level > 3 };",
+    );
+    assert_eq!(diagnostics.diagnostics, []);
+
+    let hover: Hover =
+        serde_json::from_value(client.request(HoverRequest::METHOD, position(&uri, 1, 10)))
+            .unwrap();
+    let HoverContents::Markup(contents) = hover.contents else {
+        panic!("expected markup contents");
+    };
+    assert_eq!(contents.value, "```cedarschema\nPhoto\n```");
+
+    let definition: GotoDefinitionResponse =
+        serde_json::from_value(client.request(GotoDefinition::METHOD, position(&uri, 0, 40)))
+            .unwrap();
+    let GotoDefinitionResponse::Scalar(location) = definition else {
+        panic!("expected a single location");
+    };
+    assert!(location.uri.path().ends_with("photos.cedarschema"));
+    assert_eq!(location.range.start.line, 1);
+
+    client.shutdown();
+}
+
+#[test]
+fn formatting() {
+    let uri = Url::parse("file:///policies.cedar").unwrap();
+    let mut client = Client::start(Value::Null);
+    client.open(
+        &uri,
+        "permit(principal,action,resource) when {principal.level>3};",
+    );
+    let edits: Vec<TextEdit> = serde_json::from_value(client.request(
+        Formatting::METHOD,
+        DocumentFormattingParams {
+            text_document: TextDocumentIdentifier::new(uri),
+            options: FormattingOptions {
+                tab_size: 4,
+                insert_spaces: true,
+                ..FormattingOptions::default()
+            },
+            work_done_progress_params: Default::default(),
+        },
+    ))
+    .unwrap();
+    assert_eq!(
+        edits[0].new_text,
+        "permit (principal, action, resource)\nwhen { principal.level > 3 };\n"
+    );
+    client.shutdown();
+}
+
+#[test]
+fn malformed_notifications_are_ignored() {
+    let client = Client::start(json!({}));
+    for method in [DidOpenTextDocument::METHOD, DidChangeTextDocument::METHOD] {
+        client.notify(method, json!({ "textDocument": 3 }));
+        let Message::Notification(not) = client.connection.receiver.recv().unwrap() else {
+            panic!("expected a notification");
+        };
+        let params: LogMessageParams = not.extract(LogMessage::METHOD).unwrap();
+        assert_eq!(params.typ, MessageType::ERROR);
+        assert!(
+            params
+                .message
+                .starts_with(&format!("ignoring malformed `{method}` notification")),
+            "{}",
+            params.message
+        );
+    }
+    // the server keeps running
+    let uri = Url::parse("file:///tmp/malformed/policies.cedar").unwrap();
+    let params = client.open(&uri, "permit(principal, action, resource);");
+    assert_eq!(params.diagnostics, vec![]);
+    client.shutdown();
+}
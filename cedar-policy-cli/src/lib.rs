@@ -21,20 +21,28 @@
 
 use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 use miette::{miette, IntoDiagnostic, NamedSource, Report, Result, WrapErr};
-use serde::{Deserialize, Serialize};
-use std::io::Write;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
 use std::{
     collections::HashMap,
     fmt::{self, Display},
     fs::OpenOptions,
+    net::{Ipv4Addr, TcpListener, TcpStream},
     path::{Path, PathBuf},
     process::{ExitCode, Termination},
     str::FromStr,
-    time::Instant,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, PoisonError, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
 use cedar_policy::*;
 use cedar_policy_formatter::{policies_str_to_pretty, Config};
+use cedar_testing::cedar_test_impl::RustEngine;
+use cedar_testing::mutation::run_mutation_testing;
+use cedar_testing::test_suite::{junit_xml, run_test_suite, TestSuiteReport};
 
 /// Basic Cedar CLI for evaluating authorization queries
 #[derive(Parser, Debug)]
@@ -87,8 +95,25 @@ pub enum Commands {
     Authorize(AuthorizeArgs),
     /// Evaluate a Cedar expression
     Evaluate(EvaluateArgs),
+    /// Evaluate Cedar expressions interactively, against a schema, entities
+    /// and request loaded once
+    Repl(ReplArgs),
+    /// Serve JSON endpoints for authorization and validation over HTTP on
+    /// localhost, reloading the policies, schema and entities when they change
+    /// or on SIGHUP
+    Serve(ServeArgs),
     /// Validate a policy set against a schema
     Validate(ValidateArgs),
+    /// Report shadowed, conflicting and duplicate policies in a policy set
+    Analyze(AnalyzeArgs),
+    /// Report breaking and non-breaking changes between two versions of a schema
+    SchemaDiff(SchemaDiffArgs),
+    /// Run policy test suites, reporting requests whose decision differs from
+    /// the expected one
+    Test(TestArgs),
+    /// Run policy test suites against mutated versions of their policies,
+    /// reporting mutations no test case detects
+    Mutate(MutateArgs),
     /// Check that policies, schema, and/or entities successfully parse.
     /// (All arguments are optional; this checks that whatever is provided parses)
     ///
@@ -105,10 +130,15 @@ pub enum Commands {
     /// Visualize a set of JSON entities to the graphviz format.
     /// Warning: Entity visualization is best-effort and not well tested.
     Visualize(VisualizeArgs),
+    /// Work with entities JSON files
+    Entities(EntitiesArgs),
     /// Create a Cedar project
     New(NewArgs),
     /// Partially evaluate an authorization request
     PartiallyAuthorize(PartiallyAuthorizeArgs),
+    /// List the principals or resources of a given type which are allowed to
+    /// perform an action
+    Query(QueryArgs),
     /// Print Cedar language version
     LanguageVersion,
 }
@@ -194,6 +224,82 @@ pub struct ValidateArgs {
     pub level: Option<u32>,
 }
 
+#[derive(Args, Debug)]
+pub struct AnalyzeArgs {
+    /// Schema args (incorporated by reference)
+    #[command(flatten)]
+    pub schema: SchemaArgs,
+    /// Policies args (incorporated by reference)
+    #[command(flatten)]
+    pub policies: PoliciesArgs,
+    /// Exit with a failure code if the analysis reports any warnings
+    #[arg(long)]
+    pub deny_warnings: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SchemaDiffArgs {
+    /// File containing the old version of the schema
+    #[arg(long = "old", value_name = "FILE")]
+    pub old_schema_file: PathBuf,
+    /// File containing the new version of the schema
+    #[arg(long = "new", value_name = "FILE")]
+    pub new_schema_file: PathBuf,
+    /// Format of both schemas
+    #[arg(long, value_enum, default_value_t)]
+    pub schema_format: SchemaFormat,
+    /// Policies which must keep validating under the new schema (incorporated by reference)
+    #[command(flatten)]
+    pub policies: OptionalPoliciesArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct TestArgs {
+    /// Test suite files to run
+    #[arg(required = true, value_name = "FILE")]
+    pub suites: Vec<PathBuf>,
+    /// Format of the test report
+    #[arg(long, value_enum, default_value_t)]
+    pub format: TestReportFormat,
+    /// File to write the coverage of the policies to. All suites must use
+    /// the same policies file.
+    #[arg(long, value_name = "FILE")]
+    pub coverage: Option<PathBuf>,
+    /// Format of the coverage report
+    #[arg(long, value_enum, default_value_t)]
+    pub coverage_format: CoverageFormat,
+}
+
+#[derive(Args, Debug)]
+pub struct MutateArgs {
+    /// Test suite files to run
+    #[arg(required = true, value_name = "FILE")]
+    pub suites: Vec<PathBuf>,
+    /// Fail if a suite kills less than this percentage of the mutants
+    #[arg(long, value_name = "PERCENT")]
+    pub min_score: Option<f64>,
+}
+
+/// Format of the coverage report produced by `cedar test --coverage`
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum CoverageFormat {
+    /// lcov tracefile
+    #[default]
+    Lcov,
+    /// Cobertura XML
+    Cobertura,
+}
+
+/// Format of the report produced by `cedar test`
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum TestReportFormat {
+    /// Human-readable report
+    #[default]
+    Human,
+    /// JUnit XML report
+    Junit,
+}
+
 #[derive(Args, Debug)]
 pub struct CheckParseArgs {
     /// Policies args (incorporated by reference)
@@ -599,6 +705,48 @@ pub struct AuthorizeArgs {
     /// Time authorization and report timing information
     #[arg(short, long)]
     pub timing: bool,
+    /// How policies which encounter evaluation errors affect the decision
+    #[arg(long, value_enum, default_value_t = ErrorHandlingMode::Skip)]
+    pub error_handling: ErrorHandlingMode,
+    /// Audit log args (incorporated by reference)
+    #[command(flatten)]
+    pub audit_log: AuditLogArgs,
+}
+
+/// This struct contains the arguments that together configure the audit log
+/// of authorization decisions.
+#[derive(Args, Debug, Default)]
+pub struct AuditLogArgs {
+    /// File to append a JSON record of the decision to, as one line. The file
+    /// is created if it does not exist.
+    #[arg(long = "audit-log", value_name = "FILE")]
+    pub audit_log_file: Option<PathBuf>,
+    /// Annotation of the determining policies to include in the audit log
+    /// record. May be repeated.
+    #[arg(
+        long = "audit-annotation",
+        value_name = "KEY",
+        requires = "audit_log_file"
+    )]
+    pub annotations: Vec<String>,
+}
+
+impl AuditLogArgs {
+    /// Turn this `AuditLogArgs` into the appropriate `AuditLog`, along with
+    /// its sink to check for write errors, or `None` if there is no audit log
+    fn get_audit_log(&self) -> Result<Option<(AuditLog, Arc<JsonLinesAuditSink>)>> {
+        let Some(path) = &self.audit_log_file else {
+            return Ok(None);
+        };
+        let sink = Arc::new(
+            JsonLinesAuditSink::append_to_file(path)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to open audit log {}", path.display()))?,
+        );
+        let audit_log =
+            AuditLog::new(sink.clone()).with_annotations(self.annotations.iter().cloned());
+        Ok(Some((audit_log, sink)))
+    }
 }
 
 #[cfg(feature = "partial-eval")]
@@ -622,18 +770,142 @@ pub struct PartiallyAuthorizeArgs {
     /// Time authorization and report timing information
     #[arg(short, long)]
     pub timing: bool,
+    /// How policies which encounter evaluation errors (or, once concretized,
+    /// residuals) affect the decision
+    #[arg(long, value_enum, default_value_t = ErrorHandlingMode::Skip)]
+    pub error_handling: ErrorHandlingMode,
 }
 
 #[cfg(not(feature = "partial-eval"))]
 #[derive(Debug, Args)]
 pub struct PartiallyAuthorizeArgs;
 
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// Principal whose accessible resources are listed, e.g., User::"alice"
+    #[arg(short = 'l', long, conflicts_with = "principal_type")]
+    pub principal: Option<String>,
+    /// Type of the principals to list, e.g., User
+    #[arg(long, value_name = "TYPE")]
+    pub principal_type: Option<String>,
+    /// Action for the query, e.g., Action::"view"
+    #[arg(short, long)]
+    pub action: String,
+    /// Resource whose principals are listed, e.g., File::"myfile.txt"
+    #[arg(short, long, conflicts_with = "resource_type")]
+    pub resource: Option<String>,
+    /// Type of the resources to list, e.g., File
+    #[arg(long, value_name = "TYPE")]
+    pub resource_type: Option<String>,
+    /// Policies args (incorporated by reference)
+    #[command(flatten)]
+    pub policies: PoliciesArgs,
+    /// Schema args (incorporated by reference)
+    ///
+    /// Used to populate the store with action entities and for schema-based
+    /// parsing of entity hierarchy, if present
+    #[command(flatten)]
+    pub schema: OptionalSchemaArgs,
+    /// File containing JSON representation of the Cedar entity hierarchy.
+    /// Only entities in this file are listed.
+    #[arg(long = "entities", value_name = "FILE")]
+    pub entities_file: String,
+    /// How policies which encounter evaluation errors affect the decision
+    #[arg(long, value_enum, default_value_t = ErrorHandlingMode::Skip)]
+    pub error_handling: ErrorHandlingMode,
+}
+
 #[derive(Args, Debug)]
 pub struct VisualizeArgs {
     #[arg(long = "entities", value_name = "FILE")]
     pub entities_file: String,
 }
 
+#[derive(Args, Debug)]
+pub struct EntitiesArgs {
+    #[command(subcommand)]
+    pub command: EntitiesCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EntitiesCommands {
+    /// Apply change-sets to an entities JSON file, in order, and print the
+    /// resulting entities. Each change-set is applied all together or not at
+    /// all.
+    ApplyPatch(ApplyPatchArgs),
+    /// Compare two entities JSON files, printing the entities which were
+    /// added or removed and the attributes, tags and parents which changed
+    Diff(EntitiesDiffArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ApplyPatchArgs {
+    /// File containing the entities to apply the change-sets to
+    #[arg(long = "entities", value_name = "FILE")]
+    pub entities_file: PathBuf,
+    /// Files containing the change-sets, applied in the order given
+    #[arg(required = true, value_name = "FILE")]
+    pub patch_files: Vec<PathBuf>,
+    /// Schema args (incorporated by reference)
+    #[command(flatten)]
+    pub schema: OptionalSchemaArgs,
+    /// File to write the resulting entities to, instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+    /// File to write the change-set which undoes all of the change-sets to
+    #[arg(long, value_name = "FILE")]
+    pub inverse: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct EntitiesDiffArgs {
+    /// File containing the old version of the entities
+    #[arg(long = "old", value_name = "FILE")]
+    pub old_entities_file: PathBuf,
+    /// File containing the new version of the entities
+    #[arg(long = "new", value_name = "FILE")]
+    pub new_entities_file: PathBuf,
+    /// Schema args (incorporated by reference)
+    #[command(flatten)]
+    pub schema: OptionalSchemaArgs,
+    /// Format of the report
+    #[arg(long, value_enum, default_value_t)]
+    pub format: EntitiesDiffFormat,
+}
+
+/// Format of the report produced by `cedar entities diff`
+#[derive(Debug, Default, Clone, Copy, ValueEnum)]
+pub enum EntitiesDiffFormat {
+    /// Human-readable report, one change per line
+    #[default]
+    Human,
+    /// JSON array of changes
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum ErrorHandlingMode {
+    /// Skip policies which encounter evaluation errors, as described by the
+    /// Cedar language specification
+    #[default]
+    Skip,
+    /// Deny the request if any policy encounters an evaluation error
+    DenyOnError,
+    /// Treat `forbid` policies which encounter evaluation errors as satisfied,
+    /// and skip `permit` policies which encounter evaluation errors
+    ForbidOnError,
+}
+
+impl From<ErrorHandlingMode> for ErrorHandling {
+    fn from(mode: ErrorHandlingMode) -> Self {
+        match mode {
+            ErrorHandlingMode::Skip => Self::Skip,
+            ErrorHandlingMode::DenyOnError => Self::DenyOnError,
+            ErrorHandlingMode::ForbidOnError => Self::ForbidOnError,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
 pub enum PolicyFormat {
     /// The standard Cedar policy format, documented at <https://docs.cedarpolicy.com/policies/syntax-policy.html>
@@ -767,6 +1039,54 @@ pub struct EvaluateArgs {
     pub expression: String,
 }
 
+#[derive(Args, Debug)]
+pub struct ReplArgs {
+    /// Initial request (incorporated by reference). Unlike for `evaluate`,
+    /// any of the principal, action and resource may be omitted, and set
+    /// later with the `:principal`, `:action` and `:resource` commands.
+    #[command(flatten)]
+    pub request: RequestArgs,
+    /// Schema args (incorporated by reference)
+    ///
+    /// Used to populate the store with action entities, for schema-based
+    /// parsing of entity hierarchy, and by the `:type` command
+    #[command(flatten)]
+    pub schema: OptionalSchemaArgs,
+    /// File containing JSON representation of the Cedar entity hierarchy.
+    /// This is optional; if not present, we'll just use an empty hierarchy.
+    #[arg(long = "entities", value_name = "FILE")]
+    pub entities_file: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Policies args (incorporated by reference). `--policies` is required,
+    /// since policies read from stdin could not be reloaded.
+    #[command(flatten)]
+    pub policies: PoliciesArgs,
+    /// Schema args (incorporated by reference)
+    ///
+    /// Used for schema-based parsing of the entity hierarchy and of requests,
+    /// for request validation, and by the `/validate` endpoint
+    #[command(flatten)]
+    pub schema: OptionalSchemaArgs,
+    /// File containing JSON representation of the Cedar entity hierarchy.
+    /// This is optional; if not present, we'll just use an empty hierarchy.
+    #[arg(long = "entities", value_name = "FILE")]
+    pub entities_file: Option<String>,
+    /// Port to listen on. The server only listens on localhost. Use 0 to pick
+    /// any free port.
+    #[arg(long, default_value_t = 8180)]
+    pub port: u16,
+    /// How often to check the input files for changes, in milliseconds. On
+    /// Unix, the inputs are also reloaded (within this interval) on SIGHUP.
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 1000)]
+    pub reload_interval: u64,
+    /// How long a client has to send its whole request, in milliseconds
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 10_000)]
+    pub request_timeout: u64,
+}
+
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub enum CedarExitCode {
     // The command completed successfully with a result other than a
@@ -915,6 +1235,164 @@ pub fn validate(args: &ValidateArgs) -> CedarExitCode {
     }
 }
 
+pub fn analyze(args: &AnalyzeArgs) -> CedarExitCode {
+    let pset = match args.policies.get_policy_set() {
+        Ok(pset) => pset,
+        Err(e) => {
+            println!("{e:?}");
+            return CedarExitCode::Failure;
+        }
+    };
+
+    let schema = match args.schema.get_schema() {
+        Ok(schema) => schema,
+        Err(e) => {
+            println!("{e:?}");
+            return CedarExitCode::Failure;
+        }
+    };
+
+    let warnings = pset.analyze(&schema);
+    for warning in &warnings {
+        println!("{:?}", Report::new(warning.clone()));
+    }
+    if warnings.is_empty() {
+        println!("no shadowed, conflicting or duplicate policies found");
+        CedarExitCode::Success
+    } else if args.deny_warnings {
+        CedarExitCode::ValidationFailure
+    } else {
+        CedarExitCode::Success
+    }
+}
+
+pub fn schema_diff(args: &SchemaDiffArgs) -> CedarExitCode {
+    let schemas =
+        read_schema_from_file(&args.old_schema_file, args.schema_format).and_then(|old| {
+            read_schema_from_file(&args.new_schema_file, args.schema_format).map(|new| (old, new))
+        });
+    let (old, new) = match schemas {
+        Ok(schemas) => schemas,
+        Err(e) => {
+            println!("{e:?}");
+            return CedarExitCode::Failure;
+        }
+    };
+    let report = match args.policies.get_policy_set() {
+        Ok(Some(pset)) => Schema::diff_with_policies(&old, &new, &pset),
+        Ok(None) => Schema::diff(&old, &new),
+        Err(e) => {
+            println!("{e:?}");
+            return CedarExitCode::Failure;
+        }
+    };
+
+    let mut found = false;
+    for change in report.changes() {
+        found = true;
+        let kind = if change.is_breaking() {
+            "breaking"
+        } else {
+            "non-breaking"
+        };
+        println!("{kind}: {change}");
+    }
+    for id in report.invalidated_policies() {
+        found = true;
+        println!("breaking: policy `{id}` no longer validates");
+    }
+    if !found {
+        println!("no schema changes found");
+    }
+    if report.is_breaking() {
+        CedarExitCode::ValidationFailure
+    } else {
+        CedarExitCode::Success
+    }
+}
+
+pub fn run_tests(args: &TestArgs) -> CedarExitCode {
+    let engine = if args.coverage.is_some() {
+        RustEngine::with_coverage()
+    } else {
+        RustEngine::new()
+    };
+    let mut reports = Vec::with_capacity(args.suites.len());
+    for suite in &args.suites {
+        match run_test_suite(suite, &engine) {
+            Ok(report) => reports.push(report),
+            Err(e) => {
+                println!("{:?}", Report::new(e));
+                return CedarExitCode::Failure;
+            }
+        }
+    }
+    match args.format {
+        TestReportFormat::Human => {
+            for report in &reports {
+                println!("{report}");
+            }
+        }
+        TestReportFormat::Junit => print!("{}", junit_xml(&reports)),
+    }
+    if let (Some(path), Some(coverage)) = (&args.coverage, engine.take_coverage()) {
+        let written = coverage_policies_file(&reports)
+            .map(|policies| match args.coverage_format {
+                CoverageFormat::Lcov => coverage.to_lcov(&policies),
+                CoverageFormat::Cobertura => coverage.to_cobertura(&policies),
+            })
+            .and_then(|contents| {
+                std::fs::write(path, contents)
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("failed to write coverage to {}", path.display()))
+            });
+        if let Err(e) = written {
+            println!("{e:?}");
+            return CedarExitCode::Failure;
+        }
+    }
+    if reports.iter().all(TestSuiteReport::success) {
+        CedarExitCode::Success
+    } else {
+        CedarExitCode::Failure
+    }
+}
+
+pub fn mutate(args: &MutateArgs) -> CedarExitCode {
+    let engine = RustEngine::new();
+    let mut exit_code = CedarExitCode::Success;
+    for suite in &args.suites {
+        let report = match run_mutation_testing(suite, &engine) {
+            Ok(report) => report,
+            Err(e) => {
+                println!("{:?}", Report::new(e));
+                return CedarExitCode::Failure;
+            }
+        };
+        println!("{report}");
+        let below_min_score = args
+            .min_score
+            .is_some_and(|min| report.score().is_some_and(|score| score < min));
+        if !report.baseline.success() || below_min_score {
+            exit_code = CedarExitCode::Failure;
+        }
+    }
+    exit_code
+}
+
+/// The policies file shared by the suites in `reports`, which coverage is
+/// reported for
+fn coverage_policies_file(reports: &[TestSuiteReport]) -> Result<String> {
+    match reports {
+        [first, rest @ ..] if rest.iter().all(|r| r.policies == first.policies) => {
+            Ok(first.policies.display().to_string())
+        }
+        _ => Err(miette!(
+            "coverage can only be reported for suites using the same policies file"
+        )),
+    }
+}
+
 pub fn evaluate(args: &EvaluateArgs) -> (CedarExitCode, EvalResult) {
     println!();
     let schema = match args.schema.get_schema() {
@@ -962,6 +1440,662 @@ pub fn evaluate(args: &EvaluateArgs) -> (CedarExitCode, EvalResult) {
     }
 }
 
+const REPL_HELP: &str = r#"Enter a Cedar expression to evaluate it, or one of the commands:
+  :principal [UID]   set the principal, e.g. User::"alice", or unset it
+  :action [UID]      set the action, e.g. Action::"view", or unset it
+  :resource [UID]    set the resource, e.g. Photo::"vacation.jpg", or unset it
+  :context [JSON]    set the context to a JSON object, or unset it
+  :request           show the current request
+  :partial on|off    evaluate with unset request components as unknowns
+  :type EXPRESSION   show the type of an expression, according to the schema
+  :reload            reload the schema and entities files
+  :help              show this message
+  :quit              exit"#;
+
+pub fn repl(args: &ReplArgs) -> CedarExitCode {
+    let mut session = match ReplSession::new(args) {
+        Ok(session) => session,
+        Err(e) => {
+            println!("{e:?}");
+            return CedarExitCode::Failure;
+        }
+    };
+    let interactive = std::io::stdin().is_terminal();
+    if interactive {
+        println!("Cedar {} REPL. Enter :help for help.", get_lang_version());
+    }
+    let prompt = || {
+        if interactive {
+            print!("cedar> ");
+            let _ = std::io::stdout().flush();
+        }
+    };
+    prompt();
+    for line in std::io::stdin().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                println!("{:?}", miette!("failed to read input: {e}"));
+                return CedarExitCode::Failure;
+            }
+        };
+        if !session.handle(line.trim()) {
+            break;
+        }
+        prompt();
+    }
+    CedarExitCode::Success
+}
+
+/// State of a `repl` session: the loaded files, and the request expressions
+/// are evaluated in
+struct ReplSession<'a> {
+    args: &'a ReplArgs,
+    schema: Option<Schema>,
+    entities: Entities,
+    principal: Option<EntityUid>,
+    action: Option<EntityUid>,
+    resource: Option<EntityUid>,
+    /// `None` means unset: the empty context for evaluation, and an unknown
+    /// context for partial evaluation
+    context: Option<serde_json::Value>,
+    partial: bool,
+}
+
+impl<'a> ReplSession<'a> {
+    fn new(args: &'a ReplArgs) -> Result<Self> {
+        let mut session = Self {
+            args,
+            schema: None,
+            entities: Entities::empty(),
+            principal: None,
+            action: None,
+            resource: None,
+            context: None,
+            partial: false,
+        };
+        session.load()?;
+        let request = &args.request;
+        let (principal, action, resource) = match &request.request_json_file {
+            Some(jsonfile) => {
+                let jsonstring = std::fs::read_to_string(jsonfile)
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("failed to open request-json file {jsonfile}"))?;
+                let qjson: RequestJSON = serde_json::from_str(&jsonstring)
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("failed to parse request-json file {jsonfile}"))?;
+                session.context = Some(qjson.context);
+                let non_empty = |s: String| (!s.is_empty()).then_some(s);
+                (
+                    non_empty(qjson.principal),
+                    non_empty(qjson.action),
+                    non_empty(qjson.resource),
+                )
+            }
+            None => {
+                if let Some(jsonfile) = &request.context_json_file {
+                    let jsonstring = std::fs::read_to_string(jsonfile)
+                        .into_diagnostic()
+                        .wrap_err_with(|| format!("failed to open context file {jsonfile}"))?;
+                    session.context = Some(
+                        serde_json::from_str(&jsonstring)
+                            .into_diagnostic()
+                            .wrap_err_with(|| format!("failed to parse context file {jsonfile}"))?,
+                    );
+                }
+                (
+                    request.principal.clone(),
+                    request.action.clone(),
+                    request.resource.clone(),
+                )
+            }
+        };
+        session.principal = principal.map(|s| parse_uid(&s, "principal")).transpose()?;
+        session.action = action.map(|s| parse_uid(&s, "action")).transpose()?;
+        session.resource = resource.map(|s| parse_uid(&s, "resource")).transpose()?;
+        Ok(session)
+    }
+
+    /// (Re)load the schema and entities files
+    fn load(&mut self) -> Result<()> {
+        let schema = self.args.schema.get_schema()?;
+        let entities = match &self.args.entities_file {
+            None => Entities::empty(),
+            Some(file) => load_entities(file, schema.as_ref())?,
+        };
+        self.schema = schema;
+        self.entities = entities;
+        Ok(())
+    }
+
+    /// Handle one line of input. Returns `false` if the session should end.
+    fn handle(&mut self, line: &str) -> bool {
+        let (command, arg) = match line.split_once(char::is_whitespace) {
+            Some((command, arg)) => (command, arg.trim()),
+            None => (line, ""),
+        };
+        let result = match command {
+            "" => Ok(()),
+            ":quit" | ":exit" => return false,
+            ":help" => {
+                println!("{REPL_HELP}");
+                Ok(())
+            }
+            ":principal" => Self::set_uid(&mut self.principal, arg, "principal"),
+            ":action" => Self::set_uid(&mut self.action, arg, "action"),
+            ":resource" => Self::set_uid(&mut self.resource, arg, "resource"),
+            ":context" => self.set_context(arg),
+            ":request" => {
+                self.show_request();
+                Ok(())
+            }
+            ":partial" => self.set_partial(arg),
+            ":type" => self.typecheck(arg),
+            ":reload" => self.load().map(|()| println!("reloaded")),
+            _ if command.starts_with(':') => Err(miette!(
+                "unknown command `{command}`; enter :help for the list of commands"
+            )),
+            _ => self.evaluate(line),
+        };
+        if let Err(e) = result {
+            println!("{e:?}");
+        }
+        true
+    }
+
+    fn set_uid(uid: &mut Option<EntityUid>, arg: &str, var: &str) -> Result<()> {
+        *uid = if arg.is_empty() {
+            None
+        } else {
+            Some(parse_uid(arg, var)?)
+        };
+        Ok(())
+    }
+
+    fn set_context(&mut self, arg: &str) -> Result<()> {
+        self.context = if arg.is_empty() {
+            None
+        } else {
+            let context: serde_json::Value = serde_json::from_str(arg)
+                .into_diagnostic()
+                .wrap_err("failed to parse the context as JSON")?;
+            if !context.is_object() {
+                return Err(miette!("the context must be a JSON object"));
+            }
+            Some(context)
+        };
+        Ok(())
+    }
+
+    fn show_request(&self) {
+        let show = |uid: &Option<EntityUid>| match uid {
+            Some(uid) => uid.to_string(),
+            None => "(unset)".to_string(),
+        };
+        println!("principal: {}", show(&self.principal));
+        println!("action: {}", show(&self.action));
+        println!("resource: {}", show(&self.resource));
+        match &self.context {
+            Some(context) => println!("context: {context}"),
+            None => println!("context: (unset)"),
+        }
+        println!(
+            "partial evaluation: {}",
+            if self.partial { "on" } else { "off" }
+        );
+    }
+
+    #[cfg(feature = "partial-eval")]
+    fn set_partial(&mut self, arg: &str) -> Result<()> {
+        self.partial = match arg {
+            "on" => true,
+            "off" => false,
+            _ => return Err(miette!("expected `:partial on` or `:partial off`")),
+        };
+        Ok(())
+    }
+
+    #[cfg(not(feature = "partial-eval"))]
+    fn set_partial(&self, _: &str) -> Result<()> {
+        Err(miette!("partial evaluation is experimental, but this executable was not built with `partial-eval` experimental feature enabled"))
+    }
+
+    /// The context, parsed using the schema if there is one
+    fn context(&self) -> Result<Context> {
+        match &self.context {
+            None => Ok(Context::empty()),
+            Some(context) => Context::from_json_value(
+                context.clone(),
+                self.schema.as_ref().zip(self.action.as_ref()),
+            )
+            .wrap_err("failed to create the context"),
+        }
+    }
+
+    /// Schema to validate the request against, if any
+    fn request_schema(&self) -> Option<&Schema> {
+        self.schema
+            .as_ref()
+            .filter(|_| self.args.request.request_validation)
+    }
+
+    fn request(&self) -> Result<Request> {
+        match (&self.principal, &self.action, &self.resource) {
+            (Some(principal), Some(action), Some(resource)) => Request::new(
+                principal.clone(),
+                action.clone(),
+                resource.clone(),
+                self.context()?,
+                self.request_schema(),
+            )
+            .map_err(|e| miette!("{e}")),
+            _ => Err(miette!(
+                help = "set them with `:principal`, `:action` and `:resource`, or evaluate with unknowns using `:partial on`",
+                "All three (`principal`, `action`, `resource`) variables must be set to evaluate expressions"
+            )),
+        }
+    }
+
+    #[cfg(feature = "partial-eval")]
+    fn partial_request(&self) -> Result<Request> {
+        let mut builder = RequestBuilder::default();
+        if let Some(principal) = &self.principal {
+            builder = builder.principal(principal.clone());
+        }
+        if let Some(action) = &self.action {
+            builder = builder.action(action.clone());
+        }
+        if let Some(resource) = &self.resource {
+            builder = builder.resource(resource.clone());
+        }
+        if self.context.is_some() {
+            builder = builder.context(self.context()?);
+        }
+        match self.request_schema() {
+            Some(schema) => builder.schema(schema).build().map_err(|e| miette!("{e}")),
+            None => Ok(builder.build()),
+        }
+    }
+
+    fn evaluate(&self, src: &str) -> Result<()> {
+        let expr = Expression::from_str(src)
+            .map_err(|e| Report::new(e).with_source_code(src.to_string()))
+            .wrap_err("failed to parse the expression")?;
+        #[cfg(feature = "partial-eval")]
+        if self.partial {
+            match partial_eval_expression(&self.partial_request()?, &self.entities, &expr)
+                .wrap_err("failed to evaluate the expression")?
+            {
+                PartialEvalResult::Value(result) => println!("{result}"),
+                PartialEvalResult::Residual(residual) => println!("{residual}"),
+            }
+            return Ok(());
+        }
+        let result = eval_expression(&self.request()?, &self.entities, &expr)
+            .wrap_err("failed to evaluate the expression")?;
+        println!("{result}");
+        Ok(())
+    }
+
+    /// Show the type of `src` in the request environments matching the
+    /// principal, action and resource which are set
+    fn typecheck(&self, src: &str) -> Result<()> {
+        let Some(schema) = &self.schema else {
+            return Err(miette!("`:type` requires a schema"));
+        };
+        let expr = Expression::from_str(src)
+            .map_err(|e| Report::new(e).with_source_code(src.to_string()))
+            .wrap_err("failed to parse the expression")?;
+        let validator = Validator::new(schema.clone());
+        let types: Vec<_> = validator
+            .typecheck_expression(&expr, cedar_policy::ValidationMode::Strict)
+            .into_iter()
+            .filter(|(env, _)| {
+                self.principal
+                    .as_ref()
+                    .map_or(true, |p| p.type_name() == env.principal())
+                    && self.action.as_ref().map_or(true, |a| a == env.action())
+                    && self
+                        .resource
+                        .as_ref()
+                        .map_or(true, |r| r.type_name() == env.resource())
+            })
+            .collect();
+        if types.is_empty() {
+            return Err(miette!(
+                "no request in the schema has the current principal, action and resource types"
+            ));
+        }
+        let rendered: Vec<(String, String)> = types
+            .into_iter()
+            .map(|(env, ty)| {
+                let env = format!("{}, {}, {}", env.principal(), env.action(), env.resource());
+                let ty = match ty {
+                    Ok(ty) => ty.to_string(),
+                    Err(errs) => errs
+                        .into_iter()
+                        .map(|err| {
+                            format!("{:?}", Report::new(err).with_source_code(src.to_string()))
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                };
+                (env, ty)
+            })
+            .collect();
+        // if the expression has the same type (or errors) in every request,
+        // show it once
+        if let Some((_, first)) = rendered.first() {
+            if rendered.iter().all(|(_, ty)| ty == first) {
+                println!("{first}");
+                return Ok(());
+            }
+        }
+        for (env, ty) in rendered {
+            if ty.contains('\n') {
+                println!("{env}:\n{ty}");
+            } else {
+                println!("{env}: {ty}");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_uid(s: &str, var: &str) -> Result<EntityUid> {
+    s.parse()
+        .wrap_err_with(|| format!("failed to parse {var} {s} as entity Uid"))
+}
+
+/// Largest request body `serve` accepts, in bytes
+const MAX_REQUEST_BODY: usize = 16 * 1024 * 1024;
+/// Largest request line and headers `serve` accepts, in bytes
+const MAX_REQUEST_HEAD: u64 = 64 * 1024;
+/// Number of connections `serve` handles at once. Further connections wait to
+/// be accepted.
+const MAX_CONNECTIONS: usize = 16;
+
+/// Serve JSON authorization and validation endpoints over HTTP on localhost.
+/// Runs until the process is killed.
+pub fn serve(args: &ServeArgs) -> CedarExitCode {
+    match serve_inner(args) {
+        Ok(()) => CedarExitCode::Success,
+        Err(e) => {
+            println!("{e:?}");
+            CedarExitCode::Failure
+        }
+    }
+}
+
+impl ServeArgs {
+    /// Parse the policies, entities and schema
+    fn load(&self) -> Result<ffi::PreparsedInputs> {
+        let schema = self.schema.get_schema()?;
+        let policies = self.policies.get_policy_set()?;
+        let entities = match &self.entities_file {
+            Some(entities_file) => load_entities(entities_file, schema.as_ref())?,
+            None => Entities::from_entities([], schema.as_ref())
+                .wrap_err("failed to add action entities from the schema")?,
+        };
+        Ok(ffi::PreparsedInputs::new(policies, entities, schema))
+    }
+
+    /// When each of the input files was last modified, to detect changes.
+    /// Files which can't be read are `None`, so they are reloaded (and
+    /// reported) once they can be.
+    fn modified_times(&self) -> Vec<Option<std::time::SystemTime>> {
+        let files: [Option<&Path>; 4] = [
+            self.policies.policies_file.as_ref().map(Path::new),
+            self.policies.template_linked_file.as_ref().map(Path::new),
+            self.schema.schema_file.as_deref(),
+            self.entities_file.as_ref().map(Path::new),
+        ];
+        files
+            .into_iter()
+            .flatten()
+            .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+fn serve_inner(args: &ServeArgs) -> Result<()> {
+    if args.policies.policies_file.is_none() {
+        return Err(miette!(
+            "`serve` requires `--policies`, since policies read from stdin could not be reloaded"
+        ));
+    }
+    let mut modified = args.modified_times();
+    let inputs = RwLock::new(Arc::new(args.load()?));
+    let hangup = Arc::new(AtomicBool::new(false));
+    #[cfg(unix)]
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&hangup))
+        .into_diagnostic()
+        .wrap_err("failed to handle SIGHUP")?;
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, args.port))
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to listen on port {}", args.port))?;
+    let addr = listener.local_addr().into_diagnostic()?;
+    println!("listening on http://{addr}");
+    let (sender, receiver) = mpsc::sync_channel::<TcpStream>(MAX_CONNECTIONS);
+    let receiver = Mutex::new(receiver);
+    std::thread::scope(|scope| {
+        // Reload when the input files change, or on SIGHUP
+        scope.spawn(|| loop {
+            std::thread::sleep(Duration::from_millis(args.reload_interval));
+            let now = args.modified_times();
+            if !hangup.swap(false, Ordering::Relaxed) && now == modified {
+                continue;
+            }
+            modified = now;
+            match args.load() {
+                Ok(reloaded) => {
+                    *inputs.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(reloaded);
+                    println!("reloaded policies, schema and entities");
+                }
+                Err(e) => println!(
+                    "{:?}",
+                    e.wrap_err("failed to reload; keeping the old inputs")
+                ),
+            }
+        });
+        // A fixed pool of workers answers the connections, so a burst of
+        // clients can't start an unbounded number of threads
+        for _ in 0..MAX_CONNECTIONS {
+            scope.spawn(|| loop {
+                let next = receiver
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .recv();
+                let Ok(stream) = next else {
+                    break;
+                };
+                let inputs = Arc::clone(&inputs.read().unwrap_or_else(PoisonError::into_inner));
+                handle_connection(
+                    &stream,
+                    &inputs,
+                    Duration::from_millis(args.request_timeout),
+                );
+            });
+        }
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if sender.send(stream).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => println!("{:?}", miette!("failed to accept connection: {e}")),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Why an HTTP request could not be read
+#[derive(Debug)]
+enum ReadRequestError {
+    /// The request body is larger than [`MAX_REQUEST_BODY`]
+    TooLarge(usize),
+    /// The request is malformed, or reading it failed or timed out
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for ReadRequestError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Answer one HTTP request on `stream`, which the client must send within
+/// `timeout`. We don't support keep-alive, so the connection is closed
+/// afterwards.
+fn handle_connection(stream: &TcpStream, inputs: &ffi::PreparsedInputs, timeout: Duration) {
+    let (status, body) = match read_http_request(stream, timeout) {
+        Ok((method, path, body)) if method == "POST" => route(&path, &body, inputs),
+        Ok(_) => (
+            "405 Method Not Allowed",
+            error_json("only POST requests are supported"),
+        ),
+        Err(ReadRequestError::TooLarge(length)) => (
+            "413 Content Too Large",
+            error_json(&format!(
+                "request body of {length} bytes is larger than the limit of {MAX_REQUEST_BODY} bytes"
+            )),
+        ),
+        Err(ReadRequestError::Io(e)) => ("400 Bad Request", error_json(&e.to_string())),
+    };
+    let mut stream = stream;
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+}
+
+/// Reads from a `TcpStream` until a deadline, after which reads fail. The
+/// stream's read timeout only applies to each read, so on its own it doesn't
+/// stop a client which sends its request a byte at a time.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "the request was not received in time",
+            ));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+/// Read the method, path and body of an HTTP/1.1 request, giving up if the
+/// client takes longer than `timeout` to send all of it
+fn read_http_request(
+    stream: &TcpStream,
+    timeout: Duration,
+) -> std::result::Result<(String, String, String), ReadRequestError> {
+    stream.set_write_timeout(Some(timeout))?;
+    let malformed = |message| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    let stream = DeadlineReader {
+        stream,
+        deadline: Instant::now() + timeout,
+    };
+    // the request line and headers are read through a limit, so a client
+    // can't make us buffer an endless line
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_HEAD));
+    let mut line = String::new();
+    let mut read_line = |line: &mut String| -> std::io::Result<()> {
+        line.clear();
+        reader.read_line(line)?;
+        if line.ends_with('\n') {
+            Ok(())
+        } else {
+            Err(malformed("incomplete or too large request headers"))
+        }
+    };
+    read_line(&mut line)?;
+    let mut request_line = line.split_whitespace();
+    let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else {
+        return Err(malformed("malformed request line").into());
+    };
+    let (method, path) = (method.to_owned(), path.to_owned());
+    let mut content_length = 0;
+    loop {
+        read_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| malformed("invalid Content-Length"))?;
+            }
+        }
+    }
+    if content_length > MAX_REQUEST_BODY {
+        return Err(ReadRequestError::TooLarge(content_length));
+    }
+    reader.get_mut().set_limit(content_length as u64);
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8(body)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Ok((method, path, body))
+}
+
+/// Answer a POST request to `path`, returning the HTTP status and JSON body
+fn route(path: &str, body: &str, inputs: &ffi::PreparsedInputs) -> (&'static str, String) {
+    match path {
+        "/authorize" => answer_call(body, |call| inputs.is_authorized(call)),
+        "/batch" => answer_call(body, |call| inputs.is_authorized_batch(call)),
+        #[cfg(feature = "partial-eval")]
+        "/authorize/partial" => answer_call(body, |call| inputs.is_authorized_partial(call)),
+        #[cfg(not(feature = "partial-eval"))]
+        "/authorize/partial" => (
+            "501 Not Implemented",
+            error_json("partial authorization is experimental, but this executable was not built with `partial-eval` experimental feature enabled"),
+        ),
+        // The validation settings are optional
+        "/validate" if body.trim().is_empty() => {
+            json_response(&inputs.validate(&ffi::ValidationSettings::default()))
+        }
+        "/validate" => answer_call(body, |settings| inputs.validate(&settings)),
+        _ => ("404 Not Found", error_json(&format!("no endpoint `{path}`"))),
+    }
+}
+
+/// Deserialize `body` as an FFI call, and serialize the answer `f` gives
+fn answer_call<C: DeserializeOwned, A: Serialize>(
+    body: &str,
+    f: impl FnOnce(C) -> A,
+) -> (&'static str, String) {
+    match serde_json::from_str(body) {
+        Ok(call) => json_response(&f(call)),
+        Err(e) => ("400 Bad Request", error_json(&e.to_string())),
+    }
+}
+
+fn json_response(answer: &impl Serialize) -> (&'static str, String) {
+    match serde_json::to_string(answer) {
+        Ok(answer) => ("200 OK", answer),
+        Err(e) => ("500 Internal Server Error", error_json(&e.to_string())),
+    }
+}
+
+fn error_json(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
 pub fn link(args: &LinkArgs) -> CedarExitCode {
     if let Err(err) = link_inner(args) {
         println!("{err:?}");
@@ -984,6 +2118,108 @@ pub fn visualize(args: &VisualizeArgs) -> CedarExitCode {
     }
 }
 
+pub fn entities(args: &EntitiesArgs) -> CedarExitCode {
+    let result = match &args.command {
+        EntitiesCommands::ApplyPatch(args) => apply_patch(args),
+        EntitiesCommands::Diff(args) => entities_diff(args),
+    };
+    match result {
+        Ok(()) => CedarExitCode::Success,
+        Err(e) => {
+            println!("{e:?}");
+            CedarExitCode::Failure
+        }
+    }
+}
+
+fn apply_patch(args: &ApplyPatchArgs) -> Result<()> {
+    let schema = args.schema.get_schema()?;
+    // Keep only the parents of each entity, so that the entities written out
+    // don't gain their indirect ancestors as parents. Starting from an empty
+    // store also keeps the schema's actions out of the output.
+    let mut entities = Entities::empty()
+        .with_ancestor_mode(AncestorMode::OnDemand)?
+        .add_entities_from_json_str(
+            &read_from_file(&args.entities_file, "entities")?,
+            schema.as_ref(),
+        )
+        .wrap_err_with(|| {
+            format!(
+                "failed to parse entities from file {}",
+                args.entities_file.display()
+            )
+        })?;
+    let mut inverses = Vec::new();
+    for patch_file in &args.patch_files {
+        let patch =
+            EntityPatch::from_json_str(&read_from_file(patch_file, "change-set")?, schema.as_ref())
+                .wrap_err_with(|| {
+                    format!(
+                        "failed to parse change-set from file {}",
+                        patch_file.display()
+                    )
+                })?;
+        let inverse = entities
+            .apply_patch(patch, schema.as_ref())
+            .wrap_err_with(|| {
+                format!(
+                    "failed to apply change-set from file {}",
+                    patch_file.display()
+                )
+            })?;
+        inverses.push(inverse);
+    }
+
+    if let Some(path) = &args.inverse {
+        // undo the last change-set first
+        let mut ops = Vec::new();
+        for inverse in inverses.iter().rev() {
+            if let serde_json::Value::Array(inverse_ops) = inverse.to_json_value()? {
+                ops.extend(inverse_ops);
+            }
+        }
+        let contents = serde_json::to_string_pretty(&ops).into_diagnostic()?;
+        std::fs::write(path, contents)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to write change-set to {}", path.display()))?;
+    }
+    match &args.output {
+        Some(path) => {
+            let f = std::fs::File::create(path)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to write entities to {}", path.display()))?;
+            entities.write_to_json(f)?;
+        }
+        None => {
+            entities.write_to_json(std::io::stdout())?;
+            println!();
+        }
+    }
+    Ok(())
+}
+
+fn entities_diff(args: &EntitiesDiffArgs) -> Result<()> {
+    let schema = args.schema.get_schema()?;
+    let old = load_entities(&args.old_entities_file, schema.as_ref())?;
+    let new = load_entities(&args.new_entities_file, schema.as_ref())?;
+    let diff = Entities::diff(&old, &new);
+    match args.format {
+        EntitiesDiffFormat::Human => {
+            if diff.is_empty() {
+                println!("no entity changes found");
+            }
+            for change in diff.changes() {
+                println!("{change}");
+            }
+        }
+        EntitiesDiffFormat::Json => {
+            diff.write_to_json(std::io::stdout())?;
+            println!();
+        }
+    }
+    Ok(())
+}
+
 /// Format the policies in the given file or stdin.
 ///
 /// Returns a boolean indicating whether the formatted policies are the same as the original
@@ -1365,6 +2601,8 @@ pub fn authorize(args: &AuthorizeArgs) -> CedarExitCode {
         &args.entities_file,
         &args.schema,
         args.timing,
+        args.error_handling.into(),
+        &args.audit_log,
     );
     match ans {
         Ok(ans) => {
@@ -1424,6 +2662,7 @@ pub fn partial_authorize(args: &PartiallyAuthorizeArgs) -> CedarExitCode {
         &args.entities_file,
         &args.schema,
         args.timing,
+        args.error_handling.into(),
     );
     match ans {
         Ok(ans) => match ans.decision() {
@@ -1453,6 +2692,71 @@ pub fn partial_authorize(args: &PartiallyAuthorizeArgs) -> CedarExitCode {
     }
 }
 
+#[cfg(not(feature = "partial-eval"))]
+pub fn query(_: &QueryArgs) -> CedarExitCode {
+    {
+        eprintln!("Error: option `query` is experimental, but this executable was not built with `partial-eval` experimental feature enabled");
+        return CedarExitCode::Failure;
+    }
+}
+
+#[cfg(feature = "partial-eval")]
+pub fn query(args: &QueryArgs) -> CedarExitCode {
+    match execute_query(args) {
+        Ok(uids) => {
+            for uid in uids {
+                println!("{uid}");
+            }
+            CedarExitCode::Success
+        }
+        Err(err) => {
+            println!("{err:?}");
+            CedarExitCode::Failure
+        }
+    }
+}
+
+#[cfg(feature = "partial-eval")]
+fn execute_query(args: &QueryArgs) -> Result<Vec<EntityUid>> {
+    let parse_uid = |s: &str, var: &str| -> Result<EntityUid> {
+        s.parse()
+            .wrap_err_with(|| format!("failed to parse {var} {s} as entity Uid"))
+    };
+    let parse_type = |s: &str, var: &str| -> Result<EntityTypeName> {
+        s.parse()
+            .wrap_err_with(|| format!("failed to parse {var} type {s} as entity type"))
+    };
+    let policies = args.policies.get_policy_set()?;
+    let schema = args.schema.get_schema()?;
+    let entities = load_entities(&args.entities_file, schema.as_ref())?;
+    let action = parse_uid(&args.action, "action")?;
+    let authorizer = Authorizer::new().with_error_handling(args.error_handling.into());
+    match (
+        &args.principal,
+        &args.principal_type,
+        &args.resource,
+        &args.resource_type,
+    ) {
+        (Some(principal), None, None, Some(resource_type)) => Ok(authorizer.query_resources(
+            parse_uid(principal, "principal")?,
+            action,
+            parse_type(resource_type, "resource")?,
+            &policies,
+            &entities,
+        )),
+        (None, Some(principal_type), Some(resource), None) => Ok(authorizer.query_principals(
+            parse_type(principal_type, "principal")?,
+            action,
+            parse_uid(resource, "resource")?,
+            &policies,
+            &entities,
+        )),
+        _ => Err(miette!(
+            "specify either `--principal` and `--resource-type`, or `--principal-type` and `--resource`"
+        )),
+    }
+}
+
 /// Load an `Entities` object from the given JSON filename and optional schema.
 fn load_entities(entities_filename: impl AsRef<Path>, schema: Option<&Schema>) -> Result<Entities> {
     match std::fs::OpenOptions::new()
@@ -1616,6 +2920,8 @@ fn execute_request(
     entities_filename: impl AsRef<Path>,
     schema: &OptionalSchemaArgs,
     compute_duration: bool,
+    error_handling: ErrorHandling,
+    audit_log: &AuditLogArgs,
 ) -> Result<Response, Vec<Report>> {
     let mut errs = vec![];
     let policies = match policies.get_policy_set() {
@@ -1639,11 +2945,23 @@ fn execute_request(
             Entities::empty()
         }
     };
+    let audit_log = match audit_log.get_audit_log() {
+        Ok(audit_log) => audit_log,
+        Err(e) => {
+            errs.push(e);
+            None
+        }
+    };
     match request.get_request(schema.as_ref()) {
         Ok(request) if errs.is_empty() => {
-            let authorizer = Authorizer::new();
+            let authorizer = Authorizer::new().with_error_handling(error_handling);
             let auth_start = Instant::now();
-            let ans = authorizer.is_authorized(&request, &policies, &entities);
+            let ans = match &audit_log {
+                Some((audit_log, _)) => authorizer
+                    .with_audit_log(audit_log.clone())
+                    .is_authorized(&request, &policies, &entities),
+                None => authorizer.is_authorized(&request, &policies, &entities),
+            };
             let auth_dur = auth_start.elapsed();
             if compute_duration {
                 println!(
@@ -1651,6 +2969,11 @@ fn execute_request(
                     auth_dur.as_micros()
                 );
             }
+            if let Some(e) = audit_log.and_then(|(_, sink)| sink.take_error()) {
+                return Err(vec![miette!(
+                    "failed to write the decision to the audit log: {e}"
+                )]);
+            }
             Ok(ans)
         }
         Ok(_) => Err(errs),
@@ -1668,6 +2991,7 @@ fn execute_partial_request(
     entities_filename: impl AsRef<Path>,
     schema: &OptionalSchemaArgs,
     compute_duration: bool,
+    error_handling: ErrorHandling,
 ) -> Result<PartialResponse, Vec<Report>> {
     let mut errs = vec![];
     let policies = match policies.get_policy_set() {
@@ -1693,7 +3017,7 @@ fn execute_partial_request(
     };
     match request.get_request(schema.as_ref()) {
         Ok(request) if errs.is_empty() => {
-            let authorizer = Authorizer::new();
+            let authorizer = Authorizer::new().with_error_handling(error_handling);
             let auth_start = Instant::now();
             let ans = authorizer.is_authorized_partial(&request, &policies, &entities);
             let auth_dur = auth_start.elapsed();
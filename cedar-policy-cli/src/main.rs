@@ -20,9 +20,9 @@ use clap::Parser;
 use miette::ErrorHook;
 
 use cedar_policy_cli::{
-    authorize, check_parse, evaluate, format_policies, language_version, link, new,
-    partial_authorize, translate_policy, translate_schema, validate, visualize, CedarExitCode, Cli,
-    Commands, ErrorFormat,
+    analyze, authorize, check_parse, entities, evaluate, format_policies, language_version, link,
+    mutate, new, partial_authorize, query, repl, run_tests, schema_diff, serve, translate_policy,
+    translate_schema, validate, visualize, CedarExitCode, Cli, Commands, ErrorFormat,
 };
 
 fn main() -> CedarExitCode {
@@ -44,15 +44,23 @@ fn main() -> CedarExitCode {
     match cli.command {
         Commands::Authorize(args) => authorize(&args),
         Commands::Evaluate(args) => evaluate(&args).0,
+        Commands::Repl(args) => repl(&args),
+        Commands::Serve(args) => serve(&args),
         Commands::CheckParse(args) => check_parse(&args),
         Commands::Validate(args) => validate(&args),
+        Commands::Analyze(args) => analyze(&args),
+        Commands::SchemaDiff(args) => schema_diff(&args),
+        Commands::Test(args) => run_tests(&args),
+        Commands::Mutate(args) => mutate(&args),
         Commands::Format(args) => format_policies(&args),
         Commands::Link(args) => link(&args),
         Commands::TranslatePolicy(args) => translate_policy(&args),
         Commands::Visualize(args) => visualize(&args),
+        Commands::Entities(args) => entities(&args),
         Commands::TranslateSchema(args) => translate_schema(&args),
         Commands::New(args) => new(&args),
         Commands::PartiallyAuthorize(args) => partial_authorize(&args),
+        Commands::Query(args) => query(&args),
         Commands::LanguageVersion => language_version(),
     }
 }
@@ -24,9 +24,10 @@ use std::path::{Path, PathBuf};
 use cedar_policy::EvalResult;
 use cedar_policy::SlotId;
 use cedar_policy_cli::{
-    authorize, check_parse, evaluate, link, validate, Arguments, AuthorizeArgs, CedarExitCode,
-    CheckParseArgs, EvaluateArgs, LinkArgs, OptionalPoliciesArgs, OptionalSchemaArgs, PoliciesArgs,
-    PolicyFormat, RequestArgs, SchemaArgs, SchemaFormat, ValidateArgs,
+    authorize, check_parse, evaluate, link, validate, Arguments, AuditLogArgs, AuthorizeArgs,
+    CedarExitCode, CheckParseArgs, ErrorHandlingMode, EvaluateArgs, LinkArgs, OptionalPoliciesArgs,
+    OptionalSchemaArgs, PoliciesArgs, PolicyFormat, RequestArgs, SchemaArgs, SchemaFormat,
+    ValidateArgs,
 };
 
 use predicates::prelude::*;
@@ -106,6 +107,8 @@ fn run_authorize_test_with_linked_policies(
         entities_file: entities_file.into(),
         verbose: true,
         timing: false,
+        error_handling: ErrorHandlingMode::Skip,
+        audit_log: AuditLogArgs::default(),
     };
     let output = authorize(&cmd);
     assert_eq!(exit_code, output, "{:#?}", cmd,);
@@ -167,6 +170,8 @@ fn run_authorize_test_context(
         entities_file: entities_file.into(),
         verbose: true,
         timing: false,
+        error_handling: ErrorHandlingMode::Skip,
+        audit_log: AuditLogArgs::default(),
     };
     let output = authorize(&cmd);
     assert_eq!(exit_code, output, "{:#?}", cmd,);
@@ -200,6 +205,8 @@ fn run_authorize_test_json(
         entities_file: entities_file.into(),
         verbose: true,
         timing: false,
+        error_handling: ErrorHandlingMode::Skip,
+        audit_log: AuditLogArgs::default(),
     };
     let output = authorize(&cmd);
     assert_eq!(exit_code, output, "{:#?}", cmd,);
@@ -1011,6 +1018,80 @@ fn test_format_samples(#[files("sample-data/**/polic*.cedar")] path: PathBuf) {
     );
 }
 
+#[rstest]
+#[case("skip", "ALLOW")]
+#[case("deny-on-error", "DENY")]
+#[case("forbid-on-error", "DENY")]
+fn test_authorize_error_handling(#[case] error_handling: &str, #[case] decision: &str) {
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .arg("authorize")
+        .arg("--policies")
+        .arg("sample-data/tiny_sandboxes/error-handling/policy.cedar")
+        .arg("--entities")
+        .arg("sample-data/tiny_sandboxes/error-handling/entity.json")
+        .arg("--principal")
+        .arg(r#"User::"alice""#)
+        .arg("--action")
+        .arg(r#"Action::"view""#)
+        .arg("--resource")
+        .arg(r#"Photo::"VacationPhoto94.jpg""#)
+        .arg("--error-handling")
+        .arg(error_handling)
+        .assert()
+        .stdout(predicate::str::contains(decision))
+        .stdout(predicate::str::contains(
+            "does not have the attribute `suspended`",
+        ));
+}
+
+#[test]
+fn test_authorize_audit_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let audit_log = dir.path().join("audit.jsonl");
+    let authorize = |action: &str| {
+        assert_cmd::Command::cargo_bin("cedar")
+            .expect("bin exists")
+            .arg("authorize")
+            .arg("--policies")
+            .arg("sample-data/sandbox_b/policies_6.cedar")
+            .arg("--entities")
+            .arg("sample-data/sandbox_b/entities.json")
+            .arg("--principal")
+            .arg(r#"User::"alice""#)
+            .arg("--action")
+            .arg(action)
+            .arg("--resource")
+            .arg(r#"Photo::"vacation.jpg""#)
+            .arg("--audit-log")
+            .arg(&audit_log)
+            .arg("--audit-annotation")
+            .arg("id")
+            .assert()
+    };
+    authorize(r#"Action::"view""#).success();
+    authorize(r#"Action::"delete""#).code(2);
+
+    let records = std::fs::read_to_string(&audit_log)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["principal"], r#"User::"alice""#);
+    assert_eq!(records[0]["action"], r#"Action::"view""#);
+    assert_eq!(records[0]["decision"], "allow");
+    assert_eq!(
+        records[0]["determiningPolicies"][0]["annotations"]["id"],
+        "alice's friends view policy"
+    );
+    assert_eq!(records[1]["action"], r#"Action::"delete""#);
+    assert_eq!(records[1]["decision"], "deny");
+    // The context has no `source_ip`, so the IP denylist policy errors
+    assert_eq!(records[1]["errors"][0]["policyId"], "ip_denylist");
+    assert_eq!(records[0]["policySetHash"], records[1]["policySetHash"]);
+}
+
 #[test]
 fn test_format_write() {
     const POLICY_SOURCE: &str = "sample-data/tiny_sandboxes/format/unformatted.cedar";
@@ -1305,3 +1386,772 @@ fn visualize_entities_parses_as_dot(
     let visualized = std::str::from_utf8(&visualize.get_output().stdout).unwrap();
     graphviz_rust::parse(visualized).unwrap();
 }
+
+#[cfg(feature = "partial-eval")]
+#[test]
+fn test_query() {
+    const POLICIES: &str = "sample-data/sandbox_a/policies_1.cedar";
+    const ENTITIES: &str = "sample-data/sandbox_a/entities.json";
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["query", "-p", POLICIES, "--entities", ENTITIES])
+        .args(["-l", r#"User::"alice""#, "-a", r#"Action::"view""#])
+        .args(["--resource-type", "Photo"])
+        .assert()
+        .success()
+        .stdout("Photo::\"VacationPhoto94.jpg\"\n");
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["query", "-p", POLICIES, "--entities", ENTITIES])
+        .args(["--principal-type", "User", "-a", r#"Action::"view""#])
+        .args(["-r", r#"Photo::"VacationPhoto94.jpg""#])
+        .assert()
+        .success()
+        .stdout("User::\"alice\"\n");
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["query", "-p", POLICIES, "--entities", ENTITIES])
+        .args(["-l", r#"User::"tim""#, "-a", r#"Action::"view""#])
+        .args(["--resource-type", "Photo"])
+        .assert()
+        .success()
+        .stdout("");
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["query", "-p", POLICIES, "--entities", ENTITIES])
+        .args(["-l", r#"User::"alice""#, "-a", r#"Action::"view""#])
+        .args(["-r", r#"Photo::"VacationPhoto94.jpg""#])
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn test_analyze() {
+    const SCHEMA: &str = "sample-data/sandbox_a/schema.cedarschema";
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["analyze", "-s", SCHEMA])
+        .args(["-p", "sample-data/sandbox_a/policies_2.cedar"])
+        .assert()
+        .success()
+        .stdout("no shadowed, conflicting or duplicate policies found\n");
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["--error-format", "plain", "analyze", "-s", SCHEMA])
+        .args(["-p", "sample-data/sandbox_a/policies_1.cedar"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "permit policy may conflict with forbid policy `disallow tim policy`",
+        ));
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["analyze", "-s", SCHEMA, "--deny-warnings"])
+        .args(["-p", "sample-data/sandbox_a/policies_1.cedar"])
+        .assert()
+        .code(3);
+}
+
+#[test]
+fn test_schema_diff() {
+    const SCHEMA: &str = "sample-data/sandbox_a/schema.cedarschema";
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["schema-diff", "--old", SCHEMA, "--new", SCHEMA])
+        .assert()
+        .success()
+        .stdout("no schema changes found\n");
+
+    let new_schema = tempfile::NamedTempFile::new().expect("failed to create temp file");
+    std::fs::write(
+        new_schema.path(),
+        std::fs::read_to_string(SCHEMA)
+            .expect("failed to read schema")
+            .replace(
+                "entity Video in [Account, Album];",
+                "entity Video in [Account];",
+            )
+            .replace(
+                "resource: [Photo, Video, Album]",
+                "resource: [Photo, Album]",
+            ),
+    )
+    .expect("failed to write schema");
+    let new_schema = new_schema.path().to_str().expect("valid path");
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["schema-diff", "--old", SCHEMA, "--new", new_schema])
+        .args(["-p", "sample-data/sandbox_a/policies_1.cedar"])
+        .assert()
+        .code(3)
+        .stdout(predicates::str::contains(
+            r#"breaking: action `Action::"view"` resource types removed: `Video`"#,
+        ))
+        .stdout(predicates::str::contains(
+            "breaking: entity type `Video` ancestor types removed: `Album`",
+        ));
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["schema-diff", "--old", new_schema, "--new", SCHEMA])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_entities_apply_patch() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let path = |name: &str| {
+        dir.path()
+            .join(name)
+            .to_str()
+            .expect("valid path")
+            .to_owned()
+    };
+    std::fs::write(
+        path("entities.json"),
+        serde_json::json!([
+            { "uid": { "type": "User", "id": "alice" }, "attrs": {}, "parents": [{ "type": "Group", "id": "eng" }] },
+            { "uid": { "type": "Group", "id": "eng" }, "attrs": {}, "parents": [{ "type": "Group", "id": "all" }] },
+        ])
+        .to_string(),
+    )
+    .expect("failed to write entities");
+    std::fs::write(
+        path("patch1.json"),
+        serde_json::json!([
+            { "op": "removeParent", "uid": { "type": "User", "id": "alice" }, "parent": { "type": "Group", "id": "eng" } },
+        ])
+        .to_string(),
+    )
+    .expect("failed to write change-set");
+    std::fs::write(
+        path("patch2.json"),
+        serde_json::json!([
+            { "op": "setAttr", "uid": { "type": "User", "id": "alice" }, "attr": "level", "value": 3 },
+        ])
+        .to_string(),
+    )
+    .expect("failed to write change-set");
+    std::fs::write(
+        path("bad_patch.json"),
+        serde_json::json!([
+            { "op": "setAttr", "uid": { "type": "User", "id": "bob" }, "attr": "level", "value": 3 },
+        ])
+        .to_string(),
+    )
+    .expect("failed to write change-set");
+
+    let read_entities = |name: &str| -> HashMap<String, serde_json::Value> {
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path(name)).expect("file exists"))
+                .expect("valid json");
+        json.as_array()
+            .expect("entities are an array")
+            .iter()
+            .map(|e| (e["uid"]["id"].as_str().unwrap().to_owned(), e.clone()))
+            .collect()
+    };
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "entities",
+            "apply-patch",
+            "--entities",
+            &path("entities.json"),
+        ])
+        .args([path("patch1.json"), path("patch2.json")])
+        .args([
+            "--output",
+            &path("out.json"),
+            "--inverse",
+            &path("undo.json"),
+        ])
+        .assert()
+        .success();
+    let out = read_entities("out.json");
+    assert_eq!(out["alice"]["parents"], serde_json::json!([]));
+    assert_eq!(out["alice"]["attrs"]["level"], serde_json::json!(3));
+    // the indirect ancestors of entities are not written out as parents
+    assert_eq!(out["eng"]["parents"].as_array().unwrap().len(), 1);
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["entities", "apply-patch", "--entities", &path("out.json")])
+        .args([path("undo.json")])
+        .args(["--output", &path("undone.json")])
+        .assert()
+        .success();
+    let undone = read_entities("undone.json");
+    assert_eq!(undone["alice"]["attrs"], serde_json::json!({}));
+    assert_eq!(undone["alice"]["parents"].as_array().unwrap().len(), 1);
+
+    // a change-set which can't be applied fails without writing anything
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "entities",
+            "apply-patch",
+            "--entities",
+            &path("entities.json"),
+        ])
+        .args([path("patch2.json"), path("bad_patch.json")])
+        .assert()
+        .code(1)
+        .stdout(predicates::str::contains(
+            "failed to apply change-set from file",
+        ))
+        .stdout(predicates::str::contains(
+            r#"cannot update `User::"bob"` because it does not exist"#,
+        ));
+}
+
+#[test]
+fn test_entities_diff() {
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let path = |name: &str| {
+        dir.path()
+            .join(name)
+            .to_str()
+            .expect("valid path")
+            .to_owned()
+    };
+    std::fs::write(
+        path("old.json"),
+        serde_json::json!([
+            { "uid": { "type": "User", "id": "alice" }, "attrs": { "level": 3 }, "parents": [{ "type": "Group", "id": "eng" }] },
+            { "uid": { "type": "User", "id": "bob" }, "attrs": {}, "parents": [] },
+            { "uid": { "type": "Group", "id": "eng" }, "attrs": {}, "parents": [] },
+        ])
+        .to_string(),
+    )
+    .expect("failed to write entities");
+    std::fs::write(
+        path("new.json"),
+        serde_json::json!([
+            { "uid": { "type": "User", "id": "alice" }, "attrs": { "level": 4 }, "parents": [] },
+            { "uid": { "type": "Group", "id": "eng" }, "attrs": {}, "parents": [] },
+        ])
+        .to_string(),
+    )
+    .expect("failed to write entities");
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "entities",
+            "diff",
+            "--old",
+            &path("old.json"),
+            "--new",
+            &path("new.json"),
+        ])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            r#"attribute `level` of `User::"alice"` changed from 3 to 4"#,
+        ))
+        .stdout(predicates::str::contains(
+            r#"parent `Group::"eng"` was removed from `User::"alice"`"#,
+        ))
+        .stdout(predicates::str::contains(
+            r#"entity `User::"bob"` was removed"#,
+        ));
+
+    let output = assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "entities",
+            "diff",
+            "--old",
+            &path("old.json"),
+            "--new",
+            &path("new.json"),
+        ])
+        .args(["--format", "json"])
+        .output()
+        .expect("command runs");
+    assert!(output.status.success());
+    let diff: serde_json::Value = serde_json::from_slice(&output.stdout).expect("valid json");
+    let changes: Vec<_> = diff
+        .as_array()
+        .expect("diff is an array")
+        .iter()
+        .map(|change| change["change"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        changes,
+        ["attributeChanged", "parentRemoved", "entityRemoved"]
+    );
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "entities",
+            "diff",
+            "--old",
+            &path("new.json"),
+            "--new",
+            &path("new.json"),
+        ])
+        .assert()
+        .success()
+        .stdout("no entity changes found\n");
+}
+
+#[test]
+fn test_policy_test_suite() {
+    const SUITE: &str = "sample-data/tiny_sandboxes/test-suite/suite.json";
+    const FAILING_SUITE: &str = "sample-data/tiny_sandboxes/test-suite/failing_suite.json";
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["test", SUITE])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "  PASS  alice can view the vacation photo",
+        ))
+        .stdout(predicates::str::contains("4 passed, 0 failed, 0 errored"));
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["test", SUITE, FAILING_SUITE])
+        .assert()
+        .code(1)
+        .stdout(predicates::str::contains(
+            "  FAIL  bob can view the vacation photo\n        expected decision allow, got deny",
+        ))
+        .stdout(predicates::str::contains("3 passed, 1 failed, 0 errored"));
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["test", "--format", "junit", FAILING_SUITE])
+        .assert()
+        .code(1)
+        .stdout(predicates::str::starts_with(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        ))
+        .stdout(predicates::str::contains(
+            r#"<failure message="expected decision allow, got deny">"#,
+        ));
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "-f",
+            "plain",
+            "test",
+            "sample-data/sandbox_a/schema.cedarschema",
+        ])
+        .assert()
+        .code(1)
+        .stdout(predicates::str::contains("failed to parse"));
+}
+
+#[test]
+fn test_policy_test_suite_coverage() {
+    const SUITE: &str = "sample-data/tiny_sandboxes/test-suite/suite.json";
+    let dir = tempfile::tempdir().expect("failed to create temp dir");
+    let lcov = dir.path().join("coverage.info");
+    let cobertura = dir.path().join("coverage.xml");
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["test", SUITE, "--coverage"])
+        .arg(&lcov)
+        .assert()
+        .success();
+    let lcov = std::fs::read_to_string(lcov).expect("coverage was written");
+    assert!(lcov.contains("SF:sample-data/tiny_sandboxes/test-suite/policies.cedar\n"));
+    assert!(lcov.contains("FNDA:1,jane's friends view-permission policy\n"));
+    assert!(lcov.contains("FNDA:1,disallow tim policy\n"));
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "test",
+            SUITE,
+            "--coverage-format",
+            "cobertura",
+            "--coverage",
+        ])
+        .arg(&cobertura)
+        .assert()
+        .success();
+    let cobertura = std::fs::read_to_string(cobertura).expect("coverage was written");
+    assert!(cobertura.contains(r#"<class name="disallow tim policy""#));
+}
+
+#[test]
+fn test_mutation_testing() {
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["mutate", "sample-data/tiny_sandboxes/test-suite/suite.json"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "mutation testing `photo sharing`: 8 mutants, 5 killed, 3 survived",
+        ))
+        .stdout(predicates::str::contains(
+            r#"SURVIVED policy `disallow tim policy` (line 10): removed the constraint `resource == Photo::"VacationPhoto94.jpg"`"#,
+        ));
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "mutate",
+            "sample-data/tiny_sandboxes/test-suite/suite.json",
+            "--min-score",
+            "80",
+        ])
+        .assert()
+        .code(1);
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "mutate",
+            "sample-data/tiny_sandboxes/test-suite/failing_suite.json",
+        ])
+        .assert()
+        .code(1)
+        .stdout(predicates::str::contains(
+            "the test suite fails against the unmutated policies",
+        ));
+}
+
+#[test]
+fn test_repl() {
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "repl",
+            "--entities",
+            "sample-data/sandbox_b/entities.json",
+            "--principal",
+            r#"User::"alice""#,
+        ])
+        .write_stdin(
+            r#"principal.jobLevel
+:action Action::"view"
+:resource Photo::"VacationPhoto94.jpg"
+principal.jobLevel + 1
+principal in UserGroup::"alice_friends"
+:resource
+principal.jobLevel
+:bogus
+:quit
+principal.jobLevel
+"#,
+        )
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "All three (`principal`, `action`, `resource`) variables must be set",
+        ))
+        .stdout(predicates::str::contains("6\ntrue\n"))
+        .stdout(predicates::str::contains("unknown command `:bogus`"))
+        .stdout(predicates::str::contains("must be set").count(2));
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args([
+            "--error-format",
+            "plain",
+            "repl",
+            "--schema",
+            "sample-data/sandbox_b/schema.cedarschema",
+        ])
+        .write_stdin(
+            r#":type principal.jobLevel
+:type resource.admins
+:action Action::"view"
+:resource Photo::"VacationPhoto94.jpg"
+:type resource.admins
+:type principal.nope
+"#,
+        )
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Long\n"))
+        .stdout(predicates::str::contains(
+            r#"User, Action::"view", Photo: Set<User>"#,
+        ))
+        .stdout(predicates::str::contains(
+            "attribute `admins` on entity type `Album` not found",
+        ))
+        .stdout(predicates::str::contains("Set<User>\n"))
+        .stdout(predicates::str::contains(
+            "attribute `nope` on entity type `User` not found",
+        ));
+
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["repl", "--entities", "sample-data/sandbox_b/missing.json"])
+        .assert()
+        .code(1);
+}
+
+#[cfg(feature = "partial-eval")]
+#[test]
+fn test_repl_partial_evaluation() {
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["repl", "--entities", "sample-data/sandbox_b/entities.json"])
+        .write_stdin(
+            r#":partial on
+:principal User::"alice"
+principal.jobLevel > 3 && resource.private
+:partial off
+principal.jobLevel
+"#,
+        )
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            r#"(unknown("resource"))["private"]"#,
+        ))
+        .stdout(predicates::str::contains("must be set"));
+}
+
+/// A running `cedar serve`, killed when dropped
+struct Server {
+    child: std::process::Child,
+    stdout: std::io::Lines<std::io::BufReader<std::process::ChildStdout>>,
+    addr: String,
+}
+
+impl Server {
+    fn start(args: &[&str]) -> Self {
+        use std::io::BufRead;
+        let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("cedar"))
+            .arg("serve")
+            .args(["--port", "0", "--reload-interval", "50"])
+            .args(args)
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .expect("failed to start `cedar serve`");
+        let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap()).lines();
+        let line = stdout.next().unwrap().unwrap();
+        let addr = line
+            .strip_prefix("listening on http://")
+            .unwrap_or_else(|| panic!("unexpected output: {line}"))
+            .to_owned();
+        Self {
+            child,
+            stdout,
+            addr,
+        }
+    }
+
+    /// Send a request, returning the status code and JSON body of the response
+    fn request(&self, method: &str, path: &str, body: &str) -> (u16, serde_json::Value) {
+        self.send(&format!(
+            "{method} {path} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\n\r\n{body}",
+            self.addr,
+            body.len()
+        ))
+    }
+
+    /// Send a raw HTTP request, returning the status code and JSON body of the
+    /// response
+    fn send(&self, request: &str) -> (u16, serde_json::Value) {
+        use std::io::{Read, Write};
+        let mut stream = std::net::TcpStream::connect(&self.addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn test_serve() {
+    let dir = tempfile::tempdir().unwrap();
+    let policies = dir.path().join("policies.cedar");
+    std::fs::write(
+        &policies,
+        r#"permit(principal == User::"alice", action, resource);"#,
+    )
+    .unwrap();
+    let server = Server::start(&[
+        "--policies",
+        policies.to_str().unwrap(),
+        "--entities",
+        "sample-data/sandbox_b/entities.json",
+    ]);
+    let call = |principal: &str| {
+        serde_json::json!({
+            "principal": { "type": "User", "id": principal },
+            "action": { "type": "Action", "id": "view" },
+            "resource": { "type": "Photo", "id": "VacationPhoto94.jpg" },
+            "context": {},
+        })
+    };
+    let decision = |answer: &serde_json::Value| answer["response"]["decision"].clone();
+
+    let (status, answer) = server.request("POST", "/authorize", &call("alice").to_string());
+    assert_eq!(status, 200);
+    assert_eq!(decision(&answer), "allow");
+    let (status, answer) = server.request(
+        "POST",
+        "/batch",
+        &serde_json::json!({ "requests": [call("alice"), call("bob")] }).to_string(),
+    );
+    assert_eq!(status, 200);
+    assert_eq!(decision(&answer["answers"][0]), "allow");
+    assert_eq!(decision(&answer["answers"][1]), "deny");
+
+    // Without a schema, there is nothing to validate against
+    let (status, answer) = server.request("POST", "/validate", "");
+    assert_eq!(status, 200);
+    assert_eq!(answer["type"], "failure");
+
+    let (status, answer) = server.request("POST", "/authorize/partial", r#"{"context": {}}"#);
+    if cfg!(feature = "partial-eval") {
+        assert_eq!(status, 200);
+        assert_eq!(answer["type"], "residuals");
+    } else {
+        assert_eq!(status, 501);
+    }
+
+    let (status, _) = server.request("POST", "/authorize", r#"{"policies": {}}"#);
+    assert_eq!(status, 400);
+    let (status, _) = server.request("POST", "/bogus", "{}");
+    assert_eq!(status, 404);
+    let (status, _) = server.request("GET", "/authorize", "");
+    assert_eq!(status, 405);
+    // Large bodies are rejected before they are read
+    let (status, _) =
+        server.send("POST /authorize HTTP/1.1\r\nContent-Length: 1000000000000\r\n\r\n");
+    assert_eq!(status, 413);
+
+    // Changing the policies reloads them
+    std::fs::write(
+        &policies,
+        r#"permit(principal == User::"bob", action, resource);"#,
+    )
+    .unwrap();
+    let mut server = server;
+    assert_eq!(
+        server.stdout.next().unwrap().unwrap(),
+        "reloaded policies, schema and entities"
+    );
+    let (_, answer) = server.request("POST", "/authorize", &call("bob").to_string());
+    assert_eq!(decision(&answer), "allow");
+
+    // SIGHUP reloads the inputs, rather than stopping the server
+    #[cfg(unix)]
+    {
+        let status = std::process::Command::new("kill")
+            .args(["-HUP", &server.child.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(
+            server.stdout.next().unwrap().unwrap(),
+            "reloaded policies, schema and entities"
+        );
+        let (status, _) = server.request("POST", "/authorize", &call("bob").to_string());
+        assert_eq!(status, 200);
+    }
+}
+
+#[test]
+fn test_serve_slow_client() {
+    use std::io::{Read, Write};
+    let server = Server::start(&[
+        "--policies",
+        "sample-data/sandbox_b/policies_6.cedar",
+        "--request-timeout",
+        "500",
+    ]);
+    // A client which keeps sending its headers a byte at a time is cut off
+    // once the whole request has taken too long, even though each byte
+    // arrives well within the timeout
+    let mut stream = std::net::TcpStream::connect(&server.addr).unwrap();
+    let mut reader = stream.try_clone().unwrap();
+    let start = std::time::Instant::now();
+    let closed = std::thread::spawn(move || {
+        let mut response = Vec::new();
+        let _ = reader.read_to_end(&mut response);
+        (start.elapsed(), response)
+    });
+    stream
+        .write_all(b"POST /authorize HTTP/1.1\r\nX-Slow: ")
+        .unwrap();
+    while !closed.is_finished() && start.elapsed() < std::time::Duration::from_secs(10) {
+        if stream.write_all(b"a").is_err() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    let (elapsed, response) = closed.join().unwrap();
+    assert!(
+        elapsed < std::time::Duration::from_secs(5),
+        "the connection was open for {elapsed:?}"
+    );
+    let response = String::from_utf8_lossy(&response);
+    assert!(
+        response.is_empty() || response.starts_with("HTTP/1.1 400 "),
+        "unexpected response: {response}"
+    );
+}
+
+#[test]
+fn test_serve_with_schema() {
+    let server = Server::start(&[
+        "--policies",
+        "sample-data/sandbox_b/policies_6.cedar",
+        "--schema",
+        "sample-data/sandbox_b/schema.cedarschema",
+    ]);
+    let (status, answer) = server.request("POST", "/validate", r#"{"mode": "strict"}"#);
+    assert_eq!(status, 200);
+    assert_eq!(answer["type"], "success");
+    assert_eq!(answer["validationErrors"], serde_json::json!([]));
+
+    // Requests are validated against the schema
+    let (status, answer) = server.request(
+        "POST",
+        "/authorize",
+        r#"{
+            "principal": { "type": "Photo", "id": "alice" },
+            "action": { "type": "Action", "id": "view" },
+            "resource": { "type": "Photo", "id": "VacationPhoto94.jpg" },
+            "context": {}
+        }"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(answer["type"], "failure");
+}
+
+#[test]
+fn test_serve_requires_policies_file() {
+    assert_cmd::Command::cargo_bin("cedar")
+        .expect("bin exists")
+        .args(["--error-format", "plain", "serve", "--port", "0"])
+        .assert()
+        .failure()
+        .stdout(predicates::str::contains("`serve` requires `--policies`"));
+}
@@ -697,6 +697,29 @@ impl Entity {
         self.indirect_ancestors.clear();
     }
 
+    /// Set the attribute `attr` of this `Entity` to `value`, returning the
+    /// attribute's previous value, if any.
+    pub fn set_attr(&mut self, attr: SmolStr, value: PartialValue) -> Option<PartialValue> {
+        self.attrs.insert(attr, value)
+    }
+
+    /// Remove the attribute `attr` from this `Entity`, returning its value,
+    /// if any.
+    pub fn remove_attr(&mut self, attr: &str) -> Option<PartialValue> {
+        self.attrs.remove(attr)
+    }
+
+    /// Set the tag `tag` of this `Entity` to `value`, returning the tag's
+    /// previous value, if any.
+    pub fn set_tag(&mut self, tag: SmolStr, value: PartialValue) -> Option<PartialValue> {
+        self.tags.insert(tag, value)
+    }
+
+    /// Remove the tag `tag` from this `Entity`, returning its value, if any.
+    pub fn remove_tag(&mut self, tag: &str) -> Option<PartialValue> {
+        self.tags.remove(tag)
+    }
+
     /// Consume the entity and return the entity's owned Uid, attributes, ancestors, parents, and tags.
     #[allow(clippy::type_complexity)]
     pub fn into_inner(
@@ -956,6 +956,29 @@ impl From<StaticPolicy> for Arc<Template> {
     }
 }
 
+/// Split the condition (the non-scope constraints) of a policy located at
+/// `policy_loc` into its `when` and `unless` clauses, in source order.
+///
+/// The parser joins clauses with `&&` nodes located at the whole policy, and
+/// uses a `true` located at the whole policy for a policy without clauses, so
+/// `&&` nodes within a clause are not split. The clauses of a policy without
+/// a source location can't be told apart this way.
+pub fn condition_clauses<'a>(condition: &'a Expr, policy_loc: &Loc) -> Vec<&'a Expr> {
+    fn split<'a>(e: &'a Expr, policy_loc: &Loc, clauses: &mut Vec<&'a Expr>) {
+        match e.expr_kind() {
+            ExprKind::And { left, right } if e.source_loc() == Some(policy_loc) => {
+                split(left, policy_loc, clauses);
+                clauses.push(right);
+            }
+            ExprKind::Lit(Literal::Bool(true)) if e.source_loc() == Some(policy_loc) => (),
+            _ => clauses.push(e),
+        }
+    }
+    let mut clauses = Vec::new();
+    split(condition, policy_loc, &mut clauses);
+    clauses
+}
+
 /// Policy datatype. This is used for both templates (in which case it contains
 /// slots) and static policies (in which case it contains zero slots).
 #[derive(Educe, Clone, Debug)]
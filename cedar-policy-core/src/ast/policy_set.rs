@@ -18,12 +18,16 @@ use super::{
     EntityUID, LinkingError, LiteralPolicy, Policy, PolicyID, ReificationError, SlotId,
     StaticPolicy, Template,
 };
-use itertools::Itertools;
+use itertools::{Either, Itertools};
 use miette::Diagnostic;
 use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::{borrow::Borrow, sync::Arc};
 use thiserror::Error;
 
+mod scope_index;
+use scope_index::ScopeIndex;
+pub(crate) use scope_index::ScopeQuery;
+
 /// Represents a set of `Policy`s
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct PolicySet {
@@ -44,6 +48,11 @@ pub struct PolicySet {
     /// There is a key `t` iff `templates` contains the key `t`. The value of `t` will be a (possibly empty)
     /// set of every `p` in `links` s.t. `p.template().id() == t`.
     template_to_links_map: HashMap<PolicyID, HashSet<PolicyID>>,
+
+    /// Index over the scope constraints of every policy in `links`, used to
+    /// find the policies that may apply to a request without evaluating all
+    /// of them. There is an entry for `p` iff `links` contains the key `p`.
+    scope_index: ScopeIndex,
 }
 
 /// A Policy Set that contains less rich information than `PolicySet`.
@@ -108,18 +117,21 @@ impl TryFrom<LiteralPolicySet> for PolicySet {
         for template in &templates {
             template_to_links_map.insert(template.0.clone(), HashSet::new());
         }
+        let mut scope_index = ScopeIndex::default();
         for (link_id, link) in &links {
             let template = link.template().id();
             match template_to_links_map.entry(template.clone()) {
                 Entry::Occupied(t) => t.into_mut().insert(link_id.clone()),
                 Entry::Vacant(_) => return Err(ReificationError::NoSuchTemplate(template.clone())),
             };
+            scope_index.insert(link_id, link);
         }
 
         Ok(Self {
             templates,
             links,
             template_to_links_map,
+            scope_index,
         })
     }
 }
@@ -209,6 +221,7 @@ impl PolicySet {
             templates: HashMap::new(),
             links: HashMap::new(),
             template_to_links_map: HashMap::new(),
+            scope_index: ScopeIndex::default(),
         }
     }
 
@@ -259,6 +272,7 @@ impl PolicySet {
                 .insert(policy.id().clone());
         }
         if let Some(ventry) = link_ventry {
+            self.scope_index.insert(policy.id(), &policy);
             ventry.insert(policy);
         }
 
@@ -344,6 +358,7 @@ impl PolicySet {
         }
         for (pid, other_policy) in &other.links {
             let pid = renaming.get(pid).unwrap_or(pid);
+            self.scope_index.insert(pid, other_policy);
             self.links.insert(pid.clone(), other_policy.clone());
         }
         for (tid, other_template_link_set) in &other.template_to_links_map {
@@ -379,6 +394,7 @@ impl PolicySet {
         match self.templates.remove(policy_id) {
             Some(_) => {
                 self.template_to_links_map.remove(policy_id);
+                self.scope_index.remove(policy_id);
                 Ok(policy)
             }
             None => {
@@ -407,6 +423,7 @@ impl PolicySet {
                         .into_iter()
                         .collect::<HashSet<PolicyID>>(),
                 );
+                self.scope_index.insert(p.id(), &p);
                 templates_entry.insert(t);
                 links_entry.insert(p);
                 Ok(())
@@ -518,6 +535,7 @@ impl PolicySet {
             self.templates.entry(new_id.clone()),
         ) {
             (Entry::Vacant(links_entry), Entry::Vacant(_)) => {
+                self.scope_index.insert(&new_id, &r);
                 //We will never use the .or_default() because we just found `t` above
                 self.template_to_links_map
                     .entry(template_id)
@@ -551,6 +569,7 @@ impl PolicySet {
                         panic!("No template found for linked policy")
                     }
                 };
+                self.scope_index.remove(policy_id);
                 Ok(p)
             }
             None => Err(PolicySetUnlinkError::UnlinkingError(policy_id.clone())),
@@ -562,6 +581,24 @@ impl PolicySet {
         self.links.values()
     }
 
+    /// Iterate over the policies whose scope may be satisfied by a request
+    /// with the given principal, action and resource.
+    ///
+    /// Every policy whose scope is satisfied is included, but some included
+    /// policies may not have their scope satisfied. Policies that are not
+    /// included are guaranteed to evaluate to `false` without error.
+    pub(crate) fn policies_matching_scope(
+        &self,
+        principal: &ScopeQuery<'_>,
+        action: &ScopeQuery<'_>,
+        resource: &ScopeQuery<'_>,
+    ) -> impl Iterator<Item = &Policy> {
+        match self.scope_index.candidates(principal, action, resource) {
+            None => Either::Left(self.policies()),
+            Some(ids) => Either::Right(ids.into_iter().filter_map(|id| self.links.get(id))),
+        }
+    }
+
     /// Consume the `PolicySet`, producing an iterator of all the policies in it
     pub fn into_policies(self) -> impl Iterator<Item = Policy> {
         self.links.into_values()
@@ -1134,4 +1171,60 @@ mod test {
         assert!(pset.get(&tid1).is_none());
         assert_eq!(pset.all_templates().count(), 4);
     }
+
+    #[test]
+    fn scope_index_tracks_links() {
+        let mut pset = PolicySet::new();
+        let template = parser::parse_policy_or_template(
+            Some(PolicyID::from_string("t")),
+            "permit(principal == ?principal, action, resource);",
+        )
+        .expect("Failed to parse");
+        pset.add_template(template).expect("Add failed");
+        let p = parser::parse_policy(
+            Some(PolicyID::from_string("static")),
+            r#"permit(principal, action == Action::"view", resource);"#,
+        )
+        .expect("Failed to parse");
+        pset.add_static(p).expect("Add failed");
+        let alice = EntityUID::with_eid("alice");
+        let bob = EntityUID::with_eid("bob");
+        let view: EntityUID = r#"Action::"view""#.parse().expect("Failed to parse");
+        for (id, principal) in [("link_alice", &alice), ("link_bob", &bob)] {
+            pset.link(
+                PolicyID::from_string("t"),
+                PolicyID::from_string(id),
+                HashMap::from([(SlotId::principal(), principal.clone())]),
+            )
+            .expect("Linking failed");
+        }
+
+        let matching = |pset: &PolicySet, principal: &EntityUID, action: &EntityUID| {
+            let principal = ScopeQuery::Entity {
+                uid: principal,
+                entity: None,
+            };
+            let action = ScopeQuery::Entity {
+                uid: action,
+                entity: None,
+            };
+            pset.policies_matching_scope(&principal, &action, &ScopeQuery::Unconstrained)
+                .map(|p| p.id().to_string())
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matching(&pset, &alice, &view), vec!["link_alice", "static"]);
+        assert_eq!(
+            matching(&pset, &bob, &EntityUID::with_eid("edit")),
+            vec!["link_bob"]
+        );
+
+        pset.unlink(&PolicyID::from_string("link_alice"))
+            .expect("Unlinking failed");
+        pset.remove_static(&PolicyID::from_string("static"))
+            .expect("Removal failed");
+        assert!(matching(&pset, &alice, &view).is_empty());
+        assert_eq!(matching(&pset, &bob, &view), vec!["link_bob"]);
+    }
 }
@@ -0,0 +1,319 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! An index over the scope constraints of the policies in a `PolicySet`.
+//!
+//! The index lets the authorizer find the policies whose scope may be
+//! satisfied by a concrete request without evaluating every policy in the
+//! set. It is conservative: every policy whose scope is satisfied by the
+//! request is returned, but some returned policies may still turn out not to
+//! be satisfied (for instance, `principal is User in Group::"g"` is only
+//! indexed on `Group::"g"`). Policies that are not returned are guaranteed to
+//! evaluate to `false` without error, because the scope is the first thing
+//! evaluated in a policy condition and scope checks never error.
+
+use crate::ast::{
+    ActionConstraint, Entity, EntityReference, EntityType, EntityUID, Policy, PolicyID,
+    PrincipalOrResourceConstraint, SlotId,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Information about one scope variable of a request, used to query the
+/// index.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ScopeQuery<'a> {
+    /// Nothing is known about this scope variable (e.g., it is unknown, or
+    /// its ancestors cannot be determined), so every policy is a candidate in
+    /// this dimension.
+    Unconstrained,
+    /// The scope variable is the given entity. `entity` is the entity data
+    /// for `uid` if it is present in the store, used to determine ancestors.
+    Entity {
+        /// UID of the scope variable
+        uid: &'a EntityUID,
+        /// Entity data for `uid`, if it exists in the store
+        entity: Option<&'a Entity>,
+    },
+}
+
+impl ScopeQuery<'_> {
+    /// Is `uid` equal to, or an ancestor of, the queried entity?
+    fn is_in(&self, target: &EntityUID) -> bool {
+        match self {
+            Self::Unconstrained => true,
+            Self::Entity { uid, entity } => {
+                *uid == target || entity.is_some_and(|e| e.is_descendant_of(target))
+            }
+        }
+    }
+
+    /// Is the queried entity equal to `target`?
+    fn is_eq(&self, target: &EntityUID) -> bool {
+        match self {
+            Self::Unconstrained => true,
+            Self::Entity { uid, .. } => *uid == target,
+        }
+    }
+
+    /// Does the queried entity have type `ty`?
+    fn is_type(&self, ty: &EntityType) -> bool {
+        match self {
+            Self::Unconstrained => true,
+            Self::Entity { uid, .. } => uid.entity_type() == ty,
+        }
+    }
+
+    /// The queried entity followed by all of its ancestors
+    fn self_and_ancestors(&self) -> impl Iterator<Item = &EntityUID> {
+        let (uid, entity) = match self {
+            Self::Unconstrained => (None, None),
+            Self::Entity { uid, entity } => (Some(*uid), *entity),
+        };
+        uid.into_iter()
+            .chain(entity.into_iter().flat_map(|e| e.ancestors()))
+    }
+}
+
+/// The indexing key for a single scope constraint of a single policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ScopeKey {
+    /// The policy places no (indexable) constraint on this scope variable
+    Any,
+    /// `== uid`
+    Eq(EntityUID),
+    /// `in uid`, or `in [uid, ...]` for actions
+    In(Vec<EntityUID>),
+    /// `is ty`
+    Is(EntityType),
+    /// `is ty in uid`
+    IsIn(EntityType, EntityUID),
+}
+
+impl ScopeKey {
+    fn for_principal_or_resource(
+        constraint: &PrincipalOrResourceConstraint,
+        policy: &Policy,
+        slot: SlotId,
+    ) -> Self {
+        let resolve = |r: &EntityReference| match r {
+            EntityReference::EUID(euid) => Some(euid.as_ref().clone()),
+            EntityReference::Slot(_) => policy.env().get(&slot).cloned(),
+        };
+        match constraint {
+            PrincipalOrResourceConstraint::Any => Self::Any,
+            PrincipalOrResourceConstraint::Eq(r) => resolve(r).map_or(Self::Any, Self::Eq),
+            PrincipalOrResourceConstraint::In(r) => {
+                resolve(r).map_or(Self::Any, |uid| Self::In(vec![uid]))
+            }
+            PrincipalOrResourceConstraint::Is(ty) => Self::Is(ty.as_ref().clone()),
+            PrincipalOrResourceConstraint::IsIn(ty, r) => match resolve(r) {
+                Some(uid) => Self::IsIn(ty.as_ref().clone(), uid),
+                None => Self::Is(ty.as_ref().clone()),
+            },
+        }
+    }
+
+    fn for_action(constraint: &ActionConstraint) -> Self {
+        match constraint {
+            ActionConstraint::Eq(uid) => Self::Eq(uid.as_ref().clone()),
+            ActionConstraint::In(uids) => {
+                Self::In(uids.iter().map(|uid| uid.as_ref().clone()).collect())
+            }
+            #[cfg(feature = "tolerant-ast")]
+            ActionConstraint::ErrorConstraint => Self::Any,
+            ActionConstraint::Any => Self::Any,
+        }
+    }
+
+    /// Could a request with the given scope variable satisfy this key?
+    fn matches(&self, query: &ScopeQuery<'_>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Eq(uid) => query.is_eq(uid),
+            Self::In(uids) => uids.iter().any(|uid| query.is_in(uid)),
+            Self::Is(ty) => query.is_type(ty),
+            Self::IsIn(ty, uid) => query.is_type(ty) && query.is_in(uid),
+        }
+    }
+}
+
+/// Index over one scope variable (principal, action, or resource)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct ScopeVarIndex {
+    /// The key of every indexed policy
+    keys: HashMap<PolicyID, ScopeKey>,
+    /// Policies with key `Any`
+    any: HashSet<PolicyID>,
+    /// Policies with key `Eq`, by the UID they must equal
+    eq: HashMap<EntityUID, HashSet<PolicyID>>,
+    /// Policies with key `In` or `IsIn`, by the UID(s) they must be `in`
+    in_: HashMap<EntityUID, HashSet<PolicyID>>,
+    /// Policies with key `Is`, by the type they must have
+    is: HashMap<EntityType, HashSet<PolicyID>>,
+}
+
+impl ScopeVarIndex {
+    fn insert(&mut self, id: PolicyID, key: ScopeKey) {
+        match &key {
+            ScopeKey::Any => {
+                self.any.insert(id.clone());
+            }
+            ScopeKey::Eq(uid) => {
+                self.eq.entry(uid.clone()).or_default().insert(id.clone());
+            }
+            ScopeKey::In(uids) => {
+                for uid in uids {
+                    self.in_.entry(uid.clone()).or_default().insert(id.clone());
+                }
+            }
+            ScopeKey::IsIn(_, uid) => {
+                self.in_.entry(uid.clone()).or_default().insert(id.clone());
+            }
+            ScopeKey::Is(ty) => {
+                self.is.entry(ty.clone()).or_default().insert(id.clone());
+            }
+        }
+        self.keys.insert(id, key);
+    }
+
+    fn remove(&mut self, id: &PolicyID) {
+        fn remove_from<K: std::hash::Hash + Eq>(
+            map: &mut HashMap<K, HashSet<PolicyID>>,
+            k: &K,
+            id: &PolicyID,
+        ) {
+            if let Some(ids) = map.get_mut(k) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    map.remove(k);
+                }
+            }
+        }
+        match self.keys.remove(id) {
+            None => (),
+            Some(ScopeKey::Any) => {
+                self.any.remove(id);
+            }
+            Some(ScopeKey::Eq(uid)) => remove_from(&mut self.eq, &uid, id),
+            Some(ScopeKey::In(uids)) => {
+                for uid in uids {
+                    remove_from(&mut self.in_, &uid, id);
+                }
+            }
+            Some(ScopeKey::IsIn(_, uid)) => remove_from(&mut self.in_, &uid, id),
+            Some(ScopeKey::Is(ty)) => remove_from(&mut self.is, &ty, id),
+        }
+    }
+
+    /// Is the policy `id` a candidate for the given query in this dimension?
+    fn matches(&self, id: &PolicyID, query: &ScopeQuery<'_>) -> bool {
+        self.keys.get(id).is_some_and(|key| key.matches(query))
+    }
+
+    /// The buckets of policies that may be candidates for `query`. A policy
+    /// may appear in more than one bucket. Returns `None` if every policy is
+    /// a candidate.
+    fn buckets(&self, query: &ScopeQuery<'_>) -> Option<Vec<&HashSet<PolicyID>>> {
+        match query {
+            ScopeQuery::Unconstrained => None,
+            ScopeQuery::Entity { uid, .. } => {
+                let mut buckets = vec![&self.any];
+                buckets.extend(self.eq.get(uid));
+                buckets.extend(self.is.get(uid.entity_type()));
+                buckets.extend(
+                    query
+                        .self_and_ancestors()
+                        .filter_map(|ancestor| self.in_.get(ancestor)),
+                );
+                Some(buckets)
+            }
+        }
+    }
+}
+
+/// Index over the principal, action and resource scope constraints of the
+/// policies in a `PolicySet`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(super) struct ScopeIndex {
+    principal: ScopeVarIndex,
+    action: ScopeVarIndex,
+    resource: ScopeVarIndex,
+}
+
+impl ScopeIndex {
+    /// Add `policy` to the index under the id `id`. If a policy with the same
+    /// id was already indexed, it is replaced.
+    pub(super) fn insert(&mut self, id: &PolicyID, policy: &Policy) {
+        self.remove(id);
+        let principal = ScopeKey::for_principal_or_resource(
+            policy.template().principal_constraint().as_inner(),
+            policy,
+            SlotId::principal(),
+        );
+        let resource = ScopeKey::for_principal_or_resource(
+            policy.template().resource_constraint().as_inner(),
+            policy,
+            SlotId::resource(),
+        );
+        let action = ScopeKey::for_action(policy.action_constraint());
+        self.principal.insert(id.clone(), principal);
+        self.action.insert(id.clone(), action);
+        self.resource.insert(id.clone(), resource);
+    }
+
+    /// Remove a policy from the index, if present
+    pub(super) fn remove(&mut self, id: &PolicyID) {
+        self.principal.remove(id);
+        self.action.remove(id);
+        self.resource.remove(id);
+    }
+
+    /// Get the ids of all policies whose scope may be satisfied by a request
+    /// with the given principal, action and resource. Returns `None` if every
+    /// policy is a candidate.
+    pub(super) fn candidates(
+        &self,
+        principal: &ScopeQuery<'_>,
+        action: &ScopeQuery<'_>,
+        resource: &ScopeQuery<'_>,
+    ) -> Option<HashSet<&PolicyID>> {
+        let dims = [
+            (&self.principal, principal),
+            (&self.action, action),
+            (&self.resource, resource),
+        ];
+        // Enumerate candidates from the most selective dimension, and check
+        // the remaining dimensions directly against each candidate's key.
+        let (driver, buckets) = dims
+            .iter()
+            .copied()
+            .enumerate()
+            .filter_map(|(i, (index, query))| index.buckets(query).map(|b| (i, b)))
+            .min_by_key(|(_, buckets)| buckets.iter().map(|b| b.len()).sum::<usize>())?;
+        Some(
+            buckets
+                .into_iter()
+                .flatten()
+                .filter(|id| {
+                    dims.iter()
+                        .copied()
+                        .enumerate()
+                        .all(|(i, (index, query))| i == driver || index.matches(id, query))
+                })
+                .collect(),
+        )
+    }
+}
@@ -21,12 +21,14 @@
 //! the "authorization engine".
 
 use crate::ast::*;
-use crate::entities::Entities;
-use crate::evaluator::Evaluator;
+use crate::entities::{AncestorMode, Dereference, Entities, EntityStore};
+use crate::evaluator::{
+    self, CoverageReport, EvaluationCache, EvaluationLimits, Evaluator, TraceNode,
+};
 use crate::extensions::Extensions;
 use itertools::{Either, Itertools};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 #[cfg(feature = "wasm")]
@@ -46,17 +48,30 @@ pub struct Authorizer {
     extensions: &'static Extensions<'static>,
     /// Error-handling behavior of this `Authorizer`
     error_handling: ErrorHandling,
+    /// Limits on the work done to evaluate each policy, and the deadline for
+    /// each request
+    limits: EvaluationLimits,
 }
 
-/// Describes the possible Cedar error-handling modes.
-/// We currently only have one mode: [`ErrorHandling::Skip`].
-/// Other modes were debated during development, so this is here as an easy
-/// way to add modes if the future if we so decide.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ErrorHandling {
+/// Describes the possible Cedar error-handling modes, i.e., how a policy
+/// which encounters an evaluation error affects the authorization decision.
+///
+/// In every mode, the error itself is reported in the response diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify::Tsify))]
+#[cfg_attr(feature = "wasm", tsify(into_wasm_abi, from_wasm_abi))]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorHandling {
     /// If a policy encounters an evaluation error, skip it.  The decision will
-    /// be as if the erroring policy did not exist.
+    /// be as if the erroring policy did not exist. This is the default, and is
+    /// the behavior described by the Cedar language specification.
     Skip,
+    /// If any policy encounters an evaluation error, the decision is `Deny`,
+    /// and the erroring policies are reported as the reason for the decision.
+    DenyOnError,
+    /// If a `forbid` policy encounters an evaluation error, treat it as
+    /// satisfied. Erroring `permit` policies are skipped.
+    ForbidOnError,
 }
 
 impl Default for ErrorHandling {
@@ -71,15 +86,161 @@ impl Authorizer {
         Self {
             extensions: Extensions::all_available(), // set at compile time
             error_handling: Default::default(),
+            limits: EvaluationLimits::unlimited(),
+        }
+    }
+
+    /// Use the given error-handling mode for this `Authorizer`, instead of the
+    /// default [`ErrorHandling::Skip`]
+    pub fn with_error_handling(self, error_handling: ErrorHandling) -> Self {
+        Self {
+            error_handling,
+            ..self
         }
     }
 
+    /// Get the error-handling mode of this `Authorizer`
+    pub fn error_handling(&self) -> ErrorHandling {
+        self.error_handling
+    }
+
+    /// Impose `limits` on the evaluation of each policy. The limits on
+    /// steps, entity dereferences and set size apply to each policy
+    /// separately, not to the request as a whole; only the deadline bounds
+    /// the whole request. A policy which exceeds a limit results in an
+    /// evaluation error, which is handled according to the `Authorizer`'s
+    /// [`ErrorHandling`] mode.
+    pub fn with_limits(self, limits: EvaluationLimits) -> Self {
+        Self { limits, ..self }
+    }
+
+    /// Get the evaluation limits of this `Authorizer`
+    pub fn limits(&self) -> EvaluationLimits {
+        self.limits
+    }
+
+    /// Create an `Evaluator` for `q` using this `Authorizer`'s configuration
+    fn evaluator<'e>(&self, q: Request, entities: &'e dyn EntityStore) -> Evaluator<'e> {
+        Evaluator::new(q, entities, self.extensions).with_limits(self.limits)
+    }
+
     /// Returns an authorization response for `q` with respect to the given `Slice`.
     ///
     /// The language spec and formal model give a precise definition of how this is
     /// computed.
     pub fn is_authorized(&self, q: Request, pset: &PolicySet, entities: &Entities) -> Response {
-        self.is_authorized_core(q, pset, entities).concretize()
+        self.is_authorized_internal(q, pset, entities, None)
+    }
+
+    /// Returns an authorization response for `q` with respect to the given
+    /// `Slice`, sharing the results of extension constructor calls on
+    /// literals and of hierarchy searches with other calls through `cache`
+    /// (see [`EvaluationCache`]). `cache` must only be shared between calls
+    /// with the same `entities`.
+    ///
+    /// The response is the same as from [`Authorizer::is_authorized`].
+    pub fn is_authorized_with_cache(
+        &self,
+        q: Request,
+        pset: &PolicySet,
+        entities: &Entities,
+        cache: &EvaluationCache,
+    ) -> Response {
+        self.is_authorized_internal(q, pset, entities, Some(cache))
+    }
+
+    fn is_authorized_internal(
+        &self,
+        q: Request,
+        pset: &PolicySet,
+        entities: &Entities,
+        cache: Option<&EvaluationCache>,
+    ) -> Response {
+        // Policies whose scope cannot match `q` evaluate to `false` without
+        // error, so skipping them does not change the response.
+        let principal = scope_query(q.principal(), entities);
+        let action = scope_query(q.action(), entities);
+        let resource = scope_query(q.resource(), entities);
+        let policies = pset.policies_matching_scope(&principal, &action, &resource);
+        let eval = self.evaluator(q.clone(), entities);
+        let eval = match cache {
+            Some(cache) => eval.with_cache(cache),
+            None => eval,
+        };
+        self.evaluate_policies(&eval, q, policies, |_, _| ())
+            .concretize()
+    }
+
+    /// Returns an authorization response for `q` with respect to the given
+    /// policies, looking up entities in `store` as they are needed.
+    ///
+    /// Errors from `store` are reported as evaluation errors of the policies
+    /// which needed the entity. Policies are not selected using the scope
+    /// index, since that would require loading the ancestors of the
+    /// principal and resource up front.
+    pub fn is_authorized_with_store(
+        &self,
+        q: Request,
+        pset: &PolicySet,
+        store: &dyn EntityStore,
+    ) -> Response {
+        let eval = self.evaluator(q.clone(), store);
+        self.evaluate_policies(&eval, q, pset.policies(), |_, _| ())
+            .concretize()
+    }
+
+    /// Returns an authorization response for `q` with respect to the given
+    /// `Slice`, along with a trace of the evaluation of every policy in
+    /// `pset`, keyed by policy id.
+    ///
+    /// The response is the same as from [`Authorizer::is_authorized`], but
+    /// computing it is slower, because every subexpression evaluated is
+    /// recorded and no policies are skipped based on their scope.
+    pub fn is_authorized_with_trace(
+        &self,
+        q: Request,
+        pset: &PolicySet,
+        entities: &Entities,
+    ) -> (Response, HashMap<PolicyID, TraceNode>) {
+        let eval = self.evaluator(q.clone(), entities).with_tracing();
+        let mut traces = HashMap::new();
+        let response = self
+            .evaluate_policies(&eval, q, pset.policies(), |p, _| {
+                traces.extend(eval.take_trace().map(|trace| (p.id().clone(), trace)));
+            })
+            .concretize();
+        (response, traces)
+    }
+
+    /// Returns an authorization response for `q` with respect to the given
+    /// `Slice`, recording the coverage of the policies in `pset` in
+    /// `coverage`.
+    ///
+    /// The response is the same as from [`Authorizer::is_authorized`], but
+    /// computing it is slower, because every subexpression evaluated is
+    /// recorded and no policies are skipped based on their scope.
+    pub fn is_authorized_with_coverage(
+        &self,
+        q: Request,
+        pset: &PolicySet,
+        entities: &Entities,
+        coverage: &mut CoverageReport,
+    ) -> Response {
+        let eval = self.evaluator(q.clone(), entities).with_coverage();
+        coverage.add_policies(pset);
+        let response = self
+            .evaluate_policies(&eval, q, pset.policies(), |p, result| {
+                if let Some(recorder) = eval.take_coverage() {
+                    coverage.record_evaluation(
+                        p,
+                        recorder,
+                        matches!(result, Ok(Either::Left(true))),
+                    );
+                }
+            })
+            .concretize();
+        coverage.record_determining(&response.diagnostics.reason);
+        response
     }
 
     /// Returns an authorization response for `q` with respect to the given `Slice`.
@@ -91,7 +252,7 @@ impl Authorizer {
         pset: &PolicySet,
         entities: &Entities,
     ) -> PartialResponse {
-        let eval = Evaluator::new(q.clone(), entities, self.extensions);
+        let eval = self.evaluator(q.clone(), entities);
         self.is_authorized_core_internal(&eval, q, pset)
     }
 
@@ -102,6 +263,88 @@ impl Authorizer {
         eval: &Evaluator<'_>,
         q: Request,
         pset: &PolicySet,
+    ) -> PartialResponse {
+        self.evaluate_policies(eval, q, pset.policies(), |_, _| ())
+    }
+
+    /// Returns the entities in `entities` which, substituted for the unknown
+    /// resource of `q`, result in an `Allow` decision.
+    ///
+    /// Only entities of the type of the unknown resource (if it has one) are
+    /// considered. `q` is partially evaluated once, and only the resulting
+    /// residuals are evaluated for each entity.
+    #[cfg(feature = "partial-eval")]
+    pub fn query_resources(
+        &self,
+        q: Request,
+        pset: &PolicySet,
+        entities: &Entities,
+    ) -> Vec<EntityUID> {
+        let ty = match q.resource() {
+            EntityUIDEntry::Unknown { ty, .. } => ty.clone(),
+            // the resource is already known
+            EntityUIDEntry::Known { .. } => return vec![],
+        };
+        self.query(q, "resource", ty.as_ref(), pset, entities)
+    }
+
+    /// Returns the entities in `entities` which, substituted for the unknown
+    /// principal of `q`, result in an `Allow` decision.
+    ///
+    /// Only entities of the type of the unknown principal (if it has one) are
+    /// considered. `q` is partially evaluated once, and only the resulting
+    /// residuals are evaluated for each entity.
+    #[cfg(feature = "partial-eval")]
+    pub fn query_principals(
+        &self,
+        q: Request,
+        pset: &PolicySet,
+        entities: &Entities,
+    ) -> Vec<EntityUID> {
+        let ty = match q.principal() {
+            EntityUIDEntry::Unknown { ty, .. } => ty.clone(),
+            // the principal is already known
+            EntityUIDEntry::Known { .. } => return vec![],
+        };
+        self.query(q, "principal", ty.as_ref(), pset, entities)
+    }
+
+    /// Returns the entities in `entities` (of type `ty`, if given) which,
+    /// substituted for the unknown `var` of `q`, result in an `Allow`
+    /// decision, in sorted order
+    #[cfg(feature = "partial-eval")]
+    fn query(
+        &self,
+        q: Request,
+        var: &str,
+        ty: Option<&EntityType>,
+        pset: &PolicySet,
+        entities: &Entities,
+    ) -> Vec<EntityUID> {
+        let candidates = entities
+            .iter()
+            .map(Entity::uid)
+            .filter(|uid| ty.map_or(true, |ty| uid.entity_type() == ty));
+        let response = self.is_authorized_core(q, pset, entities);
+        let mut allowed = match response.decision() {
+            // the decision does not depend on `var`
+            Some(Decision::Allow) => candidates.cloned().collect(),
+            Some(Decision::Deny) => vec![],
+            None => response.allowed_substitutions(var, candidates, self, entities),
+        };
+        allowed.sort();
+        allowed
+    }
+
+    /// Evaluate each of `policies` and collect the results into a
+    /// `PartialResponse` for `q`. `evaluated` is called with each policy and
+    /// its result right after it is evaluated.
+    fn evaluate_policies<'a>(
+        &self,
+        eval: &Evaluator<'_>,
+        q: Request,
+        policies: impl Iterator<Item = &'a Policy>,
+        mut evaluated: impl FnMut(&Policy, &evaluator::Result<Either<bool, Expr>>),
     ) -> PartialResponse {
         let mut true_permits = vec![];
         let mut true_forbids = vec![];
@@ -111,9 +354,11 @@ impl Authorizer {
         let mut residual_forbids = vec![];
         let mut errors = vec![];
 
-        for p in pset.policies() {
+        for p in policies {
             let (id, annotations) = (p.id().clone(), p.annotations_arc().clone());
-            match eval.partial_evaluate(p) {
+            let result = eval.partial_evaluate(p);
+            evaluated(p, &result);
+            match result {
                 Ok(Either::Left(satisfied)) => match (satisfied, p.effect()) {
                     (true, Effect::Permit) => true_permits.push((id, annotations)),
                     (true, Effect::Forbid) => true_forbids.push((id, annotations)),
@@ -139,6 +384,12 @@ impl Authorizer {
                     });
                     let satisfied = match self.error_handling {
                         ErrorHandling::Skip => false,
+                        // Erroring permits are never satisfied. In
+                        // `DenyOnError` mode, `PartialResponse` ensures that
+                        // they still result in `Deny`.
+                        ErrorHandling::DenyOnError | ErrorHandling::ForbidOnError => {
+                            p.effect() == Effect::Forbid
+                        }
                     };
                     match (satisfied, p.effect()) {
                         (true, Effect::Permit) => true_permits.push((id, annotations)),
@@ -164,6 +415,32 @@ impl Authorizer {
             errors,
             Arc::new(q),
         )
+        .with_error_handling(self.error_handling)
+    }
+}
+
+/// Build the query used to look up the policies in the scope index which may
+/// apply to the request component `entry`
+fn scope_query<'a>(entry: &'a EntityUIDEntry, entities: &'a Entities) -> ScopeQuery<'a> {
+    match entry {
+        // the index needs all the ancestors of the entity, which a store with
+        // on-demand ancestors would have to search for
+        EntityUIDEntry::Known { .. } if entities.ancestor_mode() == AncestorMode::OnDemand => {
+            ScopeQuery::Unconstrained
+        }
+        EntityUIDEntry::Known { euid, .. } => match entities.entity(euid) {
+            Dereference::Data(entity) => ScopeQuery::Entity {
+                uid: euid,
+                entity: Some(entity),
+            },
+            Dereference::NoSuchEntity => ScopeQuery::Entity {
+                uid: euid,
+                entity: None,
+            },
+            // the ancestors of a residual entity are unknown
+            Dereference::Residual(_) => ScopeQuery::Unconstrained,
+        },
+        EntityUIDEntry::Unknown { .. } => ScopeQuery::Unconstrained,
     }
 }
 
@@ -193,7 +470,10 @@ impl std::fmt::Debug for Authorizer {
 mod test {
     use super::*;
     use crate::ast::Annotations;
+    use crate::entities::{err::EntityStoreError, NoEntitiesSchema, StoredEntity, TCComputation};
+    use crate::evaluator::EvaluationError;
     use crate::parser;
+    use cool_asserts::assert_matches;
 
     /// Sanity unit test case for is_authorized.
     /// More robust testing is accomplished through the integration tests.
@@ -271,6 +551,226 @@ mod test {
         assert_eq!(ans.decision, Decision::Deny);
     }
 
+    /// Build a policy set containing a satisfied permit, along with the given
+    /// erroring policies, and authorize an empty request against it
+    fn authorize_with_errors(error_handling: ErrorHandling, erroring: &[Effect]) -> Response {
+        let a = Authorizer::new().with_error_handling(error_handling);
+        let q = Request::new(
+            (EntityUID::with_eid("p"), None),
+            (EntityUID::with_eid("a"), None),
+            (EntityUID::with_eid("r"), None),
+            Context::empty(),
+            None::<&RequestSchemaAllPass>,
+            Extensions::none(),
+        )
+        .unwrap();
+        let mut pset = PolicySet::new();
+        pset.add_static(true_policy("ok", Effect::Permit)).unwrap();
+        for (i, effect) in erroring.iter().enumerate() {
+            let src = format!("{effect}(principal, action, resource) when {{ context.bad }};");
+            let id = PolicyID::from_string(format!("bad{i}"));
+            pset.add_static(parser::parse_policy(Some(id), &src).unwrap())
+                .unwrap();
+        }
+        a.is_authorized(q, &pset, &Entities::new())
+    }
+
+    #[test]
+    fn deny_on_error_tests() {
+        let ans = authorize_with_errors(ErrorHandling::DenyOnError, &[]);
+        assert_eq!(ans.decision, Decision::Allow);
+
+        let ans = authorize_with_errors(ErrorHandling::DenyOnError, &[Effect::Permit]);
+        assert_eq!(ans.decision, Decision::Deny);
+        assert_eq!(
+            ans.diagnostics.reason,
+            HashSet::from([PolicyID::from_string("bad0")])
+        );
+        assert_eq!(ans.diagnostics.errors.len(), 1);
+
+        let ans = authorize_with_errors(ErrorHandling::DenyOnError, &[Effect::Forbid]);
+        assert_eq!(ans.decision, Decision::Deny);
+        assert_eq!(
+            ans.diagnostics.reason,
+            HashSet::from([PolicyID::from_string("bad0")])
+        );
+    }
+
+    #[test]
+    fn forbid_on_error_tests() {
+        let ans = authorize_with_errors(ErrorHandling::ForbidOnError, &[Effect::Permit]);
+        assert_eq!(ans.decision, Decision::Allow);
+        assert_eq!(
+            ans.diagnostics.reason,
+            HashSet::from([PolicyID::from_string("ok")])
+        );
+        assert_eq!(ans.diagnostics.errors.len(), 1);
+
+        let ans = authorize_with_errors(
+            ErrorHandling::ForbidOnError,
+            &[Effect::Permit, Effect::Forbid],
+        );
+        assert_eq!(ans.decision, Decision::Deny);
+        assert_eq!(
+            ans.diagnostics.reason,
+            HashSet::from([PolicyID::from_string("bad1")])
+        );
+        assert_eq!(ans.diagnostics.errors.len(), 2);
+
+        let ans = authorize_with_errors(ErrorHandling::Skip, &[Effect::Permit, Effect::Forbid]);
+        assert_eq!(ans.decision, Decision::Allow);
+    }
+
+    #[test]
+    fn limits_produce_policy_errors() {
+        let q = Request::new(
+            (EntityUID::with_eid("p"), None),
+            (EntityUID::with_eid("a"), None),
+            (EntityUID::with_eid("r"), None),
+            Context::empty(),
+            None::<&RequestSchemaAllPass>,
+            Extensions::none(),
+        )
+        .unwrap();
+        let mut pset = PolicySet::new();
+        pset.add_static(
+            parser::parse_policy(
+                Some(PolicyID::from_string("big")),
+                "permit(principal, action, resource) when { [1, 2, 3].contains(1) };",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        pset.add_static(
+            parser::parse_policy(
+                Some(PolicyID::from_string("small")),
+                "forbid(principal, action, resource) when { [1].contains(2) };",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let limits = EvaluationLimits::unlimited().with_max_set_size(2);
+
+        let ans = Authorizer::new().is_authorized(q.clone(), &pset, &Entities::new());
+        assert_eq!(ans.decision, Decision::Allow);
+
+        let ans = Authorizer::new()
+            .with_limits(limits)
+            .is_authorized(q, &pset, &Entities::new());
+        assert_eq!(ans.decision, Decision::Deny);
+        assert_matches!(
+            ans.diagnostics.errors.as_slice(),
+            [AuthorizationError::PolicyEvaluationError { id, error: EvaluationError::EvaluationLimit(e) }] => {
+                assert_eq!(id, &PolicyID::from_string("big"));
+                assert_eq!(e.limit(), crate::evaluator::ExceededLimit::SetSize(2));
+            }
+        );
+    }
+
+    /// `EntityStore` which fails to load `Bad::"bad"`, and records the
+    /// entities it was asked for
+    struct TestStore {
+        entities: Entities,
+        requested: std::cell::RefCell<Vec<EntityUID>>,
+    }
+
+    impl EntityStore for TestStore {
+        fn entity(&self, uid: &EntityUID) -> Result<StoredEntity, EntityStoreError> {
+            self.requested.borrow_mut().push(uid.clone());
+            if uid == &EntityUID::with_eid_and_type("Bad", "bad").unwrap() {
+                Err(EntityStoreError::new("connection refused"))
+            } else {
+                EntityStore::entity(&self.entities, uid)
+            }
+        }
+    }
+
+    #[test]
+    fn is_authorized_with_store() {
+        let store = TestStore {
+            entities: Entities::from_entities(
+                [Entity::with_uid(EntityUID::with_eid("p"))],
+                None::<&NoEntitiesSchema>,
+                TCComputation::ComputeNow,
+                Extensions::none(),
+            )
+            .unwrap(),
+            requested: Default::default(),
+        };
+        let q = Request::new(
+            (EntityUID::with_eid("p"), None),
+            (EntityUID::with_eid("a"), None),
+            (EntityUID::with_eid("r"), None),
+            Context::empty(),
+            None::<&RequestSchemaAllPass>,
+            Extensions::none(),
+        )
+        .unwrap();
+        let pset = parser::parse_policyset(
+            r#"
+            permit(principal, action, resource) when { principal in test_entity_type::"g" };
+            forbid(principal, action, resource) when { Bad::"bad" has attr };
+            "#,
+        )
+        .unwrap();
+
+        let ans = Authorizer::new().is_authorized_with_store(q, &pset, &store);
+        assert_eq!(ans.decision, Decision::Deny);
+        assert_matches!(
+            ans.diagnostics.errors.as_slice(),
+            [AuthorizationError::PolicyEvaluationError { id, error: EvaluationError::EntityLoad(e) }] => {
+                assert_eq!(id, &PolicyID::from_string("policy1"));
+                assert_eq!(e.to_string(), r#"failed to load entity `Bad::"bad"`: connection refused"#);
+            }
+        );
+        // only the entities needed by the policies are requested
+        let mut requested = store.requested.take();
+        requested.sort();
+        assert_eq!(
+            requested,
+            vec![
+                EntityUID::with_eid_and_type("Bad", "bad").unwrap(),
+                EntityUID::with_eid("p"),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "partial-eval")]
+    fn error_handling_concretize_residuals() {
+        let q = Request::new(
+            (EntityUID::with_eid("p"), None),
+            (EntityUID::with_eid("a"), None),
+            (EntityUID::with_eid("r"), None),
+            Context::empty(),
+            None::<&RequestSchemaAllPass>,
+            Extensions::none(),
+        )
+        .unwrap();
+        let mut pset = PolicySet::new();
+        pset.add_static(true_policy("ok", Effect::Permit)).unwrap();
+        let src = r#"permit(principal, action, resource) when { unknown("test") };"#;
+        pset.add_static(
+            parser::parse_policy(Some(PolicyID::from_string("residual")), src).unwrap(),
+        )
+        .unwrap();
+        let es = Entities::new();
+
+        for (error_handling, decision) in [
+            (ErrorHandling::Skip, Decision::Allow),
+            (ErrorHandling::ForbidOnError, Decision::Allow),
+            (ErrorHandling::DenyOnError, Decision::Deny),
+        ] {
+            let a = Authorizer::new().with_error_handling(error_handling);
+            let r = a.is_authorized_core(q.clone(), &pset, &es);
+            match error_handling {
+                ErrorHandling::DenyOnError => assert_eq!(r.decision(), None),
+                _ => assert_eq!(r.decision(), Some(Decision::Allow)),
+            }
+            assert_eq!(r.concretize().decision, decision);
+        }
+    }
+
     fn true_policy(id: &str, e: Effect) -> StaticPolicy {
         let pid = PolicyID::from_string(id);
         StaticPolicy::new(
@@ -650,6 +1150,244 @@ mod test {
         assert!(r.residual_permits.contains_key(&PolicyID::from_string("2")));
         assert!(r.residual_forbids.is_empty());
     }
+
+    /// The scope index must not change the response: compare against
+    /// evaluating every policy in the set.
+    #[test]
+    fn scope_index_matches_full_evaluation() {
+        use std::collections::HashMap;
+
+        let a = Authorizer::new();
+        let mut pset = parser::parse_policyset(
+            r#"
+            @id("any") permit(principal, action, resource);
+            @id("eq") permit(principal == User::"alice", action, resource);
+            @id("in") permit(principal in Group::"all", action == Action::"write", resource);
+            @id("is") forbid(principal is Service, action, resource) when { context.bad };
+            @id("isin") permit(principal is User in Group::"admins", action in [Action::"readOnly"], resource);
+            @id("res") forbid(principal, action, resource == Doc::"secret") when { principal.missing };
+            @id("tmpl") permit(principal in ?principal, action, resource is Doc in ?resource);
+            "#,
+        )
+        .unwrap();
+        let any = pset
+            .policies()
+            .find(|p| p.annotation(&"id".parse().unwrap()).unwrap().val == "any")
+            .unwrap()
+            .id()
+            .clone();
+        pset.remove_static(&any).unwrap();
+        let template_id = pset
+            .all_templates()
+            .find(|t| t.slots().count() != 0)
+            .unwrap()
+            .id()
+            .clone();
+        pset.link(
+            template_id,
+            PolicyID::from_string("link"),
+            HashMap::from([
+                (SlotId::principal(), r#"Group::"admins""#.parse().unwrap()),
+                (SlotId::resource(), r#"Folder::"root""#.parse().unwrap()),
+            ]),
+        )
+        .unwrap();
+
+        let mut alice = Entity::with_uid(r#"User::"alice""#.parse().unwrap());
+        alice.add_parent(r#"Group::"admins""#.parse().unwrap());
+        alice.add_indirect_ancestor(r#"Group::"all""#.parse().unwrap());
+        let mut admins = Entity::with_uid(r#"Group::"admins""#.parse().unwrap());
+        admins.add_parent(r#"Group::"all""#.parse().unwrap());
+        let mut read = Entity::with_uid(r#"Action::"read""#.parse().unwrap());
+        read.add_parent(r#"Action::"readOnly""#.parse().unwrap());
+        let mut doc = Entity::with_uid(r#"Doc::"d""#.parse().unwrap());
+        doc.add_parent(r#"Folder::"root""#.parse().unwrap());
+        let entities = Entities::from_entities(
+            [alice, admins, read, doc],
+            None::<&crate::entities::NoEntitiesSchema>,
+            crate::entities::TCComputation::ComputeNow,
+            Extensions::none(),
+        )
+        .unwrap();
+
+        for principal in [r#"User::"alice""#, r#"User::"bob""#, r#"Service::"s""#] {
+            for action in [r#"Action::"read""#, r#"Action::"write""#] {
+                for resource in [r#"Doc::"d""#, r#"Doc::"secret""#] {
+                    let q = Request::new(
+                        (principal.parse().unwrap(), None),
+                        (action.parse().unwrap(), None),
+                        (resource.parse().unwrap(), None),
+                        Context::empty(),
+                        None::<&RequestSchemaAllPass>,
+                        Extensions::none(),
+                    )
+                    .unwrap();
+                    let indexed = a.is_authorized(q.clone(), &pset, &entities);
+                    let full = a.is_authorized_core(q, &pset, &entities).concretize();
+                    assert_eq!(indexed.decision, full.decision);
+                    assert_eq!(indexed.diagnostics.reason, full.diagnostics.reason);
+                    let errors = |r: &Response| {
+                        r.diagnostics
+                            .errors
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<HashSet<_>>()
+                    };
+                    assert_eq!(errors(&indexed), errors(&full));
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "partial-eval")]
+    fn query_matches_is_authorized() {
+        let a = Authorizer::new();
+        let pset = parser::parse_policyset(
+            r#"
+            permit(principal, action == Action::"read", resource in Folder::"public");
+            permit(principal, action, resource is Doc) when { resource.owner == principal };
+            permit(principal in Group::"admins", action, resource);
+            forbid(principal, action, resource) when { resource has secret && resource.secret };
+            "#,
+        )
+        .unwrap();
+        let entities = crate::entities::EntityJsonParser::new(
+            None::<&NoEntitiesSchema>,
+            Extensions::none(),
+            TCComputation::ComputeNow,
+        )
+        .from_json_value(serde_json::json!([
+            { "uid": { "type": "User", "id": "alice" }, "attrs": {}, "parents": [] },
+            { "uid": { "type": "User", "id": "bob" }, "attrs": {},
+              "parents": [{ "type": "Group", "id": "admins" }] },
+            { "uid": { "type": "Doc", "id": "a" }, "parents": [],
+              "attrs": { "owner": { "__entity": { "type": "User", "id": "alice" } } } },
+            { "uid": { "type": "Doc", "id": "b" }, "attrs": { "owner": "nobody" },
+              "parents": [{ "type": "Folder", "id": "public" }] },
+            { "uid": { "type": "Doc", "id": "c" }, "attrs": { "owner": "nobody", "secret": true },
+              "parents": [{ "type": "Folder", "id": "public" }] },
+            { "uid": { "type": "Doc", "id": "d" }, "attrs": { "owner": "nobody" }, "parents": [] },
+            { "uid": { "type": "Folder", "id": "public" }, "attrs": {}, "parents": [] }
+        ]))
+        .unwrap();
+        let users: Vec<EntityUID> = vec![
+            r#"User::"alice""#.parse().unwrap(),
+            r#"User::"bob""#.parse().unwrap(),
+        ];
+        let docs: Vec<EntityUID> = ["a", "b", "c", "d"]
+            .into_iter()
+            .map(|id| format!(r#"Doc::"{id}""#).parse().unwrap())
+            .collect();
+        let allowed = |principal: &EntityUID, action: &EntityUID, resource: &EntityUID| {
+            let q = Request::new_unchecked(
+                EntityUIDEntry::known(principal.clone(), None),
+                EntityUIDEntry::known(action.clone(), None),
+                EntityUIDEntry::known(resource.clone(), None),
+                Some(Context::empty()),
+            );
+            a.is_authorized(q, &pset, &entities).decision == Decision::Allow
+        };
+
+        for action in [r#"Action::"read""#, r#"Action::"write""#] {
+            let action: EntityUID = action.parse().unwrap();
+            for principal in &users {
+                let q = Request::new_unchecked(
+                    EntityUIDEntry::known(principal.clone(), None),
+                    EntityUIDEntry::known(action.clone(), None),
+                    EntityUIDEntry::unknown_with_type("Doc".parse().unwrap(), None),
+                    Some(Context::empty()),
+                );
+                let expected: Vec<_> = docs
+                    .iter()
+                    .filter(|doc| allowed(principal, &action, doc))
+                    .cloned()
+                    .collect();
+                assert_eq!(a.query_resources(q, &pset, &entities), expected);
+            }
+            for doc in &docs {
+                let q = Request::new_unchecked(
+                    EntityUIDEntry::unknown_with_type("User".parse().unwrap(), None),
+                    EntityUIDEntry::known(action.clone(), None),
+                    EntityUIDEntry::known(doc.clone(), None),
+                    Some(Context::empty()),
+                );
+                let expected: Vec<_> = users
+                    .iter()
+                    .filter(|user| allowed(user, &action, doc))
+                    .cloned()
+                    .collect();
+                assert_eq!(a.query_principals(q, &pset, &entities), expected);
+            }
+        }
+
+        // alice can read the public docs she doesn't own, except the secret one
+        let q = Request::new_unchecked(
+            EntityUIDEntry::known(r#"User::"alice""#.parse().unwrap(), None),
+            EntityUIDEntry::known(r#"Action::"read""#.parse().unwrap(), None),
+            EntityUIDEntry::unknown_with_type("Doc".parse().unwrap(), None),
+            Some(Context::empty()),
+        );
+        assert_eq!(
+            a.query_resources(q, &pset, &entities),
+            vec![
+                r#"Doc::"a""#.parse().unwrap(),
+                r#"Doc::"b""#.parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn trace_records_every_policy() {
+        use crate::evaluator::{TraceNode, TraceOutcome};
+
+        fn all_nodes(node: &TraceNode) -> Vec<&TraceNode> {
+            std::iter::once(node)
+                .chain(node.children().flat_map(all_nodes))
+                .collect()
+        }
+
+        let a = Authorizer::new();
+        let q = Request::new(
+            (EntityUID::with_eid("p"), None),
+            (EntityUID::with_eid("a"), None),
+            (EntityUID::with_eid("r"), None),
+            Context::empty(),
+            None::<&RequestSchemaAllPass>,
+            Extensions::none(),
+        )
+        .unwrap();
+        let pset = parser::parse_policyset(
+            r#"
+            permit(principal == test_entity_type::"other", action, resource);
+            permit(principal, action, resource) when { 1 > 2 && context.bad };
+            forbid(principal, action, resource) when { context.bad };
+            "#,
+        )
+        .unwrap();
+        let (response, traces) = a.is_authorized_with_trace(q.clone(), &pset, &Entities::new());
+        assert_eq!(response, a.is_authorized(q, &pset, &Entities::new()));
+        assert_eq!(traces.len(), 3);
+
+        let scope = &traces[&PolicyID::from_string("policy0")];
+        assert_eq!(scope.outcome(), &TraceOutcome::Value(false.into()));
+
+        let short_circuit = &traces[&PolicyID::from_string("policy1")];
+        assert_eq!(short_circuit.outcome(), &TraceOutcome::Value(false.into()));
+        let nodes = all_nodes(short_circuit);
+        assert!(nodes.iter().any(|n| n.short_circuited()));
+        assert!(nodes.iter().all(|n| n.source_loc().is_some()));
+        assert!(!nodes
+            .iter()
+            .any(|n| matches!(n.expr().expr_kind(), ExprKind::GetAttr { .. })));
+
+        let error = &traces[&PolicyID::from_string("policy2")];
+        assert_matches!(error.outcome(), TraceOutcome::Error(_));
+        assert!(all_nodes(error).iter().any(|n| matches!(
+            (n.expr().expr_kind(), n.outcome()),
+            (ExprKind::GetAttr { .. }, TraceOutcome::Error(_))
+        )));
+    }
 }
 
 /// Authorization response returned from the `Authorizer`
@@ -20,8 +20,8 @@ use either::Either;
 use std::sync::Arc;
 
 use super::{
-    Annotations, AuthorizationError, Decision, Effect, EntityUIDEntry, Expr, Policy, Request,
-    Response,
+    Annotations, AuthorizationError, Decision, Effect, EntityUIDEntry, ErrorHandling, Expr, Policy,
+    Request, Response,
 };
 use crate::{ast::PolicyID, evaluator::EvaluationError};
 
@@ -29,12 +29,12 @@ use crate::{ast::PolicyID, evaluator::EvaluationError};
 use smol_str::SmolStr;
 
 #[cfg(feature = "partial-eval")]
-use crate::{entities::Entities, evaluator::Evaluator};
+use crate::entities::Entities;
 
 #[cfg(feature = "partial-eval")]
 use super::{
     err::{ConcretizationError, ReauthorizationError},
-    Authorizer, Context, PolicySet, PolicySetError, Value,
+    Authorizer, Context, EntityUID, PolicySet, PolicySetError, Value,
 };
 
 type PolicyComponents<'a> = (Effect, &'a PolicyID, &'a Arc<Expr>, &'a Arc<Annotations>);
@@ -74,6 +74,8 @@ pub struct PartialResponse {
     true_expr: Arc<Expr>,
     /// The trivial `false` expression, used for materializing a residual for non-satisfied policies
     false_expr: Arc<Expr>,
+    /// The error-handling mode used to reach a decision
+    error_handling: ErrorHandling,
     /// The request associated with the partial response
     #[cfg(feature = "partial-eval")]
     request: Arc<Request>,
@@ -102,11 +104,37 @@ impl PartialResponse {
             errors: errors.into_iter().collect(),
             true_expr: Arc::new(Expr::val(true)),
             false_expr: Arc::new(Expr::val(false)),
+            error_handling: ErrorHandling::default(),
             #[cfg(feature = "partial-eval")]
             request: _request,
         }
     }
 
+    /// Use the given error-handling mode when reaching a decision. Errors
+    /// are always reported, but this determines how they affect the decision.
+    ///
+    /// Note that the categorization of erroring policies is determined by the
+    /// `Authorizer` which produced this response: e.g., in
+    /// [`ErrorHandling::ForbidOnError`] mode, erroring `forbid` policies are
+    /// reported as satisfied.
+    pub fn with_error_handling(self, error_handling: ErrorHandling) -> Self {
+        Self {
+            error_handling,
+            ..self
+        }
+    }
+
+    /// The error-handling mode used to reach a decision
+    pub fn error_handling(&self) -> ErrorHandling {
+        self.error_handling
+    }
+
+    /// The request this response answers
+    #[cfg(feature = "partial-eval")]
+    pub fn request(&self) -> &Request {
+        &self.request
+    }
+
     /// Convert this response into a concrete evaluation response.
     /// All residuals are treated as errors
     pub fn concretize(self) -> Response {
@@ -116,6 +144,18 @@ impl PartialResponse {
     /// Attempt to reach a partial decision; the presence of residuals may result in returning [`None`],
     /// indicating that a decision could not be reached given the unknowns
     pub fn decision(&self) -> Option<Decision> {
+        if self.error_handling == ErrorHandling::DenyOnError {
+            if self.definitely_errored().next().is_some() {
+                return Some(Decision::Deny);
+            }
+            // Any residual may still error, resulting in `Deny`
+            if self.satisfied_forbids.is_empty()
+                && !self.satisfied_permits.is_empty()
+                && !self.residual_permits.is_empty()
+            {
+                return None;
+            }
+        }
         match (
             !self.satisfied_forbids.is_empty(),
             !self.satisfied_permits.is_empty(),
@@ -331,14 +371,79 @@ impl PartialResponse {
         es: &Entities,
     ) -> Result<Self, ReauthorizationError> {
         let policyset = self.all_residual_policies()?;
+        self.reauthorize_residuals(&policyset, mapping, auth, es)
+    }
+
+    /// Returns the entities among `candidates` for which re-authorizing this
+    /// response, with the entity substituted for the unknown `var`, results
+    /// in an `Allow` decision.
+    ///
+    /// The residual policies are only constructed once, so this is faster
+    /// than calling [`Self::reauthorize`] for each candidate. Candidates for
+    /// which re-authorization fails are not allowed.
+    #[cfg(feature = "partial-eval")]
+    pub(crate) fn allowed_substitutions<'a>(
+        &self,
+        var: &str,
+        candidates: impl IntoIterator<Item = &'a EntityUID>,
+        auth: &Authorizer,
+        es: &Entities,
+    ) -> Vec<EntityUID> {
+        let Ok(policyset) = self.all_residual_policies() else {
+            return vec![];
+        };
+        candidates
+            .into_iter()
+            .filter(|uid| {
+                let mapping = HashMap::from([(SmolStr::new(var), Value::from((*uid).clone()))]);
+                self.reauthorize_residuals(&policyset, &mapping, auth, es)
+                    .is_ok_and(|response| response.concretize().decision == Decision::Allow)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Re-authorize `policyset`, which holds the residuals of this response,
+    /// given a mapping from unknowns to values
+    #[cfg(feature = "partial-eval")]
+    fn reauthorize_residuals(
+        &self,
+        policyset: &PolicySet,
+        mapping: &HashMap<SmolStr, Value>,
+        auth: &Authorizer,
+        es: &Entities,
+    ) -> Result<Self, ReauthorizationError> {
         let new_request = self.concretize_request(mapping)?;
         // Although this function takes a HashMap, keep the internal mapping function generic
         let unknowns_mapper =
             |unknown_name: &str| -> Option<Value> { mapping.get(unknown_name).cloned() };
         // Construct an evaluator resolving these specific unknown mappings
-        let eval = Evaluator::new(new_request.clone(), es, auth.extensions)
+        let eval = auth
+            .evaluator(new_request.clone(), es)
             .with_unknowns_mapper(Box::new(unknowns_mapper));
-        Ok(auth.is_authorized_core_internal(&eval, new_request, &policyset))
+        let mut response = auth.is_authorized_core_internal(&eval, new_request, policyset);
+        response.carry_errors_from(self);
+        Ok(response)
+    }
+
+    /// The residuals of the policies which errored in `previous` are `false`,
+    /// so re-authorizing them loses their errors. Mark them as errored again
+    /// and keep their errors, so that the error-handling mode still applies to
+    /// them: e.g., in [`ErrorHandling::DenyOnError`] mode, they still result
+    /// in `Deny`.
+    #[cfg(feature = "partial-eval")]
+    fn carry_errors_from(&mut self, previous: &Self) {
+        for (errored, false_policies) in [
+            (&previous.false_permits, &mut self.false_permits),
+            (&previous.false_forbids, &mut self.false_forbids),
+        ] {
+            for id in errored.iter().filter_map(did_error) {
+                if let Some((state, _)) = false_policies.get_mut(id) {
+                    *state = ErrorState::Error;
+                }
+            }
+        }
+        self.errors.extend(previous.errors.iter().cloned());
     }
 
     #[cfg(feature = "partial-eval")]
@@ -471,6 +576,28 @@ impl EntityUIDEntry {
 
 impl From<PartialResponse> for Response {
     fn from(p: PartialResponse) -> Self {
+        // Residuals are treated as errors. In the `Skip` mode, they are simply
+        // not satisfied; in the other modes, some of them deny the request.
+        let denying_errors: Vec<PolicyID> = match p.error_handling {
+            ErrorHandling::Skip => vec![],
+            ErrorHandling::ForbidOnError => p.residual_forbids.keys().cloned().collect(),
+            ErrorHandling::DenyOnError => p
+                .residual_forbids
+                .keys()
+                .chain(p.residual_permits.keys())
+                .chain(p.definitely_errored())
+                .cloned()
+                .collect(),
+        };
+        if !denying_errors.is_empty() {
+            let reason = p
+                .satisfied_forbids
+                .keys()
+                .cloned()
+                .chain(denying_errors)
+                .collect();
+            return Response::new(Decision::Deny, reason, p.errors().collect());
+        }
         let decision = if !p.satisfied_permits.is_empty() && p.satisfied_forbids.is_empty() {
             Decision::Allow
         } else {
@@ -849,4 +976,48 @@ mod test {
             Some(Decision::Deny)
         );
     }
+
+    #[test]
+    #[cfg(feature = "partial-eval")]
+    fn reauthorize_keeps_errors() {
+        // `policy0` errors, because `principal` has no attribute `level`
+        let policies = parse_policyset(
+            r#"
+            permit(principal, action, resource) when { principal.level > 3 };
+            permit(principal, action, resource) when { resource == NS::"b" };
+        "#,
+        )
+        .unwrap();
+        let partial_request = Request {
+            principal: EntityUIDEntry::known(r#"NS::"a""#.parse().unwrap(), None),
+            action: EntityUIDEntry::known(r#"Action::"view""#.parse().unwrap(), None),
+            resource: EntityUIDEntry::unknown(),
+            context: Some(Context::empty()),
+        };
+        let entities = Entities::new();
+        let mapping = HashMap::from([(
+            "resource".into(),
+            EntityUID::from_normalized_str(r#"NS::"b""#).unwrap().into(),
+        )]);
+
+        for (error_handling, decision) in [
+            (ErrorHandling::Skip, Decision::Allow),
+            (ErrorHandling::DenyOnError, Decision::Deny),
+        ] {
+            let authorizer = Authorizer::new().with_error_handling(error_handling);
+            let partial_response =
+                authorizer.is_authorized_core(partial_request.clone(), &policies, &entities);
+            let response = partial_response
+                .reauthorize(&mapping, &authorizer, &entities)
+                .unwrap();
+            assert_eq!(
+                response.definitely_errored().collect::<Vec<_>>(),
+                vec![&PolicyID::from_string("policy0")]
+            );
+            assert_eq!(response.decision(), Some(decision));
+            let response = response.concretize();
+            assert_eq!(response.decision, decision);
+            assert_eq!(response.diagnostics.errors.len(), 1);
+        }
+    }
 }
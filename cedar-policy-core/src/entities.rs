@@ -18,8 +18,10 @@
 
 use crate::ast::*;
 use crate::extensions::Extensions;
-use crate::transitive_closure::{compute_tc, enforce_tc_and_dag};
-use std::collections::{hash_map, HashMap};
+use crate::transitive_closure::{compute_tc, enforce_dag_from, enforce_tc_and_dag, update_tc};
+use educe::Educe;
+use itertools::Either;
+use std::collections::{hash_map, HashMap, HashSet};
 use std::sync::Arc;
 
 /// Module for checking that entities conform with a schema
@@ -28,11 +30,19 @@ pub mod conformance;
 pub mod err;
 pub mod json;
 use json::err::JsonSerializationError;
+mod store;
+pub use store::{EntityStore, StoredEntity};
+mod patch;
+pub use patch::{EntityPatch, EntityPatchOp};
+mod diff;
+pub use diff::{EntitiesDiff, EntityChange};
+mod index;
+use index::EntityIndexes;
 
 pub use json::{
     AllEntitiesNoAttrsSchema, AttributeType, CedarValueJson, ContextJsonParser, ContextSchema,
-    EntityJson, EntityJsonParser, EntityTypeDescription, EntityUidJson, FnAndArg, NoEntitiesSchema,
-    NoStaticContext, Schema, SchemaType, TypeAndId,
+    EntityChangeJson, EntityJson, EntityJsonParser, EntityPatchOpJson, EntityTypeDescription,
+    EntityUidJson, FnAndArg, NoEntitiesSchema, NoStaticContext, Schema, SchemaType, TypeAndId,
 };
 
 use conformance::EntitySchemaConformanceChecker;
@@ -45,17 +55,28 @@ use err::*;
 /// `from_json_*()` and `write_to_json()` methods here, or the `proto` module in
 /// `cedar-policy`, which is capable of ser/de both Core types like this and
 /// `cedar-policy` types.
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Educe, Clone, Debug, Default)]
+#[educe(PartialEq, Eq)]
 pub struct Entities {
     /// Important internal invariant: for any `Entities` object that exists,
     /// the `ancestor` relation is transitively closed.
     entities: HashMap<EntityUID, Arc<Entity>>,
 
+    /// Indexes of the children of each `EntityUID`, the entities of each type
+    /// and the entities with each attribute value, kept up to date as
+    /// entities are added, updated and removed
+    #[educe(PartialEq(ignore))]
+    indexes: EntityIndexes,
+
     /// The mode flag determines whether this store functions as a partial store or
     /// as a fully concrete store.
     /// Mode::Concrete means that the store is fully concrete, and failed dereferences are an error.
     /// Mode::Partial means the store is partial, and failed dereferences result in a residual.
     mode: Mode,
+
+    /// Whether the `ancestor` relation is stored transitively closed, as
+    /// described above, or only direct parents are stored
+    ancestor_mode: AncestorMode,
 }
 
 impl Entities {
@@ -63,7 +84,9 @@ impl Entities {
     pub fn new() -> Self {
         Self {
             entities: HashMap::new(),
+            indexes: EntityIndexes::default(),
             mode: Mode::default(),
+            ancestor_mode: AncestorMode::default(),
         }
     }
 
@@ -74,7 +97,9 @@ impl Entities {
     pub fn partial(self) -> Self {
         Self {
             entities: self.entities,
+            indexes: self.indexes,
             mode: Mode::Partial,
+            ancestor_mode: self.ancestor_mode,
         }
     }
 
@@ -131,17 +156,16 @@ impl Entities {
         extensions: &Extensions<'_>,
     ) -> Result<Self> {
         let checker = schema.map(|schema| EntitySchemaConformanceChecker::new(schema, extensions));
-        for entity in collection.into_iter() {
+        let mut changed = Vec::new();
+        for mut entity in collection.into_iter() {
             if let Some(checker) = checker.as_ref() {
                 checker.validate_entity(&entity)?;
             }
-            update_entity_map(&mut self.entities, entity, false)?;
+            self.ancestor_mode.prepare(&mut entity);
+            changed.push(entity.uid().clone());
+            update_entity_map(&mut self.entities, Some(&mut self.indexes), entity, false)?;
         }
-        match tc_computation {
-            TCComputation::AssumeAlreadyComputed => (),
-            TCComputation::EnforceAlreadyComputed => enforce_tc_and_dag(&self.entities)?,
-            TCComputation::ComputeNow => compute_tc(&mut self.entities, true)?,
-        };
+        self.restore_tc(changed, tc_computation)?;
         Ok(self)
     }
 
@@ -155,12 +179,14 @@ impl Entities {
         collection: impl IntoIterator<Item = EntityUID>,
         tc_computation: TCComputation,
     ) -> Result<Self> {
+        let mut changed = Vec::new();
         for uid_to_remove in collection.into_iter() {
             match self.entities.remove(&uid_to_remove) {
                 None => (),
                 Some(entity_to_remove) => {
-                    for entity in self.entities.values_mut() {
-                        if entity.is_descendant_of(&uid_to_remove) {
+                    self.indexes.remove(&entity_to_remove);
+                    for descendant_uid in self.descendants_of(&uid_to_remove) {
+                        if let Some(entity) = self.entities.get_mut(&descendant_uid) {
                             // remove any direct or indirect link between `entity` and `entity_to_remove`
                             Arc::make_mut(entity).remove_indirect_ancestor(&uid_to_remove);
                             Arc::make_mut(entity).remove_parent(&uid_to_remove);
@@ -170,14 +196,12 @@ impl Entities {
                             }
                         }
                     }
+                    // the children of `entity_to_remove` no longer have it as a parent
+                    changed.extend(self.indexes.remove_children(&uid_to_remove));
                 }
             }
         }
-        match tc_computation {
-            TCComputation::AssumeAlreadyComputed => (),
-            TCComputation::EnforceAlreadyComputed => enforce_tc_and_dag(&self.entities)?,
-            TCComputation::ComputeNow => compute_tc(&mut self.entities, true)?,
-        }
+        self.restore_tc(changed, tc_computation)?;
         Ok(self)
     }
 
@@ -201,20 +225,181 @@ impl Entities {
         extensions: &Extensions<'_>,
     ) -> Result<Self> {
         let checker = schema.map(|schema| EntitySchemaConformanceChecker::new(schema, extensions));
-        for entity in collection.into_iter() {
+        let mut changed = Vec::new();
+        for mut entity in collection.into_iter() {
             if let Some(checker) = checker.as_ref() {
                 checker.validate_entity(&entity)?;
             }
-            update_entity_map(&mut self.entities, entity, true)?;
+            self.ancestor_mode.prepare(&mut entity);
+            changed.push(entity.uid().clone());
+            update_entity_map(&mut self.entities, Some(&mut self.indexes), entity, true)?;
+        }
+        self.restore_tc(changed, tc_computation)?;
+        Ok(self)
+    }
+
+    /// How this store keeps the ancestors of its entities
+    pub fn ancestor_mode(&self) -> AncestorMode {
+        self.ancestor_mode
+    }
+
+    /// Change how this store keeps the ancestors of its entities. Switching to
+    /// [`AncestorMode::OnDemand`] drops the indirect ancestors of every entity,
+    /// and switching to [`AncestorMode::Precomputed`] computes them.
+    ///
+    /// Setting the mode on an empty store, before adding entities to it, avoids
+    /// the cost of converting.
+    ///
+    /// Returns an error if the hierarchy has a cycle, which can be the case if
+    /// entities were added with [`TCComputation::AssumeAlreadyComputed`].
+    pub fn with_ancestor_mode(mut self, ancestor_mode: AncestorMode) -> Result<Self> {
+        if ancestor_mode != self.ancestor_mode {
+            match ancestor_mode {
+                AncestorMode::OnDemand => {
+                    for entity in self.entities.values_mut() {
+                        ancestor_mode.prepare(entity);
+                    }
+                    enforce_dag_from(&self.entities, self.entities.keys().cloned())?;
+                }
+                AncestorMode::Precomputed => compute_tc(&mut self.entities, true)?,
+            }
+            self.ancestor_mode = ancestor_mode;
         }
-        match tc_computation {
-            TCComputation::AssumeAlreadyComputed => (),
-            TCComputation::EnforceAlreadyComputed => enforce_tc_and_dag(&self.entities)?,
-            TCComputation::ComputeNow => compute_tc(&mut self.entities, true)?,
-        };
         Ok(self)
     }
 
+    /// Get the (direct and indirect) ancestors of `entity`, which must be an
+    /// entity in this store. Like the ancestors stored in an entity, these
+    /// include parents which are not themselves entities in this store.
+    pub fn ancestors<'a>(&'a self, entity: &'a Entity) -> impl Iterator<Item = &'a EntityUID> {
+        match self.ancestor_mode {
+            AncestorMode::Precomputed => Either::Left(entity.ancestors()),
+            AncestorMode::OnDemand => {
+                Either::Right(self.search_ancestors(entity, |_| false).0.into_iter())
+            }
+        }
+    }
+
+    /// Is `entity`, which must be an entity in this store, a (direct or
+    /// indirect) descendant of `ancestor`?
+    pub fn is_descendant_of(&self, entity: &Entity, ancestor: &EntityUID) -> bool {
+        match self.ancestor_mode {
+            AncestorMode::Precomputed => entity.is_descendant_of(ancestor),
+            AncestorMode::OnDemand => self.search_ancestors(entity, |uid| uid == ancestor).1,
+        }
+    }
+
+    /// Search the hierarchy for the ancestors of `entity`, stopping early if
+    /// `stop` returns `true` for one of them. Returns the ancestors found and
+    /// whether the search stopped early.
+    fn search_ancestors<'a>(
+        &'a self,
+        entity: &'a Entity,
+        stop: impl Fn(&EntityUID) -> bool,
+    ) -> (HashSet<&'a EntityUID>, bool) {
+        let mut ancestors = HashSet::new();
+        let mut worklist = entity.ancestors().collect::<Vec<_>>();
+        while let Some(uid) = worklist.pop() {
+            if ancestors.insert(uid) {
+                if stop(uid) {
+                    return (ancestors, true);
+                }
+                if let Some(ancestor) = self.entities.get(uid) {
+                    worklist.extend(ancestor.ancestors());
+                }
+            }
+        }
+        (ancestors, false)
+    }
+
+    /// Restore the transitive closure after the parents of the entities in
+    /// `changed` were updated, according to `tc_computation`
+    fn restore_tc(
+        &mut self,
+        changed: impl IntoIterator<Item = EntityUID>,
+        tc_computation: TCComputation,
+    ) -> Result<()> {
+        match (self.ancestor_mode, tc_computation) {
+            // there is no transitive closure to assume, enforce or compute,
+            // but the hierarchy must still be a DAG
+            (AncestorMode::OnDemand, _) => enforce_dag_from(&self.entities, changed)?,
+            (AncestorMode::Precomputed, TCComputation::AssumeAlreadyComputed) => (),
+            (AncestorMode::Precomputed, TCComputation::EnforceAlreadyComputed) => {
+                enforce_tc_and_dag(&self.entities)?
+            }
+            (AncestorMode::Precomputed, TCComputation::ComputeNow) => {
+                let indexes = &self.indexes;
+                update_tc(
+                    &mut self.entities,
+                    changed,
+                    |uid| indexes.children(uid).cloned(),
+                    true,
+                )?;
+            }
+        };
+        Ok(())
+    }
+
+    /// The (direct and indirect) descendants of `uid`, found through the
+    /// `children` index rather than by searching every entity
+    fn descendants_of(&self, uid: &EntityUID) -> HashSet<EntityUID> {
+        let mut descendants = HashSet::new();
+        let mut worklist = vec![uid];
+        while let Some(uid) = worklist.pop() {
+            for child in self.indexes.children(uid) {
+                if descendants.insert(child.clone()) {
+                    worklist.push(child);
+                }
+            }
+        }
+        descendants
+    }
+
+    /// Iterate over the entities of type `ty`
+    pub fn entities_of_type<'a>(&'a self, ty: &EntityType) -> impl Iterator<Item = &'a Entity> {
+        self.indexed(self.indexes.of_type(ty))
+    }
+
+    /// Iterate over the entities which have `uid` as a direct parent. `uid`
+    /// need not be an entity in this store.
+    pub fn children<'a>(&'a self, uid: &EntityUID) -> impl Iterator<Item = &'a Entity> {
+        self.indexed(self.indexes.children(uid))
+    }
+
+    /// Iterate over the (direct and indirect) descendants of `uid`, that is
+    /// the entities `e` for which `e in uid` holds, other than `uid` itself.
+    /// `uid` need not be an entity in this store.
+    pub fn descendants<'a>(&'a self, uid: &EntityUID) -> impl Iterator<Item = &'a Entity> {
+        self.descendants_of(uid)
+            .into_iter()
+            .filter_map(move |uid| self.entities.get(&uid).map(AsRef::as_ref))
+    }
+
+    /// Iterate over the entities whose attribute `attr` is equal to `value`.
+    /// Entities whose value for `attr` is a residual are never returned.
+    ///
+    /// The index of attribute values is built by the first call, which takes
+    /// time linear in the size of the store, and then kept up to date as
+    /// entities are added and removed.
+    pub fn entities_with_attr<'a>(
+        &'a self,
+        attr: &str,
+        value: &Value,
+    ) -> impl Iterator<Item = &'a Entity> {
+        self.indexed(
+            self.indexes
+                .with_attr(attr, value, || self.entities.values().map(AsRef::as_ref)),
+        )
+    }
+
+    /// The entities with the UIDs from an index
+    fn indexed<'a>(
+        &'a self,
+        uids: impl Iterator<Item = &'a EntityUID>,
+    ) -> impl Iterator<Item = &'a Entity> {
+        uids.filter_map(|uid| self.entities.get(uid).map(AsRef::as_ref))
+    }
+
     /// Create an `Entities` object with the given entities.
     ///
     /// If `schema` is present, then action entities from that schema will also
@@ -281,9 +466,12 @@ impl Entities {
                     .map(|e: Arc<Entity>| (e.uid().clone(), e)),
             );
         }
+        let indexes = EntityIndexes::new(entity_map.values().map(AsRef::as_ref));
         Ok(Self {
             entities: entity_map,
+            indexes,
             mode: Mode::default(),
+            ancestor_mode: AncestorMode::default(),
         })
     }
 
@@ -297,6 +485,23 @@ impl Entities {
         self.entities.is_empty()
     }
 
+    /// Returns `true` if `self` and `other` share all of their entities, as
+    /// when one is an unmodified clone of the other. Unlike `==`, which
+    /// compares entities by uid, this detects any update to an entity, and
+    /// doesn't need to compare entity data. `Entities` with the same data
+    /// which don't share entities are not the same snapshot.
+    pub fn same_snapshot(&self, other: &Self) -> bool {
+        self.mode == other.mode
+            && self.ancestor_mode == other.ancestor_mode
+            && self.entities.len() == other.entities.len()
+            && self.entities.iter().all(|(uid, entity)| {
+                other
+                    .entities
+                    .get(uid)
+                    .is_some_and(|e| Arc::ptr_eq(entity, e))
+            })
+    }
+
     /// Convert an `Entities` object into a JSON value suitable for parsing in
     /// via `EntityJsonParser`.
     ///
@@ -408,7 +613,7 @@ fn create_entity_map(
 ) -> Result<HashMap<EntityUID, Arc<Entity>>> {
     let mut map: HashMap<EntityUID, Arc<Entity>> = HashMap::new();
     for e in es {
-        update_entity_map(&mut map, e, false)?;
+        update_entity_map(&mut map, None, e, false)?;
     }
     Ok(map)
 }
@@ -418,14 +623,20 @@ fn create_entity_map(
 /// with the same EntityUID as the specified entity. If such an entity is found and is
 /// not structurally equal to the specified entity produces an error. Otherwise,
 /// if a structurally equal entity is found, the state of the map is unchanged.
+/// Also updates the indexes of the map, if there are any.
 fn update_entity_map(
     map: &mut HashMap<EntityUID, Arc<Entity>>,
+    indexes: Option<&mut EntityIndexes>,
     entity: Arc<Entity>,
     allow_override: bool,
 ) -> Result<()> {
     match map.entry(entity.uid().clone()) {
         hash_map::Entry::Occupied(mut occupied_entry) => {
             if allow_override {
+                if let Some(indexes) = indexes {
+                    indexes.remove(occupied_entry.get());
+                    indexes.insert(&entity);
+                }
                 occupied_entry.insert(entity);
             } else {
                 // Check whether the occupying entity is structurally equal to the
@@ -437,6 +648,9 @@ fn update_entity_map(
             }
         }
         hash_map::Entry::Vacant(v) => {
+            if let Some(indexes) = indexes {
+                indexes.insert(&entity);
+            }
             v.insert(entity);
         }
     }
@@ -553,6 +767,40 @@ pub enum TCComputation {
     ComputeNow,
 }
 
+/// Describes how an [`Entities`] keeps the ancestors of its entities
+///
+/// Both modes give the same results for `in` checks; they trade memory for
+/// the cost of those checks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AncestorMode {
+    /// Store every direct and indirect ancestor of each entity, computing the
+    /// transitive closure of the hierarchy as entities are added, so that
+    /// checking whether one entity is an ancestor of another is a single
+    /// lookup
+    #[default]
+    Precomputed,
+    /// Store only the direct parents of each entity, and search the
+    /// hierarchy when an indirect ancestor is needed. This uses much less
+    /// memory for deep or wide hierarchies, where entities have many indirect
+    /// ancestors. An `Evaluator` remembers the ancestors it has searched for,
+    /// so each entity's ancestors are searched for at most once per request
+    /// (or per batch of requests sharing an `EvaluationCache`).
+    ///
+    /// In this mode the `TCComputation` passed when adding entities is
+    /// ignored: there is no transitive closure to assume, enforce or compute,
+    /// and the hierarchy is always checked for cycles.
+    OnDemand,
+}
+
+impl AncestorMode {
+    /// Get `entity` ready to be stored in this mode
+    fn prepare(self, entity: &mut Arc<Entity>) {
+        if self == Self::OnDemand && entity.indirect_ancestors().next().is_some() {
+            Arc::make_mut(entity).remove_all_indirect_ancestors();
+        }
+    }
+}
+
 // PANIC SAFETY: Unit Test Code
 #[allow(clippy::panic)]
 #[cfg(test)]
@@ -2159,6 +2407,37 @@ mod entities_tests {
         assert!(es.is_empty());
     }
 
+    #[test]
+    fn test_same_snapshot() {
+        let (e0, e1, e2, e3) = test_entities();
+        let build = || {
+            Entities::from_entities(
+                vec![e0.clone(), e1.clone(), e2.clone(), e3.clone()],
+                None::<&NoEntitiesSchema>,
+                TCComputation::ComputeNow,
+                Extensions::all_available(),
+            )
+            .expect("Failed to construct entities")
+        };
+        let es = build();
+        assert!(es.same_snapshot(&es.clone()));
+        // equal data, but not shared
+        let rebuilt = build();
+        assert_eq!(es, rebuilt);
+        assert!(!es.same_snapshot(&rebuilt));
+        let extended = es
+            .clone()
+            .add_entities(
+                [Arc::new(Entity::with_uid(EntityUID::with_eid("other")))],
+                None::<&NoEntitiesSchema>,
+                TCComputation::ComputeNow,
+                Extensions::all_available(),
+            )
+            .expect("Failed to add entity");
+        assert!(!es.same_snapshot(&extended));
+        assert!(!extended.same_snapshot(&es));
+    }
+
     #[test]
     fn test_iter() {
         let (e0, e1, e2, e3) = test_entities();
@@ -2351,6 +2630,628 @@ mod entities_tests {
         // Assert that there is no longer an edge from F to E
         assert!(!f.is_descendant_of(&eid));
     }
+
+    /// helper function: an entity with the given eid and parents
+    fn entity_with_parents(eid: &str, parents: &[&str]) -> Arc<Entity> {
+        let mut entity = Entity::with_uid(EntityUID::with_eid(eid));
+        for parent in parents {
+            entity.add_parent(EntityUID::with_eid(parent));
+        }
+        Arc::new(entity)
+    }
+
+    /// helper function: check that the ancestors in `entities` are the same as
+    /// if the transitive closure was computed from scratch
+    fn assert_tc_from_scratch(entities: &Entities) {
+        let from_scratch = Entities::from_entities(
+            entities.iter().map(|e| {
+                let mut e = e.clone();
+                e.remove_all_indirect_ancestors();
+                e
+            }),
+            None::<&NoEntitiesSchema>,
+            TCComputation::ComputeNow,
+            Extensions::all_available(),
+        )
+        .expect("Failed to construct entities");
+        for entity in entities.iter() {
+            let expected = from_scratch.entity(entity.uid()).unwrap();
+            assert_eq!(
+                entity.ancestors().collect::<HashSet<_>>(),
+                expected.ancestors().collect::<HashSet<_>>(),
+                "ancestors of {}",
+                entity.uid()
+            );
+        }
+    }
+
+    /// helper function: check that the indexes of `entities` are the same as
+    /// if they were built from scratch
+    fn assert_indexes_from_scratch(entities: &Entities) {
+        let expected = EntityIndexes::new(entities.iter());
+        // the attribute index is only built when first used, so build it in
+        // both (if it was already built in `entities`, it was kept up to date)
+        for indexes in [&entities.indexes, &expected] {
+            indexes
+                .with_attr("", &Value::from(0), || entities.iter())
+                .for_each(drop);
+        }
+        assert_eq!(entities.indexes, expected);
+    }
+
+    /// helper function: an entity of type `ty` with a `level` attribute
+    fn entity_with_level(ty: &str, eid: &str, level: i64, parents: &[&str]) -> Arc<Entity> {
+        Arc::new(Entity::new_with_attr_partial_value(
+            EntityUID::with_eid_and_type(ty, eid).unwrap(),
+            [("level".into(), PartialValue::from(level))],
+            HashSet::new(),
+            parents
+                .iter()
+                .map(|parent| EntityUID::with_eid_and_type("Group", parent).unwrap())
+                .collect(),
+            [],
+        ))
+    }
+
+    /// helper function: the sorted eids of `entities`
+    fn eids<'a>(entities: impl Iterator<Item = &'a Entity>) -> Vec<String> {
+        let mut eids: Vec<String> = entities
+            .map(|e| <Eid as AsRef<str>>::as_ref(e.uid().eid()).to_owned())
+            .collect();
+        eids.sort();
+        eids
+    }
+
+    #[test]
+    fn test_indexes() {
+        let user = EntityType::from_normalized_str("User").unwrap();
+        let group = EntityType::from_normalized_str("Group").unwrap();
+        let group_uid = |eid| EntityUID::with_eid_and_type("Group", eid).unwrap();
+        for ancestor_mode in [AncestorMode::Precomputed, AncestorMode::OnDemand] {
+            // Groups: eng -> all, ops -> all
+            let entities = Entities::new()
+                .with_ancestor_mode(ancestor_mode)
+                .unwrap()
+                .add_entities(
+                    [
+                        entity_with_level("User", "alice", 1, &["eng"]),
+                        entity_with_level("User", "bob", 2, &["ops"]),
+                        entity_with_level("Group", "eng", 1, &["all"]),
+                        entity_with_level("Group", "ops", 1, &["all"]),
+                    ],
+                    None::<&NoEntitiesSchema>,
+                    TCComputation::ComputeNow,
+                    Extensions::all_available(),
+                )
+                .unwrap();
+            assert_indexes_from_scratch(&entities);
+            assert_eq!(eids(entities.entities_of_type(&user)), ["alice", "bob"]);
+            assert_eq!(eids(entities.entities_of_type(&group)), ["eng", "ops"]);
+            // `all` is not itself an entity in the store
+            assert_eq!(eids(entities.children(&group_uid("all"))), ["eng", "ops"]);
+            assert_eq!(
+                eids(entities.descendants(&group_uid("all"))),
+                ["alice", "bob", "eng", "ops"]
+            );
+            assert_eq!(eids(entities.descendants(&group_uid("eng"))), ["alice"]);
+            assert_eq!(
+                eids(entities.entities_with_attr("level", &Value::from(1))),
+                ["alice", "eng", "ops"]
+            );
+            assert_eq!(
+                eids(entities.entities_with_attr("level", &Value::from("1"))).len(),
+                0
+            );
+            assert_eq!(
+                eids(entities.entities_with_attr("name", &Value::from(1))).len(),
+                0
+            );
+
+            // updating an entity moves it in the indexes
+            let entities = entities
+                .upsert_entities(
+                    [entity_with_level("User", "alice", 2, &["ops"])],
+                    None::<&NoEntitiesSchema>,
+                    TCComputation::ComputeNow,
+                    Extensions::all_available(),
+                )
+                .unwrap();
+            assert_indexes_from_scratch(&entities);
+            assert_eq!(eids(entities.descendants(&group_uid("eng"))).len(), 0);
+            assert_eq!(
+                eids(entities.descendants(&group_uid("ops"))),
+                ["alice", "bob"]
+            );
+            assert_eq!(
+                eids(entities.entities_with_attr("level", &Value::from(2))),
+                ["alice", "bob"]
+            );
+
+            // removing an entity removes it from the indexes, and its children
+            // lose it as a parent
+            let entities = entities
+                .remove_entities([group_uid("ops")], TCComputation::ComputeNow)
+                .unwrap();
+            assert_indexes_from_scratch(&entities);
+            assert_eq!(eids(entities.entities_of_type(&group)), ["eng"]);
+            assert_eq!(eids(entities.children(&group_uid("ops"))).len(), 0);
+            assert_eq!(eids(entities.descendants(&group_uid("all"))), ["eng"]);
+            assert_eq!(
+                eids(entities.entities_with_attr("level", &Value::from(1))),
+                ["eng"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_upsert_updates_descendants() {
+        // Original Hierarchy
+        // A -> B -> C
+        // D -> E
+        // X
+        let entities = Entities::from_entities(
+            ["A", "B", "C", "D", "E", "X"].into_iter().map(|eid| {
+                let parents: &[&str] = match eid {
+                    "A" => &["B"],
+                    "B" => &["C"],
+                    "D" => &["E"],
+                    _ => &[],
+                };
+                Arc::unwrap_or_clone(entity_with_parents(eid, parents))
+            }),
+            None::<&NoEntitiesSchema>,
+            TCComputation::ComputeNow,
+            Extensions::all_available(),
+        )
+        .expect("Failed to construct entities");
+        let before = entities.clone();
+
+        // Add C -> D, so that A and B gain D and E as ancestors
+        let entities = entities
+            .upsert_entities(
+                [entity_with_parents("C", &["D"])],
+                None::<&NoEntitiesSchema>,
+                TCComputation::ComputeNow,
+                &Extensions::all_available(),
+            )
+            .expect("Failed to upsert entities");
+        assert_tc_from_scratch(&entities);
+        let a = entities.entity(&EntityUID::with_eid("A")).unwrap();
+        assert!(a.is_descendant_of(&EntityUID::with_eid("E")));
+        // Entities outside the affected part of the hierarchy are untouched,
+        // and still shared with the original store
+        for eid in ["D", "E", "X"] {
+            let uid = EntityUID::with_eid(eid);
+            assert!(Arc::ptr_eq(
+                &entities.entities[&uid],
+                &before.entities[&uid]
+            ));
+        }
+
+        // Now remove B -> C again, and add E -> A, which would be a cycle if
+        // B -> C were still there
+        let entities = entities
+            .upsert_entities(
+                [
+                    entity_with_parents("B", &[]),
+                    entity_with_parents("E", &["A"]),
+                ],
+                None::<&NoEntitiesSchema>,
+                TCComputation::ComputeNow,
+                &Extensions::all_available(),
+            )
+            .expect("Failed to upsert entities");
+        assert_tc_from_scratch(&entities);
+        let a = entities.entity(&EntityUID::with_eid("A")).unwrap();
+        assert!(!a.is_descendant_of(&EntityUID::with_eid("E")));
+        let c = entities.entity(&EntityUID::with_eid("C")).unwrap();
+        assert!(c.is_descendant_of(&EntityUID::with_eid("B")));
+
+        // Removing A means C and D lose A and B as ancestors
+        let entities = entities
+            .remove_entities([EntityUID::with_eid("A")], TCComputation::ComputeNow)
+            .expect("Failed to remove entities");
+        assert_tc_from_scratch(&entities);
+        let c = entities.entity(&EntityUID::with_eid("C")).unwrap();
+        assert!(!c.is_descendant_of(&EntityUID::with_eid("B")));
+    }
+
+    #[test]
+    fn test_on_demand_ancestors() {
+        // A -> B -> C -> D
+        let chain = || {
+            [
+                entity_with_parents("A", &["B"]),
+                entity_with_parents("B", &["C"]),
+                entity_with_parents("C", &["D"]),
+                entity_with_parents("D", &[]),
+            ]
+        };
+        let entities = Entities::new()
+            .with_ancestor_mode(AncestorMode::OnDemand)
+            .unwrap()
+            .add_entities(
+                chain(),
+                None::<&NoEntitiesSchema>,
+                TCComputation::ComputeNow,
+                Extensions::all_available(),
+            )
+            .expect("Failed to construct entities");
+        let a = entities.entity(&EntityUID::with_eid("A")).unwrap();
+        // only the parent is stored
+        assert_eq!(
+            a.ancestors().collect::<Vec<_>>(),
+            vec![&EntityUID::with_eid("B")]
+        );
+        // but all the ancestors can be found
+        assert_eq!(
+            entities.ancestors(a).collect::<HashSet<_>>(),
+            HashSet::from([
+                &EntityUID::with_eid("B"),
+                &EntityUID::with_eid("C"),
+                &EntityUID::with_eid("D")
+            ])
+        );
+        assert!(entities.is_descendant_of(a, &EntityUID::with_eid("D")));
+        assert!(!entities.is_descendant_of(a, &EntityUID::with_eid("A")));
+
+        // converting to precomputed ancestors gives the same store as
+        // computing them in the first place
+        let precomputed = entities
+            .clone()
+            .with_ancestor_mode(AncestorMode::Precomputed)
+            .unwrap();
+        let expected = Entities::new()
+            .add_entities(
+                chain(),
+                None::<&NoEntitiesSchema>,
+                TCComputation::ComputeNow,
+                Extensions::all_available(),
+            )
+            .unwrap();
+        for entity in expected.iter() {
+            assert!(entity.deep_eq(precomputed.entity(entity.uid()).unwrap()));
+        }
+        // and back again
+        let on_demand = precomputed
+            .with_ancestor_mode(AncestorMode::OnDemand)
+            .unwrap();
+        assert!(on_demand
+            .iter()
+            .all(|e| e.indirect_ancestors().next().is_none()));
+
+        // cycles are still rejected
+        assert_matches!(
+            entities.clone().upsert_entities(
+                [entity_with_parents("D", &["A"])],
+                None::<&NoEntitiesSchema>,
+                TCComputation::ComputeNow,
+                &Extensions::all_available(),
+            ),
+            Err(EntitiesError::TransitiveClosureError(_))
+        );
+
+        // removing C leaves A -> B
+        let entities = entities
+            .remove_entities([EntityUID::with_eid("C")], TCComputation::ComputeNow)
+            .unwrap();
+        let a = entities.entity(&EntityUID::with_eid("A")).unwrap();
+        assert!(entities.is_descendant_of(a, &EntityUID::with_eid("B")));
+        assert!(!entities.is_descendant_of(a, &EntityUID::with_eid("D")));
+    }
+
+    #[test]
+    fn test_on_demand_ancestors_reject_assumed_cycles() {
+        // A -> B -> A, with the TC (wrongly) assumed to be computed
+        let cycle = || {
+            [
+                entity_with_parents("A", &["B"]),
+                entity_with_parents("B", &["A"]),
+            ]
+        };
+        let cyclic = Entities::from_entities(
+            cycle().map(Arc::unwrap_or_clone),
+            None::<&NoEntitiesSchema>,
+            TCComputation::AssumeAlreadyComputed,
+            Extensions::all_available(),
+        )
+        .unwrap();
+        // a store with on-demand ancestors is always a DAG, so it can't be
+        // made from a cyclic one
+        assert_matches!(
+            cyclic.with_ancestor_mode(AncestorMode::OnDemand),
+            Err(EntitiesError::TransitiveClosureError(_))
+        );
+        // nor can the cycle be added to it
+        assert_matches!(
+            Entities::new()
+                .with_ancestor_mode(AncestorMode::OnDemand)
+                .unwrap()
+                .add_entities(
+                    cycle(),
+                    None::<&NoEntitiesSchema>,
+                    TCComputation::AssumeAlreadyComputed,
+                    Extensions::all_available(),
+                ),
+            Err(EntitiesError::TransitiveClosureError(_))
+        );
+    }
+
+    #[test]
+    fn test_upsert_cycle() {
+        // A -> B -> C
+        let entities = Entities::from_entities(
+            [
+                entity_with_parents("A", &["B"]),
+                entity_with_parents("B", &["C"]),
+                entity_with_parents("C", &[]),
+            ]
+            .into_iter()
+            .map(Arc::unwrap_or_clone),
+            None::<&NoEntitiesSchema>,
+            TCComputation::ComputeNow,
+            Extensions::all_available(),
+        )
+        .expect("Failed to construct entities");
+        // Adding C -> A makes a cycle
+        assert_matches!(
+            entities.upsert_entities(
+                [entity_with_parents("C", &["A"])],
+                None::<&NoEntitiesSchema>,
+                TCComputation::ComputeNow,
+                &Extensions::all_available(),
+            ),
+            Err(EntitiesError::TransitiveClosureError(_))
+        );
+    }
+
+    /// helper function: check that `entities` and `expected` contain the same
+    /// entities, with the same attributes, tags, parents and ancestors
+    fn assert_same_entities(entities: &Entities, expected: &Entities) {
+        assert_eq!(entities.len(), expected.len());
+        assert_indexes_from_scratch(entities);
+        for expected in expected.iter() {
+            let entity = entities.entity(expected.uid()).unwrap();
+            assert_eq!(
+                entity.attrs().collect::<Vec<_>>(),
+                expected.attrs().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                entity.tags().collect::<Vec<_>>(),
+                expected.tags().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                entity.parents().collect::<HashSet<_>>(),
+                expected.parents().collect::<HashSet<_>>(),
+                "parents of {}",
+                entity.uid()
+            );
+            assert_eq!(
+                entity.ancestors().collect::<HashSet<_>>(),
+                expected.ancestors().collect::<HashSet<_>>(),
+                "ancestors of {}",
+                entity.uid()
+            );
+        }
+    }
+
+    /// helper function: the hierarchy A -> B -> C, D -> C
+    fn patch_test_entities(ancestor_mode: AncestorMode) -> Entities {
+        Entities::new()
+            .with_ancestor_mode(ancestor_mode)
+            .unwrap()
+            .add_entities(
+                [
+                    entity_with_parents("A", &["B"]),
+                    entity_with_parents("B", &["C"]),
+                    entity_with_parents("C", &[]),
+                    entity_with_parents("D", &["C"]),
+                ],
+                None::<&NoEntitiesSchema>,
+                TCComputation::ComputeNow,
+                Extensions::all_available(),
+            )
+            .expect("Failed to construct entities")
+    }
+
+    /// helper function: parse a change-set without a schema
+    fn parse_patch(json: serde_json::Value) -> EntityPatch {
+        EntityJsonParser::new(
+            None::<&NoEntitiesSchema>,
+            Extensions::all_available(),
+            TCComputation::ComputeNow,
+        )
+        .patch_from_json_value(json)
+        .expect("Failed to parse change-set")
+    }
+
+    #[test]
+    fn test_apply_patch_and_inverse() {
+        for ancestor_mode in [AncestorMode::Precomputed, AncestorMode::OnDemand] {
+            let original = patch_test_entities(ancestor_mode);
+            let mut entities = original.clone();
+            let patch = parse_patch(serde_json::json!([
+                { "op": "setAttr", "uid": { "type": "test_entity_type", "id": "A" }, "attr": "age", "value": 7 },
+                { "op": "setTag", "uid": { "type": "test_entity_type", "id": "A" }, "tag": "t", "value": "x" },
+                { "op": "removeParent", "uid": { "type": "test_entity_type", "id": "B" }, "parent": { "type": "test_entity_type", "id": "C" } },
+                { "op": "addParent", "uid": { "type": "test_entity_type", "id": "B" }, "parent": { "type": "test_entity_type", "id": "D" } },
+                { "op": "put", "entity": { "uid": { "type": "test_entity_type", "id": "E" }, "attrs": {}, "parents": [{ "type": "test_entity_type", "id": "A" }] } },
+                { "op": "remove", "uid": { "type": "test_entity_type", "id": "C" } },
+                { "op": "setAttr", "uid": { "type": "test_entity_type", "id": "A" }, "attr": "age", "value": 8 },
+            ]));
+            let inverse = entities
+                .apply_patch(
+                    patch,
+                    None::<&NoEntitiesSchema>,
+                    Extensions::all_available(),
+                )
+                .unwrap();
+            assert_eq!(entities.ancestor_mode(), ancestor_mode);
+
+            let a = entities.entity(&EntityUID::with_eid("A")).unwrap();
+            assert_eq!(a.get("age"), Some(&PartialValue::from(8)));
+            assert_eq!(a.get_tag("t"), Some(&PartialValue::from("x")));
+            let e = entities.entity(&EntityUID::with_eid("E")).unwrap();
+            // E -> A -> B -> D -> C, where C no longer exists
+            for ancestor in ["A", "B", "D", "C"] {
+                assert!(entities.is_descendant_of(e, &EntityUID::with_eid(ancestor)));
+            }
+            assert_matches!(
+                entities.entity(&EntityUID::with_eid("C")),
+                Dereference::NoSuchEntity
+            );
+            if ancestor_mode == AncestorMode::Precomputed {
+                assert_tc_from_scratch(&entities);
+            }
+
+            // the inverse undoes the changes, in reverse order, and survives a
+            // round trip through JSON
+            let inverse = parse_patch(inverse.to_json_value().unwrap());
+            assert_eq!(inverse.len(), 7);
+            assert_matches!(inverse.ops().next(), Some(EntityPatchOp::SetAttr { .. }));
+            entities
+                .apply_patch(
+                    inverse,
+                    None::<&NoEntitiesSchema>,
+                    Extensions::all_available(),
+                )
+                .unwrap();
+            assert_same_entities(&entities, &original);
+        }
+    }
+
+    #[test]
+    fn test_apply_patch_no_ops() {
+        let mut entities = patch_test_entities(AncestorMode::Precomputed);
+        // removing things which aren't there doesn't need undoing
+        let patch = parse_patch(serde_json::json!([
+            { "op": "remove", "uid": { "type": "test_entity_type", "id": "Z" } },
+            { "op": "removeAttr", "uid": { "type": "test_entity_type", "id": "A" }, "attr": "age" },
+            { "op": "removeParent", "uid": { "type": "test_entity_type", "id": "A" }, "parent": { "type": "test_entity_type", "id": "C" } },
+            { "op": "addParent", "uid": { "type": "test_entity_type", "id": "A" }, "parent": { "type": "test_entity_type", "id": "B" } },
+        ]));
+        let inverse = entities
+            .apply_patch(
+                patch,
+                None::<&NoEntitiesSchema>,
+                Extensions::all_available(),
+            )
+            .unwrap();
+        assert!(inverse.is_empty());
+        assert_same_entities(&entities, &patch_test_entities(AncestorMode::Precomputed));
+    }
+
+    #[test]
+    fn test_apply_patch_is_atomic() {
+        for ancestor_mode in [AncestorMode::Precomputed, AncestorMode::OnDemand] {
+            let original = patch_test_entities(ancestor_mode);
+            let mut entities = original.clone();
+            // C -> A makes a cycle, so none of the changes are applied
+            let patch = parse_patch(serde_json::json!([
+                { "op": "setAttr", "uid": { "type": "test_entity_type", "id": "A" }, "attr": "age", "value": 7 },
+                { "op": "removeParent", "uid": { "type": "test_entity_type", "id": "D" }, "parent": { "type": "test_entity_type", "id": "C" } },
+                { "op": "addParent", "uid": { "type": "test_entity_type", "id": "C" }, "parent": { "type": "test_entity_type", "id": "A" } },
+            ]));
+            assert_matches!(
+                entities.apply_patch(
+                    patch,
+                    None::<&NoEntitiesSchema>,
+                    Extensions::all_available()
+                ),
+                Err(EntitiesError::TransitiveClosureError(_))
+            );
+            assert_same_entities(&entities, &original);
+
+            // updating an entity after removing it fails
+            let patch = parse_patch(serde_json::json!([
+                { "op": "remove", "uid": { "type": "test_entity_type", "id": "A" } },
+                { "op": "setAttr", "uid": { "type": "test_entity_type", "id": "A" }, "attr": "age", "value": 7 },
+            ]));
+            assert_matches!(
+                entities.apply_patch(patch, None::<&NoEntitiesSchema>, Extensions::all_available()),
+                Err(EntitiesError::MissingEntity(e)) => {
+                    assert_eq!(e.euid(), &EntityUID::with_eid("A"));
+                }
+            );
+            assert_same_entities(&entities, &original);
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        for ancestor_mode in [AncestorMode::Precomputed, AncestorMode::OnDemand] {
+            let old = patch_test_entities(ancestor_mode);
+            assert!(Entities::diff(&old, &old.clone()).is_empty());
+
+            let mut new = old.clone();
+            let patch = parse_patch(serde_json::json!([
+                { "op": "setAttr", "uid": { "type": "test_entity_type", "id": "A" }, "attr": "age", "value": 7 },
+                { "op": "setTag", "uid": { "type": "test_entity_type", "id": "A" }, "tag": "t", "value": "x" },
+                { "op": "removeParent", "uid": { "type": "test_entity_type", "id": "B" }, "parent": { "type": "test_entity_type", "id": "C" } },
+                { "op": "addParent", "uid": { "type": "test_entity_type", "id": "B" }, "parent": { "type": "test_entity_type", "id": "D" } },
+                { "op": "put", "entity": { "uid": { "type": "test_entity_type", "id": "E" }, "attrs": {}, "parents": [{ "type": "test_entity_type", "id": "A" }] } },
+                { "op": "remove", "uid": { "type": "test_entity_type", "id": "C" } },
+            ]));
+            new.apply_patch(
+                patch,
+                None::<&NoEntitiesSchema>,
+                Extensions::all_available(),
+            )
+            .unwrap();
+
+            // `A`'s ancestors changed too, but not its parents
+            let diff = Entities::diff(&old, &new);
+            assert_eq!(
+                diff.changes().map(ToString::to_string).collect::<Vec<_>>(),
+                vec![
+                    r#"attribute `age` was added to `test_entity_type::"A"` with value 7"#,
+                    r#"tag `t` was added to `test_entity_type::"A"` with value "x""#,
+                    r#"parent `test_entity_type::"D"` was added to `test_entity_type::"B"`"#,
+                    r#"parent `test_entity_type::"C"` was removed from `test_entity_type::"B"`"#,
+                    r#"entity `test_entity_type::"C"` was removed"#,
+                    r#"entity `test_entity_type::"E"` was added"#,
+                ]
+            );
+            assert_matches!(diff.changes().last(), Some(EntityChange::EntityAdded(e)) => {
+                assert_eq!(e.ancestors().collect::<Vec<_>>(), vec![&EntityUID::with_eid("A")]);
+            });
+            assert_eq!(
+                diff.to_json_value().unwrap(),
+                serde_json::json!([
+                    { "change": "attributeChanged", "uid": { "type": "test_entity_type", "id": "A" }, "attr": "age", "new": 7 },
+                    { "change": "tagChanged", "uid": { "type": "test_entity_type", "id": "A" }, "tag": "t", "new": "x" },
+                    { "change": "parentAdded", "uid": { "type": "test_entity_type", "id": "B" }, "parent": { "type": "test_entity_type", "id": "D" } },
+                    { "change": "parentRemoved", "uid": { "type": "test_entity_type", "id": "B" }, "parent": { "type": "test_entity_type", "id": "C" } },
+                    { "change": "entityRemoved", "entity": { "uid": { "type": "test_entity_type", "id": "C" }, "attrs": {}, "parents": [] } },
+                    { "change": "entityAdded", "entity": { "uid": { "type": "test_entity_type", "id": "E" }, "attrs": {}, "parents": [{ "type": "test_entity_type", "id": "A" }] } },
+                ])
+            );
+
+            // changed and removed values are reported with their old values
+            let mut newer = new.clone();
+            let patch = parse_patch(serde_json::json!([
+                { "op": "setAttr", "uid": { "type": "test_entity_type", "id": "A" }, "attr": "age", "value": 8 },
+                { "op": "removeTag", "uid": { "type": "test_entity_type", "id": "A" }, "tag": "t" },
+            ]));
+            newer
+                .apply_patch(
+                    patch,
+                    None::<&NoEntitiesSchema>,
+                    Extensions::all_available(),
+                )
+                .unwrap();
+            assert_eq!(
+                Entities::diff(&new, &newer)
+                    .changes()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                vec![
+                    r#"attribute `age` of `test_entity_type::"A"` changed from 7 to 8"#,
+                    r#"tag `t` was removed from `test_entity_type::"A"` (was "x")"#,
+                ]
+            );
+        }
+    }
 }
 
 // PANIC SAFETY: Unit Test Code
@@ -0,0 +1,276 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::err::Result;
+use super::json::{err::JsonSerializationError, EntityChangeJson};
+use super::patch::with_parents_only;
+use super::Entities;
+use crate::ast::{Entity, EntityUID, PartialValue};
+use itertools::{EitherOrBoth, Itertools};
+use smol_str::SmolStr;
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+use std::sync::Arc;
+
+/// A single difference between two [`Entities`]
+#[derive(Debug, Clone)]
+pub enum EntityChange {
+    /// An entity was added. The entity's ancestors are only its direct
+    /// parents.
+    EntityAdded(Arc<Entity>),
+    /// An entity was removed. The entity's ancestors are only its direct
+    /// parents.
+    EntityRemoved(Arc<Entity>),
+    /// An attribute of an entity was added, removed or changed
+    AttributeChanged {
+        /// UID of the entity
+        uid: EntityUID,
+        /// Name of the attribute
+        attr: SmolStr,
+        /// Old value of the attribute, or `None` if it was added
+        old: Option<PartialValue>,
+        /// New value of the attribute, or `None` if it was removed
+        new: Option<PartialValue>,
+    },
+    /// A tag of an entity was added, removed or changed
+    TagChanged {
+        /// UID of the entity
+        uid: EntityUID,
+        /// Name of the tag
+        tag: SmolStr,
+        /// Old value of the tag, or `None` if it was added
+        old: Option<PartialValue>,
+        /// New value of the tag, or `None` if it was removed
+        new: Option<PartialValue>,
+    },
+    /// A parent was added to an entity
+    ParentAdded {
+        /// UID of the entity
+        uid: EntityUID,
+        /// UID of the new parent
+        parent: EntityUID,
+    },
+    /// A parent was removed from an entity
+    ParentRemoved {
+        /// UID of the entity
+        uid: EntityUID,
+        /// UID of the former parent
+        parent: EntityUID,
+    },
+}
+
+impl EntityChange {
+    /// UID of the entity this change is to
+    pub fn uid(&self) -> &EntityUID {
+        match self {
+            Self::EntityAdded(entity) | Self::EntityRemoved(entity) => entity.uid(),
+            Self::AttributeChanged { uid, .. }
+            | Self::TagChanged { uid, .. }
+            | Self::ParentAdded { uid, .. }
+            | Self::ParentRemoved { uid, .. } => uid,
+        }
+    }
+}
+
+impl Display for EntityChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EntityAdded(entity) => write!(f, "entity `{}` was added", entity.uid()),
+            Self::EntityRemoved(entity) => write!(f, "entity `{}` was removed", entity.uid()),
+            Self::AttributeChanged {
+                uid,
+                attr,
+                old,
+                new,
+            } => fmt_value_change(f, "attribute", attr, uid, old.as_ref(), new.as_ref()),
+            Self::TagChanged { uid, tag, old, new } => {
+                fmt_value_change(f, "tag", tag, uid, old.as_ref(), new.as_ref())
+            }
+            Self::ParentAdded { uid, parent } => {
+                write!(f, "parent `{parent}` was added to `{uid}`")
+            }
+            Self::ParentRemoved { uid, parent } => {
+                write!(f, "parent `{parent}` was removed from `{uid}`")
+            }
+        }
+    }
+}
+
+fn fmt_value_change(
+    f: &mut fmt::Formatter<'_>,
+    kind: &str,
+    name: &str,
+    uid: &EntityUID,
+    old: Option<&PartialValue>,
+    new: Option<&PartialValue>,
+) -> fmt::Result {
+    match (old, new) {
+        (Some(old), Some(new)) => {
+            write!(f, "{kind} `{name}` of `{uid}` changed from {old} to {new}")
+        }
+        (None, Some(new)) => write!(f, "{kind} `{name}` was added to `{uid}` with value {new}"),
+        (Some(old), None) => {
+            write!(f, "{kind} `{name}` was removed from `{uid}` (was {old})")
+        }
+        (None, None) => write!(f, "{kind} `{name}` of `{uid}` is unchanged"),
+    }
+}
+
+/// The differences between two [`Entities`], as computed by
+/// [`Entities::diff()`]
+#[derive(Debug, Clone, Default)]
+pub struct EntitiesDiff {
+    changes: Vec<EntityChange>,
+}
+
+impl EntitiesDiff {
+    /// Iterate over the changes, which are grouped by entity and ordered by
+    /// entity UID
+    pub fn changes(&self) -> impl Iterator<Item = &EntityChange> {
+        self.changes.iter()
+    }
+
+    /// Number of changes
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Are the two [`Entities`] the same?
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Convert this diff into a JSON value: an array of changes in the format
+    /// of `EntityChangeJson`
+    pub fn to_json_value(&self) -> Result<serde_json::Value> {
+        let changes = self.to_change_jsons()?;
+        serde_json::to_value(changes)
+            .map_err(JsonSerializationError::from)
+            .map_err(Into::into)
+    }
+
+    /// Dump this diff into a JSON file: an array of changes in the format of
+    /// `EntityChangeJson`
+    pub fn write_to_json(&self, f: impl std::io::Write) -> Result<()> {
+        let changes = self.to_change_jsons()?;
+        serde_json::to_writer_pretty(f, &changes).map_err(JsonSerializationError::from)?;
+        Ok(())
+    }
+
+    fn to_change_jsons(&self) -> Result<Vec<EntityChangeJson>> {
+        self.changes
+            .iter()
+            .map(EntityChangeJson::from_change)
+            .collect::<std::result::Result<_, JsonSerializationError>>()
+            .map_err(Into::into)
+    }
+}
+
+impl IntoIterator for EntitiesDiff {
+    type Item = EntityChange;
+
+    type IntoIter = std::vec::IntoIter<EntityChange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl Entities {
+    /// Compute the differences between `old` and `new`: the entities which
+    /// were added or removed, and for the entities in both, the attributes and
+    /// tags whose values changed and the direct parents which were added or
+    /// removed.
+    ///
+    /// Only direct parents are compared, so an entity whose indirect ancestors
+    /// changed only because some other entity's parents changed is not
+    /// reported.
+    pub fn diff(old: &Entities, new: &Entities) -> EntitiesDiff {
+        let uids: BTreeSet<&EntityUID> = old.entities.keys().chain(new.entities.keys()).collect();
+        let mut changes = Vec::new();
+        for uid in uids {
+            match (old.entities.get(uid), new.entities.get(uid)) {
+                (Some(old), Some(new)) => diff_entity(old, new, &mut changes),
+                (Some(old), None) => {
+                    changes.push(EntityChange::EntityRemoved(with_parents_only(old.clone())))
+                }
+                (None, Some(new)) => {
+                    changes.push(EntityChange::EntityAdded(with_parents_only(new.clone())))
+                }
+                (None, None) => (),
+            }
+        }
+        EntitiesDiff { changes }
+    }
+}
+
+/// Push the changes from `old` to `new`, two versions of the same entity, onto
+/// `changes`
+fn diff_entity(old: &Arc<Entity>, new: &Arc<Entity>, changes: &mut Vec<EntityChange>) {
+    // stores are often cloned from one another, sharing unchanged entities
+    if Arc::ptr_eq(old, new) {
+        return;
+    }
+    let uid = old.uid();
+    for (attr, old, new) in diff_values(old.attrs(), new.attrs()) {
+        changes.push(EntityChange::AttributeChanged {
+            uid: uid.clone(),
+            attr,
+            old,
+            new,
+        });
+    }
+    for (tag, old, new) in diff_values(old.tags(), new.tags()) {
+        changes.push(EntityChange::TagChanged {
+            uid: uid.clone(),
+            tag,
+            old,
+            new,
+        });
+    }
+    let old_parents: BTreeSet<&EntityUID> = old.parents().collect();
+    let new_parents: BTreeSet<&EntityUID> = new.parents().collect();
+    for parent in new_parents.difference(&old_parents) {
+        changes.push(EntityChange::ParentAdded {
+            uid: uid.clone(),
+            parent: (*parent).clone(),
+        });
+    }
+    for parent in old_parents.difference(&new_parents) {
+        changes.push(EntityChange::ParentRemoved {
+            uid: uid.clone(),
+            parent: (*parent).clone(),
+        });
+    }
+}
+
+/// The names whose values differ between `old` and `new`, which must both be
+/// sorted by name, with their old and new values
+fn diff_values<'a>(
+    old: impl Iterator<Item = (&'a SmolStr, &'a PartialValue)>,
+    new: impl Iterator<Item = (&'a SmolStr, &'a PartialValue)>,
+) -> Vec<(SmolStr, Option<PartialValue>, Option<PartialValue>)> {
+    old.merge_join_by(new, |(old, _), (new, _)| old.cmp(new))
+        .filter_map(|values| match values {
+            EitherOrBoth::Both((_, old), (_, new)) if old == new => None,
+            EitherOrBoth::Both((name, old), (_, new)) => {
+                Some((name.clone(), Some(old.clone()), Some(new.clone())))
+            }
+            EitherOrBoth::Left((name, old)) => Some((name.clone(), Some(old.clone()), None)),
+            EitherOrBoth::Right((name, new)) => Some((name.clone(), None, Some(new.clone()))),
+        })
+        .collect()
+}
@@ -17,10 +17,12 @@
 use super::EntityUID;
 use crate::transitive_closure;
 use miette::Diagnostic;
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Errors in serializing, deserializing, and processing of Entities
 #[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
 pub enum EntitiesError {
     /// Error occurring in serialization of entities
     #[error("error during entity serialization")]
@@ -44,12 +46,20 @@ pub enum EntitiesError {
     #[error("entity does not conform to the schema")]
     #[diagnostic(transparent)]
     InvalidEntity(#[from] crate::entities::conformance::err::EntitySchemaConformanceError),
+    /// Error because a change-set updates an entity which doesn't exist
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    MissingEntity(MissingEntity),
 }
 
 impl EntitiesError {
     pub(crate) fn duplicate(euid: EntityUID) -> Self {
         Self::Duplicate(Duplicate { euid })
     }
+
+    pub(crate) fn missing_entity(euid: EntityUID) -> Self {
+        Self::MissingEntity(MissingEntity { euid })
+    }
 }
 
 impl From<transitive_closure::TcError<EntityUID>> for EntitiesError {
@@ -96,5 +106,49 @@ impl Duplicate {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Error, Diagnostic)]
+#[error("cannot update `{}` because it does not exist", .euid)]
+#[diagnostic(help("add the entity with a `put` operation before updating it"))]
+/// Error type for change-sets which update an entity that isn't in the entity set
+pub struct MissingEntity {
+    /// The [`EntityUID`] that doesn't exist
+    euid: EntityUID,
+}
+
+impl MissingEntity {
+    /// The [`EntityUID`] that doesn't exist
+    pub fn euid(&self) -> &EntityUID {
+        &self.euid
+    }
+}
+
+/// Error raised by an [`super::EntityStore`] which failed to load an entity,
+/// e.g., because its backing database could not be reached
+#[derive(Debug, Clone, Error)]
+#[error("{err}")]
+pub struct EntityStoreError {
+    err: Arc<dyn std::error::Error + Send + Sync>,
+}
+
+impl EntityStoreError {
+    /// Wrap the error which caused the entity store to fail. Accepts any error
+    /// type, or a `String` or `&str` describing the failure.
+    pub fn new(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self {
+            err: Arc::from(err.into()),
+        }
+    }
+}
+
+// Errors are compared by their messages, so that `EvaluationError`s containing
+// them can be compared
+impl PartialEq for EntityStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        self.err.to_string() == other.err.to_string()
+    }
+}
+
+impl Eq for EntityStoreError {}
+
 /// Type alias for convenience
 pub type Result<T> = std::result::Result<T, EntitiesError>;
@@ -0,0 +1,167 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::ast::{Entity, EntityType, EntityUID, PartialValue, Value};
+use smol_str::SmolStr;
+use std::collections::{btree_map, hash_map, BTreeMap, HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::OnceLock;
+
+/// Maps each attribute name and value to the entities with that value for the
+/// attribute. Attributes whose values are residuals are not indexed.
+type AttrIndex = HashMap<SmolStr, BTreeMap<Value, HashSet<EntityUID>>>;
+
+/// Indexes over the entities in an `Entities`, which must be updated whenever
+/// an entity is added, replaced or removed.
+///
+/// Only an entity's UID, parents and attributes are indexed, so updating the
+/// indirect ancestors of an entity needs no update to the indexes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct EntityIndexes {
+    /// Maps each `EntityUID` to the entities which have it as a direct
+    /// parent, so that updates only need to visit the affected part of the
+    /// hierarchy. The keys need not be entities in the store.
+    children: HashMap<EntityUID, HashSet<EntityUID>>,
+    /// Maps each entity type to the entities of that type
+    types: HashMap<EntityType, HashSet<EntityUID>>,
+    /// Index of attribute values. Most stores are never searched by
+    /// attribute, so this is only built by the first search, and then kept
+    /// up to date.
+    attrs: OnceLock<AttrIndex>,
+}
+
+impl EntityIndexes {
+    /// Build the indexes for `entities`
+    pub(super) fn new<'a>(entities: impl IntoIterator<Item = &'a Entity>) -> Self {
+        let mut indexes = Self::default();
+        for entity in entities {
+            indexes.insert(entity);
+        }
+        indexes
+    }
+
+    /// Add `entity` to the indexes
+    pub(super) fn insert(&mut self, entity: &Entity) {
+        let uid = entity.uid();
+        for parent in entity.parents() {
+            self.children
+                .entry(parent.clone())
+                .or_default()
+                .insert(uid.clone());
+        }
+        self.types
+            .entry(uid.entity_type().clone())
+            .or_default()
+            .insert(uid.clone());
+        if let Some(attrs) = self.attrs.get_mut() {
+            insert_attrs(attrs, entity);
+        }
+    }
+
+    /// Remove `entity`, as it was added, from the indexes
+    pub(super) fn remove(&mut self, entity: &Entity) {
+        let uid = entity.uid();
+        for parent in entity.parents() {
+            remove_from(self.children.entry(parent.clone()), uid);
+        }
+        remove_from(self.types.entry(uid.entity_type().clone()), uid);
+        if let Some(attrs) = self.attrs.get_mut() {
+            remove_attrs(attrs, entity);
+        }
+    }
+
+    /// Forget the entities which have `uid` as a direct parent, returning them
+    pub(super) fn remove_children(&mut self, uid: &EntityUID) -> HashSet<EntityUID> {
+        self.children.remove(uid).unwrap_or_default()
+    }
+
+    /// The entities which have `uid` as a direct parent
+    pub(super) fn children(&self, uid: &EntityUID) -> impl Iterator<Item = &EntityUID> {
+        self.children.get(uid).into_iter().flatten()
+    }
+
+    /// The entities of type `ty`
+    pub(super) fn of_type(&self, ty: &EntityType) -> impl Iterator<Item = &EntityUID> {
+        self.types.get(ty).into_iter().flatten()
+    }
+
+    /// The entities whose attribute `attr` has the value `value`. If the
+    /// attribute index hasn't been built yet, it is built from `entities`,
+    /// which must be all the entities indexed.
+    pub(super) fn with_attr<'a, 'e, I: IntoIterator<Item = &'e Entity>>(
+        &'a self,
+        attr: &str,
+        value: &Value,
+        entities: impl FnOnce() -> I,
+    ) -> impl Iterator<Item = &'a EntityUID> {
+        self.attrs
+            .get_or_init(|| {
+                let mut attrs = AttrIndex::new();
+                for entity in entities() {
+                    insert_attrs(&mut attrs, entity);
+                }
+                attrs
+            })
+            .get(attr)
+            .and_then(|values| values.get(value))
+            .into_iter()
+            .flatten()
+    }
+}
+
+/// Add the attributes of `entity` to `attrs`
+fn insert_attrs(attrs: &mut AttrIndex, entity: &Entity) {
+    for (attr, value) in entity.attrs() {
+        if let PartialValue::Value(value) = value {
+            attrs
+                .entry(attr.clone())
+                .or_default()
+                .entry(value.clone())
+                .or_default()
+                .insert(entity.uid().clone());
+        }
+    }
+}
+
+/// Remove the attributes of `entity`, as it was added, from `attrs`
+fn remove_attrs(attrs: &mut AttrIndex, entity: &Entity) {
+    for (attr, value) in entity.attrs() {
+        if let PartialValue::Value(value) = value {
+            if let hash_map::Entry::Occupied(mut values) = attrs.entry(attr.clone()) {
+                if let btree_map::Entry::Occupied(mut uids) = values.get_mut().entry(value.clone())
+                {
+                    uids.get_mut().remove(entity.uid());
+                    if uids.get().is_empty() {
+                        uids.remove();
+                    }
+                }
+                if values.get().is_empty() {
+                    values.remove();
+                }
+            }
+        }
+    }
+}
+
+/// Remove `uid` from the set in `entry`, removing the set if it becomes empty
+fn remove_from<K: Eq + Hash>(entry: hash_map::Entry<'_, K, HashSet<EntityUID>>, uid: &EntityUID) {
+    if let hash_map::Entry::Occupied(mut uids) = entry {
+        uids.get_mut().remove(uid);
+        if uids.get().is_empty() {
+            uids.remove();
+        }
+    }
+}
@@ -19,12 +19,16 @@ use super::{
     CedarValueJson, EntityTypeDescription, EntityUidJson, NoEntitiesSchema, Schema, TypeAndId,
     ValueParser,
 };
-use crate::ast::{BorrowedRestrictedExpr, Entity, EntityUID, PartialValue, RestrictedExpr};
+use crate::ast::{
+    BorrowedRestrictedExpr, Entity, EntityAttrEvaluationError, EntityUID, PartialValue,
+    RestrictedExpr,
+};
 use crate::entities::conformance::EntitySchemaConformanceChecker;
 use crate::entities::{
     conformance::err::{EntitySchemaConformanceError, UnexpectedEntityTypeError},
-    Entities, EntitiesError, TCComputation,
+    Entities, EntitiesError, EntityChange, EntityPatch, EntityPatchOp, TCComputation,
 };
+use crate::evaluator::RestrictedEvaluator;
 use crate::extensions::Extensions;
 use crate::jsonvalue::JsonValueWithNoDuplicateKeys;
 use serde::{Deserialize, Serialize};
@@ -67,6 +71,144 @@ pub struct EntityJson {
     tags: HashMap<SmolStr, JsonValueWithNoDuplicateKeys>,
 }
 
+/// Serde JSON format for a single operation in an entity change-set.
+///
+/// A change-set is a JSON array of these operations, which are distinguished
+/// by their `op` field.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+#[cfg_attr(feature = "wasm", derive(tsify::Tsify))]
+#[cfg_attr(feature = "wasm", tsify(into_wasm_abi, from_wasm_abi))]
+pub enum EntityPatchOpJson {
+    /// Add an entity, replacing any existing entity with the same UID
+    Put {
+        /// The entity, in the same format as in an entities JSON file
+        entity: EntityJson,
+    },
+    /// Remove an entity, if it exists
+    Remove {
+        /// UID of the entity
+        uid: EntityUidJson,
+    },
+    /// Set an attribute of an existing entity
+    SetAttr {
+        /// UID of the entity
+        uid: EntityUidJson,
+        /// Name of the attribute
+        attr: SmolStr,
+        /// New value of the attribute
+        #[cfg_attr(feature = "wasm", tsify(type = "CedarValueJson"))]
+        value: JsonValueWithNoDuplicateKeys,
+    },
+    /// Remove an attribute of an existing entity
+    RemoveAttr {
+        /// UID of the entity
+        uid: EntityUidJson,
+        /// Name of the attribute
+        attr: SmolStr,
+    },
+    /// Add a parent to an existing entity
+    AddParent {
+        /// UID of the entity
+        uid: EntityUidJson,
+        /// UID of the new parent
+        parent: EntityUidJson,
+    },
+    /// Remove a parent of an existing entity
+    RemoveParent {
+        /// UID of the entity
+        uid: EntityUidJson,
+        /// UID of the parent
+        parent: EntityUidJson,
+    },
+    /// Set a tag of an existing entity
+    SetTag {
+        /// UID of the entity
+        uid: EntityUidJson,
+        /// Name of the tag
+        tag: SmolStr,
+        /// New value of the tag
+        #[cfg_attr(feature = "wasm", tsify(type = "CedarValueJson"))]
+        value: JsonValueWithNoDuplicateKeys,
+    },
+    /// Remove a tag of an existing entity
+    RemoveTag {
+        /// UID of the entity
+        uid: EntityUidJson,
+        /// Name of the tag
+        tag: SmolStr,
+    },
+}
+
+/// Serde JSON format for a single change in an entities diff.
+///
+/// A diff is a JSON array of these changes, which are distinguished by their
+/// `change` field.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "change", rename_all = "camelCase")]
+#[cfg_attr(feature = "wasm", derive(tsify::Tsify))]
+#[cfg_attr(feature = "wasm", tsify(into_wasm_abi, from_wasm_abi))]
+pub enum EntityChangeJson {
+    /// An entity was added
+    EntityAdded {
+        /// The entity, in the same format as in an entities JSON file
+        entity: EntityJson,
+    },
+    /// An entity was removed
+    EntityRemoved {
+        /// The entity, in the same format as in an entities JSON file
+        entity: EntityJson,
+    },
+    /// An attribute of an entity was added, removed or changed
+    AttributeChanged {
+        /// UID of the entity
+        uid: EntityUidJson,
+        /// Name of the attribute
+        attr: SmolStr,
+        /// Old value of the attribute, absent if it was added
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "wasm", tsify(type = "CedarValueJson", optional))]
+        old: Option<JsonValueWithNoDuplicateKeys>,
+        /// New value of the attribute, absent if it was removed
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "wasm", tsify(type = "CedarValueJson", optional))]
+        new: Option<JsonValueWithNoDuplicateKeys>,
+    },
+    /// A tag of an entity was added, removed or changed
+    TagChanged {
+        /// UID of the entity
+        uid: EntityUidJson,
+        /// Name of the tag
+        tag: SmolStr,
+        /// Old value of the tag, absent if it was added
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "wasm", tsify(type = "CedarValueJson", optional))]
+        old: Option<JsonValueWithNoDuplicateKeys>,
+        /// New value of the tag, absent if it was removed
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "wasm", tsify(type = "CedarValueJson", optional))]
+        new: Option<JsonValueWithNoDuplicateKeys>,
+    },
+    /// A parent was added to an entity
+    ParentAdded {
+        /// UID of the entity
+        uid: EntityUidJson,
+        /// UID of the new parent
+        parent: EntityUidJson,
+    },
+    /// A parent was removed from an entity
+    ParentRemoved {
+        /// UID of the entity
+        uid: EntityUidJson,
+        /// UID of the former parent
+        parent: EntityUidJson,
+    },
+}
+
 /// Struct used to parse entities from JSON.
 #[derive(Debug, Clone)]
 pub struct EntityJsonParser<'e, 's, S = NoEntitiesSchema> {
@@ -222,6 +364,41 @@ impl<S: Schema> EntityJsonParser<'_, '_, S> {
         Ok(entities.into_iter())
     }
 
+    /// Parse an entity change-set JSON file (in [`&str`] form) into an [`EntityPatch`].
+    ///
+    /// If the `EntityJsonParser` has a `schema`, the values of attributes and
+    /// tags are parsed according to their types in the `schema`, but the
+    /// change-set is only fully validated when it's applied.
+    pub fn patch_from_json_str(&self, json: &str) -> Result<EntityPatch, EntitiesError> {
+        let ops: Vec<EntityPatchOpJson> =
+            serde_json::from_str(json).map_err(JsonDeserializationError::from)?;
+        self.parse_patch_ops(ops)
+    }
+
+    /// Parse an entity change-set JSON file (in [`serde_json::Value`] form) into an [`EntityPatch`].
+    ///
+    /// See notes on [`EntityJsonParser::patch_from_json_str()`].
+    pub fn patch_from_json_value(
+        &self,
+        json: serde_json::Value,
+    ) -> Result<EntityPatch, EntitiesError> {
+        let ops: Vec<EntityPatchOpJson> =
+            serde_json::from_value(json).map_err(JsonDeserializationError::from)?;
+        self.parse_patch_ops(ops)
+    }
+
+    /// Parse an entity change-set JSON file (in [`std::io::Read`] form) into an [`EntityPatch`].
+    ///
+    /// See notes on [`EntityJsonParser::patch_from_json_str()`].
+    pub fn patch_from_json_file(
+        &self,
+        json: impl std::io::Read,
+    ) -> Result<EntityPatch, EntitiesError> {
+        let ops: Vec<EntityPatchOpJson> =
+            serde_json::from_reader(json).map_err(JsonDeserializationError::from)?;
+        self.parse_patch_ops(ops)
+    }
+
     /// Parse a single entity from an in-memory JSON value
     pub fn single_from_json_value(
         &self,
@@ -279,147 +456,21 @@ impl<S: Schema> EntityJsonParser<'_, '_, S> {
         let uid = ejson
             .uid
             .into_euid(|| JsonDeserializationErrorContext::EntityUid)?;
-        let etype = uid.entity_type();
-        let entity_schema_info = match &self.schema {
-            None => EntitySchemaInfo::NoSchema,
-            Some(schema) => {
-                if etype.is_action() {
-                    // Action entities do not have attribute type information in the schema.
-                    EntitySchemaInfo::NoSchema
-                } else {
-                    EntitySchemaInfo::NonAction(schema.entity_type(etype).ok_or_else(|| {
-                        let suggested_types = schema
-                            .entity_types_with_basename(&etype.name().basename())
-                            .collect();
-                        JsonDeserializationError::EntitySchemaConformance(
-                            UnexpectedEntityTypeError {
-                                uid: uid.clone(),
-                                suggested_types,
-                            }
-                            .into(),
-                        )
-                    })?)
-                }
-            }
-        };
-        let vparser = ValueParser::new(self.extensions);
+        let entity_schema_info = self.entity_schema_info(&uid)?;
         let attrs: HashMap<SmolStr, RestrictedExpr> = ejson
             .attrs
             .into_iter()
-            .map(|(k, v)| match &entity_schema_info {
-                EntitySchemaInfo::NoSchema => Ok((
-                    k.clone(),
-                    vparser.val_into_restricted_expr(v.into(), None, || {
-                        JsonDeserializationErrorContext::EntityAttribute {
-                            uid: uid.clone(),
-                            attr: k.clone(),
-                        }
-                    })?,
-                )),
-                EntitySchemaInfo::NonAction(desc) => {
-                    // Depending on the expected type, we may parse the contents
-                    // of the attribute differently.
-                    let rexpr = match desc.attr_type(&k) {
-                        // `None` indicates the attribute shouldn't exist -- see
-                        // docs on the `attr_type()` trait method
-                        None => {
-                            if desc.open_attributes() {
-                                vparser.val_into_restricted_expr(v.into(), None, || {
-                                    JsonDeserializationErrorContext::EntityAttribute {
-                                        uid: uid.clone(),
-                                        attr: k.clone(),
-                                    }
-                                })?
-                            } else {
-                                return Err(JsonDeserializationError::EntitySchemaConformance(
-                                    EntitySchemaConformanceError::unexpected_entity_attr(
-                                        uid.clone(),
-                                        k,
-                                    ),
-                                ));
-                            }
-                        }
-                        Some(expected_ty) => vparser.val_into_restricted_expr(
-                            v.into(),
-                            Some(&expected_ty),
-                            || JsonDeserializationErrorContext::EntityAttribute {
-                                uid: uid.clone(),
-                                attr: k.clone(),
-                            },
-                        )?,
-                    };
-                    Ok((k, rexpr))
-                }
-            })
+            .map(|(k, v)| self.parse_attr(&uid, &entity_schema_info, k, v))
             .collect::<Result<_, JsonDeserializationError>>()?;
         let tags: HashMap<SmolStr, RestrictedExpr> = ejson
             .tags
             .into_iter()
-            .map(|(k, v)| match &entity_schema_info {
-                EntitySchemaInfo::NoSchema => Ok((
-                    k.clone(),
-                    vparser.val_into_restricted_expr(v.into(), None, || {
-                        JsonDeserializationErrorContext::EntityTag {
-                            uid: uid.clone(),
-                            tag: k.clone(),
-                        }
-                    })?,
-                )),
-                EntitySchemaInfo::NonAction(desc) => {
-                    // Depending on the expected type, we may parse the contents
-                    // of the tag differently.
-                    let rexpr = match desc.tag_type() {
-                        // `None` indicates no tags should exist -- see docs on
-                        // the `tag_type()` trait method
-                        None => {
-                            return Err(JsonDeserializationError::EntitySchemaConformance(
-                                EntitySchemaConformanceError::unexpected_entity_tag(uid.clone(), k),
-                            ));
-                        }
-                        Some(expected_ty) => vparser.val_into_restricted_expr(
-                            v.into(),
-                            Some(&expected_ty),
-                            || JsonDeserializationErrorContext::EntityTag {
-                                uid: uid.clone(),
-                                tag: k.clone(),
-                            },
-                        )?,
-                    };
-                    Ok((k, rexpr))
-                }
-            })
+            .map(|(k, v)| self.parse_tag(&uid, &entity_schema_info, k, v))
             .collect::<Result<_, JsonDeserializationError>>()?;
-        let is_parent_allowed = |parent_euid: &EntityUID| {
-            // full validation isn't done in this function (see doc comments on
-            // this function), but we do need to do the following check which
-            // happens even when there is no schema
-            if etype.is_action() {
-                if parent_euid.is_action() {
-                    Ok(())
-                } else {
-                    Err(JsonDeserializationError::action_parent_is_not_action(
-                        uid.clone(),
-                        parent_euid.clone(),
-                    ))
-                }
-            } else {
-                Ok(()) // all parents are allowed
-            }
-        };
         let parents = ejson
             .parents
             .into_iter()
-            .map(|parent| {
-                parent.into_euid(|| JsonDeserializationErrorContext::EntityParents {
-                    uid: uid.clone(),
-                })
-            })
-            .map(|res| {
-                res.and_then(|parent_euid| {
-                    is_parent_allowed(&parent_euid)?;
-                    Ok(parent_euid)
-                })
-            })
+            .map(|parent| self.parse_parent(&uid, parent))
             .collect::<Result<_, JsonDeserializationError>>()?;
         Ok(Entity::new(
             uid,
@@ -430,6 +481,244 @@ impl<S: Schema> EntityJsonParser<'_, '_, S> {
             self.extensions,
         )?)
     }
+
+    /// Internal function that gets the schema information for the entity
+    /// with UID `uid`
+    fn entity_schema_info(
+        &self,
+        uid: &EntityUID,
+    ) -> Result<EntitySchemaInfo<S::EntityTypeDescription>, JsonDeserializationError> {
+        let etype = uid.entity_type();
+        match &self.schema {
+            None => Ok(EntitySchemaInfo::NoSchema),
+            Some(schema) => {
+                if etype.is_action() {
+                    // Action entities do not have attribute type information in the schema.
+                    Ok(EntitySchemaInfo::NoSchema)
+                } else {
+                    Ok(EntitySchemaInfo::NonAction(
+                        schema.entity_type(etype).ok_or_else(|| {
+                            let suggested_types = schema
+                                .entity_types_with_basename(&etype.name().basename())
+                                .collect();
+                            JsonDeserializationError::EntitySchemaConformance(
+                                UnexpectedEntityTypeError {
+                                    uid: uid.clone(),
+                                    suggested_types,
+                                }
+                                .into(),
+                            )
+                        })?,
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Internal function that parses the value of the attribute `k` of the
+    /// entity with UID `uid`
+    fn parse_attr(
+        &self,
+        uid: &EntityUID,
+        entity_schema_info: &EntitySchemaInfo<S::EntityTypeDescription>,
+        k: SmolStr,
+        v: JsonValueWithNoDuplicateKeys,
+    ) -> Result<(SmolStr, RestrictedExpr), JsonDeserializationError> {
+        let vparser = ValueParser::new(self.extensions);
+        match entity_schema_info {
+            EntitySchemaInfo::NoSchema => Ok((
+                k.clone(),
+                vparser.val_into_restricted_expr(v.into(), None, || {
+                    JsonDeserializationErrorContext::EntityAttribute {
+                        uid: uid.clone(),
+                        attr: k.clone(),
+                    }
+                })?,
+            )),
+            EntitySchemaInfo::NonAction(desc) => {
+                // Depending on the expected type, we may parse the contents
+                // of the attribute differently.
+                let rexpr = match desc.attr_type(&k) {
+                    // `None` indicates the attribute shouldn't exist -- see
+                    // docs on the `attr_type()` trait method
+                    None => {
+                        if desc.open_attributes() {
+                            vparser.val_into_restricted_expr(v.into(), None, || {
+                                JsonDeserializationErrorContext::EntityAttribute {
+                                    uid: uid.clone(),
+                                    attr: k.clone(),
+                                }
+                            })?
+                        } else {
+                            return Err(JsonDeserializationError::EntitySchemaConformance(
+                                EntitySchemaConformanceError::unexpected_entity_attr(
+                                    uid.clone(),
+                                    k,
+                                ),
+                            ));
+                        }
+                    }
+                    Some(expected_ty) => {
+                        vparser.val_into_restricted_expr(v.into(), Some(&expected_ty), || {
+                            JsonDeserializationErrorContext::EntityAttribute {
+                                uid: uid.clone(),
+                                attr: k.clone(),
+                            }
+                        })?
+                    }
+                };
+                Ok((k, rexpr))
+            }
+        }
+    }
+
+    /// Internal function that parses the value of the tag `k` of the entity
+    /// with UID `uid`
+    fn parse_tag(
+        &self,
+        uid: &EntityUID,
+        entity_schema_info: &EntitySchemaInfo<S::EntityTypeDescription>,
+        k: SmolStr,
+        v: JsonValueWithNoDuplicateKeys,
+    ) -> Result<(SmolStr, RestrictedExpr), JsonDeserializationError> {
+        let vparser = ValueParser::new(self.extensions);
+        match entity_schema_info {
+            EntitySchemaInfo::NoSchema => Ok((
+                k.clone(),
+                vparser.val_into_restricted_expr(v.into(), None, || {
+                    JsonDeserializationErrorContext::EntityTag {
+                        uid: uid.clone(),
+                        tag: k.clone(),
+                    }
+                })?,
+            )),
+            EntitySchemaInfo::NonAction(desc) => {
+                // Depending on the expected type, we may parse the contents
+                // of the tag differently.
+                let rexpr = match desc.tag_type() {
+                    // `None` indicates no tags should exist -- see docs on
+                    // the `tag_type()` trait method
+                    None => {
+                        return Err(JsonDeserializationError::EntitySchemaConformance(
+                            EntitySchemaConformanceError::unexpected_entity_tag(uid.clone(), k),
+                        ));
+                    }
+                    Some(expected_ty) => {
+                        vparser.val_into_restricted_expr(v.into(), Some(&expected_ty), || {
+                            JsonDeserializationErrorContext::EntityTag {
+                                uid: uid.clone(),
+                                tag: k.clone(),
+                            }
+                        })?
+                    }
+                };
+                Ok((k, rexpr))
+            }
+        }
+    }
+
+    /// Internal function that parses a parent of the entity with UID `uid`
+    fn parse_parent(
+        &self,
+        uid: &EntityUID,
+        parent: EntityUidJson,
+    ) -> Result<EntityUID, JsonDeserializationError> {
+        let parent_euid = parent
+            .into_euid(|| JsonDeserializationErrorContext::EntityParents { uid: uid.clone() })?;
+        // full validation isn't done in this function (see doc comments on
+        // `parse_ejson()`), but we do need to do the following check which
+        // happens even when there is no schema
+        if uid.entity_type().is_action() && !parent_euid.is_action() {
+            return Err(JsonDeserializationError::action_parent_is_not_action(
+                uid.clone(),
+                parent_euid,
+            ));
+        }
+        Ok(parent_euid)
+    }
+
+    /// Internal function that creates an [`EntityPatch`] from a sequence of
+    /// [`EntityPatchOpJson`]
+    fn parse_patch_ops(
+        &self,
+        ops: impl IntoIterator<Item = EntityPatchOpJson>,
+    ) -> Result<EntityPatch, EntitiesError> {
+        let ops: Vec<EntityPatchOp> = ops
+            .into_iter()
+            .map(|op| self.parse_patch_op(op))
+            .collect::<Result<_, _>>()?;
+        Ok(EntityPatch::new(ops))
+    }
+
+    /// Internal function that parses an `EntityPatchOpJson` into an
+    /// `EntityPatchOp`
+    fn parse_patch_op(
+        &self,
+        op: EntityPatchOpJson,
+    ) -> Result<EntityPatchOp, JsonDeserializationError> {
+        let parse_uid =
+            |uid: EntityUidJson| uid.into_euid(|| JsonDeserializationErrorContext::EntityUid);
+        Ok(match op {
+            EntityPatchOpJson::Put { entity } => {
+                EntityPatchOp::Put(Arc::new(self.parse_ejson(entity)?))
+            }
+            EntityPatchOpJson::Remove { uid } => EntityPatchOp::Remove(parse_uid(uid)?),
+            EntityPatchOpJson::SetAttr { uid, attr, value } => {
+                let uid = parse_uid(uid)?;
+                let entity_schema_info = self.entity_schema_info(&uid)?;
+                let (attr, rexpr) = self.parse_attr(&uid, &entity_schema_info, attr, value)?;
+                let value = self.evaluate(&uid, &attr, true, &rexpr)?;
+                EntityPatchOp::SetAttr { uid, attr, value }
+            }
+            EntityPatchOpJson::RemoveAttr { uid, attr } => EntityPatchOp::RemoveAttr {
+                uid: parse_uid(uid)?,
+                attr,
+            },
+            EntityPatchOpJson::AddParent { uid, parent } => {
+                let uid = parse_uid(uid)?;
+                let parent = self.parse_parent(&uid, parent)?;
+                EntityPatchOp::AddParent { uid, parent }
+            }
+            EntityPatchOpJson::RemoveParent { uid, parent } => {
+                let uid = parse_uid(uid)?;
+                let parent = self.parse_parent(&uid, parent)?;
+                EntityPatchOp::RemoveParent { uid, parent }
+            }
+            EntityPatchOpJson::SetTag { uid, tag, value } => {
+                let uid = parse_uid(uid)?;
+                let entity_schema_info = self.entity_schema_info(&uid)?;
+                let (tag, rexpr) = self.parse_tag(&uid, &entity_schema_info, tag, value)?;
+                let value = self.evaluate(&uid, &tag, false, &rexpr)?;
+                EntityPatchOp::SetTag { uid, tag, value }
+            }
+            EntityPatchOpJson::RemoveTag { uid, tag } => EntityPatchOp::RemoveTag {
+                uid: parse_uid(uid)?,
+                tag,
+            },
+        })
+    }
+
+    /// Internal function that evaluates the value of an attribute or tag, as
+    /// `Entity::new()` does
+    fn evaluate(
+        &self,
+        uid: &EntityUID,
+        attr_or_tag: &SmolStr,
+        was_attr: bool,
+        rexpr: &RestrictedExpr,
+    ) -> Result<PartialValue, JsonDeserializationError> {
+        RestrictedEvaluator::new(self.extensions)
+            .partial_interpret(rexpr.as_borrowed())
+            .map_err(|err| {
+                EntityAttrEvaluationError {
+                    uid: uid.clone(),
+                    attr_or_tag: attr_or_tag.clone(),
+                    was_attr,
+                    err,
+                }
+                .into()
+            })
+    }
 }
 
 impl EntityJson {
@@ -438,19 +727,7 @@ impl EntityJson {
     /// (for the reverse transformation, use `EntityJsonParser`)
     pub fn from_entity(entity: &Entity) -> Result<Self, JsonSerializationError> {
         let serialize_kpvalue = |(k, pvalue): (&SmolStr, &PartialValue)| -> Result<_, _> {
-            match pvalue {
-                PartialValue::Value(value) => {
-                    let cedarvaluejson = CedarValueJson::from_value(value.clone())?;
-                    Ok((k.clone(), serde_json::to_value(cedarvaluejson)?.into()))
-                }
-                PartialValue::Residual(expr) => match BorrowedRestrictedExpr::new(expr) {
-                    Ok(expr) => {
-                        let cedarvaluejson = CedarValueJson::from_expr(expr)?;
-                        Ok((k.clone(), serde_json::to_value(cedarvaluejson)?.into()))
-                    }
-                    Err(_) => Err(JsonSerializationError::residual(expr.clone())),
-                },
-            }
+            Ok((k.clone(), serialize_pvalue(pvalue)?))
         };
         Ok(Self {
             // for now, we encode `uid` and `parents` using an implied `__entity` escape
@@ -471,6 +748,110 @@ impl EntityJson {
     }
 }
 
+impl EntityPatchOpJson {
+    /// Convert an `EntityPatchOp` into an `EntityPatchOpJson`
+    ///
+    /// (for the reverse transformation, use `EntityJsonParser`)
+    pub fn from_op(op: &EntityPatchOp) -> Result<Self, JsonSerializationError> {
+        // as in `EntityJson`, we encode UIDs using an implied `__entity` escape
+        let uid_json = |uid: &EntityUID| EntityUidJson::ImplicitEntityEscape(TypeAndId::from(uid));
+        Ok(match op {
+            EntityPatchOp::Put(entity) => Self::Put {
+                entity: EntityJson::from_entity(entity)?,
+            },
+            EntityPatchOp::Remove(uid) => Self::Remove { uid: uid_json(uid) },
+            EntityPatchOp::SetAttr { uid, attr, value } => Self::SetAttr {
+                uid: uid_json(uid),
+                attr: attr.clone(),
+                value: serialize_pvalue(value)?,
+            },
+            EntityPatchOp::RemoveAttr { uid, attr } => Self::RemoveAttr {
+                uid: uid_json(uid),
+                attr: attr.clone(),
+            },
+            EntityPatchOp::AddParent { uid, parent } => Self::AddParent {
+                uid: uid_json(uid),
+                parent: uid_json(parent),
+            },
+            EntityPatchOp::RemoveParent { uid, parent } => Self::RemoveParent {
+                uid: uid_json(uid),
+                parent: uid_json(parent),
+            },
+            EntityPatchOp::SetTag { uid, tag, value } => Self::SetTag {
+                uid: uid_json(uid),
+                tag: tag.clone(),
+                value: serialize_pvalue(value)?,
+            },
+            EntityPatchOp::RemoveTag { uid, tag } => Self::RemoveTag {
+                uid: uid_json(uid),
+                tag: tag.clone(),
+            },
+        })
+    }
+}
+
+impl EntityChangeJson {
+    /// Convert an `EntityChange` into an `EntityChangeJson`
+    pub fn from_change(change: &EntityChange) -> Result<Self, JsonSerializationError> {
+        // as in `EntityJson`, we encode UIDs using an implied `__entity` escape
+        let uid_json = |uid: &EntityUID| EntityUidJson::ImplicitEntityEscape(TypeAndId::from(uid));
+        let value_json =
+            |value: &Option<PartialValue>| value.as_ref().map(serialize_pvalue).transpose();
+        Ok(match change {
+            EntityChange::EntityAdded(entity) => Self::EntityAdded {
+                entity: EntityJson::from_entity(entity)?,
+            },
+            EntityChange::EntityRemoved(entity) => Self::EntityRemoved {
+                entity: EntityJson::from_entity(entity)?,
+            },
+            EntityChange::AttributeChanged {
+                uid,
+                attr,
+                old,
+                new,
+            } => Self::AttributeChanged {
+                uid: uid_json(uid),
+                attr: attr.clone(),
+                old: value_json(old)?,
+                new: value_json(new)?,
+            },
+            EntityChange::TagChanged { uid, tag, old, new } => Self::TagChanged {
+                uid: uid_json(uid),
+                tag: tag.clone(),
+                old: value_json(old)?,
+                new: value_json(new)?,
+            },
+            EntityChange::ParentAdded { uid, parent } => Self::ParentAdded {
+                uid: uid_json(uid),
+                parent: uid_json(parent),
+            },
+            EntityChange::ParentRemoved { uid, parent } => Self::ParentRemoved {
+                uid: uid_json(uid),
+                parent: uid_json(parent),
+            },
+        })
+    }
+}
+
+/// Serialize the value of an attribute or tag
+fn serialize_pvalue(
+    pvalue: &PartialValue,
+) -> Result<JsonValueWithNoDuplicateKeys, JsonSerializationError> {
+    match pvalue {
+        PartialValue::Value(value) => {
+            let cedarvaluejson = CedarValueJson::from_value(value.clone())?;
+            Ok(serde_json::to_value(cedarvaluejson)?.into())
+        }
+        PartialValue::Residual(expr) => match BorrowedRestrictedExpr::new(expr) {
+            Ok(expr) => {
+                let cedarvaluejson = CedarValueJson::from_expr(expr)?;
+                Ok(serde_json::to_value(cedarvaluejson)?.into())
+            }
+            Err(_) => Err(JsonSerializationError::residual(expr.clone())),
+        },
+    }
+}
+
 // PANIC SAFETY unit test code
 #[allow(clippy::panic)]
 #[cfg(test)]
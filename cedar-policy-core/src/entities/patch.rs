@@ -0,0 +1,351 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::err::{EntitiesError, Result};
+use super::json::{err::JsonSerializationError, EntityPatchOpJson};
+use super::{AncestorMode, Entities, EntitySchemaConformanceChecker, Schema, TCComputation};
+use crate::ast::{Entity, EntityUID, PartialValue};
+use crate::extensions::Extensions;
+use smol_str::SmolStr;
+use std::collections::{hash_map, HashMap};
+use std::sync::Arc;
+
+/// A single change to an [`Entities`]
+#[derive(Debug, Clone)]
+pub enum EntityPatchOp {
+    /// Add the entity, replacing any existing entity with the same UID
+    Put(Arc<Entity>),
+    /// Remove the entity with this UID, if there is one.
+    ///
+    /// Unlike [`Entities::remove_entities()`], this does not remove the entity
+    /// from the parents of its children, so that the operation can be undone;
+    /// use [`EntityPatchOp::RemoveParent`] for that.
+    Remove(EntityUID),
+    /// Set an attribute of an existing entity
+    SetAttr {
+        /// UID of the entity
+        uid: EntityUID,
+        /// Name of the attribute
+        attr: SmolStr,
+        /// New value of the attribute
+        value: PartialValue,
+    },
+    /// Remove an attribute of an existing entity, if it has that attribute
+    RemoveAttr {
+        /// UID of the entity
+        uid: EntityUID,
+        /// Name of the attribute
+        attr: SmolStr,
+    },
+    /// Add a parent to an existing entity
+    AddParent {
+        /// UID of the entity
+        uid: EntityUID,
+        /// UID of the new parent
+        parent: EntityUID,
+    },
+    /// Remove a parent of an existing entity, if it has that parent
+    RemoveParent {
+        /// UID of the entity
+        uid: EntityUID,
+        /// UID of the parent
+        parent: EntityUID,
+    },
+    /// Set a tag of an existing entity
+    SetTag {
+        /// UID of the entity
+        uid: EntityUID,
+        /// Name of the tag
+        tag: SmolStr,
+        /// New value of the tag
+        value: PartialValue,
+    },
+    /// Remove a tag of an existing entity, if it has that tag
+    RemoveTag {
+        /// UID of the entity
+        uid: EntityUID,
+        /// Name of the tag
+        tag: SmolStr,
+    },
+}
+
+impl EntityPatchOp {
+    /// UID of the entity this operation changes
+    pub fn uid(&self) -> &EntityUID {
+        match self {
+            Self::Put(entity) => entity.uid(),
+            Self::Remove(uid)
+            | Self::SetAttr { uid, .. }
+            | Self::RemoveAttr { uid, .. }
+            | Self::AddParent { uid, .. }
+            | Self::RemoveParent { uid, .. }
+            | Self::SetTag { uid, .. }
+            | Self::RemoveTag { uid, .. } => uid,
+        }
+    }
+}
+
+/// A change-set for an [`Entities`]: a sequence of operations which are
+/// applied in order, and all together or not at all, by
+/// [`Entities::apply_patch()`].
+///
+/// To parse a change-set from JSON, use `EntityJsonParser`.
+#[derive(Debug, Clone, Default)]
+pub struct EntityPatch {
+    ops: Vec<EntityPatchOp>,
+}
+
+impl EntityPatch {
+    /// Create a change-set which applies `ops` in order
+    pub fn new(ops: impl IntoIterator<Item = EntityPatchOp>) -> Self {
+        Self {
+            ops: ops.into_iter().collect(),
+        }
+    }
+
+    /// Iterate over the operations in this change-set, in order
+    pub fn ops(&self) -> impl Iterator<Item = &EntityPatchOp> {
+        self.ops.iter()
+    }
+
+    /// Number of operations in this change-set
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Is this change-set empty?
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Convert this change-set into a JSON value suitable for parsing in via
+    /// `EntityJsonParser`.
+    pub fn to_json_value(&self) -> Result<serde_json::Value> {
+        let ops = self.to_op_jsons()?;
+        serde_json::to_value(ops)
+            .map_err(JsonSerializationError::from)
+            .map_err(Into::into)
+    }
+
+    /// Dump this change-set into a JSON file, which can be parsed in via
+    /// `EntityJsonParser`.
+    pub fn write_to_json(&self, f: impl std::io::Write) -> Result<()> {
+        let ops = self.to_op_jsons()?;
+        serde_json::to_writer_pretty(f, &ops).map_err(JsonSerializationError::from)?;
+        Ok(())
+    }
+
+    fn to_op_jsons(&self) -> Result<Vec<EntityPatchOpJson>> {
+        self.ops
+            .iter()
+            .map(EntityPatchOpJson::from_op)
+            .collect::<std::result::Result<_, JsonSerializationError>>()
+            .map_err(Into::into)
+    }
+}
+
+impl IntoIterator for EntityPatch {
+    type Item = EntityPatchOp;
+
+    type IntoIter = std::vec::IntoIter<EntityPatchOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.into_iter()
+    }
+}
+
+impl FromIterator<EntityPatchOp> for EntityPatch {
+    fn from_iter<T: IntoIterator<Item = EntityPatchOp>>(iter: T) -> Self {
+        Self::new(iter)
+    }
+}
+
+impl Entities {
+    /// Apply the operations in `patch` to this [`Entities`], in order,
+    /// returning the change-set which undoes them.
+    ///
+    /// The change-set is applied atomically: if any operation fails, an error
+    /// is returned and this [`Entities`] is left unchanged. Operations fail if
+    /// they update an entity which doesn't exist (at that point in the
+    /// change-set), or if they introduce a cycle in the entity hierarchy.
+    ///
+    /// If `schema` is present, then every entity the change-set touches is
+    /// validated against the `schema` once all the operations have been
+    /// applied, so operations may pass through states which don't conform to
+    /// the `schema`.
+    pub fn apply_patch(
+        &mut self,
+        patch: EntityPatch,
+        schema: Option<&impl Schema>,
+        extensions: &Extensions<'_>,
+    ) -> Result<EntityPatch> {
+        // The state each touched entity will be in after the change-set, with
+        // `None` for entities which will be removed
+        let mut staged: HashMap<EntityUID, Option<Arc<Entity>>> = HashMap::new();
+        // UIDs of the entities whose parents the change-set may change
+        let mut changed = Vec::new();
+        let mut inverse = Vec::new();
+        for op in patch {
+            let uid = op.uid().clone();
+            let current = match staged.entry(uid.clone()) {
+                hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                hash_map::Entry::Vacant(entry) => entry.insert(self.entities.get(&uid).cloned()),
+            };
+            match op {
+                EntityPatchOp::Put(entity) => {
+                    inverse.push(match current.take() {
+                        Some(old) => EntityPatchOp::Put(with_parents_only(old)),
+                        None => EntityPatchOp::Remove(uid.clone()),
+                    });
+                    *current = Some(entity);
+                    changed.push(uid);
+                }
+                EntityPatchOp::Remove(_) => {
+                    if let Some(old) = current.take() {
+                        inverse.push(EntityPatchOp::Put(with_parents_only(old)));
+                        changed.push(uid);
+                    }
+                }
+                EntityPatchOp::SetAttr { attr, value, .. } => {
+                    let entity = existing(current, &uid)?;
+                    inverse.push(match entity.set_attr(attr.clone(), value) {
+                        Some(value) => EntityPatchOp::SetAttr { uid, attr, value },
+                        None => EntityPatchOp::RemoveAttr { uid, attr },
+                    });
+                }
+                EntityPatchOp::RemoveAttr { attr, .. } => {
+                    let entity = existing(current, &uid)?;
+                    if let Some(value) = entity.remove_attr(&attr) {
+                        inverse.push(EntityPatchOp::SetAttr { uid, attr, value });
+                    }
+                }
+                EntityPatchOp::AddParent { parent, .. } => {
+                    let entity = existing(current, &uid)?;
+                    if !entity.is_child_of(&parent) {
+                        entity.add_parent(parent.clone());
+                        inverse.push(EntityPatchOp::RemoveParent {
+                            uid: uid.clone(),
+                            parent,
+                        });
+                        changed.push(uid);
+                    }
+                }
+                EntityPatchOp::RemoveParent { parent, .. } => {
+                    let entity = existing(current, &uid)?;
+                    if entity.is_child_of(&parent) {
+                        entity.remove_parent(&parent);
+                        inverse.push(EntityPatchOp::AddParent {
+                            uid: uid.clone(),
+                            parent,
+                        });
+                        changed.push(uid);
+                    }
+                }
+                EntityPatchOp::SetTag { tag, value, .. } => {
+                    let entity = existing(current, &uid)?;
+                    inverse.push(match entity.set_tag(tag.clone(), value) {
+                        Some(value) => EntityPatchOp::SetTag { uid, tag, value },
+                        None => EntityPatchOp::RemoveTag { uid, tag },
+                    });
+                }
+                EntityPatchOp::RemoveTag { tag, .. } => {
+                    let entity = existing(current, &uid)?;
+                    if let Some(value) = entity.remove_tag(&tag) {
+                        inverse.push(EntityPatchOp::SetTag { uid, tag, value });
+                    }
+                }
+            }
+        }
+
+        let touched: Vec<EntityUID> = staged.keys().cloned().collect();
+        for entity in staged.values_mut().flatten() {
+            self.ancestor_mode.prepare(entity);
+        }
+        let previous = self.swap_entities(staged);
+        let result = self
+            .restore_tc(changed.iter().cloned(), TCComputation::ComputeNow)
+            .and_then(|()| self.validate_touched(&touched, schema, extensions));
+        if let Err(err) = result {
+            // Put back the entities as they were, and with them the hierarchy
+            // as it was: every entity whose ancestors were updated is a
+            // descendant of a changed entity in the previous hierarchy too
+            self.swap_entities(previous);
+            // PANIC SAFETY: the previous hierarchy was a DAG
+            #[allow(clippy::expect_used)]
+            self.restore_tc(changed, TCComputation::ComputeNow)
+                .expect("restoring the previous hierarchy should succeed");
+            return Err(err);
+        }
+
+        inverse.reverse();
+        Ok(EntityPatch::new(inverse))
+    }
+
+    /// Validate the entities with the UIDs in `touched` which are in this
+    /// store against `schema`, if present
+    fn validate_touched(
+        &self,
+        touched: &[EntityUID],
+        schema: Option<&impl Schema>,
+        extensions: &Extensions<'_>,
+    ) -> Result<()> {
+        if let Some(schema) = schema {
+            let checker = EntitySchemaConformanceChecker::new(schema, extensions);
+            for entity in touched.iter().filter_map(|uid| self.entities.get(uid)) {
+                checker.validate_entity(entity)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the entities with the UIDs in `entities` (removing those mapped
+    /// to `None`), keeping the indexes up to date but not the transitive
+    /// closure. Returns the entities which were replaced.
+    fn swap_entities(
+        &mut self,
+        entities: HashMap<EntityUID, Option<Arc<Entity>>>,
+    ) -> HashMap<EntityUID, Option<Arc<Entity>>> {
+        entities
+            .into_iter()
+            .map(|(uid, entity)| {
+                let old = self.entities.remove(&uid);
+                if let Some(old) = &old {
+                    self.indexes.remove(old);
+                }
+                if let Some(entity) = entity {
+                    self.indexes.insert(&entity);
+                    self.entities.insert(uid.clone(), entity);
+                }
+                (uid, old)
+            })
+            .collect()
+    }
+}
+
+/// The entity an operation updates, which must exist
+fn existing<'a>(entity: &'a mut Option<Arc<Entity>>, uid: &EntityUID) -> Result<&'a mut Entity> {
+    entity
+        .as_mut()
+        .map(Arc::make_mut)
+        .ok_or_else(|| EntitiesError::missing_entity(uid.clone()))
+}
+
+/// `entity` without its indirect ancestors, so that putting it back restores
+/// the hierarchy rather than adding edges to the entity's indirect ancestors
+pub(super) fn with_parents_only(mut entity: Arc<Entity>) -> Arc<Entity> {
+    AncestorMode::OnDemand.prepare(&mut entity);
+    entity
+}
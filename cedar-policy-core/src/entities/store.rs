@@ -0,0 +1,75 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The interface through which the evaluator looks up entity data.
+
+use super::err::EntityStoreError;
+use super::{AncestorMode, Dereference, Entities};
+use crate::ast::{Entity, EntityUID, Expr};
+use std::sync::Arc;
+
+/// A source of entity data which an `Evaluator` can dereference through.
+///
+/// [`Entities`] is an `EntityStore` holding every entity in memory. Other
+/// implementations may load entities on demand, e.g. from a database, so that
+/// only the entities actually needed to evaluate a request are loaded.
+pub trait EntityStore {
+    /// Look up the entity `uid`, including its attributes, ancestors and
+    /// tags.
+    ///
+    /// The ancestors of the returned entity must be transitively closed,
+    /// unless [`EntityStore::ancestor_mode`] is [`AncestorMode::OnDemand`].
+    /// Returning an error causes the evaluation of the expression which
+    /// dereferenced `uid` to fail with that error.
+    fn entity(&self, uid: &EntityUID) -> Result<StoredEntity, EntityStoreError>;
+
+    /// Whether the entities returned by [`EntityStore::entity`] have all of
+    /// their ancestors, or only their parents, in which case the evaluator
+    /// searches the hierarchy by looking up the parents in turn.
+    fn ancestor_mode(&self) -> AncestorMode {
+        AncestorMode::Precomputed
+    }
+}
+
+/// Result of looking up an entity in an [`EntityStore`]
+#[derive(Debug, Clone)]
+pub enum StoredEntity {
+    /// No entity with the requested UID exists
+    NoSuchEntity,
+    /// The store does not know the entity, which is represented by this
+    /// residual (used in partial evaluation)
+    Residual(Expr),
+    /// The requested entity
+    Data(Arc<Entity>),
+}
+
+impl EntityStore for Entities {
+    fn entity(&self, uid: &EntityUID) -> Result<StoredEntity, EntityStoreError> {
+        Ok(match self.entities.get(uid) {
+            Some(e) => StoredEntity::Data(Arc::clone(e)),
+            // `Entities::entity()` decides whether a missing entity is an
+            // error or a residual, depending on the mode of the store
+            None => match Entities::entity(self, uid) {
+                Dereference::Residual(r) => StoredEntity::Residual(r),
+                Dereference::NoSuchEntity | Dereference::Data(_) => StoredEntity::NoSuchEntity,
+            },
+        })
+    }
+
+    fn ancestor_mode(&self) -> AncestorMode {
+        self.ancestor_mode
+    }
+}
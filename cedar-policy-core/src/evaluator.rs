@@ -17,13 +17,26 @@
 //! This module contains the Cedar evaluator.
 
 use crate::ast::*;
-use crate::entities::{Dereference, Entities};
+use crate::entities::{AncestorMode, EntityStore, StoredEntity};
 use crate::extensions::Extensions;
 use crate::parser::Loc;
+use std::cell::RefCell;
 #[cfg(feature = "partial-eval")]
 use std::collections::BTreeMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+mod cache;
+pub use cache::EvaluationCache;
+mod limits;
+use limits::LimitTracker;
+pub use limits::{EvaluationLimits, ExceededLimit};
+mod trace;
+use trace::Tracer;
+pub use trace::{TraceNode, TraceOutcome};
+mod coverage;
+pub(crate) use coverage::CoverageRecorder;
+pub use coverage::{CoverageReport, ExprCoverage, PolicyCoverage};
 mod err;
 #[cfg(feature = "tolerant-ast")]
 use crate::evaluator::EvaluationError::ASTErrorExpr;
@@ -204,14 +217,26 @@ pub struct Evaluator<'e> {
     resource: EntityUIDEntry,
     /// `Context` for the current request; this will be a Record type
     context: PartialValue,
-    /// Entities which we use to resolve entity references.
+    /// Store which we use to resolve entity references.
     ///
     /// This is a reference, because the `Evaluator` doesn't need ownership of
-    /// (or need to modify) the `Entities`. One advantage of this is that you
+    /// (or need to modify) the store. One advantage of this is that you
     /// could create multiple `Evaluator`s without copying the `Entities`.
-    entities: &'e Entities,
+    entities: &'e dyn EntityStore,
     /// Extensions which are active for this evaluation
     extensions: &'e Extensions<'e>,
+    /// Cache of request-independent results shared with other `Evaluator`s,
+    /// if any
+    cache: Option<&'e EvaluationCache>,
+    /// Records every expression evaluated, if tracing is enabled
+    tracer: Option<RefCell<Tracer>>,
+    /// Records the located expressions evaluated, if coverage is enabled
+    coverage: Option<RefCell<CoverageRecorder>>,
+    /// Work done so far, checked against the configured `EvaluationLimits`
+    limits: LimitTracker,
+    /// Ancestors found by searching the hierarchy, when the store has
+    /// `AncestorMode::OnDemand` and there is no shared `cache`
+    ancestors: RefCell<HashMap<EntityUID, Arc<HashSet<EntityUID>>>>,
     /// Mapper of unknown values into concrete ones, if recognized
     #[cfg(feature = "partial-eval")]
     unknowns_mapper: UnknownsMapper<'e>,
@@ -341,9 +366,9 @@ pub(crate) fn valid_comparison_op_types(extensions: &Extensions<'_>) -> nonempty
 
 impl<'e> Evaluator<'e> {
     /// Create a fresh `Evaluator` for the given `request`, which uses the given
-    /// `Entities` to resolve entity references. Use the given `Extension`s when
-    /// evaluating.
-    pub fn new(q: Request, entities: &'e Entities, extensions: &'e Extensions<'e>) -> Self {
+    /// `EntityStore` (e.g., `Entities`) to resolve entity references. Use the
+    /// given `Extension`s when evaluating.
+    pub fn new(q: Request, entities: &'e dyn EntityStore, extensions: &'e Extensions<'e>) -> Self {
         Self {
             principal: q.principal,
             action: q.action,
@@ -356,11 +381,81 @@ impl<'e> Evaluator<'e> {
             },
             entities,
             extensions,
+            cache: None,
+            tracer: None,
+            coverage: None,
+            limits: LimitTracker::default(),
+            ancestors: RefCell::default(),
             #[cfg(feature = "partial-eval")]
             unknowns_mapper: Box::new(|_: &str| -> Option<Value> { None }),
         }
     }
 
+    /// Use `cache` to share the results of extension constructor calls on
+    /// literals, and of hierarchy searches, with other `Evaluator`s using the
+    /// same entities and `Extensions`
+    pub fn with_cache(self, cache: &'e EvaluationCache) -> Self {
+        Self {
+            cache: Some(cache),
+            ..self
+        }
+    }
+
+    /// Record a [`TraceNode`] for every expression this `Evaluator`
+    /// evaluates, retrievable with [`Evaluator::take_trace`]
+    pub fn with_tracing(self) -> Self {
+        Self {
+            tracer: Some(RefCell::new(Tracer::default())),
+            ..self
+        }
+    }
+
+    /// Record which located expressions this `Evaluator` evaluates, and to
+    /// which boolean values, for a [`CoverageReport`]
+    pub fn with_coverage(self) -> Self {
+        Self {
+            coverage: Some(RefCell::new(CoverageRecorder::default())),
+            ..self
+        }
+    }
+
+    /// Fail evaluation with an error if it exceeds `limits`
+    pub fn with_limits(self, limits: EvaluationLimits) -> Self {
+        Self {
+            limits: LimitTracker::new(limits),
+            ..self
+        }
+    }
+
+    /// Take the trace of the most recent call to `evaluate()`,
+    /// `partial_evaluate()`, `interpret()` or `partial_interpret()`.
+    /// Returns `None` if tracing is not enabled.
+    pub fn take_trace(&self) -> Option<TraceNode> {
+        self.tracer.as_ref()?.borrow_mut().take()
+    }
+
+    /// Take the expressions recorded since coverage was last taken.
+    /// Returns `None` if coverage is not enabled.
+    pub(crate) fn take_coverage(&self) -> Option<CoverageRecorder> {
+        Some(std::mem::take(&mut *self.coverage.as_ref()?.borrow_mut()))
+    }
+
+    /// Record that the expression currently being evaluated short-circuited
+    fn trace_short_circuit(&self) {
+        if let Some(tracer) = &self.tracer {
+            tracer.borrow_mut().short_circuit();
+        }
+    }
+
+    /// Look up `uid` in the entity store, counting the lookup against the
+    /// configured `EvaluationLimits`
+    fn entity(&self, uid: &EntityUID) -> Result<StoredEntity> {
+        self.limits.entity_deref()?;
+        self.entities
+            .entity(uid)
+            .map_err(|err| EvaluationError::entity_load(Arc::new(uid.clone()), err, None))
+    }
+
     // Constructs an Evaluator for a given unknowns mapper function.
     #[cfg(feature = "partial-eval")]
     pub(crate) fn with_unknowns_mapper(self, unknowns_mapper: UnknownsMapper<'e>) -> Self {
@@ -371,6 +466,11 @@ impl<'e> Evaluator<'e> {
             context: self.context,
             entities: self.entities,
             extensions: self.extensions,
+            cache: self.cache,
+            tracer: self.tracer,
+            coverage: self.coverage,
+            limits: self.limits,
+            ancestors: self.ancestors,
             unknowns_mapper,
         }
     }
@@ -382,6 +482,7 @@ impl<'e> Evaluator<'e> {
     /// it doesn't consider whether we're processing a `Permit` policy or a
     /// `Forbid` policy.
     pub fn evaluate(&self, p: &Policy) -> Result<bool> {
+        self.limits.reset();
         self.interpret(&p.condition(), p.env())?.get_as_bool()
     }
 
@@ -395,6 +496,7 @@ impl<'e> Evaluator<'e> {
     ///    it doesn't consider whether we're processing a `Permit` policy or a
     ///    `Forbid` policy.
     pub fn partial_evaluate(&self, p: &Policy) -> Result<Either<bool, Expr>> {
+        self.limits.reset();
         match self.partial_interpret(&p.condition(), p.env())? {
             PartialValue::Value(v) => v.get_as_bool().map(Either::Left),
             PartialValue::Residual(e) => Ok(Either::Right(e)),
@@ -407,6 +509,7 @@ impl<'e> Evaluator<'e> {
     /// May return an error, for instance if the `Expr` tries to access an
     /// attribute that doesn't exist.
     pub fn interpret(&self, e: &Expr, slots: &SlotEnv) -> Result<Value> {
+        self.limits.reset();
         match self.partial_interpret(e, slots)? {
             PartialValue::Value(v) => Ok(v),
             PartialValue::Residual(r) => Err(EvaluationError::non_value(r)),
@@ -421,6 +524,12 @@ impl<'e> Evaluator<'e> {
     pub fn partial_interpret(&self, expr: &Expr, slots: &SlotEnv) -> Result<PartialValue> {
         stack_size_check()?;
 
+        if let Some(tracer) = &self.tracer {
+            tracer.borrow_mut().enter();
+        }
+        if let Some(coverage) = &self.coverage {
+            coverage.borrow_mut().enter(expr);
+        }
         let res = self.partial_interpret_internal(expr, slots);
 
         // set the returned value's source location to the same source location
@@ -430,11 +539,19 @@ impl<'e> Evaluator<'e> {
         // also, if there is an error, set its source location to the source
         // location of the input expression as well, unless it already had a
         // more specific location
-        res.map(|pval| pval.with_maybe_source_loc(expr.source_loc().cloned()))
+        let res = res
+            .map(|pval| pval.with_maybe_source_loc(expr.source_loc().cloned()))
             .map_err(|err| match err.source_loc() {
                 None => err.with_maybe_source_loc(expr.source_loc().cloned()),
                 Some(_) => err,
-            })
+            });
+        if let Some(tracer) = &self.tracer {
+            tracer.borrow_mut().exit(expr, &res);
+        }
+        if let Some(coverage) = &self.coverage {
+            coverage.borrow_mut().exit(expr, &res);
+        }
+        res
     }
 
     /// Internal function to interpret an `Expr`. (External callers, use
@@ -448,6 +565,7 @@ impl<'e> Evaluator<'e> {
     /// `partial_interpret()`.
     #[allow(clippy::cognitive_complexity)]
     fn partial_interpret_internal(&self, expr: &Expr, slots: &SlotEnv) -> Result<PartialValue> {
+        self.limits.step()?;
         let loc = expr.source_loc(); // the `loc` describing the location of the entire expression
         match expr.expr_kind() {
             ExprKind::Lit(lit) => Ok(lit.clone().into()),
@@ -488,6 +606,7 @@ impl<'e> Evaluator<'e> {
                             }
                         } else {
                             // We can short circuit here
+                            self.trace_short_circuit();
                             Ok(false.into())
                         }
                     }
@@ -503,6 +622,7 @@ impl<'e> Evaluator<'e> {
                     PartialValue::Value(lhs) => {
                         if lhs.get_as_bool()? {
                             // We can short circuit here
+                            self.trace_short_circuit();
                             Ok(true.into())
                         } else {
                             match self.partial_interpret(right, slots)? {
@@ -577,12 +697,12 @@ impl<'e> Evaluator<'e> {
                                 };
                                 e
                             })?;
-                        match self.entities.entity(uid1) {
-                            Dereference::Residual(r) => Ok(PartialValue::Residual(
+                        match self.entity(uid1)? {
+                            StoredEntity::Residual(r) => Ok(PartialValue::Residual(
                                 Expr::binary_app(BinaryOp::In, r, arg2.into()),
                             )),
-                            Dereference::NoSuchEntity => self.eval_in(uid1, None, arg2),
-                            Dereference::Data(entity1) => self.eval_in(uid1, Some(entity1), arg2),
+                            StoredEntity::NoSuchEntity => self.eval_in(uid1, None, arg2),
+                            StoredEntity::Data(entity1) => self.eval_in(uid1, Some(&entity1), arg2),
                         }
                     }
                     // contains, which works on Sets
@@ -612,18 +732,18 @@ impl<'e> Evaluator<'e> {
                         let tag = arg2.get_as_string()?;
                         match op {
                             BinaryOp::GetTag => {
-                                match self.entities.entity(uid) {
-                                    Dereference::NoSuchEntity => {
+                                match self.entity(uid)? {
+                                    StoredEntity::NoSuchEntity => {
                                         // intentionally using the location of the euid (the LHS) and not the entire GetTag expression
                                         Err(EvaluationError::entity_does_not_exist(
                                             Arc::new(uid.clone()),
                                             arg1.source_loc().cloned(),
                                         ))
                                     }
-                                    Dereference::Residual(r) => Ok(PartialValue::Residual(
+                                    StoredEntity::Residual(r) => Ok(PartialValue::Residual(
                                         Expr::get_tag(r, Expr::val(tag.clone())),
                                     )),
-                                    Dereference::Data(entity) => entity
+                                    StoredEntity::Data(entity) => entity
                                         .get_tag(tag)
                                         .ok_or_else(|| {
                                             EvaluationError::entity_tag_does_not_exist(
@@ -638,12 +758,12 @@ impl<'e> Evaluator<'e> {
                                         .cloned(),
                                 }
                             }
-                            BinaryOp::HasTag => match self.entities.entity(uid) {
-                                Dereference::NoSuchEntity => Ok(false.into()),
-                                Dereference::Residual(r) => Ok(PartialValue::Residual(
+                            BinaryOp::HasTag => match self.entity(uid)? {
+                                StoredEntity::NoSuchEntity => Ok(false.into()),
+                                StoredEntity::Residual(r) => Ok(PartialValue::Residual(
                                     Expr::has_tag(r, Expr::val(tag.clone())),
                                 )),
-                                Dereference::Data(entity) => {
+                                StoredEntity::Data(entity) => {
                                     Ok(entity.get_tag(tag).is_some().into())
                                 }
                             },
@@ -663,9 +783,7 @@ impl<'e> Evaluator<'e> {
                     .collect::<Result<Vec<_>>>()?;
                 match split(args) {
                     Either::Left(vals) => {
-                        let vals: Vec<_> = vals.collect();
-                        let efunc = self.extensions.func(fn_name)?;
-                        efunc.call(&vals)
+                        self.call_extension_fn(fn_name, &vals.collect::<Vec<_>>())
                     }
                     Either::Right(residuals) => Ok(PartialValue::Residual(
                         Expr::call_extension_fn(fn_name.clone(), residuals.collect()),
@@ -681,12 +799,12 @@ impl<'e> Evaluator<'e> {
                 PartialValue::Value(Value {
                     value: ValueKind::Lit(Literal::EntityUID(uid)),
                     ..
-                }) => match self.entities.entity(&uid) {
-                    Dereference::NoSuchEntity => Ok(false.into()),
-                    Dereference::Residual(r) => {
+                }) => match self.entity(&uid)? {
+                    StoredEntity::NoSuchEntity => Ok(false.into()),
+                    StoredEntity::Residual(r) => {
                         Ok(PartialValue::Residual(Expr::has_attr(r, attr.clone())))
                     }
-                    Dereference::Data(e) => Ok(e.get(attr).is_some().into()),
+                    StoredEntity::Data(e) => Ok(e.get(attr).is_some().into()),
                 },
                 PartialValue::Value(val) => Err(err::EvaluationError::type_error(
                     nonempty![
@@ -728,6 +846,7 @@ impl<'e> Evaluator<'e> {
                 }
             }
             ExprKind::Set(items) => {
+                self.limits.set_size(items.len(), loc)?;
                 let vals = items
                     .iter()
                     .map(|item| self.partial_interpret(item, slots))
@@ -813,12 +932,17 @@ impl<'e> Evaluator<'e> {
                 ))
             }
         };
+        let ancestors = match (entity1, self.entities.ancestor_mode()) {
+            (Some(e1), AncestorMode::OnDemand) => Some(self.search_ancestors(e1)?),
+            _ => None,
+        };
         for uid2 in rhs {
-            if uid1 == &uid2
-                || entity1
-                    .map(|e1| e1.is_descendant_of(&uid2))
-                    .unwrap_or(false)
-            {
+            let is_descendant = match (&ancestors, entity1) {
+                (Some(ancestors), _) => ancestors.contains(&uid2),
+                (None, Some(e1)) => e1.is_descendant_of(&uid2),
+                (None, None) => false,
+            };
+            if uid1 == &uid2 || is_descendant {
                 return Ok(true.into());
             }
         }
@@ -827,6 +951,82 @@ impl<'e> Evaluator<'e> {
         Ok(false.into())
     }
 
+    /// Find all the ancestors of `entity` by searching the hierarchy, for
+    /// stores which only keep the parents of each entity. Each ancestor looked
+    /// up counts against the configured `EvaluationLimits`. The result is
+    /// remembered, in the shared cache if there is one, and used to shortcut
+    /// later searches which reach `entity`.
+    fn search_ancestors(&self, entity: &Entity) -> Result<Arc<HashSet<EntityUID>>> {
+        if let Some(ancestors) = self.known_ancestors(entity.uid()) {
+            return Ok(ancestors);
+        }
+        let mut ancestors = HashSet::new();
+        let mut worklist = entity.ancestors().cloned().collect::<Vec<_>>();
+        while let Some(uid) = worklist.pop() {
+            if ancestors.contains(&uid) {
+                continue;
+            }
+            if let Some(known) = self.known_ancestors(&uid) {
+                ancestors.extend(known.iter().cloned());
+            } else if let StoredEntity::Data(ancestor) = self.entity(&uid)? {
+                worklist.extend(ancestor.ancestors().cloned());
+            }
+            ancestors.insert(uid);
+        }
+        let ancestors = Arc::new(ancestors);
+        match self.cache {
+            Some(cache) => cache.insert_ancestors(entity.uid().clone(), Arc::clone(&ancestors)),
+            None => {
+                self.ancestors
+                    .borrow_mut()
+                    .insert(entity.uid().clone(), Arc::clone(&ancestors));
+            }
+        }
+        Ok(ancestors)
+    }
+
+    /// Get the ancestors of `uid` found by an earlier hierarchy search, by
+    /// this `Evaluator` or by any other sharing its cache
+    fn known_ancestors(&self, uid: &EntityUID) -> Option<Arc<HashSet<EntityUID>>> {
+        match self.cache {
+            Some(cache) => cache.ancestors(uid),
+            None => self.ancestors.borrow().get(uid).cloned(),
+        }
+    }
+
+    /// Call the extension function `fn_name` on `args`, using the cached result
+    /// if the call is a constructor applied to literals and has been made
+    /// before
+    fn call_extension_fn(&self, fn_name: &Name, args: &[Value]) -> Result<PartialValue> {
+        let efunc = self.extensions.func(fn_name)?;
+        let cache = match self.cache {
+            Some(cache) if efunc.is_constructor() => cache,
+            _ => return efunc.call(args),
+        };
+        let Some(literals) = args
+            .iter()
+            .map(|arg| match &arg.value {
+                ValueKind::Lit(lit) => Some(lit.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            return efunc.call(args);
+        };
+        if let Some(v) = cache.extension_call(fn_name, &literals) {
+            return Ok(v.into());
+        }
+        let res = efunc.call(args)?;
+        if let PartialValue::Value(v) = &res {
+            cache.insert_extension_call(
+                fn_name.clone(),
+                literals,
+                v.clone().with_maybe_source_loc(None),
+            );
+        }
+        Ok(res)
+    }
+
     /// Evaluation of conditionals
     /// Must be sure to respect short-circuiting semantics
     fn eval_if(
@@ -919,15 +1119,15 @@ impl<'e> Evaluator<'e> {
             PartialValue::Value(Value {
                 value: ValueKind::Lit(Literal::EntityUID(uid)),
                 loc,
-            }) => match self.entities.entity(uid.as_ref()) {
-                Dereference::NoSuchEntity => {
+            }) => match self.entity(uid.as_ref())? {
+                StoredEntity::NoSuchEntity => {
                     // intentionally using the location of the euid (the LHS) and not the entire GetAttr expression
                     Err(EvaluationError::entity_does_not_exist(uid.clone(), loc))
                 }
-                Dereference::Residual(r) => {
+                StoredEntity::Residual(r) => {
                     Ok(PartialValue::Residual(Expr::get_attr(r, attr.clone())))
                 }
-                Dereference::Data(entity) => entity
+                StoredEntity::Data(entity) => entity
                     .get(attr)
                     .map(|pv| match pv {
                         PartialValue::Value(_) => Ok(pv.clone()),
@@ -1155,7 +1355,7 @@ pub(crate) mod test {
     use super::*;
 
     use crate::{
-        entities::{EntityJsonParser, NoEntitiesSchema, TCComputation},
+        entities::{Entities, EntityJsonParser, NoEntitiesSchema, TCComputation},
         parser::{self, parse_expr, parse_policy_or_template, parse_policyset},
         test_utils::{expect_err, ExpectedErrorMessageBuilder},
     };
@@ -4085,6 +4285,53 @@ pub(crate) mod test {
         );
     }
 
+    #[test]
+    fn interpret_hierarchy_membership_on_demand() {
+        // `in` gives the same results whether the ancestors are precomputed or
+        // searched for
+        let request = basic_request();
+        let precomputed = rich_entities();
+        let on_demand = rich_entities()
+            .with_ancestor_mode(AncestorMode::OnDemand)
+            .unwrap();
+        assert!(on_demand
+            .iter()
+            .all(|e| e.indirect_ancestors().next().is_none()));
+        let precomputed_eval = Evaluator::new(request.clone(), &precomputed, Extensions::none());
+        let on_demand_eval = Evaluator::new(request, &on_demand, Extensions::none());
+        let uids = precomputed
+            .iter()
+            .flat_map(|e| std::iter::once(e.uid()).chain(e.ancestors()))
+            .cloned()
+            .chain([EntityUID::with_eid("doesnotexist")])
+            .collect::<HashSet<_>>();
+        for a in &uids {
+            for b in &uids {
+                let e = Expr::is_in(Expr::val(a.clone()), Expr::val(b.clone()));
+                assert_eq!(
+                    on_demand_eval.interpret_inline_policy(&e),
+                    precomputed_eval.interpret_inline_policy(&e),
+                    "{a} in {b}"
+                );
+                let e = Expr::is_in(
+                    Expr::val(a.clone()),
+                    Expr::set([
+                        Expr::val(b.clone()),
+                        Expr::val(EntityUID::with_eid("parent")),
+                    ]),
+                );
+                assert_eq!(
+                    on_demand_eval.interpret_inline_policy(&e),
+                    precomputed_eval.interpret_inline_policy(&e),
+                    "{a} in [{b}, parent]"
+                );
+            }
+        }
+        // the searches were remembered
+        assert!(!on_demand_eval.ancestors.borrow().is_empty());
+        assert!(precomputed_eval.ancestors.borrow().is_empty());
+    }
+
     #[test]
     fn interpret_hierarchy_membership_slice() {
         // User::"Alice" in Group::"Friends".
@@ -6373,4 +6620,156 @@ pub(crate) mod test {
         let r = eval.partial_eval_expr(&e).unwrap();
         assert_eq!(r, Either::Left(Value::from(false)));
     }
+
+    #[test]
+    fn step_limit() {
+        let request = basic_request();
+        let entities = basic_entities();
+        let src = "1 + 2 + 3 == 6";
+        let expr = parse_expr(src).unwrap();
+        // `1 + 2 + 3 == 6` has seven expression nodes
+        let eval = Evaluator::new(request.clone(), &entities, Extensions::none())
+            .with_limits(EvaluationLimits::unlimited().with_max_steps(7));
+        assert_matches!(eval.interpret_inline_policy(&expr), Ok(v) => {
+            assert_eq!(v, Value::from(true));
+        });
+        let eval = Evaluator::new(request, &entities, Extensions::none())
+            .with_limits(EvaluationLimits::unlimited().with_max_steps(6));
+        assert_matches!(eval.interpret_inline_policy(&expr), Err(e) => {
+            expect_err(
+                src,
+                &miette::Report::new(e),
+                &ExpectedErrorMessageBuilder::error(
+                    "evaluation limit exceeded: evaluated more than 6 expressions",
+                )
+                .exactly_one_underline("6")
+                .build(),
+            );
+        });
+    }
+
+    #[test]
+    fn step_limit_applies_per_policy() {
+        let request = basic_request();
+        let entities = basic_entities();
+        let policies = parse_policyset(
+            r#"
+            permit(principal, action, resource) when { 1 + 1 == 2 };
+            permit(principal, action, resource) when { 1 + 1 == 2 };
+            "#,
+        )
+        .unwrap();
+        let eval = Evaluator::new(request, &entities, Extensions::none())
+            .with_limits(EvaluationLimits::unlimited().with_max_steps(20));
+        for policy in policies.policies() {
+            assert_matches!(eval.evaluate(policy), Ok(true));
+        }
+    }
+
+    #[test]
+    fn entity_deref_limit() {
+        let request = basic_request();
+        let entities = rich_entities();
+        let eval = Evaluator::new(request, &entities, Extensions::none())
+            .with_limits(EvaluationLimits::unlimited().with_max_entity_derefs(1));
+        assert_matches!(
+            eval.interpret_inline_policy(
+                &parse_expr(r#"test_entity_type::"entity_with_attrs".spoon == 787"#).unwrap()
+            ),
+            Ok(v) => {
+                assert_eq!(v, Value::from(true));
+            }
+        );
+        let expr = parse_expr(
+            r#"test_entity_type::"entity_with_attrs".spoon == 787 && test_entity_type::"entity_with_attrs" has fork"#,
+        )
+        .unwrap();
+        assert_matches!(
+            eval.interpret_inline_policy(&expr),
+            Err(EvaluationError::EvaluationLimit(e)) => {
+                assert_eq!(e.limit, limits::ExceededLimit::EntityDerefs(1));
+            }
+        );
+    }
+
+    #[test]
+    fn entity_deref_limit_on_demand() {
+        // with on-demand ancestors, searching the hierarchy looks up entities
+        let request = basic_request();
+        let precomputed = rich_entities();
+        let on_demand = rich_entities()
+            .with_ancestor_mode(AncestorMode::OnDemand)
+            .unwrap();
+        let expr = Expr::is_in(
+            Expr::val(EntityUID::with_eid("child")),
+            Expr::val(EntityUID::with_eid("grandparent")),
+        );
+        let limits = EvaluationLimits::unlimited().with_max_entity_derefs(2);
+        let eval =
+            Evaluator::new(request.clone(), &precomputed, Extensions::none()).with_limits(limits);
+        assert_matches!(eval.interpret_inline_policy(&expr), Ok(v) => {
+            assert_eq!(v, Value::from(true));
+        });
+        // `child`, then `parent` and `grandparent` while searching
+        let eval =
+            Evaluator::new(request.clone(), &on_demand, Extensions::none()).with_limits(limits);
+        assert_matches!(
+            eval.interpret_inline_policy(&expr),
+            Err(EvaluationError::EvaluationLimit(e)) => {
+                assert_eq!(e.limit, limits::ExceededLimit::EntityDerefs(2));
+            }
+        );
+        let eval = Evaluator::new(request, &on_demand, Extensions::none())
+            .with_limits(EvaluationLimits::unlimited().with_max_entity_derefs(3));
+        assert_matches!(eval.interpret_inline_policy(&expr), Ok(v) => {
+            assert_eq!(v, Value::from(true));
+        });
+        // the ancestors found are remembered, so only `child` is looked up
+        // again
+        let eval = eval.with_limits(EvaluationLimits::unlimited().with_max_entity_derefs(1));
+        assert_matches!(eval.interpret_inline_policy(&expr), Ok(v) => {
+            assert_eq!(v, Value::from(true));
+        });
+    }
+
+    #[test]
+    fn set_size_limit() {
+        let request = basic_request();
+        let entities = basic_entities();
+        let eval = Evaluator::new(request, &entities, Extensions::none())
+            .with_limits(EvaluationLimits::unlimited().with_max_set_size(3));
+        assert_matches!(
+            eval.interpret_inline_policy(&parse_expr("[1, 2, 3].contains(2)").unwrap()),
+            Ok(v) => {
+                assert_eq!(v, Value::from(true));
+            }
+        );
+        let src = "[1, 2, 3, 4].contains(2)";
+        assert_matches!(eval.interpret_inline_policy(&parse_expr(src).unwrap()), Err(e) => {
+            expect_err(
+                src,
+                &miette::Report::new(e),
+                &ExpectedErrorMessageBuilder::error(
+                    "evaluation limit exceeded: evaluated a set literal of more than 3 elements",
+                )
+                .exactly_one_underline("[1, 2, 3, 4]")
+                .build(),
+            );
+        });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn deadline_limit() {
+        let request = basic_request();
+        let entities = basic_entities();
+        let eval = Evaluator::new(request, &entities, Extensions::none())
+            .with_limits(EvaluationLimits::unlimited().with_deadline(std::time::Instant::now()));
+        assert_matches!(
+            eval.interpret_inline_policy(&parse_expr("true").unwrap()),
+            Err(EvaluationError::EvaluationLimit(e)) => {
+                assert_eq!(e.limit, limits::ExceededLimit::Deadline);
+            }
+        );
+    }
 }
@@ -0,0 +1,178 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Evaluation work that can be shared between the `Evaluator`s for many
+//! requests.
+
+use crate::ast::{EntityUID, Literal, Name, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// Cache of results which do not depend on the request being evaluated, so
+/// can be shared by the `Evaluator`s for many requests using the same
+/// entities and `Extensions`.
+///
+/// The cache holds
+/// - the results of extension constructor calls on literal arguments, such
+///   as `ip("10.0.0.1")` or `decimal("1.23")`, which otherwise would be
+///   re-parsed every time a policy containing them is evaluated, and
+/// - the ancestors found by searching the entity hierarchy, for stores with
+///   `AncestorMode::OnDemand`, so that each `in` check against the same
+///   entity doesn't walk the hierarchy again.
+///
+/// As the ancestors depend on the entities, a cache must only be shared by
+/// `Evaluator`s over the same entity store.
+///
+/// The cache is safe to share between threads.
+#[derive(Debug, Default)]
+pub struct EvaluationCache {
+    /// Results of successful extension constructor calls, keyed on the
+    /// function name and (literal) arguments
+    extension_calls: RwLock<HashMap<Name, HashMap<Vec<Literal>, Value>>>,
+    /// All the ancestors of each entity whose hierarchy has been searched
+    ancestors: RwLock<HashMap<EntityUID, Arc<HashSet<EntityUID>>>>,
+}
+
+impl EvaluationCache {
+    /// Create a fresh, empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the cached result of calling the extension function `fn_name` on
+    /// `args`, if any
+    pub(crate) fn extension_call(&self, fn_name: &Name, args: &[Literal]) -> Option<Value> {
+        // a poisoned lock only means another thread panicked while holding
+        // it; the map is still valid, so we can keep using it
+        let calls = self
+            .extension_calls
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        calls.get(fn_name)?.get(args).cloned()
+    }
+
+    /// Record the result of calling the extension function `fn_name` on `args`
+    pub(crate) fn insert_extension_call(&self, fn_name: Name, args: Vec<Literal>, value: Value) {
+        let mut calls = self
+            .extension_calls
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        calls.entry(fn_name).or_default().insert(args, value);
+    }
+
+    /// Get the ancestors of `uid` found by an earlier hierarchy search, if any
+    pub(crate) fn ancestors(&self, uid: &EntityUID) -> Option<Arc<HashSet<EntityUID>>> {
+        let ancestors = self
+            .ancestors
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        ancestors.get(uid).cloned()
+    }
+
+    /// Record the ancestors of `uid` found by a hierarchy search
+    pub(crate) fn insert_ancestors(&self, uid: EntityUID, found: Arc<HashSet<EntityUID>>) {
+        let mut ancestors = self
+            .ancestors
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        ancestors.insert(uid, found);
+    }
+}
+
+#[cfg(test)]
+// PANIC SAFETY: unit tests
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::*;
+    use crate::ast::Expr;
+    use crate::entities::AncestorMode;
+    use crate::evaluator::test::{basic_entities, basic_request, rich_entities};
+    use crate::evaluator::Evaluator;
+    use crate::extensions::Extensions;
+    use crate::parser::parse_expr;
+
+    fn ip(s: &str) -> Value {
+        let entities = basic_entities();
+        let eval = Evaluator::new(basic_request(), &entities, Extensions::all_available());
+        eval.interpret_inline_policy(&parse_expr(&format!(r#"ip("{s}")"#)).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn caches_extension_constructor_calls() {
+        let cache = EvaluationCache::new();
+        let entities = basic_entities();
+        let name = Name::parse_unqualified_name("ip").unwrap();
+        let args = [Literal::from("10.0.0.1")];
+        let expr: Expr = parse_expr(r#"ip("10.0.0.1")"#).unwrap();
+
+        let eval = Evaluator::new(basic_request(), &entities, Extensions::all_available())
+            .with_cache(&cache);
+        assert_eq!(eval.interpret_inline_policy(&expr).unwrap(), ip("10.0.0.1"));
+        assert_eq!(cache.extension_call(&name, &args), Some(ip("10.0.0.1")));
+
+        // A later evaluator uses the cached result rather than parsing the
+        // argument again, as shown by changing the cached result
+        cache.insert_extension_call(name, args.to_vec(), ip("10.0.0.2"));
+        let eval = Evaluator::new(basic_request(), &entities, Extensions::all_available())
+            .with_cache(&cache);
+        assert_eq!(eval.interpret_inline_policy(&expr).unwrap(), ip("10.0.0.2"));
+        let uncached = Evaluator::new(basic_request(), &entities, Extensions::all_available());
+        assert_eq!(
+            uncached.interpret_inline_policy(&expr).unwrap(),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn caches_ancestor_searches() {
+        let cache = EvaluationCache::new();
+        let entities = rich_entities()
+            .with_ancestor_mode(AncestorMode::OnDemand)
+            .unwrap();
+        let child = EntityUID::with_eid("child");
+        let grandparent = EntityUID::with_eid("grandparent");
+        let other = EntityUID::with_eid("entity_no_attrs_no_parents");
+        let child_in = |ancestor: &EntityUID| {
+            Expr::is_in(Expr::val(child.clone()), Expr::val(ancestor.clone()))
+        };
+
+        let eval =
+            Evaluator::new(basic_request(), &entities, Extensions::none()).with_cache(&cache);
+        assert_eq!(
+            eval.interpret_inline_policy(&child_in(&grandparent))
+                .unwrap(),
+            Value::from(true)
+        );
+        assert!(cache.ancestors(&child).unwrap().contains(&grandparent));
+        assert!(eval.ancestors.borrow().is_empty());
+
+        // A later evaluator uses the cached ancestors rather than searching
+        // the hierarchy again, as shown by changing the cached ancestors
+        cache.insert_ancestors(child.clone(), Arc::new(HashSet::from([other.clone()])));
+        let eval =
+            Evaluator::new(basic_request(), &entities, Extensions::none()).with_cache(&cache);
+        assert_eq!(
+            eval.interpret_inline_policy(&child_in(&other)).unwrap(),
+            Value::from(true)
+        );
+        let uncached = Evaluator::new(basic_request(), &entities, Extensions::none());
+        assert_eq!(
+            uncached.interpret_inline_policy(&child_in(&other)).unwrap(),
+            Value::from(false)
+        );
+    }
+}
@@ -0,0 +1,757 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Recording of which policies, condition clauses and boolean expressions are
+//! exercised by the requests an `Evaluator` evaluates, aggregated over many
+//! requests into a [`CoverageReport`].
+//!
+//! Expressions are identified by their source location, so clauses and
+//! boolean expressions are only reported for policies parsed from Cedar
+//! syntax.
+
+use super::Result;
+use crate::ast::{
+    condition_clauses, BinaryOp, Expr, ExprKind, Literal, PartialValue, Policy, PolicyID,
+    PolicySet, UnaryOp, ValueKind,
+};
+use crate::parser::Loc;
+use crate::xml::xml_escape;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Start and end offsets of a source location, which identify an expression
+/// within a policy
+type Span = (usize, usize);
+
+fn span(loc: &Loc) -> Span {
+    (loc.start(), loc.end())
+}
+
+/// How often an expression was evaluated, and how often it evaluated to
+/// `true` and to `false`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExprCoverage {
+    evaluations: usize,
+    times_true: usize,
+    times_false: usize,
+}
+
+impl ExprCoverage {
+    /// Number of times the expression was evaluated, with any result
+    pub fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+
+    /// Number of times the expression evaluated to `true`
+    pub fn times_true(&self) -> usize {
+        self.times_true
+    }
+
+    /// Number of times the expression evaluated to `false`
+    pub fn times_false(&self) -> usize {
+        self.times_false
+    }
+
+    /// Did the expression evaluate to both `true` and `false`?
+    pub fn fully_covered(&self) -> bool {
+        self.times_true > 0 && self.times_false > 0
+    }
+
+    fn add(&mut self, other: Self) {
+        self.evaluations += other.evaluations;
+        self.times_true += other.times_true;
+        self.times_false += other.times_false;
+    }
+}
+
+/// Records the located expressions an `Evaluator` evaluates
+#[derive(Debug, Default)]
+pub(crate) struct CoverageRecorder {
+    /// Spans of the expressions currently being evaluated, innermost last
+    stack: Vec<Option<Span>>,
+    hits: HashMap<Span, (Loc, ExprCoverage)>,
+}
+
+impl CoverageRecorder {
+    /// Start evaluating `expr`
+    pub(crate) fn enter(&mut self, expr: &Expr) {
+        self.stack.push(expr.source_loc().map(span));
+    }
+
+    /// Finish evaluating `expr`, which produced `result`
+    pub(crate) fn exit(&mut self, expr: &Expr, result: &Result<PartialValue>) {
+        self.stack.pop();
+        let Some(loc) = expr.source_loc() else {
+            return;
+        };
+        // Expressions desugared from another expression, like the `<=` in
+        // `!(a <= b)` for `a > b`, share its location. Only the outermost
+        // one is recorded.
+        if self.stack.last() == Some(&Some(span(loc))) {
+            return;
+        }
+        let (_, hits) = self
+            .hits
+            .entry(span(loc))
+            .or_insert_with(|| (loc.clone(), ExprCoverage::default()));
+        hits.evaluations += 1;
+        if let Ok(PartialValue::Value(v)) = result {
+            match v.value_kind() {
+                ValueKind::Lit(Literal::Bool(true)) => hits.times_true += 1,
+                ValueKind::Lit(Literal::Bool(false)) => hits.times_false += 1,
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Coverage of a single policy
+#[derive(Debug, Clone)]
+pub struct PolicyCoverage {
+    /// Source location of the policy
+    loc: Option<Loc>,
+    /// Number of times the policy was evaluated
+    evaluations: usize,
+    /// Number of times the policy was satisfied
+    times_satisfied: usize,
+    /// Number of times the policy was a determining policy of a response
+    times_determining: usize,
+    /// Spans of the `when` and `unless` clauses, in source order
+    clauses: Vec<Span>,
+    /// Coverage of the clauses and the boolean expressions in them, keyed on
+    /// their spans
+    exprs: BTreeMap<Span, (Loc, ExprCoverage)>,
+}
+
+impl PolicyCoverage {
+    fn new(p: &Policy) -> Self {
+        let mut cov = Self {
+            loc: p.loc().cloned(),
+            evaluations: 0,
+            times_satisfied: 0,
+            times_determining: 0,
+            clauses: Vec::new(),
+            exprs: BTreeMap::new(),
+        };
+        // Clauses are joined with `&&` expressions located at the policy
+        if let Some(policy_loc) = p.loc() {
+            for clause in condition_clauses(p.non_scope_constraints(), policy_loc) {
+                if let Some(loc) = clause.source_loc() {
+                    cov.clauses.push(span(loc));
+                    cov.exprs
+                        .insert(span(loc), (loc.clone(), ExprCoverage::default()));
+                }
+                for e in clause.subexpressions().filter(|e| is_boolean(e)) {
+                    if let Some(loc) = e.source_loc() {
+                        cov.exprs
+                            .entry(span(loc))
+                            .or_insert_with(|| (loc.clone(), ExprCoverage::default()));
+                    }
+                }
+            }
+        }
+        cov
+    }
+
+    /// Source location of the policy, if known
+    pub fn source_loc(&self) -> Option<&Loc> {
+        self.loc.as_ref()
+    }
+
+    /// Number of times the policy was evaluated
+    pub fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+
+    /// Number of times the policy was satisfied
+    pub fn times_satisfied(&self) -> usize {
+        self.times_satisfied
+    }
+
+    /// Number of times the policy was one of the determining policies of an
+    /// authorization response
+    pub fn times_determining(&self) -> usize {
+        self.times_determining
+    }
+
+    /// The `when` and `unless` clauses of the policy, in source order, with
+    /// their coverage
+    pub fn clauses(&self) -> impl Iterator<Item = (&Loc, ExprCoverage)> {
+        self.clauses
+            .iter()
+            .filter_map(|s| self.exprs.get(s).map(|(loc, cov)| (loc, *cov)))
+    }
+
+    /// The boolean expressions in the clauses of the policy, including the
+    /// clauses themselves, with their coverage. Expressions are ordered by
+    /// where they start in the source, and inner expressions come before
+    /// outer expressions starting at the same place.
+    pub fn conditions(&self) -> impl Iterator<Item = (&Loc, ExprCoverage)> {
+        self.exprs.values().map(|(loc, cov)| (loc, *cov))
+    }
+
+    fn record(&mut self, recorder: CoverageRecorder, satisfied: bool) {
+        self.evaluations += 1;
+        if satisfied {
+            self.times_satisfied += 1;
+        }
+        let policy_span = self.loc.as_ref().map(span);
+        for (s, (loc, hits)) in recorder.hits {
+            if Some(s) == policy_span {
+                continue;
+            }
+            // Expressions which are not syntactically boolean, like attribute
+            // accesses or extension function calls, are included once they
+            // evaluate to a boolean
+            if let Some((_, cov)) = self.exprs.get_mut(&s) {
+                cov.add(hits);
+            } else if hits.times_true > 0 || hits.times_false > 0 {
+                self.exprs.insert(s, (loc, hits));
+            }
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.evaluations += other.evaluations;
+        self.times_satisfied += other.times_satisfied;
+        self.times_determining += other.times_determining;
+        for (s, (loc, cov)) in &other.exprs {
+            self.exprs
+                .entry(*s)
+                .or_insert_with(|| (loc.clone(), ExprCoverage::default()))
+                .1
+                .add(*cov);
+        }
+    }
+
+    /// Line coverage of the policy, keyed on line number. Each line is hit as
+    /// often as the least evaluated of the policy (on its first line),
+    /// clauses and conditions starting on it.
+    fn lines(&self) -> BTreeMap<usize, LineCoverage> {
+        let mut lines: BTreeMap<usize, LineCoverage> = BTreeMap::new();
+        let Some(loc) = &self.loc else {
+            return lines;
+        };
+        lines.insert(
+            line_of(loc),
+            LineCoverage {
+                hits: self.evaluations,
+                branches: 0,
+                branches_taken: 0,
+            },
+        );
+        for (loc, cov) in self.conditions() {
+            let line = lines.entry(line_of(loc)).or_insert(LineCoverage {
+                hits: cov.evaluations,
+                branches: 0,
+                branches_taken: 0,
+            });
+            line.hits = line.hits.min(cov.evaluations);
+            line.branches += 2;
+            line.branches_taken +=
+                usize::from(cov.times_true > 0) + usize::from(cov.times_false > 0);
+        }
+        lines
+    }
+}
+
+/// Does `e` always evaluate to a boolean, if it evaluates without error?
+fn is_boolean(e: &Expr) -> bool {
+    match e.expr_kind() {
+        ExprKind::And { .. }
+        | ExprKind::Or { .. }
+        | ExprKind::HasAttr { .. }
+        | ExprKind::Like { .. }
+        | ExprKind::Is { .. }
+        | ExprKind::UnaryApp {
+            op: UnaryOp::Not, ..
+        } => true,
+        ExprKind::BinaryApp { op, .. } => matches!(
+            op,
+            BinaryOp::Eq
+                | BinaryOp::Less
+                | BinaryOp::LessEq
+                | BinaryOp::In
+                | BinaryOp::Contains
+                | BinaryOp::ContainsAll
+                | BinaryOp::ContainsAny
+                | BinaryOp::HasTag
+        ),
+        _ => false,
+    }
+}
+
+/// 1-based line number on which `loc` starts
+fn line_of(loc: &Loc) -> usize {
+    loc.src
+        .get(..loc.start())
+        .map_or(0, |s| s.matches('\n').count())
+        + 1
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LineCoverage {
+    hits: usize,
+    branches: usize,
+    branches_taken: usize,
+}
+
+/// Coverage of a policy set, aggregated over the requests it was evaluated
+/// for, keyed on policy id.
+///
+/// Reports for which policies were never determining, which `when` and
+/// `unless` clauses were never evaluated, and which boolean expressions never
+/// evaluated to both `true` and `false`.
+#[derive(Debug, Clone, Default)]
+pub struct CoverageReport {
+    policies: BTreeMap<PolicyID, PolicyCoverage>,
+}
+
+impl CoverageReport {
+    /// Create an empty `CoverageReport`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the policies in `pset` which are not already in the report, so
+    /// that they are reported even if they are never evaluated
+    pub fn add_policies(&mut self, pset: &PolicySet) {
+        for p in pset.policies() {
+            self.policy_mut(p);
+        }
+    }
+
+    /// Coverage of each policy, ordered by policy id
+    pub fn policies(&self) -> impl Iterator<Item = (&PolicyID, &PolicyCoverage)> {
+        self.policies.iter()
+    }
+
+    /// Coverage of the policy with id `id`, if it is in the report
+    pub fn get(&self, id: &PolicyID) -> Option<&PolicyCoverage> {
+        self.policies.get(id)
+    }
+
+    /// Policies which were never a determining policy of a response
+    pub fn never_determining(&self) -> impl Iterator<Item = &PolicyID> {
+        self.policies
+            .iter()
+            .filter(|(_, cov)| cov.times_determining == 0)
+            .map(|(id, _)| id)
+    }
+
+    /// Add the coverage recorded in `other` to this report
+    pub fn merge(&mut self, other: &Self) {
+        for (id, cov) in &other.policies {
+            match self.policies.get_mut(id) {
+                Some(existing) => existing.merge(cov),
+                None => {
+                    self.policies.insert(id.clone(), cov.clone());
+                }
+            }
+        }
+    }
+
+    fn policy_mut(&mut self, p: &Policy) -> &mut PolicyCoverage {
+        self.policies
+            .entry(p.id().clone())
+            .or_insert_with(|| PolicyCoverage::new(p))
+    }
+
+    /// Record an evaluation of `p`, whose expressions were recorded by
+    /// `recorder`
+    pub(crate) fn record_evaluation(
+        &mut self,
+        p: &Policy,
+        recorder: CoverageRecorder,
+        satisfied: bool,
+    ) {
+        self.policy_mut(p).record(recorder, satisfied);
+    }
+
+    /// Record that `ids` were the determining policies of a response
+    pub(crate) fn record_determining<'a>(&mut self, ids: impl IntoIterator<Item = &'a PolicyID>) {
+        for id in ids {
+            if let Some(cov) = self.policies.get_mut(id) {
+                cov.times_determining += 1;
+            }
+        }
+    }
+
+    /// Policies with a source location, in source order
+    fn located_policies(&self) -> impl Iterator<Item = (&PolicyID, &PolicyCoverage)> {
+        let mut policies = self
+            .policies
+            .iter()
+            .filter(|(_, cov)| cov.loc.is_some())
+            .collect::<Vec<_>>();
+        policies.sort_by_key(|(id, cov)| (cov.loc.as_ref().map(Loc::start), *id));
+        policies.into_iter()
+    }
+
+    /// Render the report in the lcov tracefile format, for policies parsed
+    /// from the file `file_name`.
+    ///
+    /// Each policy is reported as a function, which is hit when the policy is
+    /// determining. Each boolean expression is reported as a pair of branches
+    /// for `true` and `false`. Lines are hit when the policy, clauses and
+    /// expressions starting on them are evaluated. Policies without a source
+    /// location are omitted.
+    pub fn to_lcov(&self, file_name: &str) -> String {
+        let mut out = String::new();
+        // `write!` to a `String` cannot fail
+        let _ = writeln!(out, "TN:\nSF:{file_name}");
+        let policies = self.located_policies().collect::<Vec<_>>();
+        for (id, cov) in &policies {
+            if let Some(loc) = &cov.loc {
+                let _ = writeln!(out, "FN:{},{}", line_of(loc), lcov_name(id));
+            }
+        }
+        for (id, cov) in &policies {
+            let _ = writeln!(out, "FNDA:{},{}", cov.times_determining, lcov_name(id));
+        }
+        let _ = writeln!(
+            out,
+            "FNF:{}\nFNH:{}",
+            policies.len(),
+            policies
+                .iter()
+                .filter(|(_, cov)| cov.times_determining > 0)
+                .count()
+        );
+        let (mut branches, mut branches_taken) = (0, 0);
+        for (block, (loc, cov)) in policies
+            .iter()
+            .flat_map(|(_, cov)| cov.conditions())
+            .enumerate()
+        {
+            for (branch, taken) in [cov.times_true, cov.times_false].into_iter().enumerate() {
+                let taken = if cov.evaluations == 0 {
+                    "-".to_string()
+                } else {
+                    taken.to_string()
+                };
+                let _ = writeln!(out, "BRDA:{},{block},{branch},{taken}", line_of(loc));
+            }
+            branches += 2;
+            branches_taken += usize::from(cov.times_true > 0) + usize::from(cov.times_false > 0);
+        }
+        let _ = writeln!(out, "BRF:{branches}\nBRH:{branches_taken}");
+        let mut lines: BTreeMap<usize, usize> = BTreeMap::new();
+        for (_, cov) in &policies {
+            for (line, line_cov) in cov.lines() {
+                lines
+                    .entry(line)
+                    .and_modify(|hits| *hits = (*hits).min(line_cov.hits))
+                    .or_insert(line_cov.hits);
+            }
+        }
+        for (line, hits) in &lines {
+            let _ = writeln!(out, "DA:{line},{hits}");
+        }
+        let _ = writeln!(
+            out,
+            "LF:{}\nLH:{}\nend_of_record",
+            lines.len(),
+            lines.values().filter(|hits| **hits > 0).count()
+        );
+        out
+    }
+
+    /// Render the report as a Cobertura XML document, for policies parsed
+    /// from the file `file_name`.
+    ///
+    /// Each policy is reported as a class. Lines are reported as in
+    /// [`CoverageReport::to_lcov`], and the boolean expressions starting on a
+    /// line are reported as its conditions. Policies without a source
+    /// location are omitted.
+    pub fn to_cobertura(&self, file_name: &str) -> String {
+        let classes = self
+            .located_policies()
+            .map(|(id, cov)| (id, cov.lines()))
+            .collect::<Vec<_>>();
+        let totals = classes
+            .iter()
+            .flat_map(|(_, lines)| lines.values())
+            .fold(Totals::default(), Totals::add);
+        let file_name = xml_escape(file_name);
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "<?xml version=\"1.0\" ?>\n<!DOCTYPE coverage SYSTEM \"http://cobertura.sourceforge.net/xml/coverage-04.dtd\">"
+        );
+        let _ = writeln!(
+            out,
+            "<coverage {} lines-covered=\"{}\" lines-valid=\"{}\" branches-covered=\"{}\" branches-valid=\"{}\" complexity=\"0\" version=\"0\" timestamp=\"0\">",
+            totals.rates(),
+            totals.lines_covered,
+            totals.lines,
+            totals.branches_taken,
+            totals.branches
+        );
+        let _ = writeln!(
+            out,
+            "  <sources>\n    <source>.</source>\n  </sources>\n  <packages>"
+        );
+        let _ = writeln!(
+            out,
+            "    <package name=\"{file_name}\" {} complexity=\"0\">\n      <classes>",
+            totals.rates()
+        );
+        for (id, lines) in &classes {
+            let class_totals = lines.values().fold(Totals::default(), Totals::add);
+            let _ = writeln!(
+                out,
+                "        <class name=\"{}\" filename=\"{file_name}\" {} complexity=\"0\">\n          <methods/>\n          <lines>",
+                xml_escape(id.as_ref()),
+                class_totals.rates()
+            );
+            for (number, line) in lines {
+                let _ = write!(
+                    out,
+                    "            <line number=\"{number}\" hits=\"{}\"",
+                    line.hits
+                );
+                if let Some(percent) = (line.branches_taken * 100).checked_div(line.branches) {
+                    let _ = writeln!(
+                        out,
+                        " branch=\"true\" condition-coverage=\"{percent}% ({}/{})\"/>",
+                        line.branches_taken, line.branches
+                    );
+                } else {
+                    let _ = writeln!(out, " branch=\"false\"/>");
+                }
+            }
+            let _ = writeln!(out, "          </lines>\n        </class>");
+        }
+        let _ = writeln!(
+            out,
+            "      </classes>\n    </package>\n  </packages>\n</coverage>"
+        );
+        out
+    }
+}
+
+/// Totals of lines and branches, for Cobertura rates
+#[derive(Debug, Default)]
+struct Totals {
+    lines: usize,
+    lines_covered: usize,
+    branches: usize,
+    branches_taken: usize,
+}
+
+impl Totals {
+    fn add(self, line: &LineCoverage) -> Self {
+        Self {
+            lines: self.lines + 1,
+            lines_covered: self.lines_covered + usize::from(line.hits > 0),
+            branches: self.branches + line.branches,
+            branches_taken: self.branches_taken + line.branches_taken,
+        }
+    }
+
+    /// The `line-rate` and `branch-rate` attributes
+    fn rates(&self) -> String {
+        #[allow(clippy::cast_precision_loss)]
+        let rate = |covered: usize, valid: usize| {
+            if valid == 0 {
+                1.0
+            } else {
+                covered as f64 / valid as f64
+            }
+        };
+        format!(
+            "line-rate=\"{:.4}\" branch-rate=\"{:.4}\"",
+            rate(self.lines_covered, self.lines),
+            rate(self.branches_taken, self.branches)
+        )
+    }
+}
+
+/// Policy ids are used as lcov function names, which cannot contain commas
+/// or line breaks
+fn lcov_name(id: &PolicyID) -> String {
+    id.as_ref().replace([',', '\n', '\r'], "_")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ast::{Context, EntityUID, Request, RequestSchemaAllPass, RestrictedExpr};
+    use crate::authorizer::Authorizer;
+    use crate::entities::Entities;
+    use crate::extensions::Extensions;
+    use crate::parser::parse_policyset;
+
+    const POLICIES: &str = r#"permit(principal, action, resource)
+when { context.level > 3 }
+unless { context.banned };
+forbid(principal, action, resource)
+when { context.level < 0 || context.banned };
+permit(principal == User::"nobody", action, resource);
+"#;
+
+    fn coverage(contexts: &[(i64, bool)]) -> CoverageReport {
+        let pset = parse_policyset(POLICIES).unwrap();
+        let mut coverage = CoverageReport::new();
+        for (level, banned) in contexts {
+            let q = Request::new(
+                (EntityUID::with_eid("p"), None),
+                (EntityUID::with_eid("a"), None),
+                (EntityUID::with_eid("r"), None),
+                Context::from_pairs(
+                    [
+                        ("level".into(), RestrictedExpr::val(*level)),
+                        ("banned".into(), RestrictedExpr::val(*banned)),
+                    ],
+                    Extensions::none(),
+                )
+                .unwrap(),
+                None::<&RequestSchemaAllPass>,
+                Extensions::none(),
+            )
+            .unwrap();
+            Authorizer::new().is_authorized_with_coverage(
+                q,
+                &pset,
+                &Entities::new(),
+                &mut coverage,
+            );
+        }
+        coverage
+    }
+
+    fn summary<'a>(cov: impl Iterator<Item = (&'a Loc, ExprCoverage)>) -> Vec<String> {
+        cov.map(|(loc, cov)| {
+            format!(
+                "{}: {} {}/{}",
+                loc.snippet().unwrap(),
+                cov.evaluations(),
+                cov.times_true(),
+                cov.times_false()
+            )
+        })
+        .collect()
+    }
+
+    #[test]
+    fn policies_clauses_and_conditions() {
+        let report = coverage(&[(5, false), (1, false)]);
+        assert_eq!(
+            report
+                .never_determining()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>(),
+            ["policy1", "policy2"]
+        );
+        let policy0 = report.get(&PolicyID::from_string("policy0")).unwrap();
+        assert_eq!(policy0.evaluations(), 2);
+        assert_eq!(policy0.times_satisfied(), 1);
+        assert_eq!(policy0.times_determining(), 1);
+        assert_eq!(
+            summary(policy0.clauses()),
+            [
+                "context.level > 3: 2 1/1",
+                "unless { context.banned }: 1 1/0"
+            ]
+        );
+        assert_eq!(
+            summary(policy0.conditions()),
+            [
+                "context.level > 3: 2 1/1",
+                "unless { context.banned }: 1 1/0",
+                "context.banned: 1 0/1",
+            ]
+        );
+        let policy1 = report.get(&PolicyID::from_string("policy1")).unwrap();
+        assert_eq!(
+            summary(policy1.conditions()),
+            [
+                "context.level < 0: 2 0/2",
+                "context.level < 0 || context.banned: 2 0/2",
+                "context.banned: 2 0/2",
+            ]
+        );
+        let policy2 = report.get(&PolicyID::from_string("policy2")).unwrap();
+        assert_eq!(policy2.evaluations(), 2);
+        assert_eq!(policy2.clauses().count(), 0);
+    }
+
+    #[test]
+    fn merge() {
+        let mut report = coverage(&[(5, false)]);
+        report.merge(&coverage(&[(1, true)]));
+        let policy0 = report.get(&PolicyID::from_string("policy0")).unwrap();
+        assert_eq!(policy0.evaluations(), 2);
+        assert!(policy0.conditions().next().unwrap().1.fully_covered());
+    }
+
+    #[test]
+    fn lcov() {
+        assert_eq!(
+            coverage(&[(5, false), (1, false)]).to_lcov("policies.cedar"),
+            "TN:
+SF:policies.cedar
+FN:1,policy0
+FN:4,policy1
+FN:6,policy2
+FNDA:1,policy0
+FNDA:0,policy1
+FNDA:0,policy2
+FNF:3
+FNH:1
+BRDA:2,0,0,1
+BRDA:2,0,1,1
+BRDA:3,1,0,1
+BRDA:3,1,1,0
+BRDA:3,2,0,0
+BRDA:3,2,1,1
+BRDA:5,3,0,0
+BRDA:5,3,1,2
+BRDA:5,4,0,0
+BRDA:5,4,1,2
+BRDA:5,5,0,0
+BRDA:5,5,1,2
+BRF:12
+BRH:7
+DA:1,2
+DA:2,2
+DA:3,1
+DA:4,2
+DA:5,2
+DA:6,2
+LF:6
+LH:6
+end_of_record
+"
+        );
+    }
+
+    #[test]
+    fn cobertura() {
+        let xml = coverage(&[(5, false)]).to_cobertura("policies.cedar");
+        assert!(xml.contains(
+            r#"<class name="policy0" filename="policies.cedar" line-rate="1.0000" branch-rate="0.5000" complexity="0">"#
+        ), "{xml}");
+        assert!(
+            xml.contains(
+                r#"<line number="3" hits="1" branch="true" condition-coverage="50% (2/4)"/>"#
+            ),
+            "{xml}"
+        );
+    }
+}
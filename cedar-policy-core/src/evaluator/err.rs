@@ -14,7 +14,9 @@
  * limitations under the License.
  */
 
+use super::limits::ExceededLimit;
 use crate::ast::*;
+use crate::entities::err::EntityStoreError;
 use crate::extensions::ExtensionFunctionLookupError;
 use crate::parser::Loc;
 use miette::Diagnostic;
@@ -99,6 +101,16 @@ pub enum EvaluationError {
     #[error(transparent)]
     #[diagnostic(transparent)]
     RecursionLimit(#[from] evaluation_errors::RecursionLimitError),
+
+    /// Evaluation exceeded one of the configured `EvaluationLimits`
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    EvaluationLimit(#[from] evaluation_errors::EvaluationLimitError),
+
+    /// The entity store failed to load an entity
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    EntityLoad(#[from] evaluation_errors::EntityLoadError),
 }
 
 impl EvaluationError {
@@ -116,6 +128,8 @@ impl EvaluationError {
             Self::FailedExtensionFunctionExecution(e) => e.source_loc.as_ref(),
             Self::NonValue(e) => e.source_loc.as_ref(),
             Self::RecursionLimit(e) => e.source_loc.as_ref(),
+            Self::EvaluationLimit(e) => e.source_loc.as_ref(),
+            Self::EntityLoad(e) => e.source_loc.as_ref(),
             #[cfg(feature = "tolerant-ast")]
             Self::ASTErrorExpr(e) => e.source_loc.as_ref(),
         }
@@ -165,6 +179,12 @@ impl EvaluationError {
             Self::RecursionLimit(_) => {
                 Self::RecursionLimit(evaluation_errors::RecursionLimitError { source_loc })
             }
+            Self::EvaluationLimit(e) => {
+                Self::EvaluationLimit(evaluation_errors::EvaluationLimitError { source_loc, ..e })
+            }
+            Self::EntityLoad(e) => {
+                Self::EntityLoad(evaluation_errors::EntityLoadError { source_loc, ..e })
+            }
             #[cfg(feature = "tolerant-ast")]
             Self::ASTErrorExpr(_) => {
                 Self::ASTErrorExpr(evaluation_errors::ASTErrorExprError { source_loc })
@@ -336,6 +356,25 @@ impl EvaluationError {
     pub(crate) fn recursion_limit(source_loc: Option<Loc>) -> Self {
         evaluation_errors::RecursionLimitError { source_loc }.into()
     }
+
+    /// Construct an [`EvaluationLimit`] error
+    pub(crate) fn evaluation_limit(limit: ExceededLimit, source_loc: Option<Loc>) -> Self {
+        evaluation_errors::EvaluationLimitError { limit, source_loc }.into()
+    }
+
+    /// Construct an [`EntityLoad`] error
+    pub(crate) fn entity_load(
+        uid: Arc<EntityUID>,
+        error: EntityStoreError,
+        source_loc: Option<Loc>,
+    ) -> Self {
+        evaluation_errors::EntityLoadError {
+            uid,
+            error,
+            source_loc,
+        }
+        .into()
+    }
 }
 
 /// Error subtypes for [`EvaluationError`]
@@ -350,7 +389,7 @@ pub mod evaluation_errors {
     use std::sync::Arc;
     use thiserror::Error;
 
-    use super::Name;
+    use super::{EntityStoreError, ExceededLimit, Name};
 
     /// Tried to lookup an entity UID, but it didn't exist in the provided entities
     //
@@ -749,6 +788,51 @@ pub mod evaluation_errors {
     impl Diagnostic for RecursionLimitError {
         impl_diagnostic_from_source_loc_opt_field!(source_loc);
     }
+
+    /// Evaluation exceeded one of the configured `EvaluationLimits`
+    //
+    // CAUTION: this type is publicly exported in `cedar-policy`.
+    // Don't make fields `pub`, don't make breaking changes, and use caution
+    // when adding public methods.
+    #[derive(Debug, PartialEq, Eq, Clone, Error)]
+    #[error("evaluation limit exceeded: {limit}")]
+    pub struct EvaluationLimitError {
+        /// The limit which was exceeded
+        pub(crate) limit: ExceededLimit,
+        /// Source location
+        pub(crate) source_loc: Option<Loc>,
+    }
+
+    impl EvaluationLimitError {
+        /// The limit which was exceeded
+        pub fn limit(&self) -> ExceededLimit {
+            self.limit
+        }
+    }
+
+    impl Diagnostic for EvaluationLimitError {
+        impl_diagnostic_from_source_loc_opt_field!(source_loc);
+    }
+
+    /// The entity store failed to load an entity
+    //
+    // CAUTION: this type is publicly exported in `cedar-policy`.
+    // Don't make fields `pub`, don't make breaking changes, and use caution
+    // when adding public methods.
+    #[derive(Debug, PartialEq, Eq, Clone, Error)]
+    #[error("failed to load entity `{uid}`: {error}")]
+    pub struct EntityLoadError {
+        /// Entity UID which failed to load
+        pub(crate) uid: Arc<EntityUID>,
+        /// Error reported by the entity store
+        pub(crate) error: EntityStoreError,
+        /// Source location
+        pub(crate) source_loc: Option<Loc>,
+    }
+
+    impl Diagnostic for EntityLoadError {
+        impl_diagnostic_from_source_loc_opt_field!(source_loc);
+    }
 }
 
 /// Type alias for convenience
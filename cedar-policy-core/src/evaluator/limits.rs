@@ -0,0 +1,226 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Limits on the amount of work an `Evaluator` may do.
+
+use super::{EvaluationError, Result};
+use crate::parser::Loc;
+use std::cell::Cell;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+/// Limits on the amount of work done to evaluate an expression or policy.
+///
+/// Exceeding a limit results in an
+/// [`EvaluationError::EvaluationLimit`] error, which is handled like any other
+/// evaluation error. By default, no limits are imposed.
+///
+/// The limits on the number of evaluation steps and entity dereferences apply
+/// separately to each policy (or each top-level expression) evaluated, and
+/// the limit on set size to each set literal evaluated, while the deadline
+/// applies to all evaluation done by an `Evaluator`, so to the whole of an
+/// authorization request. Deadlines are not available on `wasm32`, where
+/// there is no system clock to check them against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvaluationLimits {
+    /// Maximum number of expression nodes to interpret
+    max_steps: Option<usize>,
+    /// Maximum number of entities to look up in the entity store
+    max_entity_derefs: Option<usize>,
+    /// Maximum number of elements in a set literal
+    max_set_size: Option<usize>,
+    /// Point in time after which evaluation fails
+    #[cfg(not(target_arch = "wasm32"))]
+    deadline: Option<Instant>,
+}
+
+impl EvaluationLimits {
+    /// No limits
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Allow interpreting at most `max_steps` expression nodes
+    pub fn with_max_steps(self, max_steps: usize) -> Self {
+        Self {
+            max_steps: Some(max_steps),
+            ..self
+        }
+    }
+
+    /// Allow looking up at most `max_entity_derefs` entities (including
+    /// repeated lookups of the same entity). For stores with
+    /// `AncestorMode::OnDemand`, this includes the ancestors looked up while
+    /// searching the hierarchy for an `in` check, except those whose own
+    /// ancestors were already found by an earlier search.
+    pub fn with_max_entity_derefs(self, max_entity_derefs: usize) -> Self {
+        Self {
+            max_entity_derefs: Some(max_entity_derefs),
+            ..self
+        }
+    }
+
+    /// Allow set literals of at most `max_set_size` elements. Sets which are
+    /// already values, such as entity attributes or context fields, are not
+    /// limited.
+    pub fn with_max_set_size(self, max_set_size: usize) -> Self {
+        Self {
+            max_set_size: Some(max_set_size),
+            ..self
+        }
+    }
+
+    /// Fail any evaluation still in progress at `deadline`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_deadline(self, deadline: Instant) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..self
+        }
+    }
+
+    /// Maximum number of expression nodes to interpret, if limited
+    pub fn max_steps(&self) -> Option<usize> {
+        self.max_steps
+    }
+
+    /// Maximum number of entity lookups, if limited
+    pub fn max_entity_derefs(&self) -> Option<usize> {
+        self.max_entity_derefs
+    }
+
+    /// Maximum number of elements in a set literal, if limited
+    pub fn max_set_size(&self) -> Option<usize> {
+        self.max_set_size
+    }
+
+    /// Deadline for evaluation, if any
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+}
+
+/// Tracks the work done by an `Evaluator` against its `EvaluationLimits`
+#[derive(Debug, Default)]
+pub(crate) struct LimitTracker {
+    limits: EvaluationLimits,
+    /// Number of expression nodes interpreted since the last `reset()`
+    steps: Cell<usize>,
+    /// Number of entity lookups since the last `reset()`
+    entity_derefs: Cell<usize>,
+}
+
+impl LimitTracker {
+    pub(crate) fn new(limits: EvaluationLimits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    /// Start counting steps and dereferences from zero
+    pub(crate) fn reset(&self) {
+        self.steps.set(0);
+        self.entity_derefs.set(0);
+    }
+
+    /// Record that an expression node is about to be interpreted
+    pub(crate) fn step(&self) -> Result<()> {
+        if let Some(max) = self.limits.max_steps {
+            let steps = self.steps.get() + 1;
+            if steps > max {
+                return Err(EvaluationError::evaluation_limit(
+                    ExceededLimit::Steps(max),
+                    None,
+                ));
+            }
+            self.steps.set(steps);
+        }
+        self.check_deadline()
+    }
+
+    /// Record that an entity is about to be looked up
+    pub(crate) fn entity_deref(&self) -> Result<()> {
+        if let Some(max) = self.limits.max_entity_derefs {
+            let derefs = self.entity_derefs.get() + 1;
+            if derefs > max {
+                return Err(EvaluationError::evaluation_limit(
+                    ExceededLimit::EntityDerefs(max),
+                    None,
+                ));
+            }
+            self.entity_derefs.set(derefs);
+        }
+        self.check_deadline()
+    }
+
+    /// Check that the deadline, if any, has not passed
+    #[cfg(not(target_arch = "wasm32"))]
+    fn check_deadline(&self) -> Result<()> {
+        match self.limits.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(EvaluationError::evaluation_limit(
+                ExceededLimit::Deadline,
+                None,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// There are no deadlines on `wasm32`, where `Instant::now()` panics
+    #[cfg(target_arch = "wasm32")]
+    #[allow(clippy::unnecessary_wraps)]
+    fn check_deadline(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Check that a set literal of `size` elements may be constructed
+    pub(crate) fn set_size(&self, size: usize, source_loc: Option<&Loc>) -> Result<()> {
+        match self.limits.max_set_size {
+            Some(max) if size > max => Err(EvaluationError::evaluation_limit(
+                ExceededLimit::SetSize(max),
+                source_loc.cloned(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The limit which was exceeded, along with its configured value, as reported
+/// by [`EvaluationLimitError::limit()`](super::evaluation_errors::EvaluationLimitError::limit)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExceededLimit {
+    /// Too many expression nodes interpreted
+    Steps(usize),
+    /// Too many entity lookups
+    EntityDerefs(usize),
+    /// Too large a set literal
+    SetSize(usize),
+    /// Evaluation did not finish before the deadline
+    Deadline,
+}
+
+impl std::fmt::Display for ExceededLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Steps(max) => write!(f, "evaluated more than {max} expressions"),
+            Self::EntityDerefs(max) => write!(f, "looked up more than {max} entities"),
+            Self::SetSize(max) => write!(f, "evaluated a set literal of more than {max} elements"),
+            Self::Deadline => write!(f, "did not finish before the deadline"),
+        }
+    }
+}
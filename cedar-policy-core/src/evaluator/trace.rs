@@ -0,0 +1,135 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Recording of every subexpression an `Evaluator` evaluates, used to explain
+//! how a policy reached its result.
+
+use super::{EvaluationError, Result};
+use crate::ast::{Expr, PartialValue, Value};
+use crate::parser::Loc;
+
+/// Record of the evaluation of a single expression, along with the records of
+/// the subexpressions which were evaluated in order to compute it
+#[derive(Debug, Clone)]
+pub struct TraceNode {
+    /// The expression which was evaluated
+    expr: Expr,
+    /// The result of evaluating `expr`
+    outcome: TraceOutcome,
+    /// Whether evaluation of `expr` stopped early without evaluating all of
+    /// its operands, e.g. `false && ...` or `true || ...`
+    short_circuited: bool,
+    /// Records of the subexpressions of `expr` which were evaluated, in
+    /// evaluation order
+    children: Vec<TraceNode>,
+}
+
+impl TraceNode {
+    /// The expression which was evaluated
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// Source location of the expression which was evaluated, if known
+    pub fn source_loc(&self) -> Option<&Loc> {
+        self.expr.source_loc()
+    }
+
+    /// The result of evaluating the expression
+    pub fn outcome(&self) -> &TraceOutcome {
+        &self.outcome
+    }
+
+    /// Whether evaluation of the expression stopped early without evaluating
+    /// all of its operands, e.g. `false && ...` or `true || ...`
+    pub fn short_circuited(&self) -> bool {
+        self.short_circuited
+    }
+
+    /// Records of the subexpressions which were evaluated in order to compute
+    /// this expression, in evaluation order
+    pub fn children(&self) -> impl Iterator<Item = &TraceNode> {
+        self.children.iter()
+    }
+}
+
+/// Result of evaluating an expression, as recorded in a [`TraceNode`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceOutcome {
+    /// The expression evaluated to a value
+    Value(Value),
+    /// The expression (partially) evaluated to a residual
+    Residual(Expr),
+    /// The expression failed to evaluate
+    Error(EvaluationError),
+}
+
+/// Traces of the expressions which are currently being evaluated
+#[derive(Debug, Default)]
+struct Frame {
+    /// Traces of the subexpressions evaluated so far
+    children: Vec<TraceNode>,
+    /// Whether the expression has short-circuited
+    short_circuited: bool,
+}
+
+/// Builds `TraceNode`s as an `Evaluator` enters and exits expressions
+#[derive(Debug, Default)]
+pub(crate) struct Tracer {
+    /// One frame for each expression currently being evaluated, innermost last
+    stack: Vec<Frame>,
+    /// The most recently completed trace of a top-level expression
+    finished: Option<TraceNode>,
+}
+
+impl Tracer {
+    /// Start evaluating an expression
+    pub(crate) fn enter(&mut self) {
+        self.stack.push(Frame::default());
+    }
+
+    /// Finish evaluating the expression `expr` most recently `enter`ed, which
+    /// produced `result`
+    pub(crate) fn exit(&mut self, expr: &Expr, result: &Result<PartialValue>) {
+        let frame = self.stack.pop().unwrap_or_default();
+        let node = TraceNode {
+            expr: expr.clone(),
+            outcome: match result {
+                Ok(PartialValue::Value(v)) => TraceOutcome::Value(v.clone()),
+                Ok(PartialValue::Residual(r)) => TraceOutcome::Residual(r.clone()),
+                Err(e) => TraceOutcome::Error(e.clone()),
+            },
+            short_circuited: frame.short_circuited,
+            children: frame.children,
+        };
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => self.finished = Some(node),
+        }
+    }
+
+    /// Record that the expression currently being evaluated short-circuited
+    pub(crate) fn short_circuit(&mut self) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.short_circuited = true;
+        }
+    }
+
+    /// Take the trace of the most recently evaluated top-level expression
+    pub(crate) fn take(&mut self) -> Option<TraceNode> {
+        self.finished.take()
+    }
+}
@@ -70,6 +70,68 @@ where
     Ok(())
 }
 
+/// Given a graph as a map from keys with type `K` to implementations of
+/// `TCNode` with type `V`, which was transitively closed before the direct
+/// edges out of the nodes with keys in `changed` were updated, restore the
+/// transitive closure of the hierarchy. `children` must return the keys of the
+/// nodes with a direct edge to the given key in the updated graph.
+/// If `enforce_dag` then also check that the hierarchy is a DAG.
+///
+/// Only the edges of the changed nodes and their descendants can change, so
+/// this recomputes just those, and the cost is proportional to the size of
+/// that region rather than to the size of the graph. The result is the same
+/// as calling [`compute_tc`] on the whole graph, provided the graph was
+/// transitively closed and a DAG before the update. Keys in `changed` that
+/// have been removed from `nodes` are fine, and their descendants are updated.
+pub fn update_tc<K, V, I>(
+    nodes: &mut HashMap<K, V>,
+    changed: impl IntoIterator<Item = K>,
+    children: impl Fn(&K) -> I,
+    enforce_dag: bool,
+) -> Result<(), K>
+where
+    K: Clone + Eq + Hash + Debug + Display,
+    V: TCNode<K>,
+    I: IntoIterator<Item = K>,
+{
+    // Any path from a node to one of its new ancestors, or to one of the
+    // ancestors it lost, goes through a changed node, so the nodes whose
+    // edges can change are exactly the changed nodes and their descendants
+    let mut affected: HashSet<K> = HashSet::new();
+    let mut worklist: Vec<K> = changed.into_iter().collect();
+    while let Some(key) = worklist.pop() {
+        if affected.insert(key.clone()) {
+            worklist.extend(children(&key));
+        }
+    }
+    // As in `compute_tc_internal`, collect the updates before applying them
+    let mut ancestors: HashMap<K, HashSet<K>> = HashMap::new();
+    for key in &affected {
+        if let Some(node) = nodes.get(key) {
+            let this_node_ancestors: &mut HashSet<K> = ancestors.entry(key.clone()).or_default();
+            add_updated_ancestors_to_set(node, nodes, &affected, this_node_ancestors);
+        }
+    }
+    for (key, node_ancestors) in ancestors {
+        if let Some(node) = nodes.get_mut(&key) {
+            node.reset_edges();
+            for ancestor_uid in node_ancestors {
+                node.add_edge_to(ancestor_uid);
+            }
+        }
+    }
+    if enforce_dag {
+        // A new cycle must go through a changed node, so it's enough to check
+        // the affected nodes
+        for key in affected {
+            if nodes.get(&key).is_some_and(|node| node.has_edge_to(&key)) {
+                return Err(TcError::has_cycle(key));
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Given graph as a map from keys with type `K` to implementations of `TCNode`
 /// with type `V`, compute the transitive closure of the hierarchy. In case of
 /// error, the result contains an error structure `Err<K>` which contains the
@@ -117,6 +179,57 @@ where
     res
 }
 
+/// Given a graph (as a map from keys to `TCNode`), check that no cycle is
+/// reachable by following direct edges from the nodes with keys in `starts`,
+/// without computing the transitive closure. If the graph was a DAG before the
+/// direct edges out of some nodes changed, passing those nodes as `starts`
+/// checks that it is still a DAG, since any new cycle goes through one of them.
+pub fn enforce_dag_from<K, V>(
+    nodes: &HashMap<K, V>,
+    starts: impl IntoIterator<Item = K>,
+) -> Result<(), K>
+where
+    K: Clone + Eq + Hash + Debug + Display,
+    V: TCNode<K>,
+{
+    // Depth-first search, where `on_path` are the nodes on the current path,
+    // and `finished` are the nodes whose ancestors are known to be acyclic
+    let mut finished: HashSet<&K> = HashSet::new();
+    let mut on_path: HashSet<&K> = HashSet::new();
+    for start in starts {
+        let Some((start, node)) = nodes.get_key_value(&start) else {
+            continue;
+        };
+        if finished.contains(start) {
+            continue;
+        }
+        on_path.insert(start);
+        let mut stack = vec![(start, node.direct_edges())];
+        while let Some((key, edges)) = stack.last_mut() {
+            let key: &K = key;
+            match edges.next() {
+                Some(next) if on_path.contains(next) => {
+                    return Err(TcError::has_cycle(next.clone()));
+                }
+                Some(next) => {
+                    if !finished.contains(next) {
+                        if let Some((next, next_node)) = nodes.get_key_value(next) {
+                            on_path.insert(next);
+                            stack.push((next, next_node.direct_edges()));
+                        }
+                    }
+                }
+                None => {
+                    on_path.remove(key);
+                    finished.insert(key);
+                    stack.pop();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Given a DAG (as a map from keys to `TCNode`), enforce that
 /// all transitive edges are included, i.e., the transitive closure has already
 /// been computed. If this is not the case, return an appropriate
@@ -164,6 +277,31 @@ where
     }
 }
 
+/// Like `add_ancestors_to_set`, but for use by `update_tc`: nodes not in
+/// `affected` still have transitively closed edges, so their ancestors are
+/// copied rather than searched.
+fn add_updated_ancestors_to_set<K, V>(
+    node: &V,
+    hierarchy: &HashMap<K, V>,
+    affected: &HashSet<K>,
+    ancestors: &mut HashSet<K>,
+) where
+    K: Clone + Eq + Hash,
+    V: TCNode<K>,
+{
+    for parent_uid in node.direct_edges() {
+        if ancestors.insert(parent_uid.clone()) {
+            if let Some(ancestor) = hierarchy.get(parent_uid) {
+                if affected.contains(parent_uid) {
+                    add_updated_ancestors_to_set(ancestor, hierarchy, affected, ancestors);
+                } else {
+                    ancestors.extend(ancestor.out_edges().cloned());
+                }
+            }
+        }
+    }
+}
+
 /// Once the transitive closure (as defined above) is computed/enforced for the graph, we have:
 /// \forall u,v,w \in Vertices . (u,v) \in Edges /\ (v,w) \in Edges -> (u,w) \in Edges
 ///
@@ -190,6 +328,7 @@ where
 #[cfg(test)]
 mod tests {
     use crate::ast::{Entity, EntityUID};
+    use cool_asserts::assert_matches;
 
     use super::*;
 
@@ -824,4 +963,184 @@ mod tests {
         // passes cycle check after TC enforcement
         assert!(enforce_dag_from_tc(&entities).is_ok());
     }
+
+    /// Keys of the nodes with a direct edge to each key
+    fn children_of(entities: &HashMap<EntityUID, Entity>) -> HashMap<EntityUID, Vec<EntityUID>> {
+        let mut children: HashMap<EntityUID, Vec<EntityUID>> = HashMap::new();
+        for entity in entities.values() {
+            for parent in entity.parents() {
+                children
+                    .entry(parent.clone())
+                    .or_default()
+                    .push(entity.uid().clone());
+            }
+        }
+        children
+    }
+
+    /// Update `entities` with `update_tc` and check that the result is the
+    /// same as computing the closure from scratch
+    fn assert_update_matches_full(
+        entities: &mut HashMap<EntityUID, Entity>,
+        changed: &[&str],
+    ) -> Result<(), EntityUID> {
+        let mut expected = entities.clone();
+        let expected_res = compute_tc(&mut expected, true);
+        let children = children_of(entities);
+        let res = update_tc(
+            entities,
+            changed.iter().map(|eid| EntityUID::with_eid(eid)),
+            |uid| children.get(uid).cloned().unwrap_or_default(),
+            true,
+        );
+        assert_eq!(res.is_ok(), expected_res.is_ok());
+        if res.is_ok() {
+            for (uid, entity) in entities.iter() {
+                let expected = &expected[uid];
+                assert_eq!(
+                    entity.ancestors().collect::<HashSet<_>>(),
+                    expected.ancestors().collect::<HashSet<_>>(),
+                    "ancestors of {uid}"
+                );
+            }
+        }
+        res
+    }
+
+    fn set_parents(entities: &mut HashMap<EntityUID, Entity>, eid: &str, parents: &[&str]) {
+        let mut entity = Entity::with_uid(EntityUID::with_eid(eid));
+        for parent in parents {
+            entity.add_parent(EntityUID::with_eid(parent));
+        }
+        entities.insert(entity.uid().clone(), entity);
+    }
+
+    #[test]
+    fn update_add_edge() {
+        // start with A -> B -> C and D -> E
+        let mut entities = HashMap::new();
+        set_parents(&mut entities, "A", &["B"]);
+        set_parents(&mut entities, "B", &["C"]);
+        set_parents(&mut entities, "C", &[]);
+        set_parents(&mut entities, "D", &["E"]);
+        set_parents(&mut entities, "E", &[]);
+        compute_tc(&mut entities, true).unwrap();
+        // add C -> D
+        set_parents(&mut entities, "C", &["D"]);
+        assert_update_matches_full(&mut entities, &["C"]).unwrap();
+        assert!(entities[&EntityUID::with_eid("A")].has_edge_to(&EntityUID::with_eid("E")));
+        assert!(!entities[&EntityUID::with_eid("D")].has_edge_to(&EntityUID::with_eid("A")));
+    }
+
+    #[test]
+    fn update_remove_edge() {
+        // start with A -> B -> C -> D and A -> E -> D
+        let mut entities = HashMap::new();
+        set_parents(&mut entities, "A", &["B", "E"]);
+        set_parents(&mut entities, "B", &["C"]);
+        set_parents(&mut entities, "C", &["D"]);
+        set_parents(&mut entities, "D", &[]);
+        set_parents(&mut entities, "E", &["D"]);
+        compute_tc(&mut entities, true).unwrap();
+        // remove B -> C: A still reaches D through E, but not C
+        set_parents(&mut entities, "B", &[]);
+        assert_update_matches_full(&mut entities, &["B"]).unwrap();
+        let a = &entities[&EntityUID::with_eid("A")];
+        assert!(a.has_edge_to(&EntityUID::with_eid("D")));
+        assert!(!a.has_edge_to(&EntityUID::with_eid("C")));
+    }
+
+    #[test]
+    fn update_new_node() {
+        // A -> B, where B doesn't exist yet
+        let mut entities = HashMap::new();
+        set_parents(&mut entities, "A", &["B"]);
+        set_parents(&mut entities, "C", &[]);
+        compute_tc(&mut entities, true).unwrap();
+        // add B -> C
+        set_parents(&mut entities, "B", &["C"]);
+        assert_update_matches_full(&mut entities, &["B"]).unwrap();
+        assert!(entities[&EntityUID::with_eid("A")].has_edge_to(&EntityUID::with_eid("C")));
+    }
+
+    #[test]
+    fn update_removed_node() {
+        // A -> B -> C, then B is removed but A still refers to it
+        let mut entities = HashMap::new();
+        set_parents(&mut entities, "A", &["B"]);
+        set_parents(&mut entities, "B", &["C"]);
+        set_parents(&mut entities, "C", &[]);
+        compute_tc(&mut entities, true).unwrap();
+        entities.remove(&EntityUID::with_eid("B"));
+        assert_update_matches_full(&mut entities, &["B"]).unwrap();
+        let a = &entities[&EntityUID::with_eid("A")];
+        assert!(a.has_edge_to(&EntityUID::with_eid("B")));
+        assert!(!a.has_edge_to(&EntityUID::with_eid("C")));
+    }
+
+    #[test]
+    fn update_many_changes() {
+        // a chain 0 -> 1 -> ... -> 9, with shortcuts i -> i + 3
+        let mut entities = HashMap::new();
+        let eids = (0..10).map(|i| i.to_string()).collect::<Vec<_>>();
+        for i in 0..10 {
+            let parents = [i + 1, i + 3]
+                .into_iter()
+                .filter(|j| *j < 10)
+                .map(|j| eids[j].as_str())
+                .collect::<Vec<_>>();
+            set_parents(&mut entities, &eids[i], &parents);
+        }
+        compute_tc(&mut entities, true).unwrap();
+        // cut the chain in two places, and add an edge from the bottom of one
+        // piece to a new node
+        set_parents(&mut entities, "2", &["5"]);
+        set_parents(&mut entities, "6", &["9", "new"]);
+        set_parents(&mut entities, "new", &[]);
+        assert_update_matches_full(&mut entities, &["2", "6", "new"]).unwrap();
+    }
+
+    #[test]
+    fn update_cycle() {
+        // start with A -> B -> C
+        let mut entities = HashMap::new();
+        set_parents(&mut entities, "A", &["B"]);
+        set_parents(&mut entities, "B", &["C"]);
+        set_parents(&mut entities, "C", &[]);
+        compute_tc(&mut entities, true).unwrap();
+        // add C -> A
+        set_parents(&mut entities, "C", &["A"]);
+        match assert_update_matches_full(&mut entities, &["C"]) {
+            Err(TcError::HasCycle(_)) => (),
+            res => panic!("expected a cycle, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn dag_from_changed_nodes() {
+        // A -> B -> C, A -> C and D -> C
+        let mut entities = HashMap::new();
+        set_parents(&mut entities, "A", &["B", "C"]);
+        set_parents(&mut entities, "B", &["C"]);
+        set_parents(&mut entities, "C", &[]);
+        set_parents(&mut entities, "D", &["C"]);
+        let all = entities.keys().cloned().collect::<Vec<_>>();
+        assert!(enforce_dag_from(&entities, all.clone()).is_ok());
+        // C -> X is fine, and doesn't need the TC to be checked
+        set_parents(&mut entities, "C", &["X"]);
+        assert!(enforce_dag_from(&entities, [EntityUID::with_eid("C")]).is_ok());
+        // but C -> A isn't
+        set_parents(&mut entities, "C", &["A"]);
+        assert_matches!(
+            enforce_dag_from(&entities, [EntityUID::with_eid("C")]),
+            Err(TcError::HasCycle(_))
+        );
+        assert_matches!(enforce_dag_from(&entities, all), Err(TcError::HasCycle(_)));
+        // and neither is a self-loop
+        set_parents(&mut entities, "C", &["C"]);
+        assert_matches!(
+            enforce_dag_from(&entities, [EntityUID::with_eid("C")]),
+            Err(TcError::HasCycle(_))
+        );
+    }
 }
@@ -0,0 +1,352 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Contains the analysis finding policies which can never affect an
+//! authorization decision, and `permit` and `forbid` policies which may
+//! conflict.
+//!
+//! The analysis is syntactic: one policy covers another when its action
+//! constraint allows at least the same actions, its principal and resource
+//! constraints are implied by the other's, and each of its conditions is
+//! also a condition of the other. It never reports a policy as shadowed when
+//! it is not, but it may miss shadowing which depends on the entity data or
+//! on the meaning of the conditions.
+
+use std::collections::HashSet;
+
+use cedar_policy_core::ast::{
+    Effect, EntityType, EntityUID, Expr, ExprKind, ExprShapeOnly, Literal, Policy, PolicySet,
+    PrincipalOrResourceConstraint,
+};
+use itertools::Itertools;
+
+use crate::{ValidationWarning, Validator};
+
+/// The parts of a policy the analysis compares
+struct PolicySummary<'a> {
+    policy: &'a Policy,
+    principal: PrincipalOrResourceConstraint,
+    resource: PrincipalOrResourceConstraint,
+    actions: HashSet<&'a EntityUID>,
+    /// The (principal type, action, resource type) request environments the
+    /// policy may apply to
+    envs: HashSet<(&'a EntityType, &'a EntityUID, &'a EntityType)>,
+    /// The conjuncts of the policy condition, excluding the scope
+    conditions: Vec<ExprShapeOnly<'a>>,
+}
+
+impl Validator {
+    /// Analyze `policies`, reporting
+    ///
+    /// - permits which only apply to requests another permit or a forbid
+    ///   also applies to ([`ValidationWarning::ShadowedPermit`]),
+    /// - permit and forbid policies which may apply to requests in the same
+    ///   request environment ([`ValidationWarning::ConflictingPolicies`]), and
+    /// - policies which are structurally identical to another policy
+    ///   ([`ValidationWarning::DuplicatePolicy`]).
+    ///
+    /// The analysis assumes `policies` validate against the schema. Policies
+    /// which cannot apply to any request environment are not reported.
+    pub fn analyze<'a>(
+        &'a self,
+        policies: &'a PolicySet,
+    ) -> impl Iterator<Item = ValidationWarning> + 'a {
+        let summaries = policies
+            .policies()
+            .sorted_by_key(|p| p.id())
+            .map(|p| self.summarize(p))
+            .filter(|s| !s.envs.is_empty())
+            .collect::<Vec<_>>();
+        let mut warnings = Vec::new();
+        let mut duplicates = HashSet::new();
+
+        for (i, later) in summaries.iter().enumerate() {
+            if let Some(earlier) = summaries.iter().take(i).find(|s| is_duplicate(s, later)) {
+                duplicates.insert(later.policy.id());
+                warnings.push(ValidationWarning::duplicate_policy(
+                    later.policy.loc().cloned(),
+                    later.policy.id().clone(),
+                    earlier.policy.id().clone(),
+                ));
+            }
+        }
+
+        let (permits, forbids): (Vec<_>, Vec<_>) = summaries
+            .iter()
+            .filter(|s| !duplicates.contains(s.policy.id()))
+            .partition(|s| s.policy.effect() == Effect::Permit);
+        for (i, permit) in permits.iter().enumerate() {
+            // When two permits cover each other, only the later one is
+            // reported, since removing both would change the decisions.
+            let shadowing = forbids
+                .iter()
+                .find(|forbid| covers(forbid, permit))
+                .or_else(|| {
+                    permits.iter().enumerate().find_map(|(j, other)| {
+                        (i != j && covers(other, permit) && (j < i || !covers(permit, other)))
+                            .then_some(other)
+                    })
+                });
+            if let Some(shadowing) = shadowing {
+                warnings.push(ValidationWarning::shadowed_permit(
+                    permit.policy.loc().cloned(),
+                    permit.policy.id().clone(),
+                    shadowing.policy.id().clone(),
+                    shadowing.policy.effect(),
+                ));
+                continue;
+            }
+            for forbid in &forbids {
+                if !permit.envs.is_disjoint(&forbid.envs) {
+                    warnings.push(ValidationWarning::conflicting_policies(
+                        permit.policy.loc().cloned(),
+                        permit.policy.id().clone(),
+                        forbid.policy.id().clone(),
+                    ));
+                }
+            }
+        }
+        warnings.into_iter()
+    }
+
+    fn summarize<'a>(&'a self, policy: &'a Policy) -> PolicySummary<'a> {
+        let principal_constraint = policy.principal_constraint();
+        let resource_constraint = policy.resource_constraint();
+        let principals = self
+            .get_principals_satisfying_constraint(&principal_constraint)
+            .collect::<HashSet<_>>();
+        let resources = self
+            .get_resources_satisfying_constraint(&resource_constraint)
+            .collect::<HashSet<_>>();
+        let actions = self
+            .get_actions_satisfying_constraint(policy.action_constraint())
+            .collect::<HashSet<_>>();
+        let envs = actions
+            .iter()
+            .filter_map(|action| self.schema.get_action_id(action))
+            .flat_map(|action| {
+                let resources = &resources;
+                action
+                    .applies_to_principals()
+                    .filter(|p| principals.contains(p))
+                    .flat_map(move |p| {
+                        action
+                            .applies_to_resources()
+                            .filter(|r| resources.contains(r))
+                            .map(move |r| (p, &action.name, r))
+                    })
+            })
+            .collect();
+        let mut conditions = Vec::new();
+        conjuncts(policy.non_scope_constraints(), &mut conditions);
+        PolicySummary {
+            policy,
+            principal: principal_constraint.as_inner().clone(),
+            resource: resource_constraint.as_inner().clone(),
+            actions,
+            envs,
+            conditions,
+        }
+    }
+}
+
+/// Collect the conjuncts of `e`, excluding trivial `true` conjuncts
+fn conjuncts<'a>(e: &'a Expr, out: &mut Vec<ExprShapeOnly<'a>>) {
+    match e.expr_kind() {
+        ExprKind::And { left, right } => {
+            conjuncts(left, out);
+            conjuncts(right, out);
+        }
+        ExprKind::Lit(Literal::Bool(true)) => (),
+        _ => out.push(ExprShapeOnly::new_from_borrowed(e)),
+    }
+}
+
+/// Does `policy` apply to every request `other` applies to?
+fn covers(policy: &PolicySummary<'_>, other: &PolicySummary<'_>) -> bool {
+    policy.actions.is_superset(&other.actions)
+        && scope_covers(
+            &policy.principal,
+            &other.principal,
+            other.envs.iter().map(|(p, _, _)| *p),
+        )
+        && scope_covers(
+            &policy.resource,
+            &other.resource,
+            other.envs.iter().map(|(_, _, r)| *r),
+        )
+        && policy
+            .conditions
+            .iter()
+            .all(|c| other.conditions.contains(c))
+}
+
+/// Is `constraint` satisfied whenever `other` is, for a variable whose entity
+/// type is one of `other_types`?
+fn scope_covers<'a>(
+    constraint: &PrincipalOrResourceConstraint,
+    other: &PrincipalOrResourceConstraint,
+    mut other_types: impl Iterator<Item = &'a EntityType>,
+) -> bool {
+    use PrincipalOrResourceConstraint as C;
+    match constraint {
+        C::Any => true,
+        C::Eq(e) => matches!(other, C::Eq(o) if o == e),
+        C::In(e) => matches!(other, C::Eq(o) | C::In(o) | C::IsIn(_, o) if o == e),
+        C::Is(ty) => other_types.all(|t| t == ty.as_ref()),
+        C::IsIn(ty, e) => {
+            other_types.all(|t| t == ty.as_ref())
+                && matches!(other, C::Eq(o) | C::In(o) | C::IsIn(_, o) if o == e)
+        }
+    }
+}
+
+/// Are `a` and `b` the same policy, up to their ids, annotations and source
+/// locations?
+fn is_duplicate(a: &PolicySummary<'_>, b: &PolicySummary<'_>) -> bool {
+    a.policy.effect() == b.policy.effect()
+        && a.principal == b.principal
+        && a.policy.action_constraint() == b.policy.action_constraint()
+        && a.resource == b.resource
+        && ExprShapeOnly::new_from_borrowed(a.policy.non_scope_constraints())
+            == ExprShapeOnly::new_from_borrowed(b.policy.non_scope_constraints())
+}
+
+#[cfg(test)]
+mod test {
+    use cedar_policy_core::{extensions::Extensions, parser::parse_policyset};
+
+    use super::*;
+    use crate::ValidatorSchema;
+
+    fn analyze(policies: &str) -> Vec<String> {
+        let (schema, _) = ValidatorSchema::from_cedarschema_str(
+            r#"
+            entity Group;
+            entity User in [Group] { level: Long };
+            entity Doc { public: Bool };
+            action view, edit appliesTo { principal: [User], resource: [Doc] };
+            action all;
+            action read in [all] appliesTo { principal: [User], resource: [Doc] };
+            "#,
+            Extensions::all_available(),
+        )
+        .unwrap();
+        let validator = Validator::new(schema);
+        let policies = parse_policyset(policies).unwrap();
+        validator
+            .analyze(&policies)
+            .map(|w| w.to_string())
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn shadowed_by_permit() {
+        assert_eq!(
+            analyze(
+                r#"
+                permit(principal is User, action, resource) when { resource.public };
+                permit(principal == User::"alice", action in [Action::"view", Action::"edit"], resource)
+                when { principal.level > 3 && resource.public };
+                "#
+            ),
+            ["for policy `policy1`, permit policy is shadowed by permit policy `policy0`"]
+        );
+    }
+
+    #[test]
+    fn shadowed_by_forbid() {
+        assert_eq!(
+            analyze(
+                r#"
+                permit(principal, action == Action::"view", resource is Doc) when { resource.public };
+                forbid(principal is User, action, resource);
+                "#
+            ),
+            ["for policy `policy0`, permit policy is shadowed by forbid policy `policy1`"]
+        );
+    }
+
+    #[test]
+    fn action_hierarchy() {
+        assert_eq!(
+            analyze(
+                r#"
+                permit(principal, action in Action::"all", resource);
+                permit(principal, action == Action::"read", resource);
+                "#
+            ),
+            ["for policy `policy1`, permit policy is shadowed by permit policy `policy0`"]
+        );
+    }
+
+    #[test]
+    fn mutually_covering() {
+        assert_eq!(
+            analyze(
+                r#"
+                permit(principal is User, action, resource);
+                permit(principal, action, resource);
+                "#
+            ),
+            ["for policy `policy1`, permit policy is shadowed by permit policy `policy0`"]
+        );
+    }
+
+    #[test]
+    fn not_shadowed() {
+        assert_eq!(
+            analyze(
+                r#"
+                permit(principal in Group::"staff", action, resource) when { resource.public };
+                permit(principal == User::"alice", action, resource);
+                permit(principal in Group::"admins", action, resource) when { resource.public };
+                "#
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn conflicts() {
+        assert_eq!(
+            analyze(
+                r#"
+                permit(principal, action == Action::"view", resource);
+                forbid(principal, action, resource) unless { resource.public };
+                forbid(principal, action == Action::"edit", resource);
+                "#
+            ),
+            ["for policy `policy0`, permit policy may conflict with forbid policy `policy1`"]
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        assert_eq!(
+            analyze(
+                r#"
+                forbid(principal, action, resource) when { !resource.public };
+                @reason("copied")
+                forbid(principal, action, resource)
+                when { !resource.public };
+                "#
+            ),
+            ["for policy `policy1`, policy is a duplicate of policy `policy0`"]
+        );
+    }
+}
@@ -24,7 +24,7 @@ use validation_errors::UnrecognizedActionIdHelp;
 
 use std::collections::BTreeSet;
 
-use cedar_policy_core::ast::{EntityType, Expr, PolicyID};
+use cedar_policy_core::ast::{Effect, EntityType, Expr, PolicyID};
 use cedar_policy_core::parser::Loc;
 
 use crate::types::{EntityLUB, Type};
@@ -451,6 +451,18 @@ pub enum ValidationWarning {
     #[diagnostic(transparent)]
     #[error(transparent)]
     ImpossiblePolicy(#[from] validation_warnings::ImpossiblePolicy),
+    /// Policy analysis found that a permit policy only applies to requests another policy also applies to.
+    #[diagnostic(transparent)]
+    #[error(transparent)]
+    ShadowedPermit(#[from] validation_warnings::ShadowedPermit),
+    /// Policy analysis found a permit and a forbid policy which may apply to requests in the same request environment.
+    #[diagnostic(transparent)]
+    #[error(transparent)]
+    ConflictingPolicies(#[from] validation_warnings::ConflictingPolicies),
+    /// Policy analysis found a policy which is the same as another policy.
+    #[diagnostic(transparent)]
+    #[error(transparent)]
+    DuplicatePolicy(#[from] validation_warnings::DuplicatePolicy),
 }
 
 impl ValidationWarning {
@@ -528,4 +540,45 @@ impl ValidationWarning {
         }
         .into()
     }
+
+    pub(crate) fn shadowed_permit(
+        source_loc: Option<Loc>,
+        policy_id: PolicyID,
+        shadowing_policy_id: PolicyID,
+        shadowing_effect: Effect,
+    ) -> Self {
+        validation_warnings::ShadowedPermit {
+            source_loc,
+            policy_id,
+            shadowing_policy_id,
+            shadowing_effect,
+        }
+        .into()
+    }
+
+    pub(crate) fn conflicting_policies(
+        source_loc: Option<Loc>,
+        policy_id: PolicyID,
+        forbid_policy_id: PolicyID,
+    ) -> Self {
+        validation_warnings::ConflictingPolicies {
+            source_loc,
+            policy_id,
+            forbid_policy_id,
+        }
+        .into()
+    }
+
+    pub(crate) fn duplicate_policy(
+        source_loc: Option<Loc>,
+        policy_id: PolicyID,
+        duplicate_of: PolicyID,
+    ) -> Self {
+        validation_warnings::DuplicatePolicy {
+            source_loc,
+            policy_id,
+            duplicate_of,
+        }
+        .into()
+    }
 }
@@ -27,7 +27,11 @@ macro_rules! impl_diagnostic_warning {
     };
 }
 
-use cedar_policy_core::{ast::PolicyID, impl_diagnostic_from_source_loc_opt_field, parser::Loc};
+use cedar_policy_core::{
+    ast::{Effect, PolicyID},
+    impl_diagnostic_from_source_loc_opt_field,
+    parser::Loc,
+};
 use miette::Diagnostic;
 use thiserror::Error;
 
@@ -135,3 +139,81 @@ impl Diagnostic for ImpossiblePolicy {
     impl_diagnostic_from_source_loc_opt_field!(source_loc);
     impl_diagnostic_warning!();
 }
+
+/// Warning for permit policies which only apply to requests another policy
+/// also applies to
+#[derive(Debug, Clone, PartialEq, Error, Eq, Hash)]
+#[error("for policy `{policy_id}`, permit policy is shadowed by {shadowing_effect} policy `{shadowing_policy_id}`")]
+pub struct ShadowedPermit {
+    /// Source location
+    pub source_loc: Option<Loc>,
+    /// Policy ID where the warning occurred
+    pub policy_id: PolicyID,
+    /// Policy ID of the policy which applies to every request this one does
+    pub shadowing_policy_id: PolicyID,
+    /// Effect of the shadowing policy
+    pub shadowing_effect: Effect,
+}
+
+impl Diagnostic for ShadowedPermit {
+    impl_diagnostic_from_source_loc_opt_field!(source_loc);
+    impl_diagnostic_warning!();
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        Some(Box::new(match self.shadowing_effect {
+            Effect::Permit => format!(
+                "every request this policy allows is also allowed by `{}`, so it is redundant",
+                self.shadowing_policy_id
+            ),
+            Effect::Forbid => format!(
+                "every request this policy applies to is denied by `{}`, so it never allows a request",
+                self.shadowing_policy_id
+            ),
+        }))
+    }
+}
+
+/// Warning for a permit and a forbid policy which may apply to requests in
+/// the same request environment
+#[derive(Debug, Clone, PartialEq, Error, Eq, Hash)]
+#[error(
+    "for policy `{policy_id}`, permit policy may conflict with forbid policy `{forbid_policy_id}`"
+)]
+pub struct ConflictingPolicies {
+    /// Source location
+    pub source_loc: Option<Loc>,
+    /// Policy ID of the permit policy
+    pub policy_id: PolicyID,
+    /// Policy ID of the forbid policy
+    pub forbid_policy_id: PolicyID,
+}
+
+impl Diagnostic for ConflictingPolicies {
+    impl_diagnostic_from_source_loc_opt_field!(source_loc);
+    impl_diagnostic_warning!();
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        Some(Box::new(format!(
+            "both policies may apply to requests with the same principal type, action and resource type; `{}` overrides this policy for those requests where both are satisfied",
+            self.forbid_policy_id
+        )))
+    }
+}
+
+/// Warning for policies which are the same as another policy, up to their
+/// ids, annotations and source locations
+#[derive(Debug, Clone, PartialEq, Error, Eq, Hash)]
+#[error("for policy `{policy_id}`, policy is a duplicate of policy `{duplicate_of}`")]
+pub struct DuplicatePolicy {
+    /// Source location
+    pub source_loc: Option<Loc>,
+    /// Policy ID where the warning occurred
+    pub policy_id: PolicyID,
+    /// Policy ID of the policy this one duplicates
+    pub duplicate_of: PolicyID,
+}
+
+impl Diagnostic for DuplicatePolicy {
+    impl_diagnostic_from_source_loc_opt_field!(source_loc);
+    impl_diagnostic_warning!();
+}
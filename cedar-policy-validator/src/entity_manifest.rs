@@ -34,6 +34,7 @@ mod analysis;
 mod loader;
 pub mod slicing;
 mod type_annotations;
+pub use loader::{load_entities, AncestorsRequest, EntityAnswer, EntityLoader, EntityRequest};
 
 use crate::entity_manifest::analysis::{EntityManifestAnalysisResult, WrappedAccessPaths};
 use crate::{
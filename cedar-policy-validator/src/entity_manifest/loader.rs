@@ -31,7 +31,8 @@ use smol_str::SmolStr;
 
 use crate::entity_manifest::{
     slicing::{
-        EntitySliceError, PartialContextError, PartialEntityError, WrongNumberOfEntitiesError,
+        EntitySliceError, InconsistentEntityError, PartialContextError, PartialEntityError,
+        WrongNumberOfEntitiesError,
     },
     AccessTrie, EntityManifest, EntityRoot, PartialRequestError, RootAccessTrie,
 };
@@ -39,17 +40,33 @@ use crate::entity_manifest::{
 /// A request that an entity be loaded.
 /// Optionally, instead of loading the full entity the `access_trie`
 /// may be used to load only some fields of the entity.
-#[derive(Debug)]
-pub(crate) struct EntityRequest {
+// CAUTION: this type is publicly exported in `cedar-policy`.
+// Don't make fields `pub`, don't make breaking changes, and use caution
+// when adding public methods.
+#[derive(Debug, Clone)]
+pub struct EntityRequest {
     /// The id of the entity requested
     pub(crate) entity_id: EntityUID,
     /// The fieds of the entity requested
     pub(crate) access_trie: AccessTrie,
 }
 
+impl EntityRequest {
+    /// The id of the entity requested
+    pub fn entity_id(&self) -> &EntityUID {
+        &self.entity_id
+    }
+
+    /// The fields of the entity requested. Referenced entities are requested
+    /// separately, so this trie does not include any entity dereferences.
+    pub fn access_trie(&self) -> &AccessTrie {
+        &self.access_trie
+    }
+}
+
 /// An entity request may be an entity or `None` when
 /// the entity is not present.
-pub(crate) type EntityAnswer = Option<Entity>;
+pub type EntityAnswer = Option<Entity>;
 
 /// The entity request before sub-entitity tries have been
 /// pruned using `prune_child_entity_dereferences`.
@@ -69,14 +86,29 @@ impl EntityRequestRef<'_> {
 
 /// A request that the ancestors of an entity be loaded.
 /// Optionally, the `ancestors` set may be used to just load ancestors in the set.
-#[derive(Debug)]
-pub(crate) struct AncestorsRequest {
+// CAUTION: this type is publicly exported in `cedar-policy`.
+// Don't make fields `pub`, don't make breaking changes, and use caution
+// when adding public methods.
+#[derive(Debug, Clone)]
+pub struct AncestorsRequest {
     /// The id of the entity whose ancestors are requested
     pub(crate) entity_id: EntityUID,
     /// The ancestors that are requested, if present
     pub(crate) ancestors: HashSet<EntityUID>,
 }
 
+impl AncestorsRequest {
+    /// The id of the entity whose ancestors are requested
+    pub fn entity_id(&self) -> &EntityUID {
+        &self.entity_id
+    }
+
+    /// The ancestors that are requested, if they are ancestors of the entity
+    pub fn ancestors(&self) -> &HashSet<EntityUID> {
+        &self.ancestors
+    }
+}
+
 /// Implement [`EntityLoader`] to easily load entities using their ids
 /// into a Cedar [`Entities`] store.
 /// The most basic implementation loads full entities (including all ancestors) in the `load_entities` method and loads the context in the `load_context` method.
@@ -84,7 +116,7 @@ pub(crate) struct AncestorsRequest {
 ///
 /// Warning: `load_entities` is called multiple times. If database
 /// consistency is required, this API should not be used. Instead, use the entity manifest directly.
-pub(crate) trait EntityLoader {
+pub trait EntityLoader {
     /// `load_entities` is called multiple times to load entities based on their ids.
     /// For each entity request in the `to_load` vector, expects one loaded entity in the resulting vector.
     /// Each [`EntityRequest`] comes with an [`AccessTrie`], which can optionally be used.
@@ -197,7 +229,7 @@ impl AccessTrie {
 
 /// Loads entities based on the entity manifest, request, and
 /// the implemented [`EntityLoader`].
-pub(crate) fn load_entities(
+pub fn load_entities(
     manifest: &EntityManifest,
     request: &Request,
     loader: &mut dyn EntityLoader,
@@ -265,7 +297,7 @@ pub(crate) fn load_entities(
                         // attributes.  This can happen when an entity is
                         // referenced by both an entity literal and a variable.
                         let (k, v) = o.remove_entry();
-                        let merged = merge_entities(v, loaded);
+                        let merged = merge_entities(v, loaded)?;
                         entities.insert(k, merged);
                     }
                     hash_map::Entry::Vacant(v) => {
@@ -317,51 +349,39 @@ pub(crate) fn load_entities(
 /// both. If one entity is referenced by multiple entity roots in the slice,
 /// then we need to be sure that we don't clobber the attribute for the first
 /// when inserting the second into the slice.
-// INVARIANT: `e1` and `e2` must be the result of slicing the same original
-// entity using the same entity manifest and request. I.e., they may differ only in
-// what attributes they contain. When an attribute exists in both, the
-// attributes may differ only if they are records, and then only in what nested
-// attributes they contain.
-fn merge_entities(e1: Entity, e2: Entity) -> Entity {
+///
+/// `e1` and `e2` should be the result of slicing the same original entity
+/// using the same entity manifest and request. I.e., they may differ only in
+/// what attributes they contain. When an attribute exists in both, the
+/// attributes may differ only if they are records, and then only in what
+/// nested attributes they contain. Since the entities come from an
+/// `EntityLoader`, this is checked, and an error is returned if it doesn't
+/// hold.
+fn merge_entities(e1: Entity, e2: Entity) -> Result<Entity, InconsistentEntityError> {
     let (uid1, mut attrs1, ancestors1, parents1, tags1) = e1.into_inner();
     let (uid2, attrs2, ancestors2, parents2, tags2) = e2.into_inner();
-
-    assert_eq!(
-        uid1, uid2,
-        "attempting to merge entities with different uids!"
-    );
-    assert_eq!(
-        ancestors1, ancestors2,
-        "attempting to merge entities with different ancestors!"
-    );
-    assert_eq!(
-        parents1, parents2,
-        "attempting to merge entities with different parents!"
-    );
-    assert!(
-        tags1.is_empty() && tags2.is_empty(),
-        "attempting to merge entities with tags!"
-    );
+    // entity slicing does not load tags, but a loader may return whole
+    // entities, in which case both copies have the same tags
+    if uid1 != uid2 || ancestors1 != ancestors2 || parents1 != parents2 || tags1 != tags2 {
+        return Err(InconsistentEntityError { uid: uid1 });
+    }
 
     for (k, v2) in attrs2 {
         match attrs1.entry(k) {
             hash_map::Entry::Occupied(occupied) => {
                 let (k, v1) = occupied.remove_entry();
-                match (v1, v2) {
+                let merged_v = match (v1, v2) {
                     (PartialValue::Value(v1), PartialValue::Value(v2)) => {
-                        let merged_v = merge_values(v1, v2);
-                        attrs1.insert(k, PartialValue::Value(merged_v));
-                    }
-                    (PartialValue::Residual(e1), PartialValue::Residual(e2)) => {
-                        assert_eq!(e1, e2, "attempting to merge different residuals!");
-                        attrs1.insert(k, PartialValue::Residual(e1));
+                        merge_values(v1, v2).map(PartialValue::Value)
                     }
-                    // PANIC SAFETY: We're merging sliced copies of the same entity, so the attribute must be the same
-                    #[allow(clippy::panic)]
-                    (PartialValue::Value(_), PartialValue::Residual(_))
-                    | (PartialValue::Residual(_), PartialValue::Value(_)) => {
-                        panic!("attempting to merge a value with a residual")
+                    (PartialValue::Residual(e1), PartialValue::Residual(e2)) if e1 == e2 => {
+                        Some(PartialValue::Residual(e1))
                     }
+                    _ => None,
+                };
+                match merged_v {
+                    Some(merged_v) => attrs1.insert(k, merged_v),
+                    None => return Err(InconsistentEntityError { uid: uid1 }),
                 };
             }
             hash_map::Entry::Vacant(vacant) => {
@@ -370,16 +390,20 @@ fn merge_entities(e1: Entity, e2: Entity) -> Entity {
         }
     }
 
-    Entity::new_with_attr_partial_value(uid1, attrs1, ancestors1, parents1, [])
+    Ok(Entity::new_with_attr_partial_value(
+        uid1, attrs1, ancestors1, parents1, tags1,
+    ))
 }
 
-/// Merge two value for corresponding attributes in the slice.
-// INVARIANT: `v1` and `v2` must be the result of slicing the same original
-// value using the same entity manifest and request. I.e., they must be
-// identical, except for the attributes they contain when the values are a
-// records. When an attribute exists in both records, the attributes must be
-// recursively identical, with the same exception.
-fn merge_values(v1: Value, v2: Value) -> Value {
+/// Merge two value for corresponding attributes in the slice, or return
+/// `None` if they are not consistent.
+///
+/// `v1` and `v2` should be the result of slicing the same original value
+/// using the same entity manifest and request. I.e., they must be identical,
+/// except for the attributes they contain when the values are a records. When
+/// an attribute exists in both records, the attributes must be recursively
+/// identical, with the same exception.
+fn merge_values(v1: Value, v2: Value) -> Option<Value> {
     match (v1.value, v2.value) {
         (ValueKind::Record(r1), ValueKind::Record(r2)) => {
             let mut r1 = Arc::unwrap_or_clone(r1);
@@ -387,7 +411,7 @@ fn merge_values(v1: Value, v2: Value) -> Value {
                 match r1.entry(k) {
                     btree_map::Entry::Occupied(occupied) => {
                         let (k, v1) = occupied.remove_entry();
-                        let merged_v = merge_values(v1, v2);
+                        let merged_v = merge_values(v1, v2)?;
                         r1.insert(k, merged_v);
                     }
                     btree_map::Entry::Vacant(vacant) => {
@@ -395,28 +419,13 @@ fn merge_values(v1: Value, v2: Value) -> Value {
                     }
                 }
             }
-            Value::new(ValueKind::Record(Arc::new(r1)), v1.loc)
-        }
-        (ValueKind::Lit(l1), ValueKind::Lit(l2)) => {
-            assert_eq!(l1, l2, "attempting to merge different literals!");
-            Value::new(l1, v1.loc)
-        }
-        (vk1 @ ValueKind::ExtensionValue(_), vk2 @ ValueKind::ExtensionValue(_))
-        | (vk1 @ ValueKind::Set(_), vk2 @ ValueKind::Set(_)) => {
-            // It might seem that we should recur into the sets and extensions
-            // values, but `AccessTrie::slice_val` doesn't, so the merge
-            // function can stop here too.
-            assert_eq!(
-                vk1, vk2,
-                "attempting to merge different sets or extensions!"
-            );
-            Value::new(vk1, v1.loc)
-        }
-        // PANIC SAFETY: We're merging sliced copies of the same entity, so the attribute must be the same
-        #[allow(clippy::panic)]
-        _ => {
-            panic!("attempting to merge values of different kinds!")
+            Some(Value::new(ValueKind::Record(Arc::new(r1)), v1.loc))
         }
+        // It might seem that we should recur into the sets and extensions
+        // values, but `AccessTrie::slice_val` doesn't, so the merge function
+        // can stop here too.
+        (vk1, vk2) if vk1 == vk2 => Some(Value::new(vk1, v1.loc)),
+        _ => None,
     }
 }
 
@@ -582,57 +591,116 @@ fn compute_ancestors_request(
 
 #[cfg(test)]
 mod test {
-    use cedar_policy_core::ast::Value;
+    use cedar_policy_core::ast::{Entity, EntityUID, PartialValue, Value};
     use smol_str::ToSmolStr;
 
-    use super::merge_values;
+    use super::{merge_entities, merge_values, InconsistentEntityError};
 
     #[test]
     fn test_merge_values() {
         assert_eq!(
             merge_values(Value::new(1, None), Value::new(1, None)),
-            Value::new(1, None),
+            Some(Value::new(1, None)),
         );
         assert_eq!(
             merge_values(
                 Value::set([Value::new(1, None), Value::new(2, None)], None),
                 Value::set([Value::new(1, None), Value::new(2, None)], None),
             ),
-            Value::set([Value::new(1, None), Value::new(2, None)], None),
+            Some(Value::set([Value::new(1, None), Value::new(2, None)], None)),
         );
         assert_eq!(
             merge_values(
                 Value::record([("a".to_smolstr(), Value::new(1, None))], None),
                 Value::record([("a".to_smolstr(), Value::new(1, None))], None),
             ),
-            Value::record([("a".to_smolstr(), Value::new(1, None))], None),
+            Some(Value::record(
+                [("a".to_smolstr(), Value::new(1, None))],
+                None
+            )),
         );
         assert_eq!(
             merge_values(
                 Value::empty_record(None),
                 Value::record([("a".to_smolstr(), Value::new(1, None))], None),
             ),
-            Value::record([("a".to_smolstr(), Value::new(1, None))], None),
+            Some(Value::record(
+                [("a".to_smolstr(), Value::new(1, None))],
+                None
+            )),
         );
         assert_eq!(
             merge_values(
                 Value::record([("a".to_smolstr(), Value::new(1, None))], None),
                 Value::empty_record(None),
             ),
-            Value::record([("a".to_smolstr(), Value::new(1, None))], None),
+            Some(Value::record(
+                [("a".to_smolstr(), Value::new(1, None))],
+                None
+            )),
         );
         assert_eq!(
             merge_values(
                 Value::record([("a".to_smolstr(), Value::new(1, None))], None),
                 Value::record([("b".to_smolstr(), Value::new(2, None))], None),
             ),
-            Value::record(
+            Some(Value::record(
                 [
                     ("a".to_smolstr(), Value::new(1, None)),
                     ("b".to_smolstr(), Value::new(2, None))
                 ],
                 None
+            )),
+        );
+        assert_eq!(merge_values(Value::new(1, None), Value::new(2, None)), None);
+        assert_eq!(
+            merge_values(
+                Value::record([("a".to_smolstr(), Value::new(1, None))], None),
+                Value::record([("a".to_smolstr(), Value::new("x", None))], None),
             ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_merge_entities_with_tags() {
+        let tagged = |attr: &str| {
+            Entity::new_with_attr_partial_value(
+                EntityUID::with_eid_and_type("E", "e").unwrap(),
+                [(attr.to_smolstr(), PartialValue::from(1))],
+                Default::default(),
+                Default::default(),
+                [("t".to_smolstr(), PartialValue::from(2))],
+            )
+        };
+        let merged = merge_entities(tagged("a"), tagged("b")).unwrap();
+        assert_eq!(merged.get("a"), Some(&PartialValue::from(1)));
+        assert_eq!(merged.get("b"), Some(&PartialValue::from(1)));
+        assert_eq!(merged.get_tag("t"), Some(&PartialValue::from(2)));
+    }
+
+    #[test]
+    fn test_merge_inconsistent_entities() {
+        let uid = EntityUID::with_eid_and_type("E", "e").unwrap();
+        let entity = |attr: i64, parents: &[&str]| {
+            Entity::new_with_attr_partial_value(
+                uid.clone(),
+                [("a".to_smolstr(), PartialValue::from(attr))],
+                Default::default(),
+                parents
+                    .iter()
+                    .map(|p| EntityUID::with_eid_and_type("E", p).unwrap())
+                    .collect(),
+                [],
+            )
+        };
+        assert_eq!(
+            merge_entities(entity(1, &[]), entity(2, &[])),
+            Err(InconsistentEntityError { uid: uid.clone() })
+        );
+        assert_eq!(
+            merge_entities(entity(1, &["p"]), entity(1, &[])),
+            Err(InconsistentEntityError { uid })
         );
     }
 }
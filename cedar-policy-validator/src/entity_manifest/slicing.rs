@@ -3,7 +3,7 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
 
-use cedar_policy_core::entities::err::EntitiesError;
+use cedar_policy_core::entities::err::{EntitiesError, EntityStoreError};
 use cedar_policy_core::entities::Dereference;
 use cedar_policy_core::{
     ast::{Entity, EntityUID, Literal, PartialValue, Request, Value, ValueKind},
@@ -81,6 +81,19 @@ pub struct NonRecordValueError {
     pub(crate) attribute: SmolStr,
 }
 
+/// Error when an entity loader returns copies of an entity which disagree,
+/// e.g. on the entity's ancestors or on the value of an attribute.
+// CAUTION: this type is publicly exported in `cedar-policy`.
+// Don't make fields `pub`, don't make breaking changes, and use caution
+// when adding public methods.
+#[derive(Debug, Clone, Error, Eq, PartialEq)]
+#[error("entity loader produced inconsistent entity data for `{uid}`")]
+pub struct InconsistentEntityError {
+    pub(crate) uid: EntityUID,
+}
+
+impl Diagnostic for InconsistentEntityError {}
+
 /// Context was partial during entity loading
 // CAUTION: this type is publicly exported in `cedar-policy`.
 // Don't make fields `pub`, don't make breaking changes, and use caution
@@ -121,6 +134,14 @@ pub enum EntitySliceError {
     /// The entity loader produced the wrong number of entities.
     #[error(transparent)]
     WrongNumberOfEntities(#[from] WrongNumberOfEntitiesError),
+
+    /// The entity loader produced copies of an entity which disagree.
+    #[error(transparent)]
+    InconsistentEntity(#[from] InconsistentEntityError),
+
+    /// The entity loader failed to load entities from its backing store.
+    #[error("failed to load entities: {0}")]
+    EntityStore(#[from] EntityStoreError),
 }
 
 impl EntityManifest {
@@ -169,7 +190,7 @@ impl EntityLoader for EntitySlicer<'_> {
                 let mut ancestors = HashSet::new();
 
                 for required_ancestor in &request.ancestors {
-                    if entity.is_descendant_of(required_ancestor) {
+                    if self.entities.is_descendant_of(entity, required_ancestor) {
                         ancestors.insert(required_ancestor.clone());
                     }
                 }
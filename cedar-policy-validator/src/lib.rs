@@ -30,6 +30,7 @@
 use cedar_policy_core::ast::{Policy, PolicySet, Template};
 use serde::Serialize;
 use std::collections::HashSet;
+mod analysis;
 mod level_validate;
 
 mod coreschema;
@@ -51,7 +52,11 @@ mod str_checks;
 pub use str_checks::confusable_string_checks;
 pub mod cedar_schema;
 pub mod typecheck;
+#[cfg(feature = "partial-eval")]
+pub mod typed_partial_eval;
+mod usage;
 use typecheck::Typechecker;
+pub use usage::SchemaUsageReport;
 mod partition_nonempty;
 pub mod types;
 
@@ -336,7 +336,7 @@ impl Validator {
 
     /// Get the set of actions (action entity id strings) that satisfy the
     /// action scope constraint of the policy.
-    fn get_actions_satisfying_constraint<'a>(
+    pub(crate) fn get_actions_satisfying_constraint<'a>(
         &'a self,
         action_constraint: &'a ActionConstraint,
     ) -> Box<dyn Iterator<Item = &'a EntityUID> + 'a> {
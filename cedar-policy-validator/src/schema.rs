@@ -28,7 +28,7 @@ use cedar_policy_core::{
     transitive_closure::compute_tc,
 };
 use educe::Educe;
-use namespace_def::EntityTypeFragment;
+use namespace_def::{ActionFragment, EntityTypeFragment};
 use nonempty::NonEmpty;
 use serde::Deserialize;
 #[cfg(feature = "extended-schema")]
@@ -46,12 +46,15 @@ use crate::{
     json_schema,
     partition_nonempty::PartitionNonEmpty,
     types::{Attributes, EntityRecordKind, OpenTag, RequestEnv, Type},
+    usage::CommonTypeUse,
     ValidationMode,
 };
 
 mod action;
 pub use action::ValidatorActionId;
+mod diff;
 pub(crate) use action::ValidatorApplySpec;
+pub use diff::{SchemaChange, SchemaChangeReport};
 mod entity_type;
 pub use entity_type::{ValidatorEntityType, ValidatorEntityTypeKind};
 mod namespace_def;
@@ -242,6 +245,12 @@ pub struct ValidatorSchema {
     /// this cache it's O(1).
     pub(crate) actions: HashMap<EntityUID, Arc<Entity>>,
 
+    /// Map from the name of each common type declared in the schema to the
+    /// schema elements whose declared types refer to it. Common types are
+    /// inlined everywhere else in the `ValidatorSchema`, so this is the only
+    /// record of where they were used.
+    pub(crate) common_type_uses: HashMap<InternalName, Vec<CommonTypeUse>>,
+
     #[cfg(feature = "extended-schema")]
     common_types: HashSet<ValidatorCommonType>,
     #[cfg(feature = "extended-schema")]
@@ -317,6 +326,7 @@ impl ValidatorSchema {
             entity_types,
             action_ids,
             actions,
+            common_type_uses: HashMap::new(),
             #[cfg(feature = "extended-schema")]
             common_types,
             #[cfg(feature = "extended-schema")]
@@ -456,6 +466,7 @@ impl ValidatorSchema {
             entity_types: HashMap::new(),
             action_ids: HashMap::new(),
             actions: HashMap::new(),
+            common_type_uses: HashMap::new(),
             #[cfg(feature = "extended-schema")]
             common_types: HashSet::new(),
             #[cfg(feature = "extended-schema")]
@@ -662,6 +673,8 @@ impl ValidatorSchema {
             }
         }
 
+        let common_type_uses =
+            Self::common_type_uses(&common_types, &entity_type_fragments, &action_fragments);
         let resolver = CommonTypeResolver::new(&common_types);
         let common_types: HashMap<&InternalName, ValidatorType> = resolver.resolve(extensions)?;
 
@@ -820,17 +833,96 @@ impl ValidatorSchema {
             common_types.into_values(),
         )?;
         #[cfg(not(feature = "extended-schema"))]
-        let validator_schema = Ok(ValidatorSchema::new_from_maps(entity_types, action_ids));
+        let mut validator_schema = ValidatorSchema::new_from_maps(entity_types, action_ids);
         #[cfg(feature = "extended-schema")]
-        let validator_schema = Ok(ValidatorSchema::new_from_maps(
+        let mut validator_schema = ValidatorSchema::new_from_maps(
             entity_types,
             action_ids,
             #[cfg(feature = "extended-schema")]
             common_type_validators,
             #[cfg(feature = "extended-schema")]
             validator_namespaces,
-        ));
-        validator_schema
+        );
+        validator_schema.common_type_uses = common_type_uses;
+        Ok(validator_schema)
+    }
+
+    /// Find the schema elements whose declared types refer to each common type
+    /// declared in `common_types`. The primitive and extension types, which
+    /// are common types in the reserved `__cedar` namespace with aliases in
+    /// the empty namespace, are omitted.
+    fn common_type_uses(
+        common_types: &HashMap<InternalName, json_schema::Type<InternalName>>,
+        entity_types: &HashMap<EntityType, EntityTypeFragment<InternalName>>,
+        actions: &HashMap<EntityUID, ActionFragment<InternalName, EntityType>>,
+    ) -> HashMap<InternalName, Vec<CommonTypeUse>> {
+        let is_builtin = |name: &InternalName, ty: &json_schema::Type<InternalName>| {
+            let builtin_alias = match ty {
+                json_schema::Type::Type {
+                    ty: json_schema::TypeVariant::EntityOrCommon { type_name },
+                    ..
+                } => {
+                    let same_basename = type_name.basename() == name.basename();
+                    name.is_unqualified() && type_name.is_reserved() && same_basename
+                }
+                _ => false,
+            };
+            name.is_reserved() || builtin_alias
+        };
+        let mut uses: HashMap<InternalName, Vec<CommonTypeUse>> = common_types
+            .iter()
+            .filter(|(name, ty)| !is_builtin(name, ty))
+            .map(|(name, _)| (name.clone(), Vec::new()))
+            .collect();
+        let mut add_uses = |ty: &json_schema::Type<InternalName>, user: CommonTypeUse| {
+            let mut refs = Vec::new();
+            common_type_refs(ty, common_types, &mut refs);
+            for name in refs {
+                if let Some(users) = uses.get_mut(name) {
+                    users.push(user.clone());
+                }
+            }
+        };
+        for (name, ty) in common_types {
+            add_uses(ty, CommonTypeUse::CommonType(name.clone()));
+        }
+        for (name, entity_type) in entity_types {
+            if let EntityTypeFragment::Standard {
+                attributes, tags, ..
+            } = entity_type
+            {
+                match &attributes.0 {
+                    json_schema::Type::Type {
+                        ty: json_schema::TypeVariant::Record(record),
+                        ..
+                    } => {
+                        for (attr, attr_ty) in &record.attributes {
+                            add_uses(
+                                &attr_ty.ty,
+                                CommonTypeUse::Attribute {
+                                    entity_type: name.clone(),
+                                    attr: Some(attr.clone()),
+                                },
+                            );
+                        }
+                    }
+                    shape => add_uses(
+                        shape,
+                        CommonTypeUse::Attribute {
+                            entity_type: name.clone(),
+                            attr: None,
+                        },
+                    ),
+                }
+                if let Some(tags) = tags {
+                    add_uses(tags, CommonTypeUse::Tags(name.clone()));
+                }
+            }
+        }
+        for (name, action) in actions {
+            add_uses(&action.context, CommonTypeUse::Context(name.clone()));
+        }
+        uses
     }
 
     /// Check that all entity types and actions referenced in the schema are in
@@ -1215,6 +1307,39 @@ fn internal_name_to_entity_type(
     Name::try_from(name).map(Into::into)
 }
 
+/// Collect the names of the common types in `common_types` which `ty` refers
+/// to directly, without following references inside those common types.
+fn common_type_refs<'a>(
+    ty: &'a json_schema::Type<InternalName>,
+    common_types: &HashMap<InternalName, json_schema::Type<InternalName>>,
+    out: &mut Vec<&'a InternalName>,
+) {
+    match ty {
+        json_schema::Type::CommonTypeRef { type_name, .. }
+        | json_schema::Type::Type {
+            ty: json_schema::TypeVariant::EntityOrCommon { type_name },
+            ..
+        } => {
+            if common_types.contains_key(type_name) {
+                out.push(type_name);
+            }
+        }
+        json_schema::Type::Type {
+            ty: json_schema::TypeVariant::Set { element },
+            ..
+        } => common_type_refs(element, common_types, out),
+        json_schema::Type::Type {
+            ty: json_schema::TypeVariant::Record(record),
+            ..
+        } => {
+            for attr_ty in record.attributes.values() {
+                common_type_refs(&attr_ty.ty, common_types, out);
+            }
+        }
+        json_schema::Type::Type { .. } => (),
+    }
+}
+
 /// Holds the sets of all entity type, common type, and action definitions
 /// (fully-qualified names) in all fragments.
 #[derive(Debug)]
@@ -0,0 +1,958 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! This module contains the comparison of two versions of a schema,
+//! classifying each difference as breaking or non-breaking.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::{self, Display};
+
+use cedar_policy_core::ast::{EntityType, EntityUID, PolicyID, PolicySet};
+use itertools::Itertools;
+use smol_str::SmolStr;
+
+use super::{ValidatorActionId, ValidatorEntityType, ValidatorEntityTypeKind, ValidatorSchema};
+use crate::types::{Attributes, EntityRecordKind, Type};
+use crate::{ValidationMode, Validator};
+
+/// A single difference between two versions of a schema.
+///
+/// A change is breaking when entities or requests which were valid under the
+/// old schema may be invalid under the new one. Changes which only cause
+/// policies to stop validating are reported separately, by
+/// [`SchemaChangeReport::invalidated_policies`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SchemaChange {
+    /// An entity type was added
+    EntityTypeAdded {
+        /// The added entity type
+        entity_type: EntityType,
+    },
+    /// An entity type was removed
+    EntityTypeRemoved {
+        /// The removed entity type
+        entity_type: EntityType,
+    },
+    /// An entity type was changed from a standard entity type to an
+    /// enumerated entity type, or vice versa
+    EntityTypeKindChanged {
+        /// The changed entity type
+        entity_type: EntityType,
+    },
+    /// An attribute was added to an entity type
+    AttributeAdded {
+        /// The entity type the attribute was added to
+        entity_type: EntityType,
+        /// The added attribute
+        attr: SmolStr,
+        /// Whether the added attribute is required
+        required: bool,
+    },
+    /// An attribute was removed from an entity type
+    AttributeRemoved {
+        /// The entity type the attribute was removed from
+        entity_type: EntityType,
+        /// The removed attribute
+        attr: SmolStr,
+    },
+    /// An optional attribute was made required
+    AttributeMadeRequired {
+        /// The entity type of the attribute
+        entity_type: EntityType,
+        /// The attribute
+        attr: SmolStr,
+    },
+    /// A required attribute was made optional
+    AttributeMadeOptional {
+        /// The entity type of the attribute
+        entity_type: EntityType,
+        /// The attribute
+        attr: SmolStr,
+    },
+    /// The type of an attribute changed
+    AttributeTypeChanged {
+        /// The entity type of the attribute
+        entity_type: EntityType,
+        /// The attribute
+        attr: SmolStr,
+        /// The type in the old schema
+        old_type: Type,
+        /// The type in the new schema
+        new_type: Type,
+        /// Whether every value of the old type is a value of the new type
+        widened: bool,
+    },
+    /// The tag type of an entity type changed. `None` means entities of the
+    /// type may not have tags.
+    TagTypeChanged {
+        /// The entity type
+        entity_type: EntityType,
+        /// The tag type in the old schema
+        old_type: Option<Type>,
+        /// The tag type in the new schema
+        new_type: Option<Type>,
+        /// Whether every tag value allowed by the old schema is allowed by the
+        /// new one
+        widened: bool,
+    },
+    /// Entity types which entities of an entity type may be members of,
+    /// directly or transitively, were added or removed
+    MemberOfTypesChanged {
+        /// The entity type
+        entity_type: EntityType,
+        /// Ancestor types in the new schema but not the old one
+        added: BTreeSet<EntityType>,
+        /// Ancestor types in the old schema but not the new one
+        removed: BTreeSet<EntityType>,
+    },
+    /// Choices of an enumerated entity type were added or removed
+    EnumChoicesChanged {
+        /// The enumerated entity type
+        entity_type: EntityType,
+        /// Choices in the new schema but not the old one
+        added: BTreeSet<SmolStr>,
+        /// Choices in the old schema but not the new one
+        removed: BTreeSet<SmolStr>,
+    },
+    /// An action was added
+    ActionAdded {
+        /// The added action
+        action: EntityUID,
+    },
+    /// An action was removed
+    ActionRemoved {
+        /// The removed action
+        action: EntityUID,
+    },
+    /// The principal or resource types an action applies to changed
+    AppliesToChanged {
+        /// The action
+        action: EntityUID,
+        /// Principal types in the new schema but not the old one
+        principals_added: BTreeSet<EntityType>,
+        /// Principal types in the old schema but not the new one
+        principals_removed: BTreeSet<EntityType>,
+        /// Resource types in the new schema but not the old one
+        resources_added: BTreeSet<EntityType>,
+        /// Resource types in the old schema but not the new one
+        resources_removed: BTreeSet<EntityType>,
+    },
+    /// The type of the context of an action changed
+    ContextTypeChanged {
+        /// The action
+        action: EntityUID,
+        /// The context type in the old schema
+        old_type: Type,
+        /// The context type in the new schema
+        new_type: Type,
+        /// Whether every context allowed by the old schema is allowed by the
+        /// new one, i.e., no attribute was removed or made required and no
+        /// attribute type was narrowed
+        widened: bool,
+    },
+    /// Actions which an action is a member of, directly or transitively, were
+    /// added or removed
+    ActionMemberOfChanged {
+        /// The action
+        action: EntityUID,
+        /// Ancestor actions in the new schema but not the old one
+        added: BTreeSet<EntityUID>,
+        /// Ancestor actions in the old schema but not the new one
+        removed: BTreeSet<EntityUID>,
+    },
+}
+
+impl SchemaChange {
+    /// Whether entities or requests which were valid under the old schema may
+    /// be invalid under the new one
+    pub fn is_breaking(&self) -> bool {
+        match self {
+            Self::EntityTypeAdded { .. }
+            | Self::ActionAdded { .. }
+            | Self::AttributeMadeOptional { .. } => false,
+            Self::EntityTypeRemoved { .. }
+            | Self::EntityTypeKindChanged { .. }
+            | Self::AttributeRemoved { .. }
+            | Self::AttributeMadeRequired { .. }
+            | Self::ActionRemoved { .. } => true,
+            Self::AttributeAdded { required, .. } => *required,
+            Self::AttributeTypeChanged { widened, .. }
+            | Self::TagTypeChanged { widened, .. }
+            | Self::ContextTypeChanged { widened, .. } => !widened,
+            Self::MemberOfTypesChanged { removed, .. } => !removed.is_empty(),
+            Self::ActionMemberOfChanged { removed, .. } => !removed.is_empty(),
+            Self::EnumChoicesChanged { removed, .. } => !removed.is_empty(),
+            Self::AppliesToChanged {
+                principals_removed,
+                resources_removed,
+                ..
+            } => !principals_removed.is_empty() || !resources_removed.is_empty(),
+        }
+    }
+}
+
+/// Display a set of names as a comma separated list of quoted names
+fn names<T: Display>(set: &BTreeSet<T>) -> String {
+    set.iter().map(|n| format!("`{n}`")).join(", ")
+}
+
+/// Describe the additions and removals of `what`
+fn added_removed<T: Display>(
+    f: &mut fmt::Formatter<'_>,
+    what: &str,
+    added: &BTreeSet<T>,
+    removed: &BTreeSet<T>,
+) -> fmt::Result {
+    match (added.is_empty(), removed.is_empty()) {
+        (false, true) => write!(f, "{what} added: {}", names(added)),
+        (true, false) => write!(f, "{what} removed: {}", names(removed)),
+        _ => write!(
+            f,
+            "{what} added: {}; removed: {}",
+            names(added),
+            names(removed)
+        ),
+    }
+}
+
+impl Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EntityTypeAdded { entity_type } => {
+                write!(f, "entity type `{entity_type}` was added")
+            }
+            Self::EntityTypeRemoved { entity_type } => {
+                write!(f, "entity type `{entity_type}` was removed")
+            }
+            Self::EntityTypeKindChanged { entity_type } => write!(
+                f,
+                "entity type `{entity_type}` changed between standard and enumerated"
+            ),
+            Self::AttributeAdded {
+                entity_type,
+                attr,
+                required,
+            } => write!(
+                f,
+                "{} attribute `{attr}` was added to entity type `{entity_type}`",
+                if *required { "required" } else { "optional" }
+            ),
+            Self::AttributeRemoved { entity_type, attr } => write!(
+                f,
+                "attribute `{attr}` was removed from entity type `{entity_type}`"
+            ),
+            Self::AttributeMadeRequired { entity_type, attr } => write!(
+                f,
+                "attribute `{attr}` of entity type `{entity_type}` was made required"
+            ),
+            Self::AttributeMadeOptional { entity_type, attr } => write!(
+                f,
+                "attribute `{attr}` of entity type `{entity_type}` was made optional"
+            ),
+            Self::AttributeTypeChanged {
+                entity_type,
+                attr,
+                old_type,
+                new_type,
+                ..
+            } => write!(
+                f,
+                "type of attribute `{attr}` of entity type `{entity_type}` changed from `{old_type}` to `{new_type}`"
+            ),
+            Self::TagTypeChanged {
+                entity_type,
+                old_type,
+                new_type,
+                ..
+            } => match (old_type, new_type) {
+                (None, Some(ty)) => write!(
+                    f,
+                    "entity type `{entity_type}` may now have tags of type `{ty}`"
+                ),
+                (Some(_), None) => write!(f, "entity type `{entity_type}` may no longer have tags"),
+                (Some(old_type), Some(new_type)) => write!(
+                    f,
+                    "tag type of entity type `{entity_type}` changed from `{old_type}` to `{new_type}`"
+                ),
+                (None, None) => write!(f, "tag type of entity type `{entity_type}` changed"),
+            },
+            Self::MemberOfTypesChanged {
+                entity_type,
+                added,
+                removed,
+            } => {
+                write!(f, "entity type `{entity_type}` ")?;
+                added_removed(f, "ancestor types", added, removed)
+            }
+            Self::EnumChoicesChanged {
+                entity_type,
+                added,
+                removed,
+            } => {
+                write!(f, "enumerated entity type `{entity_type}` ")?;
+                added_removed(f, "choices", added, removed)
+            }
+            Self::ActionAdded { action } => write!(f, "action `{action}` was added"),
+            Self::ActionRemoved { action } => write!(f, "action `{action}` was removed"),
+            Self::AppliesToChanged {
+                action,
+                principals_added,
+                principals_removed,
+                resources_added,
+                resources_removed,
+            } => {
+                write!(f, "action `{action}` ")?;
+                if !principals_added.is_empty() || !principals_removed.is_empty() {
+                    added_removed(f, "principal types", principals_added, principals_removed)?;
+                    if !resources_added.is_empty() || !resources_removed.is_empty() {
+                        write!(f, "; ")?;
+                    }
+                }
+                if !resources_added.is_empty() || !resources_removed.is_empty() {
+                    added_removed(f, "resource types", resources_added, resources_removed)?;
+                }
+                Ok(())
+            }
+            Self::ContextTypeChanged {
+                action,
+                old_type,
+                new_type,
+                ..
+            } => write!(
+                f,
+                "context type of action `{action}` changed from `{old_type}` to `{new_type}`"
+            ),
+            Self::ActionMemberOfChanged {
+                action,
+                added,
+                removed,
+            } => {
+                write!(f, "action `{action}` ")?;
+                added_removed(f, "ancestor actions", added, removed)
+            }
+        }
+    }
+}
+
+/// The differences between two versions of a schema, as computed by
+/// [`ValidatorSchema::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaChangeReport {
+    changes: Vec<SchemaChange>,
+    invalidated_policies: Vec<PolicyID>,
+}
+
+impl SchemaChangeReport {
+    /// All changes between the two schemas, sorted by the name of the entity
+    /// type or action they concern
+    pub fn changes(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.changes.iter()
+    }
+
+    /// The changes for which [`SchemaChange::is_breaking`] holds
+    pub fn breaking_changes(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.changes.iter().filter(|c| c.is_breaking())
+    }
+
+    /// Ids of the policies, templates and template-linked policies which
+    /// validate against the old schema but not against the new one. This is
+    /// always empty for reports computed without a policy set.
+    pub fn invalidated_policies(&self) -> impl Iterator<Item = &PolicyID> {
+        self.invalidated_policies.iter()
+    }
+
+    /// Whether there are any breaking changes or invalidated policies
+    pub fn is_breaking(&self) -> bool {
+        self.changes.iter().any(SchemaChange::is_breaking) || !self.invalidated_policies.is_empty()
+    }
+}
+
+impl ValidatorSchema {
+    /// Compare two versions of a schema, reporting every added, removed or
+    /// changed entity type, attribute and action, including changes to the
+    /// context type and ancestors of an action.
+    pub fn diff(old: &ValidatorSchema, new: &ValidatorSchema) -> SchemaChangeReport {
+        let mut changes = Vec::new();
+
+        let entity_types = old
+            .entity_type_names()
+            .chain(new.entity_type_names())
+            .collect::<BTreeSet<_>>();
+        for name in entity_types {
+            match (old.get_entity_type(name), new.get_entity_type(name)) {
+                (Some(old_ety), Some(new_ety)) => {
+                    diff_entity_type(old, new, old_ety, new_ety, &mut changes)
+                }
+                (Some(_), None) => changes.push(SchemaChange::EntityTypeRemoved {
+                    entity_type: name.clone(),
+                }),
+                (None, Some(_)) => changes.push(SchemaChange::EntityTypeAdded {
+                    entity_type: name.clone(),
+                }),
+                (None, None) => (),
+            }
+        }
+
+        let actions = old.actions().chain(new.actions()).collect::<BTreeSet<_>>();
+        for action in actions {
+            match (old.get_action_id(action), new.get_action_id(action)) {
+                (Some(old_action), Some(new_action)) => {
+                    diff_action(old, new, old_action, new_action, &mut changes)
+                }
+                (Some(_), None) => changes.push(SchemaChange::ActionRemoved {
+                    action: action.clone(),
+                }),
+                (None, Some(_)) => changes.push(SchemaChange::ActionAdded {
+                    action: action.clone(),
+                }),
+                (None, None) => (),
+            }
+        }
+
+        SchemaChangeReport {
+            changes,
+            invalidated_policies: Vec::new(),
+        }
+    }
+
+    /// Compare two versions of a schema as [`ValidatorSchema::diff`] does,
+    /// additionally reporting which of `policies` validate against `old` but
+    /// not against `new` in strict mode.
+    pub fn diff_with_policies(
+        old: &ValidatorSchema,
+        new: &ValidatorSchema,
+        policies: &PolicySet,
+    ) -> SchemaChangeReport {
+        let mut report = Self::diff(old, new);
+        let old_invalid = invalid_policies(old, policies);
+        let new_invalid = invalid_policies(new, policies);
+        report.invalidated_policies = new_invalid
+            .difference(&old_invalid)
+            .map(|id| (*id).clone())
+            .collect();
+        report
+    }
+}
+
+/// Ids of the templates and policies in `policies` which do not validate
+/// against `schema` in strict mode
+fn invalid_policies<'a>(
+    schema: &ValidatorSchema,
+    policies: &'a PolicySet,
+) -> BTreeSet<&'a PolicyID> {
+    let validator = Validator::new(schema.clone());
+    let invalid_templates = policies
+        .all_templates()
+        .filter(|t| {
+            validator
+                .validate_policy(t, ValidationMode::Strict)
+                .0
+                .next()
+                .is_some()
+        })
+        .map(|t| t.id())
+        .collect::<HashSet<_>>();
+    let invalid_links = policies.policies().filter(|p| {
+        invalid_templates.contains(p.template().id())
+            || validator
+                .validate_slots(p, ValidationMode::Strict)
+                .is_some_and(|mut errs| errs.next().is_some())
+    });
+    invalid_links
+        .map(|p| p.id())
+        .chain(invalid_templates.iter().copied())
+        .collect()
+}
+
+/// The elements of `new` not in `old`, and the elements of `old` not in `new`
+fn set_diff<'a, T: Ord + Clone + 'a>(
+    old: impl Iterator<Item = &'a T>,
+    new: impl Iterator<Item = &'a T>,
+) -> (BTreeSet<T>, BTreeSet<T>) {
+    let old = old.collect::<BTreeSet<_>>();
+    let new = new.collect::<BTreeSet<_>>();
+    (
+        new.difference(&old).map(|t| (*t).clone()).collect(),
+        old.difference(&new).map(|t| (*t).clone()).collect(),
+    )
+}
+
+/// Is every value of type `old_type` also a value of type `new_type`?
+///
+/// This is not the validator's subtyping relation: a record type gaining an
+/// optional attribute widens it, since the records stored under the old
+/// schema remain valid, even though the old type is not a subtype of the new
+/// one.
+fn widens(old_type: &Type, new_type: &Type) -> bool {
+    match (old_type, new_type) {
+        (
+            Type::Set {
+                element_type: Some(old_elem),
+            },
+            Type::Set {
+                element_type: Some(new_elem),
+            },
+        ) => widens(old_elem, new_elem),
+        (
+            Type::EntityOrRecord(EntityRecordKind::Record {
+                attrs: old_attrs, ..
+            }),
+            Type::EntityOrRecord(EntityRecordKind::Record {
+                attrs: new_attrs, ..
+            }),
+        ) => {
+            old_attrs
+                .keys()
+                .all(|attr| new_attrs.get_attr(attr).is_some())
+                && new_attrs
+                    .iter()
+                    .all(|(attr, new_attr)| match old_attrs.get_attr(attr) {
+                        Some(old_attr) => {
+                            (old_attr.is_required || !new_attr.is_required)
+                                && widens(&old_attr.attr_type, &new_attr.attr_type)
+                        }
+                        None => !new_attr.is_required,
+                    })
+        }
+        (
+            Type::EntityOrRecord(EntityRecordKind::Entity(old_lub)),
+            Type::EntityOrRecord(EntityRecordKind::Entity(new_lub)),
+        ) => old_lub.iter().all(|ety| new_lub.contains(ety)),
+        _ => old_type == new_type,
+    }
+}
+
+fn diff_entity_type(
+    old: &ValidatorSchema,
+    new: &ValidatorSchema,
+    old_ety: &ValidatorEntityType,
+    new_ety: &ValidatorEntityType,
+    changes: &mut Vec<SchemaChange>,
+) {
+    let entity_type = old_ety.name();
+
+    match (&old_ety.kind, &new_ety.kind) {
+        (
+            ValidatorEntityTypeKind::Enum(old_choices),
+            ValidatorEntityTypeKind::Enum(new_choices),
+        ) => {
+            let (added, removed) = set_diff(old_choices.iter(), new_choices.iter());
+            if !added.is_empty() || !removed.is_empty() {
+                changes.push(SchemaChange::EnumChoicesChanged {
+                    entity_type: entity_type.clone(),
+                    added,
+                    removed,
+                });
+            }
+        }
+        (ValidatorEntityTypeKind::Standard(_), ValidatorEntityTypeKind::Standard(_)) => {
+            diff_attributes(
+                entity_type,
+                old_ety.attributes(),
+                new_ety.attributes(),
+                changes,
+            );
+            let (old_tags, new_tags) = (old_ety.tag_type(), new_ety.tag_type());
+            if old_tags != new_tags {
+                changes.push(SchemaChange::TagTypeChanged {
+                    entity_type: entity_type.clone(),
+                    old_type: old_tags.cloned(),
+                    new_type: new_tags.cloned(),
+                    widened: match (old_tags, new_tags) {
+                        (None, _) => true,
+                        (Some(_), None) => false,
+                        (Some(old_tags), Some(new_tags)) => widens(old_tags, new_tags),
+                    },
+                });
+            }
+        }
+        _ => changes.push(SchemaChange::EntityTypeKindChanged {
+            entity_type: entity_type.clone(),
+        }),
+    }
+
+    let ancestors = |schema: &ValidatorSchema| {
+        schema
+            .entity_types()
+            .filter(|ety| ety.has_descendant_entity_type(entity_type))
+            .map(|ety| ety.name().clone())
+            .collect::<BTreeSet<_>>()
+    };
+    let (old_ancestors, new_ancestors) = (ancestors(old), ancestors(new));
+    let added = new_ancestors
+        .difference(&old_ancestors)
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    // Ancestor types which no longer exist are reported as removed entity
+    // types instead
+    let removed = old_ancestors
+        .difference(&new_ancestors)
+        .filter(|ety| new.get_entity_type(ety).is_some())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(SchemaChange::MemberOfTypesChanged {
+            entity_type: entity_type.clone(),
+            added,
+            removed,
+        });
+    }
+}
+
+fn diff_action(
+    old: &ValidatorSchema,
+    new: &ValidatorSchema,
+    old_action: &ValidatorActionId,
+    new_action: &ValidatorActionId,
+    changes: &mut Vec<SchemaChange>,
+) {
+    let action = old_action.name();
+
+    let (principals_added, principals_removed) = set_diff(
+        old_action.applies_to_principals(),
+        new_action.applies_to_principals(),
+    );
+    let (resources_added, resources_removed) = set_diff(
+        old_action.applies_to_resources(),
+        new_action.applies_to_resources(),
+    );
+    if !(principals_added.is_empty()
+        && principals_removed.is_empty()
+        && resources_added.is_empty()
+        && resources_removed.is_empty())
+    {
+        changes.push(SchemaChange::AppliesToChanged {
+            action: action.clone(),
+            principals_added,
+            principals_removed,
+            resources_added,
+            resources_removed,
+        });
+    }
+
+    let (old_context, new_context) = (old_action.context_type(), new_action.context_type());
+    if old_context != new_context {
+        changes.push(SchemaChange::ContextTypeChanged {
+            action: action.clone(),
+            old_type: old_context.clone(),
+            new_type: new_context.clone(),
+            widened: widens(old_context, new_context),
+        });
+    }
+
+    let ancestors = |schema: &ValidatorSchema| {
+        schema
+            .action_ids()
+            .filter(|ancestor| ancestor.descendants.contains(action))
+            .map(|ancestor| ancestor.name().clone())
+            .collect::<BTreeSet<_>>()
+    };
+    let (old_ancestors, new_ancestors) = (ancestors(old), ancestors(new));
+    let added = new_ancestors
+        .difference(&old_ancestors)
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    // Ancestor actions which no longer exist are reported as removed actions
+    // instead
+    let removed = old_ancestors
+        .difference(&new_ancestors)
+        .filter(|ancestor| new.get_action_id(ancestor).is_some())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    if !added.is_empty() || !removed.is_empty() {
+        changes.push(SchemaChange::ActionMemberOfChanged {
+            action: action.clone(),
+            added,
+            removed,
+        });
+    }
+}
+
+fn diff_attributes(
+    entity_type: &EntityType,
+    old_attrs: &Attributes,
+    new_attrs: &Attributes,
+    changes: &mut Vec<SchemaChange>,
+) {
+    let attrs = old_attrs
+        .keys()
+        .chain(new_attrs.keys())
+        .collect::<BTreeSet<_>>();
+    for attr in attrs {
+        match (old_attrs.get_attr(attr), new_attrs.get_attr(attr)) {
+            (Some(old_attr), Some(new_attr)) => {
+                if old_attr.is_required && !new_attr.is_required {
+                    changes.push(SchemaChange::AttributeMadeOptional {
+                        entity_type: entity_type.clone(),
+                        attr: attr.clone(),
+                    });
+                } else if !old_attr.is_required && new_attr.is_required {
+                    changes.push(SchemaChange::AttributeMadeRequired {
+                        entity_type: entity_type.clone(),
+                        attr: attr.clone(),
+                    });
+                }
+                if old_attr.attr_type != new_attr.attr_type {
+                    changes.push(SchemaChange::AttributeTypeChanged {
+                        entity_type: entity_type.clone(),
+                        attr: attr.clone(),
+                        old_type: old_attr.attr_type.clone(),
+                        new_type: new_attr.attr_type.clone(),
+                        widened: widens(&old_attr.attr_type, &new_attr.attr_type),
+                    });
+                }
+            }
+            (Some(_), None) => changes.push(SchemaChange::AttributeRemoved {
+                entity_type: entity_type.clone(),
+                attr: attr.clone(),
+            }),
+            (None, Some(new_attr)) => changes.push(SchemaChange::AttributeAdded {
+                entity_type: entity_type.clone(),
+                attr: attr.clone(),
+                required: new_attr.is_required,
+            }),
+            (None, None) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cedar_policy_core::{extensions::Extensions, parser::parse_policyset};
+
+    use super::*;
+
+    fn schema(src: &str) -> ValidatorSchema {
+        ValidatorSchema::from_cedarschema_str(src, Extensions::all_available())
+            .unwrap()
+            .0
+    }
+
+    fn changes(report: &SchemaChangeReport) -> Vec<String> {
+        report
+            .changes()
+            .map(|c| {
+                let kind = if c.is_breaking() {
+                    "breaking"
+                } else {
+                    "non-breaking"
+                };
+                format!("{kind}: {c}")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical() {
+        let src = r#"
+            entity Group;
+            entity User in [Group] { name: String, age?: Long };
+            entity Color enum ["red", "blue"];
+            action view appliesTo { principal: [User], resource: [Group] };
+        "#;
+        let report = ValidatorSchema::diff(&schema(src), &schema(src));
+        assert_eq!(changes(&report), Vec::<String>::new());
+        assert!(!report.is_breaking());
+    }
+
+    #[test]
+    fn entity_types() {
+        let old = schema(
+            r#"
+            entity Group;
+            entity Team;
+            entity User in [Group, Team] {
+                name: String,
+                age?: Long,
+                email: String,
+                nickname?: String,
+                manager: User,
+            } tags String;
+            entity Color enum ["red", "blue"];
+            entity Doc;
+            "#,
+        );
+        let new = schema(
+            r#"
+            entity Group;
+            entity Team;
+            entity User in [Group] {
+                name: String,
+                age: Long,
+                email?: String,
+                manager: Set<User>,
+                title?: String,
+            };
+            entity Color enum ["red", "green"];
+            entity Folder;
+            "#,
+        );
+        let report = ValidatorSchema::diff(&old, &new);
+        assert_eq!(
+            changes(&report),
+            vec![
+                "breaking: enumerated entity type `Color` choices added: `green`; removed: `blue`",
+                "breaking: entity type `Doc` was removed",
+                "non-breaking: entity type `Folder` was added",
+                "breaking: attribute `age` of entity type `User` was made required",
+                "non-breaking: attribute `email` of entity type `User` was made optional",
+                "breaking: type of attribute `manager` of entity type `User` changed from `User` to `Set<User>`",
+                "breaking: attribute `nickname` was removed from entity type `User`",
+                "non-breaking: optional attribute `title` was added to entity type `User`",
+                "breaking: entity type `User` may no longer have tags",
+                "breaking: entity type `User` ancestor types removed: `Team`",
+            ]
+        );
+        assert!(report.is_breaking());
+    }
+
+    #[test]
+    fn actions() {
+        let old = schema(
+            r#"
+            entity User;
+            entity Doc;
+            entity Folder;
+            action view appliesTo { principal: [User], resource: [Doc] };
+            action edit appliesTo { principal: [User], resource: [Doc, Folder] };
+            action delete appliesTo { principal: [User], resource: [Doc] };
+            "#,
+        );
+        let new = schema(
+            r#"
+            entity User;
+            entity Doc;
+            entity Folder;
+            action view appliesTo { principal: [User], resource: [Doc, Folder] };
+            action edit appliesTo { principal: [User], resource: [Doc] };
+            action share appliesTo { principal: [User], resource: [Doc] };
+            "#,
+        );
+        let report = ValidatorSchema::diff(&old, &new);
+        assert_eq!(
+            changes(&report),
+            vec![
+                r#"breaking: action `Action::"delete"` was removed"#,
+                r#"breaking: action `Action::"edit"` resource types removed: `Folder`"#,
+                r#"non-breaking: action `Action::"share"` was added"#,
+                r#"non-breaking: action `Action::"view"` resource types added: `Folder`"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn action_context_and_member_of() {
+        let old = schema(
+            r#"
+            entity User;
+            action read;
+            action write;
+            action view in [read] appliesTo {
+                principal: [User], resource: [User], context: { ip: String, reason?: String }
+            };
+            action edit in [write] appliesTo {
+                principal: [User], resource: [User], context: { ip: String }
+            };
+            action share in [read] appliesTo {
+                principal: [User], resource: [User], context: { ip: String, note?: String }
+            };
+            "#,
+        );
+        let new = schema(
+            r#"
+            entity User;
+            action read;
+            action write;
+            action view in [read, write] appliesTo {
+                principal: [User], resource: [User], context: { ip: String, reason?: String, tag?: Long }
+            };
+            action edit appliesTo {
+                principal: [User], resource: [User], context: { ip: String, reason: String }
+            };
+            action share in [read] appliesTo {
+                principal: [User], resource: [User], context: { ip: String }
+            };
+            "#,
+        );
+        let report = ValidatorSchema::diff(&old, &new);
+        assert_eq!(
+            changes(&report),
+            vec![
+                r#"breaking: context type of action `Action::"edit"` changed from `{ip: String,}` to `{ip: String,reason: String,}`"#,
+                r#"breaking: action `Action::"edit"` ancestor actions removed: `Action::"write"`"#,
+                r#"breaking: context type of action `Action::"share"` changed from `{ip: String,note?: String,}` to `{ip: String,}`"#,
+                r#"non-breaking: context type of action `Action::"view"` changed from `{ip: String,reason?: String,}` to `{ip: String,reason?: String,tag?: Long,}`"#,
+                r#"non-breaking: action `Action::"view"` ancestor actions added: `Action::"write"`"#,
+            ]
+        );
+        assert!(report.is_breaking());
+    }
+
+    #[test]
+    fn widened_types() {
+        let old = schema(
+            r#"
+            entity User { profile: { a: Long } } tags Long;
+            "#,
+        );
+        let new = schema(
+            r#"
+            entity User { profile: { a: Long, b?: String } } tags Long;
+            "#,
+        );
+        let report = ValidatorSchema::diff(&old, &new);
+        assert_eq!(
+            changes(&report),
+            vec!["non-breaking: type of attribute `profile` of entity type `User` changed from `{a: Long,}` to `{a: Long,b?: String,}`"]
+        );
+    }
+
+    #[test]
+    fn invalidated_policies() {
+        let old = schema(
+            r#"
+            entity User { level: Long, dept: String };
+            entity Doc;
+            action view appliesTo { principal: [User], resource: [Doc] };
+            "#,
+        );
+        let new = schema(
+            r#"
+            entity User { level: Long, dept: Long };
+            entity Doc;
+            action view appliesTo { principal: [User], resource: [Doc] };
+            "#,
+        );
+        let policies = parse_policyset(
+            r#"
+            permit(principal, action, resource) when { principal.level > 3 };
+            permit(principal, action, resource) when { principal.dept like "eng*" };
+            permit(principal, action, resource) when { principal.missing };
+            "#,
+        )
+        .unwrap();
+        let report = ValidatorSchema::diff_with_policies(&old, &new, &policies);
+        assert_eq!(
+            report
+                .invalidated_policies()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["policy1"]
+        );
+        assert!(report.is_breaking());
+    }
+}
@@ -147,7 +147,7 @@ impl<'a> Typechecker<'a> {
         })
     }
 
-    fn single_env_typechecking<'b>(
+    pub(crate) fn single_env_typechecking<'b>(
         &self,
         request_env: &RequestEnv<'b>,
         policy_id: &PolicyID,
@@ -191,6 +191,39 @@ impl<'a> Typechecker<'a> {
         self.single_env_typechecking(request_env, t.id(), &t.condition())
     }
 
+    /// Type check an expression, which need not have type boolean, in every
+    /// request environment for the schema. For each environment, returns the
+    /// type of the expression, or the errors found while typechecking it.
+    ///
+    /// Results are returned in no particular order.
+    pub fn typecheck_expr_by_request_env(
+        &self,
+        expr: &Expr,
+    ) -> Vec<(RequestEnv<'a>, Result<Type, Vec<ValidationError>>)> {
+        let policy_id = PolicyID::from_string("expression");
+        self.unlinked_envs
+            .iter()
+            .map(|request_env| {
+                let mut type_errors = Vec::new();
+                let single_env_typechecker = SingleEnvTypechecker {
+                    schema: self.schema,
+                    extensions: self.extensions,
+                    mode: self.mode,
+                    policy_id: &policy_id,
+                    request_env,
+                };
+                let ans =
+                    single_env_typechecker.typecheck(&CapabilitySet::new(), expr, &mut type_errors);
+                let ty = if ans.typechecked() {
+                    ans.into_typed_expr().and_then(|e| e.into_data())
+                } else {
+                    None
+                };
+                (request_env.clone(), ty.ok_or(type_errors))
+            })
+            .collect()
+    }
+
     /// Apply `typecheck_fn` to the given policy in every schema-defined request
     /// environment, and collect all the results.
     ///
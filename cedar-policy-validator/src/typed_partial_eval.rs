@@ -0,0 +1,718 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Schema-aware simplification of partial-evaluation residuals.
+//!
+//! Partial evaluation works without types, so residuals keep `has` checks,
+//! `is` tests and branches that the schema already decides. Given the entity
+//! types of the unknown `principal` and `resource`, [`simplify_residuals`]
+//! typechecks each residual in that single request environment and replaces
+//! every subexpression whose value the typechecker determines with that
+//! value. Residuals that fail to typecheck are reported as validation errors,
+//! so the residuals that come back cannot produce type errors.
+//!
+//! Like validation, simplification assumes the entity data conforms to the
+//! schema. It further assumes the unknown `principal` and `resource` exist in
+//! the entity store, so `has` checks for their required attributes are
+//! dropped. A subexpression the typechecker proved to have a single value is
+//! only replaced by that value if evaluating it can't raise an error (e.g.,
+//! overflow), so the simplified residuals raise the same errors.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use cedar_policy_core::ast::{
+    Annotations, BinaryOp, EntityType, EntityUID, EntityUIDEntry, Expr, ExprBuilder, ExprKind,
+    Literal, PartialValue, PolicyID, UnaryOp, Unknown, Var,
+};
+use cedar_policy_core::authorizer::{ErrorState, PartialResponse};
+use cedar_policy_core::expr_builder::ExprBuilder as _;
+use miette::Diagnostic;
+use smol_str::SmolStr;
+use thiserror::Error;
+
+use crate::typecheck::{PolicyCheck, Typechecker};
+use crate::types::{EntityRecordKind, RequestEnv, Type};
+use crate::{ValidationError, ValidationMode, ValidationResult, ValidatorSchema};
+
+/// Errors when the request of a partial response cannot be matched to a
+/// single request environment of the schema
+//
+// CAUTION: this type is publicly exported in `cedar-policy`.
+// Don't make breaking changes, and use caution when adding public methods.
+#[derive(Debug, Clone, Error, Diagnostic, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TypedPartialRequestError {
+    /// The principal or resource is unknown, and its entity type is not known
+    /// either
+    #[error("the {var} of the request is unknown and has no entity type")]
+    UntypedVariable {
+        /// The request variable
+        var: Var,
+    },
+    /// The action is unknown
+    #[error("the action of the request must be known")]
+    UnknownAction,
+    /// The action is not declared in the schema
+    #[error("action `{action}` is not declared in the schema")]
+    UndeclaredAction {
+        /// The action
+        action: EntityUID,
+    },
+    /// The principal or resource type is not allowed for the action
+    #[error("`{ty}` is not a valid {var} type for action `{action}`")]
+    InvalidEntityType {
+        /// The request variable
+        var: Var,
+        /// The entity type of the variable
+        ty: EntityType,
+        /// The action
+        action: EntityUID,
+    },
+    /// A residual contains an unknown which does not stand for a request
+    /// variable
+    #[error("residual contains the unknown `{name}`, which is not a request variable")]
+    UnsupportedUnknown {
+        /// The name of the unknown
+        name: SmolStr,
+    },
+}
+
+/// An error generated by [`simplify_residuals`]
+#[derive(Debug, Error)]
+pub enum TypedPartialEvalError {
+    /// The request could not be typed
+    #[error(transparent)]
+    Request(#[from] TypedPartialRequestError),
+    /// Some residuals failed to typecheck
+    // TODO (#1158) impl Error for ValidationResult (it already is implemented for api::ValidationResult)
+    #[error("a residual policy failed to typecheck")]
+    Validation(ValidationResult),
+}
+
+/// Simplify the residuals of `response` using the types in `schema`.
+///
+/// The request of `response` must have a known action declared in `schema`,
+/// and the entity type of the principal and resource must be known (see
+/// [`EntityUIDEntry::unknown_with_type`]). The context may be unknown, in
+/// which case it has the context type of the action.
+///
+/// Residuals which simplify to `true` or `false` are moved to the satisfied
+/// or unsatisfied policies of the returned response, so it may reach a
+/// decision where `response` could not. All other residuals are replaced by
+/// their simplified form.
+///
+/// # Errors
+///
+/// Returns [`TypedPartialEvalError::Request`] if the request does not fit a
+/// request environment of `schema`, and [`TypedPartialEvalError::Validation`]
+/// with the errors for every residual which fails to typecheck.
+pub fn simplify_residuals(
+    schema: &ValidatorSchema,
+    response: &PartialResponse,
+) -> Result<PartialResponse, TypedPartialEvalError> {
+    let request = response.request();
+    let action = match request.action() {
+        EntityUIDEntry::Known { euid, .. } => euid.as_ref(),
+        EntityUIDEntry::Unknown { .. } => {
+            return Err(TypedPartialRequestError::UnknownAction.into())
+        }
+    };
+    let action_id =
+        schema
+            .get_action_id(action)
+            .ok_or_else(|| TypedPartialRequestError::UndeclaredAction {
+                action: action.clone(),
+            })?;
+    let principal = entity_type(request.principal(), Var::Principal)?;
+    if !action_id.is_applicable_principal_type(&principal) {
+        return Err(TypedPartialRequestError::InvalidEntityType {
+            var: Var::Principal,
+            ty: principal,
+            action: action.clone(),
+        }
+        .into());
+    }
+    let resource = entity_type(request.resource(), Var::Resource)?;
+    if !action_id.is_applicable_resource_type(&resource) {
+        return Err(TypedPartialRequestError::InvalidEntityType {
+            var: Var::Resource,
+            ty: resource,
+            action: action.clone(),
+        }
+        .into());
+    }
+
+    // What each request variable stands for: its value, or the unknown
+    // partial evaluation left in its place
+    let mut vars = HashMap::new();
+    for (var, entry) in [
+        (Var::Principal, request.principal()),
+        (Var::Action, request.action()),
+        (Var::Resource, request.resource()),
+    ] {
+        vars.insert(var, Expr::from(entry.evaluate(var)));
+    }
+    match request.context().cloned().map(PartialValue::from) {
+        Some(PartialValue::Value(context)) => {
+            vars.insert(Var::Context, Expr::from(context));
+        }
+        Some(PartialValue::Residual(_)) => (),
+        None => {
+            vars.insert(
+                Var::Context,
+                Expr::unknown(Unknown::new_untyped(Var::Context.to_string())),
+            );
+        }
+    }
+    for (residual, _) in response
+        .residual_permits
+        .values()
+        .chain(response.residual_forbids.values())
+    {
+        if let Some(unknown) = residual
+            .unknowns()
+            .find(|u| unknown_var(&vars, &u.name).is_none())
+        {
+            return Err(TypedPartialRequestError::UnsupportedUnknown {
+                name: unknown.name.clone(),
+            }
+            .into());
+        }
+    }
+
+    let simplifier = Simplifier {
+        schema,
+        typechecker: Typechecker::new(schema, ValidationMode::Permissive),
+        env: RequestEnv::DeclaredAction {
+            principal: &principal,
+            action,
+            resource: &resource,
+            context: &action_id.context,
+            principal_slot: None,
+            resource_slot: None,
+        },
+        vars,
+    };
+    let mut simplified = response.clone();
+    let mut errors = Vec::new();
+    simplifier.resolve(
+        &mut simplified.residual_permits,
+        &mut simplified.satisfied_permits,
+        &mut simplified.false_permits,
+        &mut errors,
+    );
+    simplifier.resolve(
+        &mut simplified.residual_forbids,
+        &mut simplified.satisfied_forbids,
+        &mut simplified.false_forbids,
+        &mut errors,
+    );
+    if errors.is_empty() {
+        Ok(simplified)
+    } else {
+        Err(TypedPartialEvalError::Validation(ValidationResult::new(
+            errors,
+            [],
+        )))
+    }
+}
+
+/// The entity type of a request variable
+fn entity_type(entry: &EntityUIDEntry, var: Var) -> Result<EntityType, TypedPartialRequestError> {
+    match entry {
+        EntityUIDEntry::Known { euid, .. } => Ok(euid.entity_type().clone()),
+        EntityUIDEntry::Unknown { ty: Some(ty), .. } => Ok(ty.clone()),
+        EntityUIDEntry::Unknown { ty: None, .. } => {
+            Err(TypedPartialRequestError::UntypedVariable { var })
+        }
+    }
+}
+
+/// The request variable which the unknown `name` stands for
+fn unknown_var(vars: &HashMap<Var, Expr>, name: &str) -> Option<Var> {
+    vars.iter().find_map(|(var, e)| match e.expr_kind() {
+        ExprKind::Unknown(u) if u.name == name => Some(*var),
+        _ => None,
+    })
+}
+
+/// Simplifies residuals in a single request environment
+struct Simplifier<'a> {
+    schema: &'a ValidatorSchema,
+    typechecker: Typechecker<'a>,
+    env: RequestEnv<'a>,
+    /// What each request variable stands for
+    vars: HashMap<Var, Expr>,
+}
+
+impl Simplifier<'_> {
+    /// Simplify each of `residuals`, moving those which simplify to a
+    /// constant into `satisfied` or `unsatisfied`. Residuals which fail to
+    /// typecheck are left unchanged, and their errors added to `errors`.
+    fn resolve(
+        &self,
+        residuals: &mut HashMap<PolicyID, (Arc<Expr>, Arc<Annotations>)>,
+        satisfied: &mut HashMap<PolicyID, Arc<Annotations>>,
+        unsatisfied: &mut HashMap<PolicyID, (ErrorState, Arc<Annotations>)>,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        for (id, (residual, annotations)) in std::mem::take(residuals) {
+            let bound = self.bind(&residual);
+            let simplified = match self
+                .typechecker
+                .single_env_typechecking(&self.env, &id, &bound)
+            {
+                PolicyCheck::Success(typed) => self.fold(&typed),
+                PolicyCheck::Irrelevant(errs, _) if errs.is_empty() => Expr::val(false),
+                PolicyCheck::Irrelevant(errs, _) | PolicyCheck::Fail(errs) => {
+                    errors.extend(errs);
+                    residuals.insert(id, (residual, annotations));
+                    continue;
+                }
+            };
+            match simplified.expr_kind() {
+                ExprKind::Lit(Literal::Bool(true)) => {
+                    satisfied.insert(id, annotations);
+                }
+                ExprKind::Lit(Literal::Bool(false)) => {
+                    unsatisfied.insert(id, (ErrorState::NoError, annotations));
+                }
+                _ => {
+                    residuals.insert(id, (Arc::new(simplified), annotations));
+                }
+            }
+        }
+    }
+
+    /// Replace the unknowns standing for request variables with the
+    /// variables, so the typechecker can give them a type
+    fn bind(&self, e: &Expr) -> Expr {
+        match e.expr_kind() {
+            ExprKind::Unknown(u) => match unknown_var(&self.vars, &u.name) {
+                Some(var) => Expr::var(var).with_maybe_source_loc(e.source_loc().cloned()),
+                None => e.clone(),
+            },
+            _ => map_subexpressions(e, |e| self.bind(e)),
+        }
+    }
+
+    /// Rebuild a typed expression, replacing every error-free subexpression
+    /// the typechecker gave a singleton boolean type with its value and
+    /// folding the boolean operators whose operands became constants
+    fn fold(&self, e: &Expr<Option<Type>>) -> Expr {
+        let builder = ExprBuilder::new().with_same_source_loc(e);
+        match (e.data(), e.expr_kind()) {
+            (Some(Type::True), _) if !self.may_error(e) => builder.val(true),
+            (Some(Type::False), _) if !self.may_error(e) => builder.val(false),
+            // The typechecker can't assume that an entity exists in the
+            // store, so it never proves `has` for a required entity
+            // attribute. The unknown principal and resource stand for
+            // entities that do exist, so we can.
+            (_, ExprKind::HasAttr { expr, attr }) if self.has_required_attr(expr, attr) => {
+                builder.val(true)
+            }
+            (_, ExprKind::Var(var)) => match self.vars.get(var) {
+                Some(e) => e.clone(),
+                None => builder.var(*var),
+            },
+            (
+                _,
+                ExprKind::If {
+                    test_expr,
+                    then_expr,
+                    else_expr,
+                },
+            ) => {
+                let test_expr = self.fold(test_expr);
+                match as_bool(&test_expr) {
+                    Some(true) => self.fold(then_expr),
+                    Some(false) => self.fold(else_expr),
+                    None => builder.ite(test_expr, self.fold(then_expr), self.fold(else_expr)),
+                }
+            }
+            (_, ExprKind::And { left, right }) => {
+                let left = self.fold(left);
+                match as_bool(&left) {
+                    Some(true) => self.fold(right),
+                    Some(false) => left,
+                    None => {
+                        let right = self.fold(right);
+                        match as_bool(&right) {
+                            Some(true) => left,
+                            _ => builder.and(left, right),
+                        }
+                    }
+                }
+            }
+            (_, ExprKind::Or { left, right }) => {
+                let left = self.fold(left);
+                match as_bool(&left) {
+                    Some(true) => left,
+                    Some(false) => self.fold(right),
+                    None => {
+                        let right = self.fold(right);
+                        match as_bool(&right) {
+                            Some(false) => left,
+                            _ => builder.or(left, right),
+                        }
+                    }
+                }
+            }
+            (
+                _,
+                ExprKind::UnaryApp {
+                    op: UnaryOp::Not,
+                    arg,
+                },
+            ) => {
+                let arg = self.fold(arg);
+                match as_bool(&arg) {
+                    Some(b) => builder.val(!b),
+                    None => builder.not(arg),
+                }
+            }
+            _ => map_subexpressions(e, |e| self.fold(e)),
+        }
+    }
+
+    /// Is `expr` the unknown principal or resource, or a record, with the
+    /// required attribute `attr`? Accessing the attribute can't fail if so.
+    fn has_required_attr(&self, expr: &Expr<Option<Type>>, attr: &str) -> bool {
+        let is_unknown_entity = matches!(
+            expr.expr_kind(),
+            ExprKind::Var(var @ (Var::Principal | Var::Resource))
+                if matches!(self.vars.get(var).map(Expr::expr_kind), Some(ExprKind::Unknown(_)))
+        );
+        expr.data().as_ref().is_some_and(|ty| {
+            (is_unknown_entity
+                || matches!(ty, Type::EntityOrRecord(EntityRecordKind::Record { .. })))
+                && Type::lookup_attribute_type(self.schema, ty, attr)
+                    .is_some_and(|attr_ty| attr_ty.is_required)
+        })
+    }
+
+    /// Could evaluating `e` raise an error, even though it typechecks? This
+    /// is conservative: arithmetic may overflow, extension functions may
+    /// fail, and attribute and tag accesses may find no entity or no value.
+    fn may_error(&self, e: &Expr<Option<Type>>) -> bool {
+        e.subexpressions().any(|e| match e.expr_kind() {
+            ExprKind::BinaryApp {
+                op: BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::GetTag,
+                ..
+            }
+            | ExprKind::UnaryApp {
+                op: UnaryOp::Neg, ..
+            }
+            | ExprKind::ExtensionFunctionApp { .. } => true,
+            ExprKind::GetAttr { expr, attr } => !self.has_required_attr(expr, attr),
+            _ => false,
+        })
+    }
+}
+
+/// The value of `e`, if it is a boolean literal
+fn as_bool(e: &Expr) -> Option<bool> {
+    match e.expr_kind() {
+        ExprKind::Lit(Literal::Bool(b)) => Some(*b),
+        _ => None,
+    }
+}
+
+/// Rebuild `e` as an untyped expression with the same source location,
+/// applying `f` to each of its immediate subexpressions
+fn map_subexpressions<T>(e: &Expr<T>, mut f: impl FnMut(&Expr<T>) -> Expr) -> Expr {
+    let kind = match e.expr_kind() {
+        ExprKind::Lit(lit) => ExprKind::Lit(lit.clone()),
+        ExprKind::Var(var) => ExprKind::Var(*var),
+        ExprKind::Slot(slot) => ExprKind::Slot(*slot),
+        ExprKind::Unknown(u) => ExprKind::Unknown(u.clone()),
+        ExprKind::If {
+            test_expr,
+            then_expr,
+            else_expr,
+        } => ExprKind::If {
+            test_expr: Arc::new(f(test_expr)),
+            then_expr: Arc::new(f(then_expr)),
+            else_expr: Arc::new(f(else_expr)),
+        },
+        ExprKind::And { left, right } => ExprKind::And {
+            left: Arc::new(f(left)),
+            right: Arc::new(f(right)),
+        },
+        ExprKind::Or { left, right } => ExprKind::Or {
+            left: Arc::new(f(left)),
+            right: Arc::new(f(right)),
+        },
+        ExprKind::UnaryApp { op, arg } => ExprKind::UnaryApp {
+            op: *op,
+            arg: Arc::new(f(arg)),
+        },
+        ExprKind::BinaryApp { op, arg1, arg2 } => ExprKind::BinaryApp {
+            op: *op,
+            arg1: Arc::new(f(arg1)),
+            arg2: Arc::new(f(arg2)),
+        },
+        ExprKind::ExtensionFunctionApp { fn_name, args } => ExprKind::ExtensionFunctionApp {
+            fn_name: fn_name.clone(),
+            args: Arc::new(args.iter().map(f).collect()),
+        },
+        ExprKind::GetAttr { expr, attr } => ExprKind::GetAttr {
+            expr: Arc::new(f(expr)),
+            attr: attr.clone(),
+        },
+        ExprKind::HasAttr { expr, attr } => ExprKind::HasAttr {
+            expr: Arc::new(f(expr)),
+            attr: attr.clone(),
+        },
+        ExprKind::Like { expr, pattern } => ExprKind::Like {
+            expr: Arc::new(f(expr)),
+            pattern: pattern.clone(),
+        },
+        ExprKind::Is { expr, entity_type } => ExprKind::Is {
+            expr: Arc::new(f(expr)),
+            entity_type: entity_type.clone(),
+        },
+        ExprKind::Set(elems) => ExprKind::Set(Arc::new(elems.iter().map(f).collect())),
+        ExprKind::Record(fields) => ExprKind::Record(Arc::new(
+            fields.iter().map(|(k, v)| (k.clone(), f(v))).collect(),
+        )),
+        #[cfg(feature = "tolerant-ast")]
+        ExprKind::Error { error_kind } => ExprKind::Error {
+            error_kind: error_kind.clone(),
+        },
+    };
+    ExprBuilder::new()
+        .with_same_source_loc(e)
+        .with_expr_kind(kind)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cedar_policy_core::ast::{Context, ExprShapeOnly, Request, RequestSchemaAllPass};
+    use cedar_policy_core::authorizer::{Authorizer, Decision};
+    use cedar_policy_core::entities::Entities;
+    use cedar_policy_core::extensions::Extensions;
+    use cedar_policy_core::parser::parse_policyset;
+    use cool_asserts::assert_matches;
+
+    fn schema() -> ValidatorSchema {
+        ValidatorSchema::from_cedarschema_str(
+            r#"
+            entity User { age: Long, nickname?: String };
+            entity Team;
+            entity Doc in [Team] { owner: User, draft: Bool, size: Long, title?: String };
+            action view appliesTo {
+                principal: [User],
+                resource: [Doc],
+                context: { mfa: Bool }
+            };
+            "#,
+            Extensions::all_available(),
+        )
+        .unwrap()
+        .0
+    }
+
+    fn uid(s: &str) -> EntityUID {
+        s.parse().unwrap()
+    }
+
+    /// Partially evaluate `policies` for `User::"alice"` viewing an unknown
+    /// `Doc`, with the given context
+    fn partial_response(policies: &str, context: Option<Context>) -> PartialResponse {
+        let request = Request::new_with_unknowns::<RequestSchemaAllPass>(
+            EntityUIDEntry::known(uid(r#"User::"alice""#), None),
+            EntityUIDEntry::known(uid(r#"Action::"view""#), None),
+            EntityUIDEntry::unknown_with_type("Doc".parse().unwrap(), None),
+            context,
+            None,
+            Extensions::none(),
+        )
+        .unwrap();
+        let policies = parse_policyset(policies).unwrap();
+        Authorizer::new().is_authorized_core(request, &policies, &Entities::new())
+    }
+
+    fn simplified_residual(policies: &str) -> Expr {
+        let response = partial_response(policies, Some(Context::empty()));
+        let simplified = simplify_residuals(&schema(), &response).unwrap();
+        let residual = simplified
+            .residual_permits
+            .values()
+            .next()
+            .expect("expected a residual");
+        residual.0.as_ref().clone()
+    }
+
+    fn resource() -> Expr {
+        Expr::unknown(Unknown::new_with_type(
+            "resource",
+            cedar_policy_core::ast::Type::Entity {
+                ty: "Doc".parse().unwrap(),
+            },
+        ))
+    }
+
+    #[test]
+    fn required_attributes() {
+        let residual = simplified_residual(
+            r#"permit(principal, action, resource) when {
+                resource has owner && resource has draft && resource.owner == principal
+            };"#,
+        );
+        assert_eq!(
+            ExprShapeOnly::new_from_owned(residual),
+            ExprShapeOnly::new_from_owned(Expr::is_eq(
+                Expr::get_attr(resource(), "owner".into()),
+                Expr::val(uid(r#"User::"alice""#)),
+            ))
+        );
+    }
+
+    #[test]
+    fn optional_attributes() {
+        let residual = simplified_residual(
+            r#"permit(principal, action, resource) when {
+                resource has title && resource.title == "draft"
+            };"#,
+        );
+        assert_eq!(
+            ExprShapeOnly::new_from_owned(residual),
+            ExprShapeOnly::new_from_owned(Expr::and(
+                Expr::has_attr(resource(), "title".into()),
+                Expr::is_eq(
+                    Expr::get_attr(resource(), "title".into()),
+                    Expr::val("draft")
+                ),
+            ))
+        );
+    }
+
+    #[test]
+    fn impossible_branches() {
+        let residual = simplified_residual(
+            r#"permit(principal, action, resource) when {
+                if resource.owner is Team then resource.owner in resource else resource.draft
+            };"#,
+        );
+        assert_eq!(
+            ExprShapeOnly::new_from_owned(residual),
+            ExprShapeOnly::new_from_owned(Expr::get_attr(resource(), "draft".into()))
+        );
+    }
+
+    #[test]
+    fn erroring_operands() {
+        // The typechecker proves this is `true`, but it is an overflow error
+        // when `resource.size` is the largest `Long`, so it isn't folded
+        let residual = simplified_residual(
+            r#"permit(principal, action, resource) when {
+                resource.size + 1 > 0 || true
+            };"#,
+        );
+        assert_eq!(
+            ExprShapeOnly::new_from_owned(residual),
+            ExprShapeOnly::new_from_owned(Expr::or(
+                Expr::greater(
+                    Expr::add(Expr::get_attr(resource(), "size".into()), Expr::val(1)),
+                    Expr::val(0)
+                ),
+                Expr::val(true)
+            ))
+        );
+        // Error-free operands are still folded
+        let residual = simplified_residual(
+            r#"permit(principal, action, resource) when {
+                resource.size > 0 && (resource.draft || true)
+            };"#,
+        );
+        assert_eq!(
+            ExprShapeOnly::new_from_owned(residual),
+            ExprShapeOnly::new_from_owned(Expr::greater(
+                Expr::get_attr(resource(), "size".into()),
+                Expr::val(0)
+            ))
+        );
+    }
+
+    #[test]
+    fn decisions() {
+        let response = partial_response(
+            r#"
+            permit(principal, action, resource) when { resource has owner };
+            forbid(principal, action, resource) when { resource in Team::"t" && resource is User };
+            "#,
+            Some(Context::empty()),
+        );
+        assert_eq!(response.decision(), None);
+        let simplified = simplify_residuals(&schema(), &response).unwrap();
+        assert_eq!(simplified.decision(), Some(Decision::Allow));
+        assert!(simplified.residual_permits.is_empty());
+        assert!(simplified.residual_forbids.is_empty());
+    }
+
+    #[test]
+    fn unknown_context() {
+        let response = partial_response(
+            r#"permit(principal, action, resource) when { context has mfa && context.mfa };"#,
+            None,
+        );
+        let simplified = simplify_residuals(&schema(), &response).unwrap();
+        let residual = simplified.residual_permits.values().next().unwrap();
+        assert_eq!(
+            ExprShapeOnly::new_from_borrowed(residual.0.as_ref()),
+            ExprShapeOnly::new_from_owned(Expr::get_attr(
+                Expr::unknown(Unknown::new_untyped("context")),
+                "mfa".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn ill_typed_residual() {
+        let response = partial_response(
+            r#"permit(principal, action, resource) when { resource.title == "x" };"#,
+            Some(Context::empty()),
+        );
+        assert_matches!(
+            simplify_residuals(&schema(), &response),
+            Err(TypedPartialEvalError::Validation(result)) => {
+                assert_eq!(result.validation_errors().count(), 1);
+            }
+        );
+    }
+
+    #[test]
+    fn request_errors() {
+        let response = partial_response(
+            r#"permit(principal, action, resource) when { resource.draft };"#,
+            Some(Context::empty()),
+        );
+        let (other, _) = ValidatorSchema::from_cedarschema_str(
+            "entity User; entity Doc; action edit appliesTo { principal: [User], resource: [Doc] };",
+            Extensions::all_available(),
+        )
+        .unwrap();
+        assert_matches!(
+            simplify_residuals(&other, &response),
+            Err(TypedPartialEvalError::Request(
+                TypedPartialRequestError::UndeclaredAction { .. }
+            ))
+        );
+    }
+}
@@ -0,0 +1,370 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Contains the lint finding schema elements which no policy in a policy set
+//! uses.
+//!
+//! The lint works on the type-annotated policies produced by the typechecker,
+//! so an attribute access is attributed to exactly the entity types the
+//! accessed expression may have in each request environment.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use cedar_policy_core::ast::{
+    BinaryOp, Effect, EntityType, EntityUID, Expr, ExprKind, InternalName, PolicySet,
+};
+use smol_str::{SmolStr, ToSmolStr};
+
+use crate::typecheck::{PolicyCheck, Typechecker};
+use crate::types::{EntityRecordKind, Type};
+use crate::{ValidationMode, Validator};
+
+/// A schema element whose declared type refers to a common type
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum CommonTypeUse {
+    /// The type of an attribute of an entity type, or of its whole shape when
+    /// `attr` is `None`
+    Attribute {
+        entity_type: EntityType,
+        attr: Option<SmolStr>,
+    },
+    /// The tag type of an entity type
+    Tags(EntityType),
+    /// The context type of an action
+    Context(EntityUID),
+    /// The definition of another common type
+    CommonType(InternalName),
+}
+
+/// Schema elements which no policy in a policy set uses, as computed by
+/// [`Validator::unused_schema_elements`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaUsageReport {
+    unused_entity_types: BTreeSet<EntityType>,
+    unused_attributes: BTreeSet<(EntityType, SmolStr)>,
+    unused_tags: BTreeSet<EntityType>,
+    unused_actions: BTreeSet<EntityUID>,
+    unpermitted_actions: BTreeSet<EntityUID>,
+    unused_common_types: BTreeSet<SmolStr>,
+}
+
+impl SchemaUsageReport {
+    /// Entity types which are neither the principal or resource type of a
+    /// request any policy applies to, nor the type of any expression in a
+    /// policy
+    pub fn unused_entity_types(&self) -> impl Iterator<Item = &EntityType> {
+        self.unused_entity_types.iter()
+    }
+
+    /// Attributes of entity types which no policy reads or tests for with
+    /// `has`
+    pub fn unused_attributes(&self) -> impl Iterator<Item = (&EntityType, &SmolStr)> {
+        self.unused_attributes.iter().map(|(ety, attr)| (ety, attr))
+    }
+
+    /// Entity types declaring a tag type whose tags no policy reads or tests
+    /// for with `hasTag`
+    pub fn unused_tags(&self) -> impl Iterator<Item = &EntityType> {
+        self.unused_tags.iter()
+    }
+
+    /// Actions which may appear in a request but no policy applies to
+    pub fn unused_actions(&self) -> impl Iterator<Item = &EntityUID> {
+        self.unused_actions.iter()
+    }
+
+    /// Actions which some `forbid` policy applies to, but no `permit` policy
+    /// does, so that every request for them is denied
+    pub fn unpermitted_actions(&self) -> impl Iterator<Item = &EntityUID> {
+        self.unpermitted_actions.iter()
+    }
+
+    /// Common types which are not part of the type of any used attribute or
+    /// tag, nor of the context of any used action
+    pub fn unused_common_types(&self) -> impl Iterator<Item = &SmolStr> {
+        self.unused_common_types.iter()
+    }
+
+    /// Whether every schema element is used
+    pub fn is_empty(&self) -> bool {
+        self.unused_entity_types.is_empty()
+            && self.unused_attributes.is_empty()
+            && self.unused_tags.is_empty()
+            && self.unused_actions.is_empty()
+            && self.unpermitted_actions.is_empty()
+            && self.unused_common_types.is_empty()
+    }
+}
+
+/// The schema elements used by a policy set
+#[derive(Debug, Default)]
+struct Usage {
+    entity_types: HashSet<EntityType>,
+    attributes: HashSet<(EntityType, SmolStr)>,
+    tags: HashSet<EntityType>,
+    actions: HashSet<EntityUID>,
+    permitted_actions: HashSet<EntityUID>,
+}
+
+impl Usage {
+    fn record_expr(&mut self, expr: &Expr<Option<Type>>) {
+        for e in expr.subexpressions() {
+            if let Some(ty) = e.data() {
+                self.record_type(ty);
+            }
+            match e.expr_kind() {
+                ExprKind::GetAttr { expr, attr } | ExprKind::HasAttr { expr, attr } => {
+                    for ety in entity_types_of(expr) {
+                        self.attributes.insert((ety.clone(), attr.clone()));
+                    }
+                }
+                ExprKind::BinaryApp {
+                    op: BinaryOp::GetTag | BinaryOp::HasTag,
+                    arg1,
+                    ..
+                } => {
+                    self.tags.extend(entity_types_of(arg1).cloned());
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn record_type(&mut self, ty: &Type) {
+        match ty {
+            Type::EntityOrRecord(EntityRecordKind::Entity(lub)) => {
+                self.entity_types.extend(lub.iter().cloned());
+            }
+            Type::Set {
+                element_type: Some(element_type),
+            } => self.record_type(element_type),
+            _ => (),
+        }
+    }
+
+    fn uses_common_type(
+        &self,
+        name: &InternalName,
+        common_type_uses: &HashMap<InternalName, Vec<CommonTypeUse>>,
+    ) -> bool {
+        common_type_uses.get(name).is_some_and(|uses| {
+            uses.iter().any(|u| match u {
+                CommonTypeUse::Attribute {
+                    entity_type,
+                    attr: Some(attr),
+                } => self
+                    .attributes
+                    .contains(&(entity_type.clone(), attr.clone())),
+                CommonTypeUse::Attribute {
+                    entity_type,
+                    attr: None,
+                } => self.attributes.iter().any(|(ety, _)| ety == entity_type),
+                CommonTypeUse::Tags(entity_type) => self.tags.contains(entity_type),
+                CommonTypeUse::Context(action) => self.actions.contains(action),
+                CommonTypeUse::CommonType(other) => self.uses_common_type(other, common_type_uses),
+            })
+        })
+    }
+}
+
+/// The entity types `expr` may evaluate to, according to its type annotation
+fn entity_types_of(expr: &Expr<Option<Type>>) -> impl Iterator<Item = &EntityType> {
+    match expr.data() {
+        Some(Type::EntityOrRecord(EntityRecordKind::Entity(lub))) => Some(lub.iter()),
+        _ => None,
+    }
+    .into_iter()
+    .flatten()
+}
+
+impl Validator {
+    /// Find the entity types, attributes, tags, actions and common types in
+    /// the schema which no policy in `policies` uses, along with the actions
+    /// which no `permit` policy applies to.
+    ///
+    /// Policies are typechecked in strict mode. Request environments in which
+    /// a policy fails to typecheck or can never apply contribute nothing, so
+    /// `policies` should validate against the schema for the report to be
+    /// meaningful.
+    pub fn unused_schema_elements(&self, policies: &PolicySet) -> SchemaUsageReport {
+        let typechecker = Typechecker::new(&self.schema, ValidationMode::Strict);
+        let mut usage = Usage::default();
+        for template in policies.all_templates() {
+            for (env, check) in typechecker.typecheck_by_request_env(template) {
+                let PolicyCheck::Success(expr) = check else {
+                    continue;
+                };
+                usage
+                    .entity_types
+                    .extend(env.principal_entity_type().cloned());
+                usage
+                    .entity_types
+                    .extend(env.resource_entity_type().cloned());
+                if let Some(action) = env.action_entity_uid() {
+                    usage.actions.insert(action.clone());
+                    if template.effect() == Effect::Permit {
+                        usage.permitted_actions.insert(action.clone());
+                    }
+                }
+                usage.record_expr(&expr);
+            }
+        }
+
+        let requestable_actions = self
+            .schema
+            .action_ids()
+            .filter(|action| {
+                action.applies_to_principals().next().is_some()
+                    && action.applies_to_resources().next().is_some()
+            })
+            .map(|action| action.name());
+        let mut report = SchemaUsageReport::default();
+        for action in requestable_actions {
+            if !usage.actions.contains(action) {
+                report.unused_actions.insert(action.clone());
+            } else if !usage.permitted_actions.contains(action) {
+                report.unpermitted_actions.insert(action.clone());
+            }
+        }
+        for ety in self.schema.entity_types() {
+            if !usage.entity_types.contains(ety.name()) {
+                report.unused_entity_types.insert(ety.name().clone());
+            }
+            for attr in ety.attributes().keys() {
+                if !usage
+                    .attributes
+                    .contains(&(ety.name().clone(), attr.clone()))
+                {
+                    report
+                        .unused_attributes
+                        .insert((ety.name().clone(), attr.clone()));
+                }
+            }
+            if ety.tag_type().is_some() && !usage.tags.contains(ety.name()) {
+                report.unused_tags.insert(ety.name().clone());
+            }
+        }
+        report.unused_common_types = self
+            .schema
+            .common_type_uses
+            .keys()
+            .filter(|name| !usage.uses_common_type(name, &self.schema.common_type_uses))
+            .map(|name| name.to_smolstr())
+            .collect();
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cedar_policy_core::{extensions::Extensions, parser::parse_policyset};
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::ValidatorSchema;
+
+    fn report(policies: &str) -> SchemaUsageReport {
+        let (schema, _) = ValidatorSchema::from_cedarschema_str(
+            r#"
+            type Address = { street: String, city: String };
+            type Audit = { by: User };
+            type Unused = Long;
+            entity Group;
+            entity User in [Group] {
+                name: String,
+                level: Long,
+                manager?: User,
+                address: Address,
+            } tags String;
+            entity Doc { owner: User, audit: Audit, public: Bool };
+            entity Folder;
+            action view appliesTo { principal: [User], resource: [Doc] };
+            action edit appliesTo { principal: [User], resource: [Doc] };
+            action share appliesTo { principal: [User], resource: [Doc, Folder] };
+            action all;
+            "#,
+            Extensions::all_available(),
+        )
+        .unwrap();
+        Validator::new(schema).unused_schema_elements(&parse_policyset(policies).unwrap())
+    }
+
+    fn strings<T: ToString>(items: impl Iterator<Item = T>) -> Vec<String> {
+        items.map(|i| i.to_string()).sorted().collect()
+    }
+
+    #[test]
+    fn attributes_and_types() {
+        let report = report(
+            r#"
+            permit(principal, action == Action::"view", resource)
+            when { (resource.owner has manager && resource.owner.manager.level > 3) || resource.public };
+            permit(principal, action == Action::"edit", resource)
+            when { principal.address.city == "Seattle" && principal.hasTag("team") };
+            "#,
+        );
+        assert_eq!(strings(report.unused_entity_types()), ["Folder", "Group"]);
+        assert_eq!(
+            strings(
+                report
+                    .unused_attributes()
+                    .map(|(ety, attr)| format!("{ety}.{attr}"))
+            ),
+            ["Doc.audit", "User.name"]
+        );
+        assert_eq!(strings(report.unused_tags()), Vec::<String>::new());
+        assert_eq!(strings(report.unused_actions()), [r#"Action::"share""#]);
+        assert_eq!(strings(report.unpermitted_actions()), Vec::<String>::new());
+        assert_eq!(strings(report.unused_common_types()), ["Audit", "Unused"]);
+    }
+
+    #[test]
+    fn only_typed_accesses_count() {
+        // `principal.level` is only accessed in request environments where
+        // the forbid policy cannot apply, so `level` is unused
+        let report = report(
+            r#"
+            permit(principal, action, resource is Folder)
+            when { principal in Group::"admins" };
+            forbid(principal, action == Action::"edit", resource)
+            when { resource is Folder && principal.level > 3 };
+            "#,
+        );
+        assert_eq!(strings(report.unused_entity_types()), ["Doc"]);
+        assert!(report
+            .unused_attributes()
+            .any(|(ety, attr)| ety.to_string() == "User" && attr == "level"));
+        assert_eq!(strings(report.unused_tags()), ["User"]);
+        assert_eq!(
+            strings(report.unused_actions()),
+            [r#"Action::"edit""#, r#"Action::"view""#]
+        );
+        assert_eq!(strings(report.unpermitted_actions()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn dead_permissions() {
+        let report = report(
+            r#"
+            permit(principal, action in [Action::"view", Action::"share"], resource);
+            forbid(principal, action == Action::"edit", resource) unless { resource.public };
+            "#,
+        );
+        assert_eq!(strings(report.unused_actions()), Vec::<String>::new());
+        assert_eq!(strings(report.unpermitted_actions()), [r#"Action::"edit""#]);
+        assert!(!report.is_empty());
+    }
+}
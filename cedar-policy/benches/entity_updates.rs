@@ -0,0 +1,114 @@
+// PANIC SAFETY: it's ok for benchmarking code to panic
+#![allow(clippy::unwrap_used)]
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use cedar_policy::{Entities, Entity, EntityUid};
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+fn uid(ty: &str, id: usize) -> EntityUid {
+    EntityUid::from_str(&format!("{ty}::\"{id}\"")).unwrap()
+}
+
+/// A store with `users` users, split into groups of `group_size` members,
+/// with all groups in one organization
+fn store(users: usize, group_size: usize) -> Entities {
+    let org = Entity::new_no_attrs(uid("Org", 0), HashSet::new());
+    let groups = (0..users.div_ceil(group_size))
+        .map(|g| Entity::new_no_attrs(uid("Group", g), HashSet::from([org.uid()])));
+    let users = (0..users).map(|u| {
+        Entity::new_no_attrs(
+            uid("User", u),
+            HashSet::from([uid("Group", u / group_size)]),
+        )
+    });
+    Entities::from_entities(
+        std::iter::once(org.clone()).chain(groups).chain(users),
+        None,
+    )
+    .unwrap()
+}
+
+/// Adding one user to a second group only affects that user, so the cost
+/// shouldn't depend on the size of the store
+fn add_membership(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Add one group membership");
+    for users in [1_000, 10_000, 100_000] {
+        let entities = store(users, 100);
+        let user = Entity::new_no_attrs(
+            uid("User", 0),
+            HashSet::from([uid("Group", 0), uid("Group", 1)]),
+        );
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{users} users")),
+            &users,
+            |b, _| {
+                b.iter_batched(
+                    || entities.clone(),
+                    |entities| {
+                        entities
+                            .upsert_entities([black_box(user.clone())], None)
+                            .unwrap()
+                    },
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Moving a group to another organization affects the group and its members,
+/// so the cost should grow with the size of the group rather than the store
+fn move_group(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Move one group");
+    for group_size in [10, 100, 1_000, 10_000] {
+        let entities = store(100_000, group_size);
+        let moved = Entity::new_no_attrs(uid("Group", 0), HashSet::from([uid("Org", 1)]));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{group_size} members")),
+            &group_size,
+            |b, _| {
+                b.iter_batched(
+                    || entities.clone(),
+                    |entities| {
+                        entities
+                            .upsert_entities([black_box(moved.clone())], None)
+                            .unwrap()
+                    },
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Removing a group affects only its members
+fn remove_group(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Remove one group");
+    for group_size in [10, 100, 1_000, 10_000] {
+        let entities = store(100_000, group_size);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{group_size} members")),
+            &group_size,
+            |b, _| {
+                b.iter_batched(
+                    || entities.clone(),
+                    |entities| {
+                        entities
+                            .remove_entities([black_box(uid("Group", 0))])
+                            .unwrap()
+                    },
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, add_membership, move_group, remove_group);
+criterion_main!(benches);
@@ -37,20 +37,31 @@ pub use id::*;
 #[cfg(feature = "deprecated-schema-compat")]
 mod deprecated_schema_compat;
 
+#[cfg(feature = "entity-manifest")]
+mod entity_loader;
+#[cfg(feature = "entity-manifest")]
+pub use entity_loader::*;
+
 mod err;
 pub use err::*;
 
+mod audit;
+pub use audit::*;
+
 pub use ast::Effect;
-pub use authorizer::Decision;
+pub use authorizer::{Decision, ErrorHandling};
 #[cfg(feature = "partial-eval")]
 use cedar_policy_core::ast::BorrowedRestrictedExpr;
 use cedar_policy_core::ast::{self, RestrictedExpr};
 use cedar_policy_core::authorizer;
-use cedar_policy_core::entities::{ContextSchema, Dereference};
+pub use cedar_policy_core::entities::AncestorMode;
+use cedar_policy_core::entities::{ContextSchema, Dereference, StoredEntity};
 use cedar_policy_core::est::{self, TemplateLink};
-use cedar_policy_core::evaluator::Evaluator;
+pub use cedar_policy_core::evaluator::ExprCoverage;
 #[cfg(feature = "partial-eval")]
 use cedar_policy_core::evaluator::RestrictedEvaluator;
+use cedar_policy_core::evaluator::{self, EvaluationCache, Evaluator};
+pub use cedar_policy_core::evaluator::{EvaluationLimits, ExceededLimit};
 use cedar_policy_core::extensions::Extensions;
 use cedar_policy_core::parser;
 use cedar_policy_core::FromNormalizedStr;
@@ -59,6 +70,7 @@ use miette::Diagnostic;
 use ref_cast::RefCast;
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
+use std::cell::RefCell;
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::io::Read;
 use std::str::FromStr;
@@ -365,7 +377,7 @@ impl AsRef<cedar_policy_core::entities::Entities> for Entities {
     }
 }
 
-use entities_errors::EntitiesError;
+use entities_errors::{EntitiesError, EntityStoreError};
 
 impl Entities {
     /// Create a fresh `Entities` with no entities
@@ -395,6 +407,40 @@ impl Entities {
         Self(self.0.partial())
     }
 
+    /// Change how this `Entities` keeps the ancestors of its entities. See
+    /// [`AncestorMode`]; authorization results are the same in either mode.
+    ///
+    /// Converting a store which already has many entities is expensive, so
+    /// set the mode on an empty store and then add the entities to it:
+    /// ```
+    /// # use cedar_policy::{AncestorMode, Entities, EntityUid};
+    /// # use std::str::FromStr;
+    /// let entities = Entities::empty()
+    ///     .with_ancestor_mode(AncestorMode::OnDemand)
+    ///     .unwrap()
+    ///     .add_entities_from_json_str(
+    ///         r#"[
+    ///             { "uid": { "type": "User", "id": "alice" }, "attrs": {}, "parents": [{ "type": "Group", "id": "eng" }] },
+    ///             { "uid": { "type": "Group", "id": "eng" }, "attrs": {}, "parents": [{ "type": "Group", "id": "all" }] }
+    ///         ]"#,
+    ///         None,
+    ///     )
+    ///     .unwrap();
+    /// let alice = EntityUid::from_str(r#"User::"alice""#).unwrap();
+    /// let all = EntityUid::from_str(r#"Group::"all""#).unwrap();
+    /// assert!(entities.is_ancestor_of(&all, &alice));
+    /// ```
+    /// ## Errors
+    /// - [`EntitiesError::TransitiveClosureError`] if the hierarchy has a cycle
+    pub fn with_ancestor_mode(self, mode: AncestorMode) -> Result<Self, EntitiesError> {
+        Ok(Self(self.0.with_ancestor_mode(mode)?))
+    }
+
+    /// How this `Entities` keeps the ancestors of its entities
+    pub fn ancestor_mode(&self) -> AncestorMode {
+        self.0.ancestor_mode()
+    }
+
     /// Iterate over the `Entity`'s in the `Entities`
     pub fn iter(&self) -> impl Iterator<Item = &Entity> {
         self.0.iter().map(Entity::ref_cast)
@@ -442,8 +488,9 @@ impl Entities {
     /// required attributes are missing or superfluous attributes are provided.
     /// (This method will not add action entities from the `schema`.)
     ///
-    /// Re-computing the transitive closure can be expensive, so it is advised
-    /// to not call this method in a loop.
+    /// Only the transitive closure of the added entities and their
+    /// descendants is re-computed, so the cost depends on the size of that
+    /// part of the hierarchy rather than on the size of this [`Entities`].
     /// ## Errors
     /// - [`EntitiesError::Duplicate`] if there is a pair of non-identical entities in `entities` with the same Entity UID,
     ///   or there is an entity in `entities` with the same Entity UID as a non-identical entity in this structure
@@ -470,8 +517,9 @@ impl Entities {
     /// from this [`Entities`] structure, re-computing the transitive
     /// closure after removing all edges to/from the removed entities.
     ///
-    /// Re-computing the transitive closure can be expensive, so it is
-    /// advised to not call this method in a loop.
+    /// Only the transitive closure of the descendants of the removed entities
+    /// is re-computed, so the cost depends on the size of that part of the
+    /// hierarchy rather than on the size of this [`Entities`].
     pub fn remove_entities(
         self,
         entity_ids: impl IntoIterator<Item = EntityUid>,
@@ -491,8 +539,9 @@ impl Entities {
     /// required attributes are missing or superfluous attributes are provided.
     /// (This method will not add action entities from the `schema`.)
     ///
-    /// Re-computing the transitive closure can be expensive, so it is advised
-    /// to not call this method in a loop.
+    /// Only the transitive closure of the added entities and their
+    /// descendants is re-computed, so the cost depends on the size of that
+    /// part of the hierarchy rather than on the size of this [`Entities`].
     /// ## Errors
     /// - [`EntitiesError::InvalidEntity`] if `schema` is not none and any entities do not conform
     ///   to the schema
@@ -524,8 +573,9 @@ impl Entities {
     /// or superfluous attributes are provided.
     /// (This method will not add action entities from the `schema`.)
     ///
-    /// Re-computing the transitive closure can be expensive, so it is advised
-    /// to not call this method in a loop.
+    /// Only the transitive closure of the added entities and their
+    /// descendants is re-computed, so the cost depends on the size of that
+    /// part of the hierarchy rather than on the size of this [`Entities`].
     /// ## Errors
     /// - [`EntitiesError::Duplicate`] if there is a pair of non-identical entities in
     ///   `entities` with the same Entity UID, or there is an entity in `entities` with the
@@ -564,8 +614,9 @@ impl Entities {
     /// or superfluous attributes are provided.
     /// (This method will not add action entities from the `schema`.)
     ///
-    /// Re-computing the transitive closure can be expensive, so it is advised
-    /// to not call this method in a loop.
+    /// Only the transitive closure of the added entities and their
+    /// descendants is re-computed, so the cost depends on the size of that
+    /// part of the hierarchy rather than on the size of this [`Entities`].
     /// ## Errors
     /// - [`EntitiesError::Duplicate`] if there is a pair of non-identical entities in
     ///   `entities` with the same Entity UID, or there is an entity in `entities` with the same
@@ -604,8 +655,9 @@ impl Entities {
     /// or superfluous attributes are provided.
     /// (This method will not add action entities from the `schema`.)
     ///
-    /// Re-computing the transitive closure can be expensive, so it is advised
-    /// to not call this method in a loop.
+    /// Only the transitive closure of the added entities and their
+    /// descendants is re-computed, so the cost depends on the size of that
+    /// part of the hierarchy rather than on the size of this [`Entities`].
     ///
     /// ## Errors
     /// - [`EntitiesError::Duplicate`] if there is a pair of non-identical entities in `entities`
@@ -792,7 +844,7 @@ impl Entities {
     /// Same semantics as `b in a` in the Cedar language
     pub fn is_ancestor_of(&self, a: &EntityUid, b: &EntityUid) -> bool {
         match self.0.entity(b.as_ref()) {
-            Dereference::Data(b) => b.is_descendant_of(a.as_ref()),
+            Dereference::Data(b) => self.0.is_descendant_of(b, a.as_ref()),
             _ => a == b, // if b doesn't exist, `b in a` is only true if `b == a`
         }
     }
@@ -807,7 +859,72 @@ impl Entities {
             Dereference::Residual(_) | Dereference::NoSuchEntity => None,
             Dereference::Data(e) => Some(e),
         }?;
-        Some(entity.ancestors().map(EntityUid::ref_cast))
+        Some(self.0.ancestors(entity).map(EntityUid::ref_cast))
+    }
+
+    /// Get an iterator over the entities of type `ty`
+    pub fn entities_of_type<'a>(&'a self, ty: &EntityTypeName) -> impl Iterator<Item = &'a Entity> {
+        self.0.entities_of_type(&ty.0).map(Entity::ref_cast)
+    }
+
+    /// Get an iterator over the entities which have `euid` as a direct parent.
+    /// `euid` need not be an entity in the `Entities`.
+    pub fn children<'a>(&'a self, euid: &EntityUid) -> impl Iterator<Item = &'a Entity> {
+        self.0.children(euid.as_ref()).map(Entity::ref_cast)
+    }
+
+    /// Get an iterator over the (direct and indirect) descendants of `euid`:
+    /// the entities `e` other than `euid` for which `e in euid` holds, with
+    /// the same semantics as in the Cedar language. `euid` need not be an
+    /// entity in the `Entities`.
+    /// ```
+    /// # use cedar_policy::{Entities, EntityUid, RestrictedExpression};
+    /// # use std::str::FromStr;
+    /// let entities = Entities::from_json_str(
+    ///     r#"[
+    ///         { "uid": { "type": "User", "id": "alice" }, "attrs": { "level": 3 }, "parents": [{ "type": "Group", "id": "eng" }] },
+    ///         { "uid": { "type": "Group", "id": "eng" }, "attrs": {}, "parents": [{ "type": "Group", "id": "all" }] }
+    ///     ]"#,
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let alice = EntityUid::from_str(r#"User::"alice""#).unwrap();
+    /// let all = EntityUid::from_str(r#"Group::"all""#).unwrap();
+    /// assert_eq!(entities.children(&all).count(), 1);
+    /// assert!(entities.descendants(&all).any(|e| e.uid() == alice));
+    ///
+    /// let level_3: Vec<_> = entities
+    ///     .entities_with_attr("level", &RestrictedExpression::new_long(3))
+    ///     .unwrap()
+    ///     .map(|e| e.uid())
+    ///     .collect();
+    /// assert_eq!(level_3, [alice]);
+    /// ```
+    pub fn descendants<'a>(&'a self, euid: &EntityUid) -> impl Iterator<Item = &'a Entity> {
+        self.0.descendants(euid.as_ref()).map(Entity::ref_cast)
+    }
+
+    /// Get an iterator over the entities whose attribute `attr` is equal to
+    /// `value`. Entities whose value for `attr` is a residual, such as an
+    /// unknown in partial evaluation, are never returned.
+    ///
+    /// The index of attribute values is built by the first call, which takes
+    /// time linear in the number of entities, and then kept up to date as
+    /// entities are added and removed.
+    ///
+    /// ## Errors
+    /// - [`EvaluationError`] if `value` fails to evaluate
+    pub fn entities_with_attr<'a>(
+        &'a self,
+        attr: &str,
+        value: &RestrictedExpression,
+    ) -> Result<impl Iterator<Item = &'a Entity>, EvaluationError> {
+        let value = evaluator::RestrictedEvaluator::new(Extensions::all_available())
+            .interpret(value.0.as_borrowed())?;
+        Ok(self
+            .0
+            .entities_with_attr(attr, &value)
+            .map(Entity::ref_cast))
     }
 
     /// Returns the number of `Entity`s in the `Entities`
@@ -831,6 +948,87 @@ impl Entities {
         self.0.write_to_json(f)
     }
 
+    /// Apply the operations in `patch` to this [`Entities`], in order,
+    /// returning the [`EntityPatch`] which undoes them.
+    ///
+    /// The change-set is applied atomically: if any operation fails, this
+    /// [`Entities`] is left unchanged. Only the transitive closure of the
+    /// entities whose parents changed and their descendants is re-computed.
+    ///
+    /// If a `schema` is provided, every entity the change-set touches must
+    /// conform to it once all the operations have been applied.
+    /// ```
+    /// # use cedar_policy::{Entities, EntityPatch, EntityUid};
+    /// # use std::str::FromStr;
+    /// let mut entities = Entities::from_json_str(
+    ///     r#"[{ "uid": { "type": "User", "id": "alice" }, "attrs": {}, "parents": [] }]"#,
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let patch = EntityPatch::from_json_str(
+    ///     r#"[
+    ///         { "op": "addParent", "uid": { "type": "User", "id": "alice" }, "parent": { "type": "Group", "id": "eng" } },
+    ///         { "op": "setAttr", "uid": { "type": "User", "id": "alice" }, "attr": "level", "value": 3 }
+    ///     ]"#,
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let undo = entities.apply_patch(patch, None).unwrap();
+    /// let alice = EntityUid::from_str(r#"User::"alice""#).unwrap();
+    /// let eng = EntityUid::from_str(r#"Group::"eng""#).unwrap();
+    /// assert!(entities.is_ancestor_of(&eng, &alice));
+    ///
+    /// entities.apply_patch(undo, None).unwrap();
+    /// assert!(!entities.is_ancestor_of(&eng, &alice));
+    /// ```
+    /// ## Errors
+    /// - [`EntitiesError::MissingEntity`] if an operation updates an entity
+    ///   which doesn't exist at that point in the change-set
+    /// - [`EntitiesError::TransitiveClosureError`] if the change-set introduces
+    ///   a cycle in the entity hierarchy
+    /// - [`EntitiesError::InvalidEntity`] if `schema` is not none and any
+    ///   entities the change-set touches do not conform to the schema
+    pub fn apply_patch(
+        &mut self,
+        patch: EntityPatch,
+        schema: Option<&Schema>,
+    ) -> Result<EntityPatch, EntitiesError> {
+        let schema = schema.map(|s| cedar_policy_validator::CoreSchema::new(&s.0));
+        self.0
+            .apply_patch(patch.0, schema.as_ref(), Extensions::all_available())
+            .map(EntityPatch)
+    }
+
+    /// Compute the differences between two snapshots of an entity store: the
+    /// entities which were added or removed, and for the entities in both, the
+    /// attributes and tags whose values changed and the direct parents which
+    /// were added or removed.
+    /// ```
+    /// # use cedar_policy::Entities;
+    /// let old = Entities::from_json_str(
+    ///     r#"[{ "uid": { "type": "User", "id": "alice" }, "attrs": { "level": 3 }, "parents": [] }]"#,
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let new = Entities::from_json_str(
+    ///     r#"[{ "uid": { "type": "User", "id": "alice" }, "attrs": { "level": 4 }, "parents": [{ "type": "Group", "id": "eng" }] }]"#,
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let diff = Entities::diff(&old, &new);
+    /// let changes: Vec<String> = diff.changes().map(ToString::to_string).collect();
+    /// assert_eq!(
+    ///     changes,
+    ///     [
+    ///         r#"attribute `level` of `User::"alice"` changed from 3 to 4"#,
+    ///         r#"parent `Group::"eng"` was added to `User::"alice"`"#,
+    ///     ]
+    /// );
+    /// ```
+    pub fn diff(old: &Self, new: &Self) -> EntitiesDiff {
+        EntitiesDiff(cedar_policy_core::entities::Entities::diff(&old.0, &new.0))
+    }
+
     #[doc = include_str!("../experimental_warning.md")]
     /// Visualize an `Entities` object in the graphviz `dot`
     /// format. Entity visualization is best-effort and not well tested.
@@ -876,6 +1074,258 @@ impl IntoIterator for Entities {
     }
 }
 
+/// A change-set for an [`Entities`]: a sequence of operations applied in
+/// order, and all together or not at all, by [`Entities::apply_patch`].
+///
+/// In JSON, a change-set is an array of operations, distinguished by their
+/// `op` field:
+/// - `{ "op": "put", "entity": <entity> }` adds an entity, in the same format
+///   as in an entities JSON file, replacing any entity with the same UID
+/// - `{ "op": "remove", "uid": <uid> }` removes an entity, if it exists. The
+///   entity's children keep it as a parent unless they are updated too.
+/// - `{ "op": "setAttr", "uid": <uid>, "attr": <name>, "value": <value> }` and
+///   `{ "op": "removeAttr", "uid": <uid>, "attr": <name> }` update an
+///   attribute of an existing entity
+/// - `{ "op": "addParent", "uid": <uid>, "parent": <uid> }` and
+///   `{ "op": "removeParent", "uid": <uid>, "parent": <uid> }` update the
+///   parents of an existing entity
+/// - `{ "op": "setTag", "uid": <uid>, "tag": <name>, "value": <value> }` and
+///   `{ "op": "removeTag", "uid": <uid>, "tag": <name> }` update a tag of an
+///   existing entity
+#[repr(transparent)]
+#[derive(Debug, Clone, Default, RefCast)]
+pub struct EntityPatch(pub(crate) cedar_policy_core::entities::EntityPatch);
+
+#[doc(hidden)] // because this converts to a private/internal type
+impl AsRef<cedar_policy_core::entities::EntityPatch> for EntityPatch {
+    fn as_ref(&self) -> &cedar_policy_core::entities::EntityPatch {
+        &self.0
+    }
+}
+
+impl EntityPatch {
+    /// Parse a change-set JSON file (in `&str` form) into an `EntityPatch`
+    ///
+    /// If a `schema` is provided, this will inform the parsing of attribute
+    /// and tag values: for instance, it will allow `__entity` and `__extn`
+    /// escapes to be implicit. The entities the change-set touches are only
+    /// checked against the `schema` when it's applied.
+    ///
+    /// ## Errors
+    /// - [`EntitiesError::Deserialization`] if there are errors while parsing the json
+    pub fn from_json_str(json: &str, schema: Option<&Schema>) -> Result<Self, EntitiesError> {
+        let schema = schema.map(|s| cedar_policy_validator::CoreSchema::new(&s.0));
+        let eparser = cedar_policy_core::entities::EntityJsonParser::new(
+            schema.as_ref(),
+            Extensions::all_available(),
+            cedar_policy_core::entities::TCComputation::ComputeNow,
+        );
+        eparser.patch_from_json_str(json).map(Self)
+    }
+
+    /// Parse a change-set JSON file (in [`serde_json::Value`] form) into an
+    /// `EntityPatch`
+    ///
+    /// If a `schema` is provided, it is handled identically to [`EntityPatch::from_json_str`]
+    ///
+    /// ## Errors
+    /// - [`EntitiesError::Deserialization`] if there are errors while parsing the json
+    pub fn from_json_value(
+        json: serde_json::Value,
+        schema: Option<&Schema>,
+    ) -> Result<Self, EntitiesError> {
+        let schema = schema.map(|s| cedar_policy_validator::CoreSchema::new(&s.0));
+        let eparser = cedar_policy_core::entities::EntityJsonParser::new(
+            schema.as_ref(),
+            Extensions::all_available(),
+            cedar_policy_core::entities::TCComputation::ComputeNow,
+        );
+        eparser.patch_from_json_value(json).map(Self)
+    }
+
+    /// Parse a change-set JSON file (in [`std::io::Read`] form) into an
+    /// `EntityPatch`
+    ///
+    /// If a `schema` is provided, it is handled identically to [`EntityPatch::from_json_str`]
+    ///
+    /// ## Errors
+    /// - [`EntitiesError::Deserialization`] if there are errors while parsing the json
+    pub fn from_json_file(
+        json: impl std::io::Read,
+        schema: Option<&Schema>,
+    ) -> Result<Self, EntitiesError> {
+        let schema = schema.map(|s| cedar_policy_validator::CoreSchema::new(&s.0));
+        let eparser = cedar_policy_core::entities::EntityJsonParser::new(
+            schema.as_ref(),
+            Extensions::all_available(),
+            cedar_policy_core::entities::TCComputation::ComputeNow,
+        );
+        eparser.patch_from_json_file(json).map(Self)
+    }
+
+    /// Returns the number of operations in the `EntityPatch`
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if the `EntityPatch` contains no operations
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Convert an `EntityPatch` into a JSON value, suitable for parsing in via
+    /// `from_json_*`
+    pub fn to_json_value(&self) -> Result<serde_json::Value, EntitiesError> {
+        self.0.to_json_value()
+    }
+
+    /// Dump an `EntityPatch` into a change-set JSON file, suitable for parsing
+    /// in via `from_json_*`
+    pub fn write_to_json(&self, f: impl std::io::Write) -> Result<(), EntitiesError> {
+        self.0.write_to_json(f)
+    }
+}
+
+/// The differences between two [`Entities`], as computed by [`Entities::diff`].
+///
+/// In JSON, a diff is an array of changes, distinguished by their `change`
+/// field:
+/// - `{ "change": "entityAdded", "entity": <entity> }` and
+///   `{ "change": "entityRemoved", "entity": <entity> }` for entities which
+///   are only in the new or the old store, in the same format as in an
+///   entities JSON file
+/// - `{ "change": "attributeChanged", "uid": <uid>, "attr": <name>, "old": <value>, "new": <value> }`
+///   for an attribute whose value changed, omitting `old` if the attribute
+///   was added and `new` if it was removed
+/// - `{ "change": "tagChanged", "uid": <uid>, "tag": <name>, "old": <value>, "new": <value> }`
+///   likewise for a tag
+/// - `{ "change": "parentAdded", "uid": <uid>, "parent": <uid> }` and
+///   `{ "change": "parentRemoved", "uid": <uid>, "parent": <uid> }` for a
+///   direct parent which was added or removed
+#[repr(transparent)]
+#[derive(Debug, Clone, Default, RefCast)]
+pub struct EntitiesDiff(cedar_policy_core::entities::EntitiesDiff);
+
+impl EntitiesDiff {
+    /// All changes between the two [`Entities`], grouped by entity and sorted
+    /// by entity UID
+    pub fn changes(&self) -> impl Iterator<Item = &EntityChange> {
+        self.0.changes().map(EntityChange::ref_cast)
+    }
+
+    /// Returns the number of changes in the `EntitiesDiff`
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if the two [`Entities`] are the same
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Convert an `EntitiesDiff` into a JSON value
+    pub fn to_json_value(&self) -> Result<serde_json::Value, EntitiesError> {
+        self.0.to_json_value()
+    }
+
+    /// Dump an `EntitiesDiff` into a JSON file
+    pub fn write_to_json(&self, f: impl std::io::Write) -> Result<(), EntitiesError> {
+        self.0.write_to_json(f)
+    }
+}
+
+/// A single change in an [`EntitiesDiff`]
+#[repr(transparent)]
+#[derive(Debug, Clone, RefCast)]
+pub struct EntityChange(cedar_policy_core::entities::EntityChange);
+
+impl EntityChange {
+    /// UID of the entity which was added, removed or changed
+    pub fn uid(&self) -> &EntityUid {
+        EntityUid::ref_cast(self.0.uid())
+    }
+}
+
+impl std::fmt::Display for EntityChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A source of entity data which the [`Authorizer`] can load entities from.
+///
+/// Unlike [`Entities`], an `EntityStore` does not need to hold every entity up
+/// front: entities are requested as they are needed to evaluate policies. An
+/// implementation might, for instance, load entities from a database. See
+/// [`Authorizer::is_authorized_with_store`].
+pub trait EntityStore {
+    /// Look up the entity `uid`, including its attributes, ancestors and
+    /// tags. Returns `Ok(None)` if there is no such entity.
+    ///
+    /// The ancestors of the returned entity must include all of its indirect
+    /// ancestors, not just its parents, unless
+    /// [`EntityStore::ancestor_mode`] is [`AncestorMode::OnDemand`].
+    fn entity(&self, uid: &EntityUid) -> Result<Option<Entity>, EntityStoreError>;
+
+    /// Whether the entities returned by [`EntityStore::entity`] have all of
+    /// their ancestors, or only their parents, in which case the
+    /// [`Authorizer`] looks up the parents in turn to find the indirect
+    /// ancestors it needs.
+    fn ancestor_mode(&self) -> AncestorMode {
+        AncestorMode::Precomputed
+    }
+}
+
+impl EntityStore for Entities {
+    fn entity(&self, uid: &EntityUid) -> Result<Option<Entity>, EntityStoreError> {
+        Ok(self.get(uid).cloned())
+    }
+
+    fn ancestor_mode(&self) -> AncestorMode {
+        self.ancestor_mode()
+    }
+}
+
+/// Adapts an [`EntityStore`] to the interface used by the evaluator, loading
+/// each entity from the store at most once
+struct LoadingEntityStore<'a, S: ?Sized> {
+    store: &'a S,
+    /// Entities loaded so far (`None` for entities which do not exist)
+    loaded: RefCell<HashMap<ast::EntityUID, Option<Arc<ast::Entity>>>>,
+}
+
+impl<'a, S: EntityStore + ?Sized> LoadingEntityStore<'a, S> {
+    fn new(store: &'a S) -> Self {
+        Self {
+            store,
+            loaded: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl<S: EntityStore + ?Sized> cedar_policy_core::entities::EntityStore
+    for LoadingEntityStore<'_, S>
+{
+    fn entity(&self, uid: &ast::EntityUID) -> Result<StoredEntity, EntityStoreError> {
+        let loaded = self.loaded.borrow().get(uid).cloned();
+        let entity = if let Some(entity) = loaded {
+            entity
+        } else {
+            let entity = self
+                .store
+                .entity(EntityUid::ref_cast(uid))?
+                .map(|e| Arc::new(e.0));
+            self.loaded.borrow_mut().insert(uid.clone(), entity.clone());
+            entity
+        };
+        Ok(entity.map_or(StoredEntity::NoSuchEntity, StoredEntity::Data))
+    }
+
+    fn ancestor_mode(&self) -> AncestorMode {
+        self.store.ancestor_mode()
+    }
+}
+
 /// Authorizer object, which provides responses to authorization queries
 #[repr(transparent)]
 #[derive(Debug, Clone, RefCast)]
@@ -955,6 +1405,133 @@ impl Authorizer {
         Self(authorizer::Authorizer::new())
     }
 
+    /// Use the given error-handling mode, which determines how policies that
+    /// encounter evaluation errors affect the authorization decision. The
+    /// default is [`ErrorHandling::Skip`], as described by the Cedar language
+    /// specification.
+    ///
+    /// The mode also applies to partial authorization: for instance, in
+    /// [`ErrorHandling::DenyOnError`] mode, concretizing a
+    /// `PartialResponse` with residual policies results in `Deny`, because
+    /// residuals are treated as errors.
+    /// ```
+    /// # use cedar_policy::{Authorizer, Context, Decision, Entities, EntityUid, ErrorHandling, PolicySet, Request};
+    /// # use std::str::FromStr;
+    /// let policies = PolicySet::from_str(
+    ///     r#"
+    ///     permit(principal, action, resource);
+    ///     forbid(principal, action, resource) when { context.is_suspended };
+    ///     "#,
+    /// )
+    /// .unwrap();
+    /// let request = Request::new(
+    ///     EntityUid::from_str(r#"User::"alice""#).unwrap(),
+    ///     EntityUid::from_str(r#"Action::"view""#).unwrap(),
+    ///     EntityUid::from_str(r#"Doc::"a""#).unwrap(),
+    ///     Context::empty(),
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let entities = Entities::empty();
+    /// let response = Authorizer::new().is_authorized(&request, &policies, &entities);
+    /// assert_eq!(response.decision(), Decision::Allow);
+    /// let response = Authorizer::new()
+    ///     .with_error_handling(ErrorHandling::ForbidOnError)
+    ///     .is_authorized(&request, &policies, &entities);
+    /// assert_eq!(response.decision(), Decision::Deny);
+    /// ```
+    #[must_use]
+    pub fn with_error_handling(self, error_handling: ErrorHandling) -> Self {
+        Self(self.0.with_error_handling(error_handling))
+    }
+
+    /// Get the error-handling mode of this `Authorizer`
+    pub fn error_handling(&self) -> ErrorHandling {
+        self.0.error_handling()
+    }
+
+    /// Impose `limits` on the work done to evaluate each policy. The limits on
+    /// steps, entity dereferences and set size apply to each policy
+    /// separately, so a request may do up to that much work for every policy
+    /// in the policy set; only the deadline bounds the whole request. A policy
+    /// which exceeds a limit fails with an
+    /// [`EvaluationError::EvaluationLimit`] error, which is reported in the
+    /// response [`Diagnostics`] and handled according to the
+    /// [`ErrorHandling`] mode, like any other policy evaluation error. The
+    /// error's [`limit()`](evaluation_errors::EvaluationLimitError::limit)
+    /// says which [`ExceededLimit`] was hit.
+    /// ```
+    /// # use cedar_policy::{Authorizer, Context, Decision, Entities, EntityUid, EvaluationLimits, PolicySet, Request};
+    /// # use std::str::FromStr;
+    /// let policies = PolicySet::from_str(
+    ///     r#"permit(principal, action, resource) when { [1, 2, 3].contains(context.n) };"#,
+    /// )
+    /// .unwrap();
+    /// let request = Request::new(
+    ///     EntityUid::from_str(r#"User::"alice""#).unwrap(),
+    ///     EntityUid::from_str(r#"Action::"view""#).unwrap(),
+    ///     EntityUid::from_str(r#"Doc::"a""#).unwrap(),
+    ///     Context::from_json_str(r#"{"n": 2}"#, None).unwrap(),
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let entities = Entities::empty();
+    /// let response = Authorizer::new()
+    ///     .with_limits(EvaluationLimits::unlimited().with_max_set_size(2))
+    ///     .is_authorized(&request, &policies, &entities);
+    /// assert_eq!(response.decision(), Decision::Deny);
+    /// assert_eq!(response.diagnostics().errors().count(), 1);
+    /// ```
+    #[must_use]
+    pub fn with_limits(self, limits: EvaluationLimits) -> Self {
+        Self(self.0.with_limits(limits))
+    }
+
+    /// Get the evaluation limits of this `Authorizer`
+    pub fn limits(&self) -> EvaluationLimits {
+        self.0.limits()
+    }
+
+    /// Record every decision this `Authorizer` makes to `audit_log`, by
+    /// wrapping it in an [`AuditedAuthorizer`]
+    ///
+    /// Each call to [`AuditedAuthorizer::is_authorized`] and its variants
+    /// records an [`AuditRecord`] for each request, with the request,
+    /// decision, determining policies, errors, time taken and hashes of the
+    /// policy set and entities. Computing the hashes takes time linear in the
+    /// size of the policy set and entities, so they are only recomputed when
+    /// those change between calls. Partial authorization is not recorded.
+    /// ```
+    /// # use cedar_policy::{AuditLog, Authorizer, Context, Decision, Entities, EntityUid, MemoryAuditSink, PolicyId, PolicySet, Request};
+    /// # use std::str::FromStr;
+    /// # use std::sync::Arc;
+    /// let policies = PolicySet::from_str(
+    ///     r#"@ticket("SEC-12") permit(principal == User::"alice", action, resource);"#,
+    /// )
+    /// .unwrap();
+    /// let request = Request::new(
+    ///     EntityUid::from_str(r#"User::"alice""#).unwrap(),
+    ///     EntityUid::from_str(r#"Action::"view""#).unwrap(),
+    ///     EntityUid::from_str(r#"Doc::"a""#).unwrap(),
+    ///     Context::empty(),
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let sink = Arc::new(MemoryAuditSink::new());
+    /// let authorizer = Authorizer::new()
+    ///     .with_audit_log(AuditLog::new(sink.clone()).with_annotations(["ticket"]));
+    /// authorizer.is_authorized(&request, &policies, &Entities::empty());
+    /// let records = sink.records();
+    /// assert_eq!(records[0].decision(), Decision::Allow);
+    /// let policy = records[0].determining_policies().next().unwrap();
+    /// assert_eq!(policy.id(), &PolicyId::new("policy0"));
+    /// assert_eq!(policy.annotation("ticket"), Some("SEC-12"));
+    /// ```
+    #[must_use]
+    pub fn with_audit_log(self, audit_log: AuditLog) -> AuditedAuthorizer {
+        AuditedAuthorizer::new(self, audit_log)
+    }
+
     /// Returns an authorization response for `r` with respect to the given
     /// `PolicySet` and `Entities`.
     ///
@@ -1012,6 +1589,188 @@ impl Authorizer {
         self.0.is_authorized(r.0.clone(), &p.ast, &e.0).into()
     }
 
+    /// Returns an authorization response for each of `requests` with respect
+    /// to the given `PolicySet` and `Entities`, in the same order as
+    /// `requests`.
+    ///
+    /// Each response is the same as [`Authorizer::is_authorized`] would return
+    /// for that request, but the results of extension constructor calls on
+    /// literals, like `ip("10.0.0.1")`, are shared across the batch rather
+    /// than parsed again for every request. When `e` only stores the parents
+    /// of each entity ([`AncestorMode::OnDemand`]), the ancestors found by
+    /// searching the hierarchy for an `in` check are also shared. If the
+    /// `rayon` feature is enabled, the requests are evaluated in parallel.
+    /// ```
+    /// # use cedar_policy::{Authorizer, Context, Decision, Entities, EntityUid, PolicySet, Request};
+    /// # use std::str::FromStr;
+    /// let policies = PolicySet::from_str(
+    ///     r#"permit(principal == User::"alice", action, resource in Folder::"shared");"#,
+    /// )
+    /// .unwrap();
+    /// let entities = Entities::from_json_str(
+    ///     r#"[{ "uid": {"type": "Doc", "id": "a"}, "attrs": {}, "parents": [{"type": "Folder", "id": "shared"}] }]"#,
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let request = |resource: &str| {
+    ///     Request::new(
+    ///         EntityUid::from_str(r#"User::"alice""#).unwrap(),
+    ///         EntityUid::from_str(r#"Action::"view""#).unwrap(),
+    ///         EntityUid::from_str(resource).unwrap(),
+    ///         Context::empty(),
+    ///         None,
+    ///     )
+    ///     .unwrap()
+    /// };
+    /// let authorizer = Authorizer::new();
+    /// let responses = authorizer.is_authorized_batch(
+    ///     [request(r#"Doc::"a""#), request(r#"Doc::"b""#)],
+    ///     &policies,
+    ///     &entities,
+    /// );
+    /// assert_eq!(responses[0].decision(), Decision::Allow);
+    /// assert_eq!(responses[1].decision(), Decision::Deny);
+    /// ```
+    pub fn is_authorized_batch(
+        &self,
+        requests: impl IntoIterator<Item = Request>,
+        p: &PolicySet,
+        e: &Entities,
+    ) -> Vec<Response> {
+        let cache = EvaluationCache::new();
+        let authorize = |r: Request| -> Response {
+            self.0
+                .is_authorized_with_cache(r.0, &p.ast, &e.0, &cache)
+                .into()
+        };
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            requests
+                .into_iter()
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .map(authorize)
+                .collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            requests.into_iter().map(authorize).collect()
+        }
+    }
+
+    /// Returns an authorization response for `r` with respect to the given
+    /// `PolicySet` and `Entities`, along with an [`EvaluationTrace`] for every
+    /// policy in the `PolicySet`, explaining why it evaluated to `true`,
+    /// `false`, or an error.
+    ///
+    /// The response is the same as from [`Authorizer::is_authorized`], but
+    /// this is considerably slower, so it is intended for debugging rather
+    /// than for use on every request.
+    /// ```
+    /// # use cedar_policy::{Authorizer, Context, Decision, Entities, EntityUid, PolicyId, PolicySet, Request, TraceOutcome};
+    /// # use std::str::FromStr;
+    /// let policies = PolicySet::from_str(
+    ///     r#"permit(principal, action, resource) when { context.readonly && principal == User::"alice" };"#,
+    /// )
+    /// .unwrap();
+    /// let request = Request::new(
+    ///     EntityUid::from_str(r#"User::"bob""#).unwrap(),
+    ///     EntityUid::from_str(r#"Action::"view""#).unwrap(),
+    ///     EntityUid::from_str(r#"Doc::"a""#).unwrap(),
+    ///     Context::from_json_str(r#"{ "readonly": true }"#, None).unwrap(),
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let authorizer = Authorizer::new();
+    /// let (response, traces) =
+    ///     authorizer.is_authorized_with_trace(&request, &policies, &Entities::empty());
+    /// assert_eq!(response.decision(), Decision::Deny);
+    /// let trace = &traces[&PolicyId::new("policy0")];
+    /// assert!(matches!(trace.outcome(), TraceOutcome::Value(_)));
+    /// ```
+    pub fn is_authorized_with_trace(
+        &self,
+        r: &Request,
+        p: &PolicySet,
+        e: &Entities,
+    ) -> (Response, HashMap<PolicyId, EvaluationTrace>) {
+        let (response, traces) = self.0.is_authorized_with_trace(r.0.clone(), &p.ast, &e.0);
+        (
+            response.into(),
+            traces
+                .into_iter()
+                .map(|(id, trace)| (PolicyId::new(id), EvaluationTrace(trace)))
+                .collect(),
+        )
+    }
+
+    /// Returns an authorization response for `r` with respect to the given
+    /// `PolicySet` and `Entities`, recording which policies, `when` and
+    /// `unless` clauses and boolean expressions were exercised in `coverage`.
+    ///
+    /// Use the same [`CoverageReport`] for many requests to aggregate their
+    /// coverage. The response is the same as from
+    /// [`Authorizer::is_authorized`], but this is considerably slower.
+    /// ```
+    /// # use cedar_policy::{Authorizer, Context, CoverageReport, Entities, EntityUid, PolicyId, PolicySet, Request};
+    /// # use std::str::FromStr;
+    /// let policies = PolicySet::from_str(
+    ///     r#"permit(principal, action, resource) when { context.readonly };"#,
+    /// )
+    /// .unwrap();
+    /// let request = Request::new(
+    ///     EntityUid::from_str(r#"User::"alice""#).unwrap(),
+    ///     EntityUid::from_str(r#"Action::"view""#).unwrap(),
+    ///     EntityUid::from_str(r#"Doc::"a""#).unwrap(),
+    ///     Context::from_json_str(r#"{ "readonly": false }"#, None).unwrap(),
+    ///     None,
+    /// )
+    /// .unwrap();
+    /// let authorizer = Authorizer::new();
+    /// let mut coverage = CoverageReport::new();
+    /// authorizer.is_authorized_with_coverage(&request, &policies, &Entities::empty(), &mut coverage);
+    /// assert_eq!(coverage.never_determining().collect::<Vec<_>>(), [&PolicyId::new("policy0")]);
+    /// let policy = coverage.get(&PolicyId::new("policy0")).unwrap();
+    /// let (_, clause) = policy.clauses().next().unwrap();
+    /// assert_eq!((clause.times_true(), clause.times_false()), (0, 1));
+    /// ```
+    pub fn is_authorized_with_coverage(
+        &self,
+        r: &Request,
+        p: &PolicySet,
+        e: &Entities,
+        coverage: &mut CoverageReport,
+    ) -> Response {
+        self.0
+            .is_authorized_with_coverage(r.0.clone(), &p.ast, &e.0, &mut coverage.0)
+            .into()
+    }
+
+    /// Returns an authorization response for `r` with respect to the given
+    /// `PolicySet`, looking up entities in `store` as the policies need them.
+    ///
+    /// Each entity is requested from `store` at most once per call. If `store`
+    /// fails to load an entity, each policy which needed it fails with an
+    /// [`EvaluationError::EntityLoad`] error, which is handled according to
+    /// the [`ErrorHandling`] mode and reported in the response
+    /// [`Diagnostics`].
+    ///
+    /// The response is the same as from [`Authorizer::is_authorized`] with
+    /// an `Entities` containing every entity in `store`, but no policies are
+    /// skipped based on their scope.
+    pub fn is_authorized_with_store(
+        &self,
+        r: &Request,
+        p: &PolicySet,
+        store: &(impl EntityStore + ?Sized),
+    ) -> Response {
+        let store = LoadingEntityStore::new(store);
+        self.0
+            .is_authorized_with_store(r.0.clone(), &p.ast, &store)
+            .into()
+    }
+
     /// A partially evaluated authorization request.
     /// The Authorizer will attempt to make as much progress as possible in the presence of unknowns.
     /// If the Authorizer can reach a response, it will return that response.
@@ -1029,6 +1788,298 @@ impl Authorizer {
             .is_authorized_core(query.0.clone(), &policy_set.ast, &entities.0);
         PartialResponse(response)
     }
+
+    /// A partially evaluated authorization request, with the residuals
+    /// simplified using the types in `schema`.
+    ///
+    /// The action of `query` must be known, and the unknown principal or
+    /// resource must have a known entity type (see
+    /// [`RequestBuilder::unknown_principal_with_type`] and
+    /// [`RequestBuilder::unknown_resource_with_type`]). Each residual is
+    /// typechecked for that request, and every `has` check, `is` test and
+    /// branch which the schema decides is replaced by its value. The
+    /// returned residuals cannot produce type errors.
+    ///
+    /// Like validation, this assumes the entities conform to `schema`. It
+    /// also assumes the unknown principal and resource exist, so checks for
+    /// their required attributes are dropped.
+    ///
+    /// ```
+    /// # use cedar_policy::{Authorizer, Context, Entities, PolicySet, Request, Schema};
+    /// let schema: Schema = r#"
+    ///     entity User;
+    ///     entity Doc { owner: User, title?: String };
+    ///     action view appliesTo { principal: User, resource: Doc };
+    /// "#.parse().unwrap();
+    /// let policies: PolicySet = r#"
+    ///     permit(principal, action == Action::"view", resource)
+    ///     when { resource has owner && resource.owner == principal };
+    /// "#.parse().unwrap();
+    /// let request = Request::builder()
+    ///     .principal(r#"User::"alice""#.parse().unwrap())
+    ///     .action(r#"Action::"view""#.parse().unwrap())
+    ///     .unknown_resource_with_type("Doc".parse().unwrap())
+    ///     .context(Context::empty())
+    ///     .build();
+    /// let response = Authorizer::new()
+    ///     .is_authorized_partial_with_schema(&request, &policies, &Entities::empty(), &schema)
+    ///     .unwrap();
+    /// let residual = response.nontrivial_residuals().next().unwrap();
+    /// assert!(!residual.to_string().contains("has"));
+    /// ```
+    #[doc = include_str!("../experimental_warning.md")]
+    #[cfg(feature = "partial-eval")]
+    pub fn is_authorized_partial_with_schema(
+        &self,
+        query: &Request,
+        policy_set: &PolicySet,
+        entities: &Entities,
+        schema: &Schema,
+    ) -> Result<PartialResponse, TypedPartialEvaluationError> {
+        let response = self
+            .0
+            .is_authorized_core(query.0.clone(), &policy_set.ast, &entities.0);
+        let response =
+            cedar_policy_validator::typed_partial_eval::simplify_residuals(&schema.0, &response)?;
+        Ok(PartialResponse(response))
+    }
+
+    /// Returns the entities of type `resource_type` in `entities` which
+    /// `principal` is allowed to perform `action` on, in an empty context.
+    ///
+    /// The request is partially evaluated once with an unknown resource (see
+    /// [`RequestBuilder::unknown_resource_with_type`]), and only the
+    /// resulting residuals are evaluated for each candidate entity. The
+    /// result is the same as calling [`Authorizer::is_authorized`] for each
+    /// entity of type `resource_type` in `entities`, but faster. Resources
+    /// which do not exist in `entities` are never returned.
+    ///
+    /// ```
+    /// # use cedar_policy::{Authorizer, Entities, PolicySet};
+    /// let policies: PolicySet = r#"
+    ///     permit(principal == User::"alice", action == Action::"view", resource)
+    ///     when { resource.public };
+    /// "#.parse().unwrap();
+    /// let entities = Entities::from_json_str(r#"[
+    ///     { "uid": { "type": "Photo", "id": "a" }, "attrs": { "public": true }, "parents": [] },
+    ///     { "uid": { "type": "Photo", "id": "b" }, "attrs": { "public": false }, "parents": [] }
+    /// ]"#, None).unwrap();
+    /// let photos = Authorizer::new().query_resources(
+    ///     r#"User::"alice""#.parse().unwrap(),
+    ///     r#"Action::"view""#.parse().unwrap(),
+    ///     "Photo".parse().unwrap(),
+    ///     &policies,
+    ///     &entities,
+    /// );
+    /// assert_eq!(photos, vec![r#"Photo::"a""#.parse().unwrap()]);
+    /// ```
+    #[doc = include_str!("../experimental_warning.md")]
+    #[cfg(feature = "partial-eval")]
+    pub fn query_resources(
+        &self,
+        principal: EntityUid,
+        action: EntityUid,
+        resource_type: EntityTypeName,
+        policy_set: &PolicySet,
+        entities: &Entities,
+    ) -> Vec<EntityUid> {
+        let request = Request::builder()
+            .principal(principal)
+            .action(action)
+            .unknown_resource_with_type(resource_type)
+            .context(Context::empty())
+            .build();
+        self.0
+            .query_resources(request.0, &policy_set.ast, &entities.0)
+            .into_iter()
+            .map(EntityUid)
+            .collect()
+    }
+
+    /// Returns the entities of type `principal_type` in `entities` which are
+    /// allowed to perform `action` on `resource`, in an empty context.
+    ///
+    /// The request is partially evaluated once with an unknown principal (see
+    /// [`RequestBuilder::unknown_principal_with_type`]), and only the
+    /// resulting residuals are evaluated for each candidate entity. The
+    /// result is the same as calling [`Authorizer::is_authorized`] for each
+    /// entity of type `principal_type` in `entities`, but faster. Principals
+    /// which do not exist in `entities` are never returned.
+    #[doc = include_str!("../experimental_warning.md")]
+    #[cfg(feature = "partial-eval")]
+    pub fn query_principals(
+        &self,
+        principal_type: EntityTypeName,
+        action: EntityUid,
+        resource: EntityUid,
+        policy_set: &PolicySet,
+        entities: &Entities,
+    ) -> Vec<EntityUid> {
+        let request = Request::builder()
+            .unknown_principal_with_type(principal_type)
+            .action(action)
+            .resource(resource)
+            .context(Context::empty())
+            .build();
+        self.0
+            .query_principals(request.0, &policy_set.ast, &entities.0)
+            .into_iter()
+            .map(EntityUid)
+            .collect()
+    }
+}
+
+/// Record of the evaluation of an expression in a policy, along with the
+/// records of the subexpressions evaluated to compute it.
+///
+/// Returned by [`Authorizer::is_authorized_with_trace`], where the outermost
+/// expression of each trace is the policy's condition (including its scope).
+#[repr(transparent)]
+#[derive(Debug, Clone, RefCast)]
+pub struct EvaluationTrace(evaluator::TraceNode);
+
+impl EvaluationTrace {
+    /// The expression which was evaluated
+    pub fn expr(&self) -> Expression {
+        Expression(self.0.expr().clone())
+    }
+
+    /// Location of the expression in the policy source, if known
+    pub fn source_span(&self) -> Option<miette::SourceSpan> {
+        self.0.source_loc().map(|loc| loc.span)
+    }
+
+    /// The result of evaluating the expression
+    pub fn outcome(&self) -> TraceOutcome {
+        match self.0.outcome() {
+            evaluator::TraceOutcome::Value(v) => TraceOutcome::Value(v.clone().into()),
+            evaluator::TraceOutcome::Residual(r) => TraceOutcome::Residual(Expression(r.clone())),
+            evaluator::TraceOutcome::Error(e) => TraceOutcome::Error(e.clone()),
+        }
+    }
+
+    /// Whether evaluation of the expression stopped early without evaluating
+    /// all of its operands, e.g. `false && ...` or `true || ...`
+    pub fn short_circuited(&self) -> bool {
+        self.0.short_circuited()
+    }
+
+    /// Records of the subexpressions which were evaluated in order to compute
+    /// this expression, in evaluation order
+    pub fn children(&self) -> impl Iterator<Item = &Self> {
+        self.0.children().map(Self::ref_cast)
+    }
+}
+
+/// Coverage of a policy set, aggregated over the requests passed to
+/// [`Authorizer::is_authorized_with_coverage`].
+///
+/// Clauses and boolean expressions are identified by their source location,
+/// so they are only reported for policies parsed from Cedar syntax.
+#[repr(transparent)]
+#[derive(Debug, Clone, Default, RefCast)]
+pub struct CoverageReport(evaluator::CoverageReport);
+
+impl CoverageReport {
+    /// Create an empty `CoverageReport`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the policies in `policies` which are not already in the report,
+    /// so that they are reported even if they are never evaluated
+    pub fn add_policies(&mut self, policies: &PolicySet) {
+        self.0.add_policies(&policies.ast);
+    }
+
+    /// Coverage of each policy, ordered by policy id
+    pub fn policies(&self) -> impl Iterator<Item = (&PolicyId, &PolicyCoverage)> {
+        self.0
+            .policies()
+            .map(|(id, cov)| (PolicyId::ref_cast(id), PolicyCoverage::ref_cast(cov)))
+    }
+
+    /// Coverage of the policy with id `id`, if it is in the report
+    pub fn get(&self, id: &PolicyId) -> Option<&PolicyCoverage> {
+        self.0.get(id.as_ref()).map(PolicyCoverage::ref_cast)
+    }
+
+    /// Policies which were never a determining policy of a response
+    pub fn never_determining(&self) -> impl Iterator<Item = &PolicyId> {
+        self.0.never_determining().map(PolicyId::ref_cast)
+    }
+
+    /// Add the coverage recorded in `other` to this report
+    pub fn merge(&mut self, other: &Self) {
+        self.0.merge(&other.0);
+    }
+
+    /// Render the report in the lcov tracefile format, for policies parsed
+    /// from the file `file_name`.
+    ///
+    /// Each policy is reported as a function, which is hit when the policy is
+    /// determining, and each boolean expression as a pair of branches.
+    pub fn to_lcov(&self, file_name: &str) -> String {
+        self.0.to_lcov(file_name)
+    }
+
+    /// Render the report as a Cobertura XML document, for policies parsed
+    /// from the file `file_name`, with a class for each policy
+    pub fn to_cobertura(&self, file_name: &str) -> String {
+        self.0.to_cobertura(file_name)
+    }
+}
+
+/// Coverage of a single policy, as recorded in a [`CoverageReport`]
+#[repr(transparent)]
+#[derive(Debug, Clone, RefCast)]
+pub struct PolicyCoverage(evaluator::PolicyCoverage);
+
+impl PolicyCoverage {
+    /// Location of the policy in its source, if known
+    pub fn source_span(&self) -> Option<miette::SourceSpan> {
+        self.0.source_loc().map(|loc| loc.span)
+    }
+
+    /// Number of times the policy was evaluated
+    pub fn evaluations(&self) -> usize {
+        self.0.evaluations()
+    }
+
+    /// Number of times the policy was satisfied
+    pub fn times_satisfied(&self) -> usize {
+        self.0.times_satisfied()
+    }
+
+    /// Number of times the policy was one of the determining policies of an
+    /// authorization response
+    pub fn times_determining(&self) -> usize {
+        self.0.times_determining()
+    }
+
+    /// The `when` and `unless` clauses of the policy, in source order, with
+    /// their coverage
+    pub fn clauses(&self) -> impl Iterator<Item = (miette::SourceSpan, ExprCoverage)> + '_ {
+        self.0.clauses().map(|(loc, cov)| (loc.span, cov))
+    }
+
+    /// The boolean expressions in the clauses of the policy, including the
+    /// clauses themselves, ordered by where they start in the source, with
+    /// their coverage
+    pub fn conditions(&self) -> impl Iterator<Item = (miette::SourceSpan, ExprCoverage)> + '_ {
+        self.0.conditions().map(|(loc, cov)| (loc.span, cov))
+    }
+}
+
+/// Result of evaluating an expression, as recorded in an [`EvaluationTrace`]
+#[derive(Debug, Clone)]
+pub enum TraceOutcome {
+    /// The expression evaluated to a value
+    Value(EvalResult),
+    /// The expression could only be partially evaluated
+    Residual(Expression),
+    /// The expression failed to evaluate
+    Error(EvaluationError),
 }
 
 /// Authorization response returned from the `Authorizer`
@@ -1064,6 +2115,17 @@ impl PartialResponse {
         self.0.concretize().into()
     }
 
+    /// The error-handling mode used to reach a decision
+    pub fn error_handling(&self) -> ErrorHandling {
+        self.0.error_handling()
+    }
+
+    /// The request this response answers
+    #[cfg(feature = "sql")]
+    pub(crate) fn request(&self) -> &Request {
+        Request::ref_cast(self.0.request())
+    }
+
     /// Returns the set of [`Policy`]s that were definitely satisfied.
     /// This will be the set of policies (both `permit` and `forbid`) that evaluated to `true`
     pub fn definitely_satisfied(&self) -> impl Iterator<Item = Policy> + '_ {
@@ -1437,6 +2499,71 @@ impl Validator {
                 .validate_with_level(&pset.ast, mode.into(), max_deref_level),
         )
     }
+
+    /// Infer the type of an expression in each request environment for the
+    /// schema. For each environment, returns the type of `expr`, or the
+    /// errors found while typechecking it. Unlike a policy condition, `expr`
+    /// need not have type `Bool`.
+    ///
+    /// Results are sorted by request environment.
+    ///
+    /// ```
+    /// # use cedar_policy::{Expression, Schema, ValidationMode, Validator};
+    /// # use std::str::FromStr;
+    /// let (schema, _) = Schema::from_cedarschema_str(r#"
+    ///     entity User { age: Long };
+    ///     entity Photo { owner: User };
+    ///     action view appliesTo { principal: User, resource: Photo };
+    /// "#).unwrap();
+    /// let validator = Validator::new(schema);
+    /// let expr = Expression::from_str("resource.owner.age").unwrap();
+    /// let types = validator.typecheck_expression(&expr, ValidationMode::Strict);
+    /// assert_eq!(types.len(), 1);
+    /// assert_eq!(types[0].1.as_ref().unwrap().to_string(), "Long");
+    ///
+    /// let expr = Expression::from_str("resource.name").unwrap();
+    /// let types = validator.typecheck_expression(&expr, ValidationMode::Strict);
+    /// assert!(types[0].1.is_err());
+    /// ```
+    pub fn typecheck_expression(
+        &self,
+        expr: &Expression,
+        mode: ValidationMode,
+    ) -> Vec<(RequestEnv, Result<ExpressionType, Vec<ValidationError>>)> {
+        let typechecker = Typechecker::new(self.0.schema(), mode.into());
+        let mut types: Vec<(RequestEnv, Result<ExpressionType, Vec<ValidationError>>)> =
+            typechecker
+                .typecheck_expr_by_request_env(&expr.0)
+                .into_iter()
+                // environments for actions not declared in the schema have no
+                // `RequestEnv` representation
+                .filter(|(env, _)| {
+                    !matches!(
+                        env,
+                        cedar_policy_validator::types::RequestEnv::UndeclaredAction
+                    )
+                })
+                .map(|(env, ty)| {
+                    (
+                        env.into(),
+                        ty.map(ExpressionType)
+                            .map_err(|errs| errs.into_iter().map(Into::into).collect()),
+                    )
+                })
+                .collect();
+        types.sort_by(|(a, _), (b, _)| a.cmp(b));
+        types
+    }
+}
+
+/// The type of an expression, as inferred by [`Validator::typecheck_expression`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpressionType(cedar_policy_validator::types::Type);
+
+impl std::fmt::Display for ExpressionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 /// Contains all the type information used to construct a `Schema` that can be
@@ -1992,6 +3119,147 @@ impl Schema {
     pub fn actions(&self) -> impl Iterator<Item = &EntityUid> {
         self.0.actions().map(RefCast::ref_cast)
     }
+
+    /// Compare two versions of a schema, classifying each added, removed or
+    /// changed entity type, attribute and action as breaking or non-breaking.
+    ///
+    /// A change is breaking when entities or requests which were valid under
+    /// `old` may be invalid under `new`: for instance, an attribute was
+    /// removed or made required, an attribute type was narrowed, or an action
+    /// no longer applies to some principal type. Use
+    /// [`Schema::diff_with_policies`] to also find policies which stop
+    /// validating.
+    ///
+    /// ```
+    /// # use cedar_policy::Schema;
+    /// # use std::str::FromStr;
+    /// let old = Schema::from_str("entity User { name: String, age?: Long };").unwrap();
+    /// let new = Schema::from_str("entity User { name: String, age: Long };").unwrap();
+    /// let report = Schema::diff(&old, &new);
+    /// assert!(report.is_breaking());
+    /// assert_eq!(
+    ///     report.changes().map(ToString::to_string).collect::<Vec<_>>(),
+    ///     ["attribute `age` of entity type `User` was made required"]
+    /// );
+    /// ```
+    pub fn diff(old: &Self, new: &Self) -> SchemaChangeReport {
+        SchemaChangeReport(cedar_policy_validator::ValidatorSchema::diff(
+            &old.0, &new.0,
+        ))
+    }
+
+    /// Compare two versions of a schema as [`Schema::diff`] does,
+    /// additionally reporting the policies, templates and template-linked
+    /// policies in `policies` which validate against `old` but not against
+    /// `new` in strict mode.
+    pub fn diff_with_policies(old: &Self, new: &Self, policies: &PolicySet) -> SchemaChangeReport {
+        SchemaChangeReport(cedar_policy_validator::ValidatorSchema::diff_with_policies(
+            &old.0,
+            &new.0,
+            &policies.ast,
+        ))
+    }
+}
+
+/// The differences between two versions of a schema, as computed by
+/// [`Schema::diff`] or [`Schema::diff_with_policies`].
+#[repr(transparent)]
+#[derive(Debug, Clone, PartialEq, Eq, RefCast)]
+pub struct SchemaChangeReport(cedar_policy_validator::SchemaChangeReport);
+
+impl SchemaChangeReport {
+    /// All changes between the two schemas, sorted by the name of the entity
+    /// type or action they concern
+    pub fn changes(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.0.changes().map(SchemaChange::ref_cast)
+    }
+
+    /// The changes which are breaking
+    pub fn breaking_changes(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.0.breaking_changes().map(SchemaChange::ref_cast)
+    }
+
+    /// Ids of the policies which validate against the old schema but not
+    /// against the new one. This is always empty for reports computed by
+    /// [`Schema::diff`].
+    pub fn invalidated_policies(&self) -> impl Iterator<Item = &PolicyId> {
+        self.0.invalidated_policies().map(PolicyId::ref_cast)
+    }
+
+    /// Whether there are any breaking changes or invalidated policies
+    pub fn is_breaking(&self) -> bool {
+        self.0.is_breaking()
+    }
+}
+
+/// A single difference between two versions of a schema
+#[repr(transparent)]
+#[derive(Debug, Clone, PartialEq, Eq, RefCast)]
+pub struct SchemaChange(cedar_policy_validator::SchemaChange);
+
+impl SchemaChange {
+    /// Whether entities or requests which were valid under the old schema may
+    /// be invalid under the new one
+    pub fn is_breaking(&self) -> bool {
+        self.0.is_breaking()
+    }
+}
+
+impl std::fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Schema elements which no policy in a policy set uses, as computed by
+/// [`PolicySet::unused_schema_elements`].
+#[repr(transparent)]
+#[derive(Debug, Clone, PartialEq, Eq, RefCast)]
+pub struct SchemaUsageReport(cedar_policy_validator::SchemaUsageReport);
+
+impl SchemaUsageReport {
+    /// Entity types which are neither the principal or resource type of a
+    /// request any policy applies to, nor the type of any expression in a
+    /// policy
+    pub fn unused_entity_types(&self) -> impl Iterator<Item = &EntityTypeName> {
+        self.0.unused_entity_types().map(RefCast::ref_cast)
+    }
+
+    /// Attributes of entity types which no policy reads or tests for with
+    /// `has`
+    pub fn unused_attributes(&self) -> impl Iterator<Item = (&EntityTypeName, &str)> {
+        self.0
+            .unused_attributes()
+            .map(|(ety, attr)| (RefCast::ref_cast(ety), attr.as_str()))
+    }
+
+    /// Entity types declaring a tag type whose tags no policy reads or tests
+    /// for with `hasTag`
+    pub fn unused_tags(&self) -> impl Iterator<Item = &EntityTypeName> {
+        self.0.unused_tags().map(RefCast::ref_cast)
+    }
+
+    /// Actions which may appear in a request but no policy applies to
+    pub fn unused_actions(&self) -> impl Iterator<Item = &EntityUid> {
+        self.0.unused_actions().map(RefCast::ref_cast)
+    }
+
+    /// Actions which some `forbid` policy applies to, but no `permit` policy
+    /// does, so that every request for them is denied
+    pub fn unpermitted_actions(&self) -> impl Iterator<Item = &EntityUid> {
+        self.0.unpermitted_actions().map(RefCast::ref_cast)
+    }
+
+    /// Fully qualified names of common types which are not part of the type
+    /// of any used attribute or tag, nor of the context of any used action
+    pub fn unused_common_types(&self) -> impl Iterator<Item = &str> {
+        self.0.unused_common_types().map(SmolStr::as_str)
+    }
+
+    /// Whether every schema element is used
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 /// Contains the result of policy validation.
@@ -2720,6 +3988,76 @@ impl PolicySet {
         entity_uids
     }
 
+    /// Analyze the policies in this set against `schema`, reporting permits
+    /// shadowed by another permit or a forbid
+    /// ([`ValidationWarning::ShadowedPermit`]), permit and forbid policies
+    /// which may apply to the same requests
+    /// ([`ValidationWarning::ConflictingPolicies`]), and duplicate policies
+    /// ([`ValidationWarning::DuplicatePolicy`]).
+    ///
+    /// The analysis compares policy scopes and conditions syntactically, so
+    /// it never reports a permit as shadowed when it is not, but may miss
+    /// shadowing which depends on entity data. It assumes the policies
+    /// validate against `schema`.
+    ///
+    /// ```
+    /// # use cedar_policy::{PolicySet, Schema};
+    /// let schema: Schema = r#"
+    ///     entity User;
+    ///     entity Doc;
+    ///     action view appliesTo { principal: User, resource: Doc };
+    /// "#.parse().unwrap();
+    /// let policies: PolicySet = r#"
+    ///     permit(principal, action, resource);
+    ///     permit(principal == User::"alice", action, resource);
+    /// "#.parse().unwrap();
+    /// let warnings = policies.analyze(&schema);
+    /// assert_eq!(warnings.len(), 1);
+    /// assert_eq!(warnings[0].policy_id().to_string(), "policy1");
+    /// ```
+    pub fn analyze(&self, schema: &Schema) -> Vec<ValidationWarning> {
+        let validator = cedar_policy_validator::Validator::new(schema.0.clone());
+        validator
+            .analyze(&self.ast)
+            .map(ValidationWarning::from)
+            .collect()
+    }
+
+    /// Find the entity types, attributes, tags, actions and common types in
+    /// `schema` which no policy in this set uses, along with the actions
+    /// which some `forbid` policy but no `permit` policy applies to.
+    ///
+    /// Attribute accesses are attributed to the entity types the typechecker
+    /// assigns to the accessed expression in each request environment, so an
+    /// attribute read only where a policy can never apply counts as unused.
+    /// Unused attributes need not be loaded into [`Entities`]. The policies
+    /// should validate against `schema` in strict mode.
+    ///
+    /// ```
+    /// # use cedar_policy::{PolicySet, Schema};
+    /// let schema: Schema = r#"
+    ///     entity User { name: String, level: Long };
+    ///     entity Doc;
+    ///     action view, edit appliesTo { principal: User, resource: Doc };
+    /// "#.parse().unwrap();
+    /// let policies: PolicySet = r#"
+    ///     permit(principal, action == Action::"view", resource) when { principal.level > 3 };
+    /// "#.parse().unwrap();
+    /// let report = policies.unused_schema_elements(&schema);
+    /// assert_eq!(
+    ///     report.unused_attributes().map(|(ety, attr)| format!("{ety}.{attr}")).collect::<Vec<_>>(),
+    ///     ["User.name"]
+    /// );
+    /// assert_eq!(
+    ///     report.unused_actions().map(ToString::to_string).collect::<Vec<_>>(),
+    ///     [r#"Action::"edit""#]
+    /// );
+    /// ```
+    pub fn unused_schema_elements(&self, schema: &Schema) -> SchemaUsageReport {
+        let validator = cedar_policy_validator::Validator::new(schema.0.clone());
+        SchemaUsageReport(validator.unused_schema_elements(&self.ast))
+    }
+
     /// Unlink a template-linked policy from the policy set.
     /// Returns the policy that was unlinked.
     pub fn unlink(&mut self, policy_id: PolicyId) -> Result<Policy, PolicySetError> {
@@ -3870,6 +5208,12 @@ impl FromStr for Expression {
     }
 }
 
+impl std::fmt::Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 /// "Restricted" expressions are used for attribute values and `context`.
 ///
 /// Restricted expressions can contain only the following:
@@ -4723,6 +6067,53 @@ pub fn eval_expression(
     ))
 }
 
+/// Result of partially evaluating an expression with [`partial_eval_expression`]
+#[doc = include_str!("../experimental_warning.md")]
+#[cfg(feature = "partial-eval")]
+#[derive(Debug, Clone)]
+pub enum PartialEvalResult {
+    /// The expression evaluated to a value
+    Value(EvalResult),
+    /// The expression depends on unknowns, and simplified to this residual
+    Residual(Expression),
+}
+
+/// Partially evaluates an expression.
+///
+/// Unlike [`eval_expression`], the `request` may have unknown components
+/// (see [`RequestBuilder`]) and `expr` and `entities` may contain unknowns.
+/// If the value of `expr` depends on them, returns the residual expression
+/// it simplifies to.
+///
+/// ```
+/// # use cedar_policy::{partial_eval_expression, Entities, Expression, PartialEvalResult, Request};
+/// # use std::str::FromStr;
+/// let request = Request::builder()
+///     .principal(r#"User::"alice""#.parse().unwrap())
+///     .build();
+/// let expr = Expression::from_str("principal has name || resource == principal").unwrap();
+/// let result = partial_eval_expression(&request, &Entities::empty(), &expr).unwrap();
+/// let PartialEvalResult::Residual(residual) = result else {
+///     panic!("expected a residual");
+/// };
+/// assert_eq!(residual.to_string(), r#"false || ((unknown("resource")) == User::"alice")"#);
+/// ```
+#[doc = include_str!("../experimental_warning.md")]
+#[cfg(feature = "partial-eval")]
+pub fn partial_eval_expression(
+    request: &Request,
+    entities: &Entities,
+    expr: &Expression,
+) -> Result<PartialEvalResult, EvaluationError> {
+    let all_ext = Extensions::all_available();
+    let eval = Evaluator::new(request.0.clone(), &entities.0, all_ext);
+    // Evaluate under the empty slot map, as an expression should not have slots
+    match eval.partial_interpret(&expr.0, &ast::SlotEnv::new())? {
+        ast::PartialValue::Value(v) => Ok(PartialEvalResult::Value(EvalResult::from(v))),
+        ast::PartialValue::Residual(r) => Ok(PartialEvalResult::Residual(Expression(r))),
+    }
+}
+
 // These are the same tests in validator, just ensuring all the plumbing is done correctly
 #[cfg(test)]
 mod test_access {
@@ -0,0 +1,639 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! This module defines the audit log of authorization decisions, and the
+//! [`AuditedAuthorizer`] which records to it
+
+use super::{
+    AuthorizationError, Authorizer, CoverageReport, Decision, Entities, EntityStore,
+    EvaluationTrace, PolicyId, PolicySet, Request, Response,
+};
+use cedar_policy_core::entities::json::CedarValueJson;
+use cedar_policy_core::{ast, entities};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A record of one authorization decision, emitted to an [`AuditSink`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    /// When the decision was made, in milliseconds since the Unix epoch
+    timestamp_ms: u64,
+    /// The principal of the request, if known
+    principal: Option<String>,
+    /// The action of the request, if known
+    action: Option<String>,
+    /// The resource of the request, if known
+    resource: Option<String>,
+    /// The context of the request, as JSON if it can be represented as JSON
+    /// and otherwise as a Cedar expression string
+    context: Option<serde_json::Value>,
+    /// The decision
+    decision: Decision,
+    /// The policies which determined the decision
+    determining_policies: Vec<AuditedPolicy>,
+    /// The errors encountered evaluating policies
+    errors: Vec<AuditedError>,
+    /// How long authorization took, in microseconds. For a batch, this is the
+    /// time taken to authorize the whole batch.
+    duration_micros: u64,
+    /// Hex-encoded SHA-256 hash of the policy set
+    policy_set_hash: String,
+    /// Hex-encoded SHA-256 hash of the entities, or `None` if the entities
+    /// were loaded from an [`EntityStore`](super::EntityStore)
+    entities_hash: Option<String>,
+}
+
+impl AuditRecord {
+    /// When the decision was made, in milliseconds since the Unix epoch
+    pub fn timestamp_ms(&self) -> u64 {
+        self.timestamp_ms
+    }
+
+    /// The principal of the request, if known
+    pub fn principal(&self) -> Option<&str> {
+        self.principal.as_deref()
+    }
+
+    /// The action of the request, if known
+    pub fn action(&self) -> Option<&str> {
+        self.action.as_deref()
+    }
+
+    /// The resource of the request, if known
+    pub fn resource(&self) -> Option<&str> {
+        self.resource.as_deref()
+    }
+
+    /// The context of the request, as JSON if it can be represented as JSON
+    /// and otherwise as a Cedar expression string
+    pub fn context(&self) -> Option<&serde_json::Value> {
+        self.context.as_ref()
+    }
+
+    /// The decision
+    pub fn decision(&self) -> Decision {
+        self.decision
+    }
+
+    /// The policies which determined the decision, with the annotations
+    /// selected by [`AuditLog::with_annotations`]
+    pub fn determining_policies(&self) -> impl Iterator<Item = &AuditedPolicy> {
+        self.determining_policies.iter()
+    }
+
+    /// The errors encountered evaluating policies
+    pub fn errors(&self) -> impl Iterator<Item = &AuditedError> {
+        self.errors.iter()
+    }
+
+    /// How long authorization took. For a batch, this is the time taken to
+    /// authorize the whole batch.
+    pub fn duration(&self) -> Duration {
+        Duration::from_micros(self.duration_micros)
+    }
+
+    /// Hex-encoded SHA-256 hash of the policy set. Policy sets with the same
+    /// policies, templates and links have the same hash, regardless of
+    /// formatting and the order of policies.
+    pub fn policy_set_hash(&self) -> &str {
+        &self.policy_set_hash
+    }
+
+    /// Hex-encoded SHA-256 hash of the entities, or `None` if the entities
+    /// were loaded from an [`EntityStore`](super::EntityStore). Sets of
+    /// entities with the same uids, attributes, tags and ancestors have the
+    /// same hash.
+    pub fn entities_hash(&self) -> Option<&str> {
+        self.entities_hash.as_deref()
+    }
+}
+
+/// A policy which determined the decision in an [`AuditRecord`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditedPolicy {
+    /// Id of the policy
+    id: PolicyId,
+    /// The annotations of the policy selected by
+    /// [`AuditLog::with_annotations`]
+    annotations: BTreeMap<String, String>,
+}
+
+impl AuditedPolicy {
+    /// Id of the policy
+    pub fn id(&self) -> &PolicyId {
+        &self.id
+    }
+
+    /// Get the value of the annotation `key`, if it was selected by
+    /// [`AuditLog::with_annotations`] and the policy has it
+    pub fn annotation(&self, key: impl AsRef<str>) -> Option<&str> {
+        self.annotations.get(key.as_ref()).map(String::as_str)
+    }
+}
+
+/// An error evaluating a policy in an [`AuditRecord`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditedError {
+    /// Id of the policy which encountered the error
+    policy_id: PolicyId,
+    /// The error message
+    message: String,
+}
+
+impl AuditedError {
+    /// Id of the policy which encountered the error
+    pub fn policy_id(&self) -> &PolicyId {
+        &self.policy_id
+    }
+
+    /// The error message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// A destination for [`AuditRecord`]s
+///
+/// An [`AuditedAuthorizer`] calls [`AuditSink::record`] once for
+/// each decision, possibly from many threads at once. Authorization can't
+/// fail because of the sink, so a sink which fails to record a decision
+/// should keep the failure for its owner to inspect, like
+/// [`JsonLinesAuditSink::take_error`].
+pub trait AuditSink: Send + Sync {
+    /// Record one decision
+    fn record(&self, record: AuditRecord);
+}
+
+/// An [`AuditSink`] which keeps the records in memory
+#[derive(Debug, Default)]
+pub struct MemoryAuditSink {
+    records: Mutex<Vec<AuditRecord>>,
+}
+
+impl MemoryAuditSink {
+    /// Create an empty `MemoryAuditSink`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the records so far, in the order they were recorded
+    pub fn records(&self) -> Vec<AuditRecord> {
+        self.records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Remove and return the records so far, in the order they were recorded
+    pub fn take(&self) -> Vec<AuditRecord> {
+        std::mem::take(&mut *self.records.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl AuditSink for MemoryAuditSink {
+    fn record(&self, record: AuditRecord) {
+        self.records
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(record);
+    }
+}
+
+/// An [`AuditSink`] which writes each record as a line of JSON
+///
+/// Each record is written and flushed as soon as it is recorded. If writing
+/// fails, the first error is kept and can be retrieved with
+/// [`JsonLinesAuditSink::take_error`].
+pub struct JsonLinesAuditSink {
+    writer: Mutex<Box<dyn std::io::Write + Send>>,
+    error: Mutex<Option<std::io::Error>>,
+}
+
+impl std::fmt::Debug for JsonLinesAuditSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonLinesAuditSink")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl JsonLinesAuditSink {
+    /// Create a `JsonLinesAuditSink` writing to `writer`
+    pub fn new(writer: impl std::io::Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+            error: Mutex::new(None),
+        }
+    }
+
+    /// Create a `JsonLinesAuditSink` appending to the file at `path`, which
+    /// is created if it does not exist
+    pub fn append_to_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self::new(file))
+    }
+
+    /// Take the first error encountered writing records since the last call
+    /// to `take_error`, if any
+    pub fn take_error(&self) -> Option<std::io::Error> {
+        self.error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    fn write(&self, record: &AuditRecord) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        writer.write_all(&line)?;
+        writer.flush()
+    }
+}
+
+impl AuditSink for JsonLinesAuditSink {
+    fn record(&self, record: AuditRecord) {
+        if let Err(e) = self.write(&record) {
+            self.error
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get_or_insert(e);
+        }
+    }
+}
+
+/// Configuration of the audit log of an [`AuditedAuthorizer`]: the sink to
+/// record decisions to, and the policy annotations to include in each record
+#[derive(Clone)]
+pub struct AuditLog {
+    sink: Arc<dyn AuditSink>,
+    annotations: Vec<String>,
+    /// The hashes of the most recently recorded policy set and entities,
+    /// shared between clones of this `AuditLog`
+    hashes: Arc<Mutex<HashCache>>,
+}
+
+/// The most recently hashed policy set and entities with their hashes, so
+/// that the hashes aren't recomputed for every call to the authorizer with
+/// the same policies and entities
+#[derive(Default)]
+struct HashCache {
+    policies: Option<Arc<(PolicySet, String)>>,
+    entities: Option<Arc<(Entities, String)>>,
+}
+
+/// Get the hash of `value` from the slot `slot` selects in `cache` if it was
+/// computed for a value which `same` says has the same hash, and otherwise
+/// compute it with `hash` and store it in the slot. Comparing and hashing
+/// take time linear in the size of `value`, so they are done without holding
+/// the lock, which is only held to get or replace the cached value.
+fn cached<T: Clone>(
+    cache: &Mutex<HashCache>,
+    slot: impl Fn(&mut HashCache) -> &mut Option<Arc<(T, String)>>,
+    value: &T,
+    same: impl FnOnce(&T, &T) -> bool,
+    hash: impl FnOnce(&T) -> String,
+) -> String {
+    let lock = || cache.lock().unwrap_or_else(PoisonError::into_inner);
+    let previous = slot(&mut lock()).clone();
+    match previous {
+        Some(previous) if same(&previous.0, value) => previous.1.clone(),
+        _ => {
+            let h = hash(value);
+            *slot(&mut lock()) = Some(Arc::new((value.clone(), h.clone())));
+            h
+        }
+    }
+}
+
+impl std::fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("annotations", &self.annotations)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AuditLog {
+    /// Create an `AuditLog` recording decisions to `sink`. Keep another
+    /// reference to `sink` to inspect it later.
+    pub fn new(sink: Arc<dyn AuditSink>) -> Self {
+        Self {
+            sink,
+            annotations: vec![],
+            hashes: Arc::default(),
+        }
+    }
+
+    /// Include the annotations with the given keys of the determining
+    /// policies in each record. No annotations are included by default.
+    #[must_use]
+    pub fn with_annotations(mut self, keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.annotations.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Record the decisions for the `requests` of one call to the authorizer,
+    /// which took `duration`
+    pub(super) fn record<'a>(
+        &self,
+        decisions: impl IntoIterator<Item = (&'a Request, &'a Response)>,
+        policies: &PolicySet,
+        entities: Option<&Entities>,
+        duration: Duration,
+    ) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX));
+        let duration_micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let policy_set_hash = cached(
+            &self.hashes,
+            |hashes| &mut hashes.policies,
+            policies,
+            PartialEq::eq,
+            policy_set_hash,
+        );
+        // `Entities` equality only compares entity uids
+        let entities_hash = entities.map(|e| {
+            cached(
+                &self.hashes,
+                |hashes| &mut hashes.entities,
+                e,
+                |a, b| a.0.same_snapshot(&b.0),
+                |e| entities_hash(&e.0),
+            )
+        });
+        for (request, response) in decisions {
+            self.sink.record(AuditRecord {
+                timestamp_ms,
+                principal: request.principal().map(ToString::to_string),
+                action: request.action().map(ToString::to_string),
+                resource: request.resource().map(ToString::to_string),
+                context: request.0.context().map(context_json),
+                decision: response.decision(),
+                determining_policies: response
+                    .diagnostics()
+                    .reason()
+                    .map(|id| AuditedPolicy {
+                        id: id.clone(),
+                        annotations: self
+                            .annotations
+                            .iter()
+                            .filter_map(|key| {
+                                policies
+                                    .annotation(id, key)
+                                    .map(|value| (key.clone(), value.to_owned()))
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+                errors: response
+                    .diagnostics()
+                    .errors()
+                    .map(|error| match error {
+                        AuthorizationError::PolicyEvaluationError(e) => AuditedError {
+                            policy_id: e.policy_id().clone(),
+                            message: error.to_string(),
+                        },
+                    })
+                    .collect(),
+                duration_micros,
+                policy_set_hash: policy_set_hash.clone(),
+                entities_hash: entities_hash.clone(),
+            });
+        }
+    }
+}
+
+/// An [`Authorizer`] which records every decision it makes to an [`AuditLog`],
+/// created with [`Authorizer::with_audit_log`]
+///
+/// Each call to [`AuditedAuthorizer::is_authorized`] and its variants records
+/// an [`AuditRecord`] for each request, and returns the same response as the
+/// same call to the [`Authorizer`].
+#[derive(Debug, Clone)]
+pub struct AuditedAuthorizer {
+    authorizer: Authorizer,
+    audit_log: AuditLog,
+}
+
+impl AuditedAuthorizer {
+    /// Create an `AuditedAuthorizer` recording the decisions of `authorizer`
+    /// to `audit_log`
+    pub fn new(authorizer: Authorizer, audit_log: AuditLog) -> Self {
+        Self {
+            authorizer,
+            audit_log,
+        }
+    }
+
+    /// Get the `Authorizer` whose decisions are recorded
+    pub fn authorizer(&self) -> &Authorizer {
+        &self.authorizer
+    }
+
+    /// Get the audit log decisions are recorded to
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit_log
+    }
+
+    /// Call `authorize` for the request `r`, recording the response
+    /// `response` extracts from its result to the audit log
+    fn audited<T>(
+        &self,
+        r: &Request,
+        p: &PolicySet,
+        e: Option<&Entities>,
+        authorize: impl FnOnce() -> T,
+        response: impl FnOnce(&T) -> &Response,
+    ) -> T {
+        let started = Instant::now();
+        let result = authorize();
+        self.audit_log
+            .record([(r, response(&result))], p, e, started.elapsed());
+        result
+    }
+
+    /// [`Authorizer::is_authorized`], recording the decision
+    pub fn is_authorized(&self, r: &Request, p: &PolicySet, e: &Entities) -> Response {
+        self.audited(
+            r,
+            p,
+            Some(e),
+            || self.authorizer.is_authorized(r, p, e),
+            |response| response,
+        )
+    }
+
+    /// [`Authorizer::is_authorized_batch`], recording the decisions. The time
+    /// recorded for each decision is the time taken by the whole batch.
+    pub fn is_authorized_batch(
+        &self,
+        requests: impl IntoIterator<Item = Request>,
+        p: &PolicySet,
+        e: &Entities,
+    ) -> Vec<Response> {
+        let requests = requests.into_iter().collect::<Vec<_>>();
+        let started = Instant::now();
+        let responses = self
+            .authorizer
+            .is_authorized_batch(requests.iter().cloned(), p, e);
+        self.audit_log.record(
+            requests.iter().zip(&responses),
+            p,
+            Some(e),
+            started.elapsed(),
+        );
+        responses
+    }
+
+    /// [`Authorizer::is_authorized_with_trace`], recording the decision
+    pub fn is_authorized_with_trace(
+        &self,
+        r: &Request,
+        p: &PolicySet,
+        e: &Entities,
+    ) -> (Response, HashMap<PolicyId, EvaluationTrace>) {
+        self.audited(
+            r,
+            p,
+            Some(e),
+            || self.authorizer.is_authorized_with_trace(r, p, e),
+            |(response, _)| response,
+        )
+    }
+
+    /// [`Authorizer::is_authorized_with_coverage`], recording the decision
+    pub fn is_authorized_with_coverage(
+        &self,
+        r: &Request,
+        p: &PolicySet,
+        e: &Entities,
+        coverage: &mut CoverageReport,
+    ) -> Response {
+        self.audited(
+            r,
+            p,
+            Some(e),
+            || {
+                self.authorizer
+                    .is_authorized_with_coverage(r, p, e, coverage)
+            },
+            |response| response,
+        )
+    }
+
+    /// [`Authorizer::is_authorized_with_store`], recording the decision. The
+    /// record has no entities hash, since the entities aren't known up front.
+    pub fn is_authorized_with_store(
+        &self,
+        r: &Request,
+        p: &PolicySet,
+        store: &(impl EntityStore + ?Sized),
+    ) -> Response {
+        self.audited(
+            r,
+            p,
+            None,
+            || self.authorizer.is_authorized_with_store(r, p, store),
+            |response| response,
+        )
+    }
+}
+
+fn context_json(context: &ast::Context) -> serde_json::Value {
+    let expr = ast::PartialValue::from(context.clone());
+    match &expr {
+        ast::PartialValue::Value(v) => CedarValueJson::from_value(v.clone())
+            .ok()
+            .and_then(|json| serde_json::to_value(json).ok()),
+        ast::PartialValue::Residual(_) => None,
+    }
+    .unwrap_or_else(|| serde_json::Value::String(expr.to_string()))
+}
+
+/// Feed `s` to `hasher`, prefixed with its length so that consecutive
+/// strings can't be confused
+fn update(hasher: &mut Sha256, s: &str) {
+    hasher.update((s.len() as u64).to_le_bytes());
+    hasher.update(s);
+}
+
+fn finish(hasher: Sha256) -> String {
+    use std::fmt::Write;
+    hasher.finalize().iter().fold(String::new(), |mut hex, b| {
+        // Writing to a `String` can't fail
+        let _ = write!(hex, "{b:02x}");
+        hex
+    })
+}
+
+fn policy_set_hash(policies: &PolicySet) -> String {
+    let mut hasher = Sha256::new();
+    for template in policies.templates().sorted_by_key(|t| t.id()) {
+        update(&mut hasher, "template");
+        update(&mut hasher, template.id().as_ref());
+        update(&mut hasher, &template.ast.to_string());
+    }
+    for policy in policies.policies().sorted_by_key(|p| p.id()) {
+        update(&mut hasher, "policy");
+        update(&mut hasher, policy.id().as_ref());
+        if policy.ast.is_static() {
+            update(&mut hasher, &policy.ast.to_string());
+        } else {
+            update(&mut hasher, policy.ast.template().id().as_ref());
+            for (slot, uid) in policy.ast.env().iter().sorted_by_key(|(slot, _)| **slot) {
+                update(&mut hasher, &slot.to_string());
+                update(&mut hasher, &uid.to_string());
+            }
+        }
+    }
+    finish(hasher)
+}
+
+fn entities_hash(entities: &entities::Entities) -> String {
+    let mut hasher = Sha256::new();
+    for entity in entities.iter().sorted_by_key(|e| e.uid()) {
+        update(&mut hasher, "entity");
+        update(&mut hasher, &entity.uid().to_string());
+        for (attr, value) in entity.attrs() {
+            update(&mut hasher, "attr");
+            update(&mut hasher, attr);
+            update(&mut hasher, &value.to_string());
+        }
+        for (tag, value) in entity.tags() {
+            update(&mut hasher, "tag");
+            update(&mut hasher, tag);
+            update(&mut hasher, &value.to_string());
+        }
+        for ancestor in entities.ancestors(entity).sorted() {
+            update(&mut hasher, "ancestor");
+            update(&mut hasher, &ancestor.to_string());
+        }
+    }
+    finish(hasher)
+}
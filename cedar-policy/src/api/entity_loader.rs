@@ -0,0 +1,199 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! This module defines the publicly exported API for loading the entities
+//! needed to answer a request, as described by an [`EntityManifest`], from an
+//! external data store.
+
+use crate::entities_errors::EntityStoreError;
+use crate::{AccessTrie, Entities, Entity, EntityManifest, EntitySliceError, EntityUid, Request};
+use cedar_policy_core::ast;
+use cedar_policy_validator::entity_manifest;
+use ref_cast::RefCast;
+use std::collections::HashSet;
+
+/// A request that an entity be loaded.
+///
+/// Only the fields of the entity described by [`EntityRequest::access_trie`]
+/// are needed, but it is sound to load other fields as well.
+#[doc = include_str!("../../experimental_warning.md")]
+#[repr(transparent)]
+#[derive(Debug, Clone, RefCast)]
+pub struct EntityRequest(entity_manifest::EntityRequest);
+
+impl EntityRequest {
+    /// The id of the entity requested
+    pub fn entity_id(&self) -> &EntityUid {
+        EntityUid::ref_cast(self.0.entity_id())
+    }
+
+    /// The fields of the entity requested. Entities referenced by the
+    /// requested fields are requested separately, so this trie does not
+    /// include any entity dereferences.
+    pub fn access_trie(&self) -> &AccessTrie {
+        self.0.access_trie()
+    }
+}
+
+/// A request that some of the ancestors of an entity be loaded.
+#[doc = include_str!("../../experimental_warning.md")]
+#[repr(transparent)]
+#[derive(Debug, Clone, RefCast)]
+pub struct AncestorsRequest(entity_manifest::AncestorsRequest);
+
+impl AncestorsRequest {
+    /// The id of the entity whose ancestors are requested
+    pub fn entity_id(&self) -> &EntityUid {
+        EntityUid::ref_cast(self.0.entity_id())
+    }
+
+    /// The entities which need to be checked. Only those which are ancestors
+    /// of the entity are needed, but it is sound to load other ancestors as
+    /// well.
+    pub fn ancestors(&self) -> impl Iterator<Item = &EntityUid> {
+        self.0.ancestors().iter().map(EntityUid::ref_cast)
+    }
+}
+
+/// Implement [`EntityLoader`] to load only the fields of entities that an
+/// [`EntityManifest`] says are needed to answer a request.
+///
+/// Use [`load_entities`] to load the entities for a request. To load whole
+/// entities instead, implement [`SimpleEntityLoader`].
+///
+/// Warning: `load_entities` is called multiple times. If database
+/// consistency is required, this API should not be used. Instead, use the
+/// entity manifest directly.
+#[doc = include_str!("../../experimental_warning.md")]
+pub trait EntityLoader {
+    /// Load the entities requested in `to_load`, in batches. The result must
+    /// have one element for each request, which is `None` if the entity does
+    /// not exist.
+    ///
+    /// Only the fields in each request's [`AccessTrie`] are needed, but it is
+    /// sound to load other fields as well. The same entity may be requested
+    /// more than once, with different [`AccessTrie`]s. The ancestors of the
+    /// loaded entities may be omitted, since they are loaded by
+    /// `load_ancestors`.
+    fn load_entities(
+        &mut self,
+        to_load: &[EntityRequest],
+    ) -> Result<Vec<Option<Entity>>, EntityStoreError>;
+
+    /// Load the ancestors requested in `to_load`. The result must have one
+    /// set of ancestors for each request.
+    ///
+    /// Only the ancestors listed in each request are needed, but it is sound
+    /// to load other ancestors as well.
+    fn load_ancestors(
+        &mut self,
+        to_load: &[AncestorsRequest],
+    ) -> Result<Vec<HashSet<EntityUid>>, EntityStoreError>;
+}
+
+/// Implement [`SimpleEntityLoader`] to load whole entities, by id, from an
+/// external data store.
+///
+/// Every type implementing `SimpleEntityLoader` is an [`EntityLoader`], so can
+/// be used with [`load_entities`].
+#[doc = include_str!("../../experimental_warning.md")]
+pub trait SimpleEntityLoader {
+    /// Load the entities with the ids in `to_load`. The result must have one
+    /// element for each id, which is `None` if the entity does not exist.
+    ///
+    /// Each entity must be loaded with all of its attributes and all of its
+    /// ancestors, including indirect ancestors.
+    fn load_entities(
+        &mut self,
+        to_load: &[EntityUid],
+    ) -> Result<Vec<Option<Entity>>, EntityStoreError>;
+}
+
+impl<L: SimpleEntityLoader + ?Sized> EntityLoader for L {
+    fn load_entities(
+        &mut self,
+        to_load: &[EntityRequest],
+    ) -> Result<Vec<Option<Entity>>, EntityStoreError> {
+        let uids = to_load
+            .iter()
+            .map(|request| request.entity_id().clone())
+            .collect::<Vec<_>>();
+        SimpleEntityLoader::load_entities(self, &uids)
+    }
+
+    fn load_ancestors(
+        &mut self,
+        to_load: &[AncestorsRequest],
+    ) -> Result<Vec<HashSet<EntityUid>>, EntityStoreError> {
+        // whole entities are loaded with all of their ancestors already
+        Ok(vec![HashSet::new(); to_load.len()])
+    }
+}
+
+/// Adapts an [`EntityLoader`] to the interface used by
+/// [`entity_manifest::load_entities`]
+struct LoaderAdapter<'a, L: ?Sized>(&'a mut L);
+
+impl<L: EntityLoader + ?Sized> entity_manifest::EntityLoader for LoaderAdapter<'_, L> {
+    fn load_entities(
+        &mut self,
+        to_load: &[entity_manifest::EntityRequest],
+    ) -> Result<Vec<entity_manifest::EntityAnswer>, EntitySliceError> {
+        let to_load = to_load
+            .iter()
+            .cloned()
+            .map(EntityRequest)
+            .collect::<Vec<_>>();
+        Ok(self
+            .0
+            .load_entities(&to_load)?
+            .into_iter()
+            .map(|entity| entity.map(|e| e.0))
+            .collect())
+    }
+
+    fn load_ancestors(
+        &mut self,
+        to_load: &[entity_manifest::AncestorsRequest],
+    ) -> Result<Vec<HashSet<ast::EntityUID>>, EntitySliceError> {
+        let to_load = to_load
+            .iter()
+            .cloned()
+            .map(AncestorsRequest)
+            .collect::<Vec<_>>();
+        Ok(self
+            .0
+            .load_ancestors(&to_load)?
+            .into_iter()
+            .map(|ancestors| ancestors.into_iter().map(ast::EntityUID::from).collect())
+            .collect())
+    }
+}
+
+/// Load the entities needed to answer `request`, as described by `manifest`,
+/// using `loader`.
+///
+/// The resulting [`Entities`] can be passed to
+/// [`crate::Authorizer::is_authorized`] to answer `request`, with the same
+/// result as if every entity in the loader's data store had been loaded.
+#[doc = include_str!("../../experimental_warning.md")]
+pub fn load_entities(
+    manifest: &EntityManifest,
+    request: &Request,
+    loader: &mut (impl EntityLoader + ?Sized),
+) -> Result<Entities, EntitySliceError> {
+    entity_manifest::load_entities(manifest, &request.0, &mut LoaderAdapter(loader)).map(Entities)
+}
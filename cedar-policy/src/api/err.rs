@@ -35,6 +35,8 @@ pub use cedar_policy_validator::entity_manifest::slicing::EntitySliceError;
 use cedar_policy_validator::entity_manifest::{
     self, PartialExpressionError, PartialRequestError, UnsupportedCedarFeatureError,
 };
+#[cfg(feature = "partial-eval")]
+pub use cedar_policy_validator::typed_partial_eval::TypedPartialRequestError;
 pub use cedar_policy_validator::{schema_errors, SchemaError};
 use miette::Diagnostic;
 use ref_cast::RefCast;
@@ -42,12 +44,14 @@ use smol_str::SmolStr;
 use thiserror::Error;
 use to_cedar_syntax_errors::NameCollisionsError;
 
-#[cfg(feature = "entity-manifest")]
+#[cfg(any(feature = "entity-manifest", feature = "partial-eval"))]
 use super::ValidationResult;
 
 /// Errors related to [`crate::Entities`]
 pub mod entities_errors {
-    pub use cedar_policy_core::entities::err::{Duplicate, EntitiesError, TransitiveClosureError};
+    pub use cedar_policy_core::entities::err::{
+        Duplicate, EntitiesError, EntityStoreError, MissingEntity, TransitiveClosureError,
+    };
 }
 
 /// Errors related to serializing/deserializing entities or contexts to/from JSON
@@ -170,6 +174,39 @@ impl From<cedar_policy_core::authorizer::ReauthorizationError> for Reauthorizati
     }
 }
 
+/// Errors that can be encountered when partially evaluating a request with a
+/// schema
+#[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
+#[cfg(feature = "partial-eval")]
+pub enum TypedPartialEvaluationError {
+    /// The request does not match a request environment of the schema
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Request(#[from] TypedPartialRequestError),
+    /// Some residuals failed to typecheck
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Validation(#[from] ValidationResult),
+}
+
+#[cfg(feature = "partial-eval")]
+#[doc(hidden)]
+impl From<cedar_policy_validator::typed_partial_eval::TypedPartialEvalError>
+    for TypedPartialEvaluationError
+{
+    fn from(e: cedar_policy_validator::typed_partial_eval::TypedPartialEvalError) -> Self {
+        match e {
+            cedar_policy_validator::typed_partial_eval::TypedPartialEvalError::Request(e) => {
+                Self::Request(e)
+            }
+            cedar_policy_validator::typed_partial_eval::TypedPartialEvalError::Validation(e) => {
+                Self::Validation(e.into())
+            }
+        }
+    }
+}
+
 /// Errors serializing Schemas to the Cedar syntax
 #[derive(Debug, Error, Diagnostic)]
 #[non_exhaustive]
@@ -572,6 +609,22 @@ pub enum ValidationWarning {
     #[diagnostic(transparent)]
     #[error(transparent)]
     ImpossiblePolicy(#[from] validation_warnings::ImpossiblePolicy),
+    /// Policy analysis found that a permit policy only applies to requests
+    /// which another permit or a forbid policy also applies to. The permit is
+    /// either redundant or never allows a request.
+    #[diagnostic(transparent)]
+    #[error(transparent)]
+    ShadowedPermit(#[from] validation_warnings::ShadowedPermit),
+    /// Policy analysis found a permit and a forbid policy which may apply to
+    /// requests with the same principal type, action and resource type.
+    #[diagnostic(transparent)]
+    #[error(transparent)]
+    ConflictingPolicies(#[from] validation_warnings::ConflictingPolicies),
+    /// Policy analysis found a policy which is the same as another policy, up
+    /// to their ids, annotations and source locations.
+    #[diagnostic(transparent)]
+    #[error(transparent)]
+    DuplicatePolicy(#[from] validation_warnings::DuplicatePolicy),
 }
 
 impl ValidationWarning {
@@ -584,6 +637,9 @@ impl ValidationWarning {
             Self::MixedScriptIdentifier(w) => w.policy_id(),
             Self::ConfusableIdentifier(w) => w.policy_id(),
             Self::ImpossiblePolicy(w) => w.policy_id(),
+            Self::ShadowedPermit(w) => w.policy_id(),
+            Self::ConflictingPolicies(w) => w.policy_id(),
+            Self::DuplicatePolicy(w) => w.policy_id(),
         }
     }
 }
@@ -610,6 +666,15 @@ impl From<cedar_policy_validator::ValidationWarning> for ValidationWarning {
             cedar_policy_validator::ValidationWarning::ImpossiblePolicy(w) => {
                 Self::ImpossiblePolicy(w.into())
             }
+            cedar_policy_validator::ValidationWarning::ShadowedPermit(w) => {
+                Self::ShadowedPermit(w.into())
+            }
+            cedar_policy_validator::ValidationWarning::ConflictingPolicies(w) => {
+                Self::ConflictingPolicies(w.into())
+            }
+            cedar_policy_validator::ValidationWarning::DuplicatePolicy(w) => {
+                Self::DuplicatePolicy(w.into())
+            }
         }
     }
 }
@@ -62,3 +62,6 @@ wrap_core_warning!(BidiCharsInIdentifier);
 wrap_core_warning!(MixedScriptIdentifier);
 wrap_core_warning!(ConfusableIdentifier);
 wrap_core_warning!(ImpossiblePolicy);
+wrap_core_warning!(ShadowedPermit);
+wrap_core_warning!(ConflictingPolicies);
+wrap_core_warning!(DuplicatePolicy);
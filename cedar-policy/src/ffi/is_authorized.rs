@@ -20,14 +20,15 @@
 #![allow(clippy::module_name_repetitions)]
 #[cfg(feature = "partial-eval")]
 use super::utils::JsonValueWithNoDuplicateKeys;
-use super::utils::{Context, DetailedError, Entities, EntityUid, PolicySet, Schema, WithWarnings};
-use crate::{Authorizer, Decision, PolicyId, Request};
+use super::utils::{
+    Context, DetailedError, Entities, EntityUid, PolicySet, Schema, SourceLocation, WithWarnings,
+};
+use crate::{Authorizer, Decision, ErrorHandling, PolicyId, Request};
 use cedar_policy_validator::cedar_schema::SchemaWarning;
+use itertools::{Either, Itertools};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-#[cfg(feature = "partial-eval")]
-use std::collections::HashMap;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 #[cfg(feature = "partial-eval")]
 use std::convert::Infallible;
 #[cfg(feature = "wasm")]
@@ -41,21 +42,27 @@ thread_local!(
     static AUTHORIZER: Authorizer = Authorizer::new();
 );
 
+/// Call `f` with the per-thread authorizer, using the given error-handling mode
+fn with_authorizer<T>(error_handling: ErrorHandling, f: impl FnOnce(&Authorizer) -> T) -> T {
+    AUTHORIZER.with(|authorizer| f(&authorizer.clone().with_error_handling(error_handling)))
+}
+
 /// Basic interface, using [`AuthorizationCall`] and [`AuthorizationAnswer`] types
 #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = "isAuthorized"))]
 pub fn is_authorized(call: AuthorizationCall) -> AuthorizationAnswer {
+    let (trace, error_handling) = (call.trace, call.error_handling);
     match call.parse() {
         WithWarnings {
             t: Ok((request, policies, entities)),
             warnings,
-        } => AuthorizationAnswer::Success {
-            response: AUTHORIZER.with(|authorizer| {
-                authorizer
-                    .is_authorized(&request, &policies, &entities)
-                    .into()
-            }),
-            warnings: warnings.into_iter().map(Into::into).collect(),
-        },
+        } => authorization_answer(
+            &request,
+            &policies,
+            &entities,
+            error_handling,
+            trace,
+            warnings.into_iter().map(Into::into).collect(),
+        ),
         WithWarnings {
             t: Err(errors),
             warnings,
@@ -66,6 +73,36 @@ pub fn is_authorized(call: AuthorizationCall) -> AuthorizationAnswer {
     }
 }
 
+/// Authorize a parsed request, building the [`AuthorizationAnswer`] returned
+/// by [`is_authorized()`]
+pub(super) fn authorization_answer(
+    request: &Request,
+    policies: &crate::PolicySet,
+    entities: &crate::Entities,
+    error_handling: ErrorHandling,
+    trace: bool,
+    warnings: Vec<DetailedError>,
+) -> AuthorizationAnswer {
+    let (response, trace) = with_authorizer(error_handling, |authorizer| {
+        if trace {
+            let (response, traces) =
+                authorizer.is_authorized_with_trace(request, policies, entities);
+            let traces = traces
+                .into_iter()
+                .map(|(id, trace)| (id, (&trace).into()))
+                .collect();
+            (response, Some(traces))
+        } else {
+            (authorizer.is_authorized(request, policies, entities), None)
+        }
+    });
+    AuthorizationAnswer::Success {
+        response: response.into(),
+        trace,
+        warnings,
+    }
+}
+
 /// Input is a JSON encoding of [`AuthorizationCall`] and output is a JSON
 /// encoding of [`AuthorizationAnswer`]
 ///
@@ -90,31 +127,126 @@ pub fn is_authorized_json_str(json: &str) -> Result<String, serde_json::Error> {
     serde_json::to_string(&ans)
 }
 
+/// Batch interface, using [`BatchAuthorizationCall`] and
+/// [`BatchAuthorizationAnswer`] types
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = "isAuthorizedBatch"))]
+pub fn is_authorized_batch(call: BatchAuthorizationCall) -> BatchAuthorizationAnswer {
+    let error_handling = call.error_handling;
+    match call.parse() {
+        WithWarnings {
+            t: Ok((requests, policies, entities)),
+            warnings,
+        } => batch_authorization_answer(
+            requests,
+            &policies,
+            &entities,
+            error_handling,
+            warnings.into_iter().map(Into::into).collect(),
+        ),
+        WithWarnings {
+            t: Err(errors),
+            warnings,
+        } => BatchAuthorizationAnswer::Failure {
+            errors: errors.into_iter().map(Into::into).collect(),
+            warnings: warnings.into_iter().map(Into::into).collect(),
+        },
+    }
+}
+
+/// Authorize parsed requests, building the [`BatchAuthorizationAnswer`]
+/// returned by [`is_authorized_batch()`]
+pub(super) fn batch_authorization_answer(
+    requests: Vec<Result<Request, Vec<miette::Report>>>,
+    policies: &crate::PolicySet,
+    entities: &crate::Entities,
+    error_handling: ErrorHandling,
+    warnings: Vec<DetailedError>,
+) -> BatchAuthorizationAnswer {
+    let (parsed, failures): (Vec<_>, Vec<_>) =
+        requests
+            .into_iter()
+            .enumerate()
+            .partition_map(|(i, request)| match request {
+                Ok(request) => Either::Left((i, request)),
+                Err(errors) => Either::Right((i, errors)),
+            });
+    let (indices, requests): (Vec<_>, Vec<_>) = parsed.into_iter().unzip();
+    let responses = with_authorizer(error_handling, |authorizer| {
+        authorizer.is_authorized_batch(requests, policies, entities)
+    });
+    let mut answers = indices
+        .into_iter()
+        .zip(responses)
+        .map(|(i, response)| {
+            (
+                i,
+                AuthorizationAnswer::Success {
+                    response: response.into(),
+                    trace: None,
+                    warnings: vec![],
+                },
+            )
+        })
+        .chain(failures.into_iter().map(|(i, errors)| {
+            (
+                i,
+                AuthorizationAnswer::Failure {
+                    errors: errors.into_iter().map(Into::into).collect(),
+                    warnings: vec![],
+                },
+            )
+        }))
+        .collect::<Vec<_>>();
+    answers.sort_by_key(|(i, _)| *i);
+    BatchAuthorizationAnswer::Success {
+        answers: answers.into_iter().map(|(_, answer)| answer).collect(),
+        warnings,
+    }
+}
+
+/// Input is a JSON encoding of [`BatchAuthorizationCall`] and output is a
+/// JSON encoding of [`BatchAuthorizationAnswer`]
+///
+/// # Errors
+///
+/// Will return `Err` if the input JSON cannot be deserialized as a
+/// [`BatchAuthorizationCall`].
+pub fn is_authorized_batch_json(
+    json: serde_json::Value,
+) -> Result<serde_json::Value, serde_json::Error> {
+    let ans = is_authorized_batch(serde_json::from_value(json)?);
+    serde_json::to_value(ans)
+}
+
+/// Input and output are strings containing serialized JSON, in the shapes
+/// expected by [`is_authorized_batch_json()`]
+///
+/// # Errors
+///
+/// Will return `Err` if the input cannot be converted to valid JSON or
+/// deserialized as a [`BatchAuthorizationCall`].
+pub fn is_authorized_batch_json_str(json: &str) -> Result<String, serde_json::Error> {
+    let ans = is_authorized_batch(serde_json::from_str(json)?);
+    serde_json::to_string(&ans)
+}
+
 /// Basic interface for partial evaluation, using [`AuthorizationCall`] and
 /// [`PartialAuthorizationAnswer`] types
 #[doc = include_str!("../../experimental_warning.md")]
 #[cfg(feature = "partial-eval")]
 pub fn is_authorized_partial(call: PartialAuthorizationCall) -> PartialAuthorizationAnswer {
+    let error_handling = call.error_handling;
     match call.parse() {
         WithWarnings {
             t: Ok((request, policies, entities)),
             warnings,
-        } => {
-            let response = AUTHORIZER.with(|authorizer| {
-                authorizer.is_authorized_partial(&request, &policies, &entities)
-            });
-            let warnings = warnings.into_iter().map(Into::into).collect();
-            match ResidualResponse::try_from(response) {
-                Ok(response) => PartialAuthorizationAnswer::Residuals {
-                    response: Box::new(response),
-                    warnings,
-                },
-                Err(e) => PartialAuthorizationAnswer::Failure {
-                    errors: vec![miette::Report::new_boxed(e).into()],
-                    warnings,
-                },
-            }
-        }
+        } => partial_authorization_answer(
+            &request,
+            &policies,
+            &entities,
+            error_handling,
+            warnings.into_iter().map(Into::into).collect(),
+        ),
         WithWarnings {
             t: Err(errors),
             warnings,
@@ -125,6 +257,31 @@ pub fn is_authorized_partial(call: PartialAuthorizationCall) -> PartialAuthoriza
     }
 }
 
+/// Partially authorize a parsed request, building the
+/// [`PartialAuthorizationAnswer`] returned by [`is_authorized_partial()`]
+#[cfg(feature = "partial-eval")]
+pub(super) fn partial_authorization_answer(
+    request: &Request,
+    policies: &crate::PolicySet,
+    entities: &crate::Entities,
+    error_handling: ErrorHandling,
+    warnings: Vec<DetailedError>,
+) -> PartialAuthorizationAnswer {
+    let response = with_authorizer(error_handling, |authorizer| {
+        authorizer.is_authorized_partial(request, policies, entities)
+    });
+    match ResidualResponse::try_from(response) {
+        Ok(response) => PartialAuthorizationAnswer::Residuals {
+            response: Box::new(response),
+            warnings,
+        },
+        Err(e) => PartialAuthorizationAnswer::Failure {
+            errors: vec![miette::Report::new_boxed(e).into()],
+            warnings,
+        },
+    }
+}
+
 /// Input is a JSON encoding of [`AuthorizationCall`] and output is a JSON
 /// encoding of [`PartialAuthorizationAnswer`]
 ///
@@ -239,6 +396,82 @@ impl Diagnostics {
     }
 }
 
+/// Interface version of an [`crate::EvaluationTrace`], recording the
+/// evaluation of an expression in a policy and of the subexpressions evaluated
+/// to compute it
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify::Tsify))]
+#[cfg_attr(feature = "wasm", tsify(into_wasm_abi, from_wasm_abi))]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct EvaluationTrace {
+    /// The expression which was evaluated, in Cedar syntax
+    pub expr: String,
+    /// Location of the expression in the policy source, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "wasm", tsify(optional))]
+    pub source_location: Option<SourceLocation>,
+    /// The result of evaluating the expression
+    pub outcome: TraceOutcome,
+    /// Whether evaluation of the expression stopped early without evaluating
+    /// all of its operands, e.g. `false && ...` or `true || ...`
+    #[serde(default)]
+    pub short_circuited: bool,
+    /// Traces of the subexpressions which were evaluated in order to compute
+    /// this expression, in evaluation order
+    #[serde(default)]
+    pub children: Vec<Self>,
+}
+
+/// Interface version of a [`crate::TraceOutcome`]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify::Tsify))]
+#[cfg_attr(feature = "wasm", tsify(into_wasm_abi, from_wasm_abi))]
+#[serde(tag = "type")]
+#[serde(rename_all = "camelCase")]
+pub enum TraceOutcome {
+    /// The expression evaluated to a value
+    Value {
+        /// The value, in Cedar syntax
+        value: String,
+    },
+    /// The expression could only be partially evaluated
+    Residual {
+        /// The residual expression, in Cedar syntax
+        residual: String,
+    },
+    /// The expression failed to evaluate
+    Error {
+        /// The evaluation error
+        error: DetailedError,
+    },
+}
+
+impl From<&crate::EvaluationTrace> for EvaluationTrace {
+    fn from(trace: &crate::EvaluationTrace) -> Self {
+        Self {
+            expr: trace.expr().0.to_string(),
+            source_location: trace.source_span().map(|span| SourceLocation {
+                start: span.offset(),
+                end: span.offset() + span.len(),
+            }),
+            outcome: match trace.outcome() {
+                crate::TraceOutcome::Value(value) => TraceOutcome::Value {
+                    value: value.to_string(),
+                },
+                crate::TraceOutcome::Residual(residual) => TraceOutcome::Residual {
+                    residual: residual.0.to_string(),
+                },
+                crate::TraceOutcome::Error(error) => TraceOutcome::Error {
+                    error: (&error).into(),
+                },
+            },
+            short_circuited: trace.short_circuited(),
+            children: trace.children().map(Into::into).collect(),
+        }
+    }
+}
+
 /// Error (or warning) which occurred in a particular policy during authorization
 #[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
 #[cfg_attr(feature = "wasm", derive(tsify::Tsify))]
@@ -421,6 +654,11 @@ pub enum AuthorizationAnswer {
         /// Authorization decision and diagnostics, which may include policy
         /// evaluation errors
         response: Response,
+        /// Trace of the evaluation of every policy, by policy id. Only
+        /// present if requested with [`AuthorizationCall`]'s `trace` option.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "wasm", tsify(optional))]
+        trace: Option<HashMap<PolicyId, EvaluationTrace>>,
         /// Warnings encountered. These are all warnings not generated by
         /// authorization itself -- e.g. general warnings about your schema,
         /// entity data, etc. Warnings generated by authorization are part of
@@ -429,6 +667,38 @@ pub enum AuthorizationAnswer {
     },
 }
 
+/// Answer struct from batch authorization call
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[cfg_attr(feature = "wasm", derive(tsify::Tsify))]
+#[cfg_attr(feature = "wasm", tsify(into_wasm_abi, from_wasm_abi))]
+#[serde(rename_all = "camelCase")]
+pub enum BatchAuthorizationAnswer {
+    /// Represents a failure to parse the inputs shared by all requests, or to
+    /// call the authorizer entirely
+    #[serde(rename_all = "camelCase")]
+    Failure {
+        /// Errors encountered
+        errors: Vec<DetailedError>,
+        /// Warnings encountered
+        warnings: Vec<DetailedError>,
+    },
+    /// Represents a successful batch authorization call (although individual
+    /// requests may have failed to parse, and individual policy evaluation may
+    /// still have errors)
+    #[serde(rename_all = "camelCase")]
+    Success {
+        /// One answer per request, in the same order as the requests in the
+        /// call. Each answer is a [`AuthorizationAnswer::Failure`] if that
+        /// request could not be parsed.
+        answers: Vec<AuthorizationAnswer>,
+        /// Warnings encountered. These are all warnings not generated by
+        /// authorization itself -- e.g. general warnings about your schema,
+        /// entity data, etc.
+        warnings: Vec<DetailedError>,
+    },
+}
+
 /// Answer struct from partial-authorization call
 #[cfg(feature = "partial-eval")]
 #[derive(Debug, Serialize, Deserialize)]
@@ -487,12 +757,73 @@ pub struct AuthorizationCall {
     /// If a schema is not provided, this option has no effect.
     #[serde(default = "constant_true")]
     validate_request: bool,
+    /// How policies which encounter evaluation errors affect the decision.
+    /// Defaults to `skip`, as described by the Cedar language specification.
+    #[serde(default)]
+    #[cfg_attr(feature = "wasm", tsify(optional))]
+    error_handling: ErrorHandling,
+    /// If this is `true`, the answer includes a trace of the evaluation of
+    /// every policy, explaining why it evaluated to `true`, `false`, or an
+    /// error. Tracing makes authorization considerably slower.
+    #[serde(default)]
+    trace: bool,
+    /// The set of policies to use during authorization
+    policies: PolicySet,
+    /// The set of entities to use during authorization
+    entities: Entities,
+}
+
+/// Struct containing the input data for batch authorization. The schema,
+/// policies and entities are shared by every request in the batch.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify::Tsify))]
+#[cfg_attr(feature = "wasm", tsify(into_wasm_abi, from_wasm_abi))]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct BatchAuthorizationCall {
+    /// The requests to authorize
+    requests: Vec<BatchRequest>,
+    /// Optional schema.
+    /// If present, this will inform the parsing: for instance, it will allow
+    /// `__entity` and `__extn` escapes to be implicit, and it will error if
+    /// attributes have the wrong types (e.g., string instead of integer).
+    #[cfg_attr(feature = "wasm", tsify(optional, type = "Schema"))]
+    schema: Option<Schema>,
+    /// If this is `true` and a schema is provided, perform request validation.
+    /// If this is `false`, the schema will only be used for schema-based
+    /// parsing of `context`, and not for request validation.
+    /// If a schema is not provided, this option has no effect.
+    #[serde(default = "constant_true")]
+    validate_request: bool,
+    /// How policies which encounter evaluation errors affect the decision.
+    /// Defaults to `skip`, as described by the Cedar language specification.
+    #[serde(default)]
+    #[cfg_attr(feature = "wasm", tsify(optional))]
+    error_handling: ErrorHandling,
     /// The set of policies to use during authorization
     policies: PolicySet,
     /// The set of entities to use during authorization
     entities: Entities,
 }
 
+/// A single request in a [`BatchAuthorizationCall`]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "wasm", derive(tsify::Tsify))]
+#[cfg_attr(feature = "wasm", tsify(into_wasm_abi, from_wasm_abi))]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct BatchRequest {
+    /// The principal taking action
+    pub(super) principal: EntityUid,
+    /// The action the principal is taking
+    pub(super) action: EntityUid,
+    /// The resource being acted on by the principal
+    pub(super) resource: EntityUid,
+    /// The context details specific to the request
+    pub(super) context: Context,
+}
+
 /// Struct containing the input data for partial authorization
 #[cfg(feature = "partial-eval")]
 #[serde_as]
@@ -522,6 +853,11 @@ pub struct PartialAuthorizationCall {
     /// If a schema is not provided, this option has no effect.
     #[serde(default = "constant_true")]
     validate_request: bool,
+    /// How policies which encounter evaluation errors affect the decision.
+    /// Defaults to `skip`, as described by the Cedar language specification.
+    #[serde(default)]
+    #[cfg_attr(feature = "wasm", tsify(optional))]
+    error_handling: ErrorHandling,
     /// The set of policies to use during authorization
     policies: PolicySet,
     /// The set of entities to use during authorization
@@ -609,6 +945,92 @@ impl AuthorizationCall {
     }
 }
 
+impl BatchRequest {
+    pub(super) fn parse(
+        self,
+        schema: Option<&crate::Schema>,
+        validate_request: bool,
+    ) -> Result<Request, Vec<miette::Report>> {
+        let mut errs = vec![];
+        let maybe_principal = self
+            .principal
+            .parse(Some("principal"))
+            .map_err(|e| errs.push(e));
+        let maybe_action = self.action.parse(Some("action")).map_err(|e| errs.push(e));
+        let maybe_resource = self
+            .resource
+            .parse(Some("resource"))
+            .map_err(|e| errs.push(e));
+        let (Ok(principal), Ok(action), Ok(resource)) =
+            (maybe_principal, maybe_action, maybe_resource)
+        else {
+            // At least one of the `errs.push(e)` statements above must have been reached
+            return Err(errs);
+        };
+        let context = self
+            .context
+            .parse(schema, Some(&action))
+            .map_err(|e| vec![e])?;
+        let schema_opt = if validate_request { schema } else { None };
+        Request::new(principal, action, resource, context, schema_opt).map_err(|e| vec![e.into()])
+    }
+}
+
+impl BatchAuthorizationCall {
+    #[allow(clippy::type_complexity)]
+    fn parse(
+        self,
+    ) -> WithWarnings<
+        Result<
+            (
+                Vec<Result<Request, Vec<miette::Report>>>,
+                crate::PolicySet,
+                crate::Entities,
+            ),
+            Vec<miette::Report>,
+        >,
+    > {
+        let mut errs = vec![];
+        let mut warnings = vec![];
+        let schema = match self
+            .schema
+            .map(|schema| {
+                schema.parse().map(|(schema, new_warnings)| {
+                    warnings.extend(new_warnings);
+                    schema
+                })
+            })
+            .transpose()
+        {
+            Ok(schema) => schema,
+            Err(e) => return build_error(vec![e], warnings),
+        };
+        let maybe_entities = self
+            .entities
+            .parse(schema.as_ref())
+            .map_err(|e| errs.push(e));
+        let maybe_policies = self.policies.parse().map_err(|es| errs.extend(es));
+
+        match (maybe_policies, maybe_entities) {
+            (Ok(policies), Ok(entities)) => {
+                let requests = self
+                    .requests
+                    .into_iter()
+                    .map(|request| request.parse(schema.as_ref(), self.validate_request))
+                    .collect();
+                WithWarnings {
+                    t: Ok((requests, policies, entities)),
+                    warnings: warnings.into_iter().map(Into::into).collect(),
+                }
+            }
+            _ => {
+                // At least one of the `errs.push(e)` statements above must have been reached
+                build_error(errs, warnings)
+            }
+        }
+    }
+}
+
 #[cfg(feature = "partial-eval")]
 impl PartialAuthorizationCall {
     fn parse(
@@ -1586,6 +2008,168 @@ mod test {
         );
         assert_is_authorized_json(bad_call_req_validation_disabled);
     }
+
+    #[test]
+    fn test_authorized_batch() {
+        let call = json!({
+            "requests": [
+                {
+                    "principal": { "type": "User", "id": "alice" },
+                    "action": { "type": "Action", "id": "view" },
+                    "resource": { "type": "Photo", "id": "door" },
+                    "context": { "source_ip": { "__extn": { "fn": "ip", "arg": "222.222.222.222" } } }
+                },
+                {
+                    "principal": { "type": "User", "id": "alice" },
+                    "action": { "type": "Action", "id": "view" },
+                    "resource": { "type": "Photo", "id": "door" },
+                    "context": { "source_ip": { "__extn": { "fn": "ip", "arg": "10.0.0.1" } } }
+                },
+                {
+                    "principal": { "type": "User", "id": "alice" },
+                    "action": { "type": "Action", "id": "view" },
+                    "resource": "not an entity",
+                    "context": {}
+                },
+                {
+                    "principal": { "type": "User", "id": "bob" },
+                    "action": { "type": "Action", "id": "view" },
+                    "resource": { "type": "Photo", "id": "door" },
+                    "context": { "source_ip": { "__extn": { "fn": "ip", "arg": "222.222.222.1" } } }
+                }
+            ],
+            "policies": {
+                "staticPolicies": "permit(principal == User::\"alice\", action, resource) when { context.source_ip.isInRange(ip(\"222.222.222.0/24\")) };"
+            },
+            "entities": []
+        });
+        let ans_val = is_authorized_batch_json(call)
+            .expect("expected input to parse as a `BatchAuthorizationCall`");
+        let result: Result<BatchAuthorizationAnswer, _> = serde_json::from_value(ans_val);
+        assert_matches!(result, Ok(BatchAuthorizationAnswer::Success { answers, .. }) => {
+            assert_eq!(answers.len(), 4);
+            assert_matches!(&answers[0], AuthorizationAnswer::Success { response, .. } => {
+                assert_eq!(response.decision(), Decision::Allow);
+            });
+            assert_matches!(&answers[1], AuthorizationAnswer::Success { response, .. } => {
+                assert_eq!(response.decision(), Decision::Deny);
+            });
+            assert_matches!(&answers[2], AuthorizationAnswer::Failure { errors, .. } => {
+                assert_eq!(errors.len(), 1);
+            });
+            assert_matches!(&answers[3], AuthorizationAnswer::Success { response, .. } => {
+                assert_eq!(response.decision(), Decision::Deny);
+            });
+        });
+    }
+
+    #[test]
+    fn test_authorized_with_trace() {
+        let call = json!({
+            "principal" : { "type": "User", "id": "alice" },
+            "action" : { "type": "Photo", "id": "view" },
+            "resource" : { "type": "Photo", "id": "door" },
+            "context": {},
+            "policies": {
+                "staticPolicies": {
+                    "ID1": "permit(principal, action, resource) when { 1 > 2 && context.missing };",
+                    "ID2": "forbid(principal, action, resource) when { context.missing };"
+                }
+            },
+            "entities": [],
+            "trace": true
+        });
+        let ans_val =
+            is_authorized_json(call).expect("expected input to parse as an `AuthorizationCall`");
+        let result: Result<AuthorizationAnswer, _> = serde_json::from_value(ans_val);
+        assert_matches!(result, Ok(AuthorizationAnswer::Success { response, trace: Some(trace), .. }) => {
+            assert_eq!(response.decision(), Decision::Deny);
+            assert_eq!(trace.len(), 2);
+            let permit = &trace[&PolicyId::new("ID1")];
+            assert_eq!(permit.outcome, TraceOutcome::Value { value: "false".into() });
+            assert!(permit.source_location.is_some());
+            fn any_short_circuited(trace: &EvaluationTrace) -> bool {
+                trace.short_circuited || trace.children.iter().any(any_short_circuited)
+            }
+            assert!(any_short_circuited(permit));
+            let forbid = &trace[&PolicyId::new("ID2")];
+            assert_matches!(&forbid.outcome, TraceOutcome::Error { error } => {
+                assert_eq!(error.message, "record does not have the attribute `missing`");
+            });
+        });
+    }
+
+    #[test]
+    fn test_authorized_with_error_handling() {
+        let call = |error_handling: &str| {
+            json!({
+                "principal" : { "type": "User", "id": "alice" },
+                "action" : { "type": "Photo", "id": "view" },
+                "resource" : { "type": "Photo", "id": "door" },
+                "context": {},
+                "policies": {
+                    "staticPolicies": {
+                        "ID1": "permit(principal, action, resource);",
+                        "ID2": "forbid(principal, action, resource) when { context.missing };"
+                    }
+                },
+                "entities": [],
+                "errorHandling": error_handling
+            })
+        };
+        let ans_val = is_authorized_json(call("skip"))
+            .expect("expected input to parse as an `AuthorizationCall`");
+        let result: Result<AuthorizationAnswer, _> = serde_json::from_value(ans_val);
+        assert_matches!(result, Ok(AuthorizationAnswer::Success { response, .. }) => {
+            assert_eq!(response.decision(), Decision::Allow);
+            assert_eq!(response.diagnostics().errors().count(), 1);
+        });
+        let ans_val = is_authorized_json(call("forbidOnError"))
+            .expect("expected input to parse as an `AuthorizationCall`");
+        let result: Result<AuthorizationAnswer, _> = serde_json::from_value(ans_val);
+        assert_matches!(result, Ok(AuthorizationAnswer::Success { response, .. }) => {
+            assert_eq!(response.decision(), Decision::Deny);
+            let reason: Vec<_> = response.diagnostics().reason().collect();
+            assert_eq!(reason, vec![&PolicyId::new("ID2")]);
+            assert_eq!(response.diagnostics().errors().count(), 1);
+        });
+        assert_matches!(
+            is_authorized_json(call("denyOnEverything")),
+            Err(e) => assert!(e.to_string().contains("unknown variant `denyOnEverything`"))
+        );
+    }
+
+    #[test]
+    fn test_authorized_without_trace() {
+        let call = json!({
+            "principal" : { "type": "User", "id": "alice" },
+            "action" : { "type": "Photo", "id": "view" },
+            "resource" : { "type": "Photo", "id": "door" },
+            "context": {},
+            "policies": { "staticPolicies": "permit(principal, action, resource);" },
+            "entities": []
+        });
+        let ans_val =
+            is_authorized_json(call).expect("expected input to parse as an `AuthorizationCall`");
+        assert!(ans_val.get("trace").is_none());
+        let result: Result<AuthorizationAnswer, _> = serde_json::from_value(ans_val);
+        assert_matches!(result, Ok(AuthorizationAnswer::Success { trace: None, .. }));
+    }
+
+    #[test]
+    fn test_authorized_batch_fails_on_bad_policies() {
+        let call = json!({
+            "requests": [],
+            "policies": {
+                "staticPolicies": "permit(principal, action, resource) when"
+            },
+            "entities": []
+        });
+        let ans_val = is_authorized_batch_json(call)
+            .expect("expected input to parse as a `BatchAuthorizationCall`");
+        let result: Result<BatchAuthorizationAnswer, _> = serde_json::from_value(ans_val);
+        assert_matches!(result, Ok(BatchAuthorizationAnswer::Failure { .. }));
+    }
 }
 
 #[cfg(feature = "partial-eval")]
@@ -20,6 +20,8 @@ mod utils;
 pub use utils::*;
 mod validate;
 pub use validate::*;
+mod preparsed;
+pub use preparsed::*;
 mod check_parse;
 pub use check_parse::*;
 mod format;
@@ -0,0 +1,412 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! JSON FFI entry points for long-running callers, such as `cedar serve`,
+//! which parse policies, entities and a schema once and then answer many
+//! calls against them. The call types here are the request-specific parts of
+//! [`AuthorizationCall`](super::AuthorizationCall) and friends, and the
+//! answers are the same.
+
+#![allow(clippy::module_name_repetitions)]
+use super::is_authorized::{
+    authorization_answer, batch_authorization_answer, AuthorizationAnswer,
+    BatchAuthorizationAnswer, BatchRequest,
+};
+#[cfg(feature = "partial-eval")]
+use super::is_authorized::{partial_authorization_answer, PartialAuthorizationAnswer};
+use super::utils::{Context, EntityUid};
+use super::validate::{validation_answer, ValidationAnswer, ValidationSettings};
+#[cfg(feature = "partial-eval")]
+use crate::Request;
+use crate::{Entities, ErrorHandling, PolicySet, Schema};
+use serde::{Deserialize, Serialize};
+
+/// Policies, entities and an optional schema, parsed ahead of time so that
+/// many calls can be answered without parsing them again
+#[derive(Debug, Clone)]
+pub struct PreparsedInputs {
+    /// The set of policies to use during authorization and validation
+    policies: PolicySet,
+    /// The set of entities to use during authorization
+    entities: Entities,
+    /// Optional schema, used to parse and validate requests and to validate
+    /// `policies`
+    schema: Option<Schema>,
+}
+
+impl PreparsedInputs {
+    /// Create a new [`PreparsedInputs`]. The `entities` should already have
+    /// been validated against `schema`, if there is one.
+    pub fn new(policies: PolicySet, entities: Entities, schema: Option<Schema>) -> Self {
+        Self {
+            policies,
+            entities,
+            schema,
+        }
+    }
+
+    /// Get the policies
+    pub fn policies(&self) -> &PolicySet {
+        &self.policies
+    }
+
+    /// Get the entities
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    /// Get the schema, if there is one
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref()
+    }
+
+    /// Like [`is_authorized()`](super::is_authorized), but using these
+    /// policies, entities and schema
+    pub fn is_authorized(&self, call: PreparsedAuthorizationCall) -> AuthorizationAnswer {
+        let PreparsedAuthorizationCall {
+            principal,
+            action,
+            resource,
+            context,
+            validate_request,
+            error_handling,
+            trace,
+        } = call;
+        let request = BatchRequest {
+            principal,
+            action,
+            resource,
+            context,
+        };
+        match request.parse(self.schema.as_ref(), validate_request) {
+            Ok(request) => authorization_answer(
+                &request,
+                &self.policies,
+                &self.entities,
+                error_handling,
+                trace,
+                vec![],
+            ),
+            Err(errors) => AuthorizationAnswer::Failure {
+                errors: errors.into_iter().map(Into::into).collect(),
+                warnings: vec![],
+            },
+        }
+    }
+
+    /// Like [`is_authorized_batch()`](super::is_authorized_batch), but using
+    /// these policies, entities and schema
+    pub fn is_authorized_batch(
+        &self,
+        call: PreparsedBatchAuthorizationCall,
+    ) -> BatchAuthorizationAnswer {
+        let requests = call
+            .requests
+            .into_iter()
+            .map(|request| request.parse(self.schema.as_ref(), call.validate_request))
+            .collect();
+        batch_authorization_answer(
+            requests,
+            &self.policies,
+            &self.entities,
+            call.error_handling,
+            vec![],
+        )
+    }
+
+    /// Like [`is_authorized_partial()`](super::is_authorized_partial), but
+    /// using these policies, entities and schema
+    #[doc = include_str!("../../experimental_warning.md")]
+    #[cfg(feature = "partial-eval")]
+    pub fn is_authorized_partial(
+        &self,
+        call: PreparsedPartialAuthorizationCall,
+    ) -> PartialAuthorizationAnswer {
+        let error_handling = call.error_handling;
+        match call.parse(self.schema.as_ref()) {
+            Ok(request) => partial_authorization_answer(
+                &request,
+                &self.policies,
+                &self.entities,
+                error_handling,
+                vec![],
+            ),
+            Err(errors) => PartialAuthorizationAnswer::Failure {
+                errors: errors.into_iter().map(Into::into).collect(),
+                warnings: vec![],
+            },
+        }
+    }
+
+    /// Like [`validate()`](super::validate), validating these policies
+    /// against this schema. Fails if there is no schema.
+    pub fn validate(&self, settings: &ValidationSettings) -> ValidationAnswer {
+        self.schema.as_ref().map_or_else(
+            || ValidationAnswer::Failure {
+                errors: vec![miette::miette!("cannot validate policies without a schema").into()],
+                warnings: vec![],
+            },
+            |schema| validation_answer(&self.policies, schema.clone(), settings, vec![]),
+        )
+    }
+}
+
+/// Struct containing the input data for authorization against
+/// [`PreparsedInputs`]
+///
+/// This is an [`AuthorizationCall`](super::AuthorizationCall) without the `schema`, `policies` and `entities`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct PreparsedAuthorizationCall {
+    /// The principal taking action
+    principal: EntityUid,
+    /// The action the principal is taking
+    action: EntityUid,
+    /// The resource being acted on by the principal
+    resource: EntityUid,
+    /// The context details specific to the request
+    context: Context,
+    /// If this is `true` and there is a schema, perform request validation.
+    #[serde(default = "constant_true")]
+    validate_request: bool,
+    /// How policies which encounter evaluation errors affect the decision.
+    /// Defaults to `skip`, as described by the Cedar language specification.
+    #[serde(default)]
+    error_handling: ErrorHandling,
+    /// If this is `true`, the answer includes a trace of the evaluation of
+    /// every policy
+    #[serde(default)]
+    trace: bool,
+}
+
+/// Struct containing the input data for batch authorization against
+/// [`PreparsedInputs`]
+///
+/// This is a [`BatchAuthorizationCall`](super::BatchAuthorizationCall) without the
+/// `schema`, `policies` and `entities`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct PreparsedBatchAuthorizationCall {
+    /// The requests to authorize
+    requests: Vec<BatchRequest>,
+    /// If this is `true` and there is a schema, perform request validation.
+    #[serde(default = "constant_true")]
+    validate_request: bool,
+    /// How policies which encounter evaluation errors affect the decision.
+    /// Defaults to `skip`, as described by the Cedar language specification.
+    #[serde(default)]
+    error_handling: ErrorHandling,
+}
+
+/// Struct containing the input data for partial authorization against
+/// [`PreparsedInputs`]
+///
+/// This is a [`PartialAuthorizationCall`](super::PartialAuthorizationCall) without the
+/// `schema`, `policies` and `entities`.
+#[cfg(feature = "partial-eval")]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct PreparsedPartialAuthorizationCall {
+    /// The principal taking action. If this field is empty, then the principal is unknown.
+    principal: Option<EntityUid>,
+    /// The action the principal is taking. If this field is empty, then the action is unknown.
+    action: Option<EntityUid>,
+    /// The resource being acted on by the principal. If this field is empty, then the resource is unknown.
+    resource: Option<EntityUid>,
+    /// The context details specific to the request
+    context: Context,
+    /// If this is `true` and there is a schema, perform request validation.
+    #[serde(default = "constant_true")]
+    validate_request: bool,
+    /// How policies which encounter evaluation errors affect the decision.
+    /// Defaults to `skip`, as described by the Cedar language specification.
+    #[serde(default)]
+    error_handling: ErrorHandling,
+}
+
+fn constant_true() -> bool {
+    true
+}
+
+#[cfg(feature = "partial-eval")]
+impl PreparsedPartialAuthorizationCall {
+    fn parse(self, schema: Option<&Schema>) -> Result<Request, Vec<miette::Report>> {
+        let mut errs = vec![];
+        let maybe_principal = self
+            .principal
+            .map(|uid| uid.parse(Some("principal")))
+            .transpose()
+            .map_err(|e| errs.push(e));
+        let maybe_action = self
+            .action
+            .map(|uid| uid.parse(Some("action")))
+            .transpose()
+            .map_err(|e| errs.push(e));
+        let maybe_resource = self
+            .resource
+            .map(|uid| uid.parse(Some("resource")))
+            .transpose()
+            .map_err(|e| errs.push(e));
+        let (Ok(principal), Ok(action), Ok(resource)) =
+            (maybe_principal, maybe_action, maybe_resource)
+        else {
+            // At least one of the `errs.push(e)` statements above must have been reached
+            return Err(errs);
+        };
+        let context = self
+            .context
+            .parse(schema, action.as_ref())
+            .map_err(|e| vec![e])?;
+
+        let mut b = Request::builder();
+        if let Some(p) = principal {
+            b = b.principal(p);
+        }
+        if let Some(a) = action {
+            b = b.action(a);
+        }
+        if let Some(r) = resource {
+            b = b.resource(r);
+        }
+        b = b.context(context);
+        match schema {
+            Some(schema) if self.validate_request => {
+                b.schema(schema).build().map_err(|e| vec![e.into()])
+            }
+            _ => Ok(b.build()),
+        }
+    }
+}
+
+// PANIC SAFETY unit tests
+#[allow(clippy::panic)]
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Decision;
+    use cool_asserts::assert_matches;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn inputs() -> PreparsedInputs {
+        let policies = PolicySet::from_str(
+            r#"permit(principal == User::"alice", action == Action::"view", resource);"#,
+        )
+        .unwrap();
+        let schema = Schema::from_str(
+            "entity User; entity Photo; action view appliesTo { principal: User, resource: Photo };",
+        )
+        .unwrap();
+        let entities = Entities::from_json_value(
+            json!([{ "uid": { "type": "User", "id": "alice" }, "attrs": {}, "parents": [] }]),
+            Some(&schema),
+        )
+        .unwrap();
+        PreparsedInputs::new(policies, entities, Some(schema))
+    }
+
+    fn call(principal: &str) -> serde_json::Value {
+        json!({
+            "principal": { "type": "User", "id": principal },
+            "action": { "type": "Action", "id": "view" },
+            "resource": { "type": "Photo", "id": "vacation.jpg" },
+            "context": {},
+        })
+    }
+
+    #[test]
+    fn authorizes_against_preparsed_inputs() {
+        let inputs = inputs();
+        let answer = inputs.is_authorized(serde_json::from_value(call("alice")).unwrap());
+        assert_matches!(answer, AuthorizationAnswer::Success { response, .. } => {
+            assert_eq!(response.decision(), Decision::Allow);
+        });
+        let answer = inputs.is_authorized(serde_json::from_value(call("bob")).unwrap());
+        assert_matches!(answer, AuthorizationAnswer::Success { response, .. } => {
+            assert_eq!(response.decision(), Decision::Deny);
+        });
+    }
+
+    #[test]
+    fn request_validation_uses_preparsed_schema() {
+        let mut call = call("alice");
+        call["resource"] = json!({ "type": "User", "id": "bob" });
+        let answer = inputs().is_authorized(serde_json::from_value(call).unwrap());
+        assert_matches!(answer, AuthorizationAnswer::Failure { .. });
+    }
+
+    #[test]
+    fn rejects_policies_in_call() {
+        let mut call = call("alice");
+        call["policies"] = json!({});
+        assert_matches!(
+            serde_json::from_value::<PreparsedAuthorizationCall>(call),
+            Err(_)
+        );
+    }
+
+    #[test]
+    fn batch_authorizes_against_preparsed_inputs() {
+        let mut bad = call("alice");
+        bad["action"] = json!({ "type": "Action", "id": "delete" });
+        let answer = inputs().is_authorized_batch(
+            serde_json::from_value(json!({ "requests": [call("alice"), bad, call("bob")] }))
+                .unwrap(),
+        );
+        assert_matches!(answer, BatchAuthorizationAnswer::Success { answers, .. } => {
+            assert_matches!(&answers[..], [
+                AuthorizationAnswer::Success { response: allow, .. },
+                AuthorizationAnswer::Failure { .. },
+                AuthorizationAnswer::Success { response: deny, .. },
+            ] => {
+                assert_eq!(allow.decision(), Decision::Allow);
+                assert_eq!(deny.decision(), Decision::Deny);
+            });
+        });
+    }
+
+    #[test]
+    fn validates_preparsed_policies() {
+        let answer = inputs().validate(&ValidationSettings::default());
+        assert_matches!(answer, ValidationAnswer::Success { validation_errors, .. } => {
+            assert_eq!(validation_errors, vec![]);
+        });
+        let inputs = PreparsedInputs::new(PolicySet::new(), Entities::empty(), None);
+        assert_matches!(
+            inputs.validate(&ValidationSettings::default()),
+            ValidationAnswer::Failure { .. }
+        );
+    }
+
+    #[cfg(feature = "partial-eval")]
+    #[test]
+    fn partially_authorizes_against_preparsed_inputs() {
+        let answer = inputs().is_authorized_partial(
+            serde_json::from_value(json!({
+                "action": { "type": "Action", "id": "view" },
+                "resource": { "type": "Photo", "id": "vacation.jpg" },
+                "context": {},
+            }))
+            .unwrap(),
+        );
+        assert_matches!(answer, PartialAuthorizationAnswer::Residuals { response, .. } => {
+            assert_eq!(response.decision(), None);
+        });
+    }
+}
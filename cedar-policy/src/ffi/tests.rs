@@ -29,7 +29,7 @@ mod ffi_tests {
         });
 
         assert_matches!(is_authorized_json(json), Err(e) => {
-            assert_eq!(e.to_string(), "unknown field `slice`, expected one of `principal`, `action`, `resource`, `context`, `schema`, `validateRequest`, `policies`, `entities`");
+            assert_eq!(e.to_string(), "unknown field `slice`, expected one of `principal`, `action`, `resource`, `context`, `schema`, `validateRequest`, `errorHandling`, `trace`, `policies`, `entities`");
         });
     }
 
@@ -55,7 +55,7 @@ mod ffi_tests {
         });
 
         assert_matches!(is_authorized_json(json), Err(e) => {
-            assert_eq!(e.to_string(), "unknown field `enableRequestValidation`, expected one of `principal`, `action`, `resource`, `context`, `schema`, `validateRequest`, `policies`, `entities`");
+            assert_eq!(e.to_string(), "unknown field `enableRequestValidation`, expected one of `principal`, `action`, `resource`, `context`, `schema`, `validateRequest`, `errorHandling`, `trace`, `policies`, `entities`");
         });
     }
 
@@ -109,7 +109,7 @@ mod ffi_tests {
         });
 
         assert_matches!(is_authorized_partial_json(json), Err(e) => {
-            assert_eq!(e.to_string(), "unknown field `partial_evaluation`, expected one of `principal`, `action`, `resource`, `context`, `schema`, `validateRequest`, `policies`, `entities`");
+            assert_eq!(e.to_string(), "unknown field `partial_evaluation`, expected one of `principal`, `action`, `resource`, `context`, `schema`, `validateRequest`, `errorHandling`, `policies`, `entities`");
         });
     }
 
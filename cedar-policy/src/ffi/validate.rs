@@ -37,30 +37,12 @@ pub fn validate(call: ValidationCall) -> ValidationAnswer {
         WithWarnings {
             t: Ok((policies, schema, settings)),
             warnings,
-        } => {
-            // otherwise, call `Validator::validate`
-            let validator = Validator::new(schema);
-            let (validation_errors, validation_warnings) = validator
-                .validate(&policies, settings.mode)
-                .into_errors_and_warnings();
-            let validation_errors: Vec<ValidationError> = validation_errors
-                .map(|error| ValidationError {
-                    policy_id: error.policy_id().clone(),
-                    error: miette::Report::new(error).into(),
-                })
-                .collect();
-            let validation_warnings: Vec<ValidationError> = validation_warnings
-                .map(|error| ValidationError {
-                    policy_id: error.policy_id().clone(),
-                    error: miette::Report::new(error).into(),
-                })
-                .collect();
-            ValidationAnswer::Success {
-                validation_errors,
-                validation_warnings,
-                other_warnings: warnings.into_iter().map(Into::into).collect(),
-            }
-        }
+        } => validation_answer(
+            &policies,
+            schema,
+            &settings,
+            warnings.into_iter().map(Into::into).collect(),
+        ),
         WithWarnings {
             t: Err(errors),
             warnings,
@@ -71,6 +53,37 @@ pub fn validate(call: ValidationCall) -> ValidationAnswer {
     }
 }
 
+/// Validate a parsed policy set against a parsed schema, building the
+/// [`ValidationAnswer`] returned by [`validate()`]
+pub(super) fn validation_answer(
+    policies: &crate::PolicySet,
+    schema: crate::Schema,
+    settings: &ValidationSettings,
+    other_warnings: Vec<DetailedError>,
+) -> ValidationAnswer {
+    let validator = Validator::new(schema);
+    let (validation_errors, validation_warnings) = validator
+        .validate(policies, settings.mode)
+        .into_errors_and_warnings();
+    let validation_errors: Vec<ValidationError> = validation_errors
+        .map(|error| ValidationError {
+            policy_id: error.policy_id().clone(),
+            error: miette::Report::new(error).into(),
+        })
+        .collect();
+    let validation_warnings: Vec<ValidationError> = validation_warnings
+        .map(|error| ValidationError {
+            policy_id: error.policy_id().clone(),
+            error: miette::Report::new(error).into(),
+        })
+        .collect();
+    ValidationAnswer::Success {
+        validation_errors,
+        validation_warnings,
+        other_warnings,
+    }
+}
+
 /// Input is a JSON encoding of [`ValidationCall`] and output is a JSON
 /// encoding of [`ValidationAnswer`]
 ///
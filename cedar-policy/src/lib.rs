@@ -51,4 +51,7 @@ pub mod ffi;
 #[cfg(feature = "protobufs")]
 pub mod proto;
 
+#[cfg(feature = "sql")]
+pub mod sql;
+
 mod test;
@@ -41,6 +41,7 @@ macro_rules! standard_conversions {
 standard_conversions!(api::Entity, api::Entity, models::Entity);
 standard_conversions!(api::EntityUid, api::EntityUid, models::EntityUid);
 standard_conversions!(api::Entities, api::Entities, models::Entities);
+standard_conversions!(api::EntityPatch, api::EntityPatch, models::EntityPatch);
 standard_conversions!(api::Schema, api::Schema, models::Schema);
 standard_conversions!(api::EntityTypeName, api::EntityTypeName, models::Name);
 standard_conversions!(api::EntityNamespace, api::EntityNamespace, models::Name);
@@ -112,6 +113,7 @@ macro_rules! standard_protobuf_impl {
 
 standard_protobuf_impl!(api::Entity, models::Entity);
 standard_protobuf_impl!(api::Entities, models::Entities);
+standard_protobuf_impl!(api::EntityPatch, models::EntityPatch);
 standard_protobuf_impl!(api::Schema, models::Schema);
 standard_protobuf_impl!(api::EntityTypeName, models::Name);
 standard_protobuf_impl!(api::EntityNamespace, models::Name);
@@ -17,7 +17,10 @@
 #![allow(clippy::use_self)]
 
 use super::models;
-use cedar_policy_core::{ast, entities, extensions};
+use cedar_policy_core::{ast, entities, evaluator::RestrictedEvaluator, extensions};
+use smol_str::SmolStr;
+use std::collections::HashSet;
+use std::sync::Arc;
 
 impl From<&models::Entities> for entities::Entities {
     // PANIC SAFETY: experimental feature
@@ -40,20 +43,155 @@ impl From<&models::Entities> for entities::Entities {
 }
 
 impl From<&entities::Entities> for models::Entities {
+    // PANIC SAFETY: stores with on-demand ancestors are always checked to be
+    // DAGs, whatever the `TCComputation`, so computing the TC cannot fail
+    #[allow(clippy::expect_used)]
     fn from(v: &entities::Entities) -> Self {
         assert!(
             !v.is_partial(),
             "protobuf does not support encoding partial Entities"
         );
+        // protobuf entities always carry all of their ancestors
+        if v.ancestor_mode() == entities::AncestorMode::OnDemand {
+            return Self::from(
+                &v.clone()
+                    .with_ancestor_mode(entities::AncestorMode::Precomputed)
+                    .expect("an `Entities` with on-demand ancestors should be a DAG"),
+            );
+        }
         Self {
             entities: v.iter().map(models::Entity::from).collect(),
         }
     }
 }
 
+impl From<&models::EntityPatch> for entities::EntityPatch {
+    fn from(v: &models::EntityPatch) -> Self {
+        v.ops.iter().map(entities::EntityPatchOp::from).collect()
+    }
+}
+
+impl From<&entities::EntityPatch> for models::EntityPatch {
+    fn from(v: &entities::EntityPatch) -> Self {
+        Self {
+            ops: v.ops().map(models::EntityPatchOp::from).collect(),
+        }
+    }
+}
+
+impl From<&models::EntityPatchOp> for entities::EntityPatchOp {
+    // PANIC SAFETY: experimental feature
+    #[allow(clippy::expect_used)]
+    fn from(v: &models::EntityPatchOp) -> Self {
+        let uid = |uid: Option<&models::EntityUid>| {
+            ast::EntityUID::from(uid.expect("uid field should exist"))
+        };
+        let value = |value: Option<&models::Expr>| {
+            let expr = ast::Expr::from(value.expect("value field should exist"));
+            RestrictedEvaluator::new(extensions::Extensions::all_available())
+                .partial_interpret(
+                    ast::BorrowedRestrictedExpr::new(&expr)
+                        .expect("value should be a RestrictedExpr"),
+                )
+                .expect("interpret on RestrictedExpr")
+        };
+        match v.data.as_ref().expect("data field should exist") {
+            models::entity_patch_op::Data::Put(entity) => {
+                // the ancestors of an entity in a change-set are its parents
+                let (uid, attrs, indirect_ancestors, parents, tags) =
+                    ast::Entity::from(entity).into_inner();
+                Self::Put(Arc::new(ast::Entity::new_with_attr_partial_value(
+                    uid,
+                    attrs,
+                    HashSet::new(),
+                    indirect_ancestors.into_iter().chain(parents).collect(),
+                    tags,
+                )))
+            }
+            models::entity_patch_op::Data::Remove(euid) => Self::Remove(euid.into()),
+            models::entity_patch_op::Data::SetAttr(msg) => Self::SetAttr {
+                uid: uid(msg.uid.as_ref()),
+                attr: msg.key.clone().into(),
+                value: value(msg.value.as_ref()),
+            },
+            models::entity_patch_op::Data::RemoveAttr(msg) => Self::RemoveAttr {
+                uid: uid(msg.uid.as_ref()),
+                attr: msg.key.clone().into(),
+            },
+            models::entity_patch_op::Data::AddParent(msg) => Self::AddParent {
+                uid: uid(msg.uid.as_ref()),
+                parent: uid(msg.parent.as_ref()),
+            },
+            models::entity_patch_op::Data::RemoveParent(msg) => Self::RemoveParent {
+                uid: uid(msg.uid.as_ref()),
+                parent: uid(msg.parent.as_ref()),
+            },
+            models::entity_patch_op::Data::SetTag(msg) => Self::SetTag {
+                uid: uid(msg.uid.as_ref()),
+                tag: msg.key.clone().into(),
+                value: value(msg.value.as_ref()),
+            },
+            models::entity_patch_op::Data::RemoveTag(msg) => Self::RemoveTag {
+                uid: uid(msg.uid.as_ref()),
+                tag: msg.key.clone().into(),
+            },
+        }
+    }
+}
+
+impl From<&entities::EntityPatchOp> for models::EntityPatchOp {
+    fn from(v: &entities::EntityPatchOp) -> Self {
+        let set = |uid: &ast::EntityUID, key: &SmolStr, value: &ast::PartialValue| {
+            models::entity_patch_op::SetMessage {
+                uid: Some(models::EntityUid::from(uid)),
+                key: key.to_string(),
+                value: Some(models::Expr::from(&ast::Expr::from(value.clone()))),
+            }
+        };
+        let remove = |uid: &ast::EntityUID, key: &SmolStr| models::entity_patch_op::RemoveMessage {
+            uid: Some(models::EntityUid::from(uid)),
+            key: key.to_string(),
+        };
+        let parent = |uid: &ast::EntityUID, parent: &ast::EntityUID| {
+            models::entity_patch_op::ParentMessage {
+                uid: Some(models::EntityUid::from(uid)),
+                parent: Some(models::EntityUid::from(parent)),
+            }
+        };
+        let data = match v {
+            entities::EntityPatchOp::Put(entity) => {
+                models::entity_patch_op::Data::Put(models::Entity::from(entity))
+            }
+            entities::EntityPatchOp::Remove(uid) => {
+                models::entity_patch_op::Data::Remove(models::EntityUid::from(uid))
+            }
+            entities::EntityPatchOp::SetAttr { uid, attr, value } => {
+                models::entity_patch_op::Data::SetAttr(set(uid, attr, value))
+            }
+            entities::EntityPatchOp::RemoveAttr { uid, attr } => {
+                models::entity_patch_op::Data::RemoveAttr(remove(uid, attr))
+            }
+            entities::EntityPatchOp::AddParent { uid, parent: p } => {
+                models::entity_patch_op::Data::AddParent(parent(uid, p))
+            }
+            entities::EntityPatchOp::RemoveParent { uid, parent: p } => {
+                models::entity_patch_op::Data::RemoveParent(parent(uid, p))
+            }
+            entities::EntityPatchOp::SetTag { uid, tag, value } => {
+                models::entity_patch_op::Data::SetTag(set(uid, tag, value))
+            }
+            entities::EntityPatchOp::RemoveTag { uid, tag } => {
+                models::entity_patch_op::Data::RemoveTag(remove(uid, tag))
+            }
+        };
+        Self { data: Some(data) }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use cool_asserts::assert_matches;
     use smol_str::SmolStr;
     use std::collections::{BTreeMap, HashMap, HashSet};
     use std::sync::Arc;
@@ -122,4 +260,53 @@ mod test {
             entities::Entities::from(&models::Entities::from(&entities3))
         );
     }
+
+    #[test]
+    fn entity_patch_roundtrip() {
+        let alice: ast::EntityUID = r#"User::"alice""#.parse().unwrap();
+        let eng: ast::EntityUID = r#"Group::"eng""#.parse().unwrap();
+        let mut bob = ast::Entity::with_uid(r#"User::"bob""#.parse().unwrap());
+        bob.add_parent(eng.clone());
+        let patch = entities::EntityPatch::new([
+            entities::EntityPatchOp::Put(Arc::new(bob)),
+            entities::EntityPatchOp::Remove(alice.clone()),
+            entities::EntityPatchOp::SetAttr {
+                uid: eng.clone(),
+                attr: "size".into(),
+                value: ast::PartialValue::from(3),
+            },
+            entities::EntityPatchOp::RemoveAttr {
+                uid: eng.clone(),
+                attr: "name".into(),
+            },
+            entities::EntityPatchOp::AddParent {
+                uid: alice.clone(),
+                parent: eng.clone(),
+            },
+            entities::EntityPatchOp::RemoveParent {
+                uid: alice.clone(),
+                parent: eng.clone(),
+            },
+            entities::EntityPatchOp::SetTag {
+                uid: eng.clone(),
+                tag: "t".into(),
+                value: ast::PartialValue::from("x"),
+            },
+            entities::EntityPatchOp::RemoveTag {
+                uid: eng,
+                tag: "t".into(),
+            },
+        ]);
+        let roundtripped = entities::EntityPatch::from(&models::EntityPatch::from(&patch));
+        assert_eq!(
+            patch.to_json_value().unwrap(),
+            roundtripped.to_json_value().unwrap()
+        );
+        assert_matches!(
+            roundtripped.ops().next(),
+            Some(entities::EntityPatchOp::Put(bob)) => {
+                assert!(bob.is_child_of(&r#"Group::"eng""#.parse().unwrap()));
+            }
+        );
+    }
 }
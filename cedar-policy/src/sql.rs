@@ -0,0 +1,1844 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Translation of the residuals of a [`PartialResponse`] with an unknown
+//! resource into a SQL predicate over the table holding the resources.
+//!
+//! The predicate selects exactly the resources for which the request would
+//! be allowed, so it can be used in the `WHERE` clause of a query to filter
+//! resources in the database instead of authorizing each one separately.
+//!
+//! The output is a dialect-neutral [`SqlExpr`], which can be rendered for a
+//! [`SqlDialect`] with parameter placeholders using [`SqlExpr::to_sql`] or
+//! [`SqlExpr::to_sql_with`]. The dialect determines how Cedar's `like`,
+//! which is case-sensitive, is rendered, as SQL `LIKE` ignores case in some
+//! databases.
+//!
+//! The translation assumes the following database layout, described by a
+//! [`SqlMapping`]:
+//! * each entity type is stored in a table, with one column holding the
+//!   entity id (the `eid`, without the entity type) and one column for each
+//!   attribute. A missing attribute is stored as `NULL`, and an attribute
+//!   whose value is an entity is stored as that entity's id. The entity type
+//!   of such an attribute must be given in the mapping, so that it is only
+//!   compared with entities of that type.
+//! * the entity hierarchy is stored in a closure table, with one row for each
+//!   pair of an entity and one of its (direct or indirect) ancestors. Both
+//!   are stored as an entity type (as written in Cedar, e.g. `NS::Folder`)
+//!   and an entity id, in separate columns.
+//!
+//! Only responses using the [`crate::ErrorHandling::Skip`] mode can be
+//! translated. Accessing a missing attribute, which is an error in Cedar, is
+//! translated to `NULL`, and operators which would not propagate `NULL` like
+//! Cedar propagates the error (such as `AND` and `OR`) are guarded against
+//! it, so a policy whose condition errors for a row never applies to it.
+//! Arithmetic overflow, which is also an error in Cedar, is not detected.
+
+use crate::{Decision, Effect, EntityTypeName, ErrorHandling, PartialResponse, Policy};
+use cedar_policy_core::ast::{self, BinaryOp, ExprKind, Literal, PatternElem, UnaryOp};
+use miette::Diagnostic;
+use ref_cast::RefCast;
+use smol_str::SmolStr;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use thiserror::Error;
+
+/// Describes how entities are stored in the database
+#[derive(Debug, Clone, Default)]
+pub struct SqlMapping {
+    /// Table storing the entities of each entity type
+    tables: HashMap<EntityTypeName, TableMapping>,
+    /// Closure table storing the entity hierarchy, if any
+    hierarchy: Option<HierarchyMapping>,
+}
+
+impl SqlMapping {
+    /// A mapping with no tables
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store the entities of type `entity_type` in `table`
+    #[must_use]
+    pub fn with_table(mut self, entity_type: EntityTypeName, table: TableMapping) -> Self {
+        self.tables.insert(entity_type, table);
+        self
+    }
+
+    /// Store the entity hierarchy in the closure table `hierarchy`
+    #[must_use]
+    pub fn with_hierarchy(self, hierarchy: HierarchyMapping) -> Self {
+        Self {
+            hierarchy: Some(hierarchy),
+            ..self
+        }
+    }
+}
+
+/// Describes the table storing the entities of one entity type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableMapping {
+    /// Name of the table
+    table: String,
+    /// Column holding the entity id
+    id_column: String,
+    /// Column holding each attribute
+    columns: HashMap<SmolStr, String>,
+    /// Entity type of each attribute whose value is an entity
+    entity_types: HashMap<SmolStr, ast::EntityType>,
+}
+
+impl TableMapping {
+    /// Entities stored in `table`, with their id in `id_column`
+    pub fn new(table: impl Into<String>, id_column: impl Into<String>) -> Self {
+        Self {
+            table: table.into(),
+            id_column: id_column.into(),
+            columns: HashMap::new(),
+            entity_types: HashMap::new(),
+        }
+    }
+
+    /// Store the attribute `attr` in `column`
+    #[must_use]
+    pub fn with_column(mut self, attr: impl Into<SmolStr>, column: impl Into<String>) -> Self {
+        self.columns.insert(attr.into(), column.into());
+        self
+    }
+
+    /// Store the attribute `attr`, whose value is an entity of type
+    /// `entity_type`, in `column`, which holds the id of that entity
+    #[must_use]
+    pub fn with_entity_column(
+        mut self,
+        attr: impl Into<SmolStr>,
+        column: impl Into<String>,
+        entity_type: EntityTypeName,
+    ) -> Self {
+        let attr = attr.into();
+        self.entity_types.insert(attr.clone(), entity_type.0);
+        self.columns.insert(attr, column.into());
+        self
+    }
+
+    fn column(&self, column: &str) -> SqlExpr {
+        SqlExpr::Column {
+            table: self.table.clone(),
+            column: column.to_owned(),
+        }
+    }
+}
+
+/// Describes the closure table storing the entity hierarchy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HierarchyMapping {
+    /// Name of the table
+    table: String,
+    /// Column holding the type of an entity
+    descendant_type_column: String,
+    /// Column holding the id of an entity
+    descendant_column: String,
+    /// Column holding the type of an ancestor of that entity
+    ancestor_type_column: String,
+    /// Column holding the id of an ancestor of that entity
+    ancestor_column: String,
+}
+
+impl HierarchyMapping {
+    /// Hierarchy stored in `table`, with one row for each entity (with its
+    /// type in `descendant_type_column` and its id in `descendant_column`) and
+    /// each of its direct and indirect ancestors (with its type in
+    /// `ancestor_type_column` and its id in `ancestor_column`)
+    pub fn new(
+        table: impl Into<String>,
+        descendant_type_column: impl Into<String>,
+        descendant_column: impl Into<String>,
+        ancestor_type_column: impl Into<String>,
+        ancestor_column: impl Into<String>,
+    ) -> Self {
+        Self {
+            table: table.into(),
+            descendant_type_column: descendant_type_column.into(),
+            descendant_column: descendant_column.into(),
+            ancestor_type_column: ancestor_type_column.into(),
+            ancestor_column: ancestor_column.into(),
+        }
+    }
+}
+
+/// A value passed to the database as a query parameter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlValue {
+    /// A boolean
+    Bool(bool),
+    /// An integer
+    Long(i64),
+    /// A string (or entity id)
+    String(SmolStr),
+}
+
+/// The SQL dialect to render a [`SqlExpr`] in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SqlDialect {
+    /// PostgreSQL, and other databases whose `LIKE` is case-sensitive as in
+    /// standard SQL. `like` is rendered as `expr LIKE pattern ESCAPE '\'`.
+    Postgres,
+    /// SQLite, whose `LIKE` ignores case. `like` is rendered as
+    /// `expr GLOB pattern`, which is case-sensitive.
+    Sqlite,
+    /// MySQL, whose `LIKE` ignores case under the default collations. `like`
+    /// is rendered as `expr LIKE BINARY pattern`, which is case-sensitive.
+    MySql,
+}
+
+/// A comparison operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    /// `=`
+    Eq,
+    /// `<`
+    Less,
+    /// `<=`
+    LessEq,
+}
+
+/// An arithmetic operator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithOp {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+}
+
+/// A dialect-neutral SQL expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlExpr {
+    /// A constant truth value
+    Constant(bool),
+    /// `NULL`, the value of an expression which would produce an error in
+    /// Cedar
+    Null,
+    /// A value, passed as a query parameter
+    Param(SqlValue),
+    /// A column of a table
+    Column {
+        /// Name of the table
+        table: String,
+        /// Name of the column
+        column: String,
+    },
+    /// `NOT expr`
+    Not(Box<Self>),
+    /// `left AND right`
+    And(Box<Self>, Box<Self>),
+    /// `left OR right`
+    Or(Box<Self>, Box<Self>),
+    /// `expr IS TRUE`, which is false if `expr` is `NULL`
+    IsTrue(Box<Self>),
+    /// `expr IS NOT NULL`
+    IsNotNull(Box<Self>),
+    /// A comparison
+    Compare {
+        /// The comparison operator
+        op: CompareOp,
+        /// Left operand
+        left: Box<Self>,
+        /// Right operand
+        right: Box<Self>,
+    },
+    /// An arithmetic operation
+    Arith {
+        /// The arithmetic operator
+        op: ArithOp,
+        /// Left operand
+        left: Box<Self>,
+        /// Right operand
+        right: Box<Self>,
+    },
+    /// `-expr`
+    Neg(Box<Self>),
+    /// A case-sensitive match of `expr` against `pattern`, rendered as
+    /// described for each [`SqlDialect`]
+    Like {
+        /// The string to match
+        expr: Box<Self>,
+        /// The pattern, as a `LIKE` pattern with `\` as the escape character.
+        /// It is passed as a query parameter, converted to a `GLOB` pattern
+        /// for [`SqlDialect::Sqlite`].
+        pattern: String,
+    },
+    /// `expr IN (list)`
+    InList {
+        /// The value to look for
+        expr: Box<Self>,
+        /// The values to compare against
+        list: Vec<Self>,
+    },
+    /// `CASE WHEN test THEN then_expr ELSE else_expr END`
+    Case {
+        /// The condition
+        test: Box<Self>,
+        /// Value if `test` is true
+        then_expr: Box<Self>,
+        /// Value otherwise
+        else_expr: Box<Self>,
+    },
+    /// `EXISTS (SELECT 1 FROM table WHERE table.descendant_type_column =
+    /// descendant_type AND table.descendant_column = descendant AND
+    /// table.ancestor_type_column = ancestor_type AND table.ancestor_column =
+    /// ancestor)`
+    Ancestor {
+        /// The closure table
+        hierarchy: HierarchyMapping,
+        /// Type of the descendant entity
+        descendant_type: Box<Self>,
+        /// Id of the descendant entity
+        descendant: Box<Self>,
+        /// Type of the ancestor entity
+        ancestor_type: Box<Self>,
+        /// Id of the ancestor entity
+        ancestor: Box<Self>,
+    },
+}
+
+impl SqlExpr {
+    /// Render this expression as SQL in `dialect`, using `?` as the
+    /// placeholder for each parameter. Returns the SQL and the parameters, in
+    /// order.
+    pub fn to_sql(&self, dialect: SqlDialect) -> (String, Vec<SqlValue>) {
+        self.to_sql_with(dialect, |_| "?".to_owned())
+    }
+
+    /// Render this expression as SQL in `dialect`, using `placeholder(i)` as
+    /// the placeholder for the `i`th parameter (counting from 0). Returns the
+    /// SQL and the parameters, in order.
+    pub fn to_sql_with(
+        &self,
+        dialect: SqlDialect,
+        placeholder: impl FnMut(usize) -> String,
+    ) -> (String, Vec<SqlValue>) {
+        let mut renderer = Renderer {
+            sql: String::new(),
+            params: Vec::new(),
+            dialect,
+            placeholder,
+        };
+        renderer.render(self);
+        (renderer.sql, renderer.params)
+    }
+
+    fn not(self) -> Self {
+        match self {
+            Self::Constant(b) => Self::Constant(!b),
+            e => Self::Not(Box::new(e)),
+        }
+    }
+
+    fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Constant(false), _) | (_, Self::Constant(false)) => Self::Constant(false),
+            (Self::Constant(true), e) | (e, Self::Constant(true)) => e,
+            (l, r) => Self::And(Box::new(l), Box::new(r)),
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Constant(true), _) | (_, Self::Constant(true)) => Self::Constant(true),
+            (Self::Constant(false), e) | (e, Self::Constant(false)) => e,
+            (l, r) => Self::Or(Box::new(l), Box::new(r)),
+        }
+    }
+
+    /// `self IS TRUE`, which is false rather than `NULL` if `self` is `NULL`
+    fn truth(self) -> Self {
+        match self {
+            Self::Constant(b) => Self::Constant(b),
+            e => Self::IsTrue(Box::new(e)),
+        }
+    }
+
+    fn compare(op: CompareOp, left: Self, right: Self) -> Self {
+        Self::Compare {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+}
+
+/// Renders a `SqlExpr` as SQL text, collecting its parameters
+struct Renderer<F> {
+    sql: String,
+    params: Vec<SqlValue>,
+    dialect: SqlDialect,
+    placeholder: F,
+}
+
+impl<F: FnMut(usize) -> String> Renderer<F> {
+    fn param(&mut self, value: SqlValue) {
+        let placeholder = (self.placeholder)(self.params.len());
+        self.sql.push_str(&placeholder);
+        self.params.push(value);
+    }
+
+    fn binary(&mut self, left: &SqlExpr, op: &str, right: &SqlExpr) {
+        self.sql.push('(');
+        self.render(left);
+        self.sql.push_str(op);
+        self.render(right);
+        self.sql.push(')');
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn render(&mut self, expr: &SqlExpr) {
+        match expr {
+            SqlExpr::Constant(true) => self.sql.push_str("(1 = 1)"),
+            SqlExpr::Constant(false) => self.sql.push_str("(1 = 0)"),
+            SqlExpr::Null => self.sql.push_str("NULL"),
+            SqlExpr::Param(value) => self.param(value.clone()),
+            SqlExpr::Column { table, column } => {
+                let _ = write!(self.sql, "{table}.{column}");
+            }
+            SqlExpr::Not(e) => {
+                self.sql.push_str("(NOT ");
+                self.render(e);
+                self.sql.push(')');
+            }
+            SqlExpr::And(l, r) => self.binary(l, " AND ", r),
+            SqlExpr::Or(l, r) => self.binary(l, " OR ", r),
+            SqlExpr::IsTrue(e) => {
+                self.sql.push('(');
+                self.render(e);
+                self.sql.push_str(" IS TRUE)");
+            }
+            SqlExpr::IsNotNull(e) => {
+                self.sql.push('(');
+                self.render(e);
+                self.sql.push_str(" IS NOT NULL)");
+            }
+            SqlExpr::Compare { op, left, right } => {
+                let op = match op {
+                    CompareOp::Eq => " = ",
+                    CompareOp::Less => " < ",
+                    CompareOp::LessEq => " <= ",
+                };
+                self.binary(left, op, right);
+            }
+            SqlExpr::Arith { op, left, right } => {
+                let op = match op {
+                    ArithOp::Add => " + ",
+                    ArithOp::Sub => " - ",
+                    ArithOp::Mul => " * ",
+                };
+                self.binary(left, op, right);
+            }
+            SqlExpr::Neg(e) => {
+                self.sql.push_str("(-");
+                self.render(e);
+                self.sql.push(')');
+            }
+            SqlExpr::Like { expr, pattern } => {
+                self.sql.push('(');
+                self.render(expr);
+                match self.dialect {
+                    SqlDialect::Postgres => {
+                        self.sql.push_str(" LIKE ");
+                        self.param(SqlValue::String(pattern.into()));
+                        self.sql.push_str(" ESCAPE '\\'");
+                    }
+                    SqlDialect::Sqlite => {
+                        self.sql.push_str(" GLOB ");
+                        self.param(SqlValue::String(glob_pattern(pattern).into()));
+                    }
+                    // `\` is the default escape character, and can't be
+                    // written as `'\'` when backslash escapes are enabled
+                    SqlDialect::MySql => {
+                        self.sql.push_str(" LIKE BINARY ");
+                        self.param(SqlValue::String(pattern.into()));
+                    }
+                }
+                self.sql.push(')');
+            }
+            SqlExpr::InList { expr, list } => {
+                if list.is_empty() {
+                    self.render(&SqlExpr::Constant(false));
+                    return;
+                }
+                self.sql.push('(');
+                self.render(expr);
+                self.sql.push_str(" IN (");
+                for (i, e) in list.iter().enumerate() {
+                    if i > 0 {
+                        self.sql.push_str(", ");
+                    }
+                    self.render(e);
+                }
+                self.sql.push_str("))");
+            }
+            SqlExpr::Case {
+                test,
+                then_expr,
+                else_expr,
+            } => {
+                self.sql.push_str("(CASE WHEN ");
+                self.render(test);
+                self.sql.push_str(" THEN ");
+                self.render(then_expr);
+                self.sql.push_str(" ELSE ");
+                self.render(else_expr);
+                self.sql.push_str(" END)");
+            }
+            SqlExpr::Ancestor {
+                hierarchy,
+                descendant_type,
+                descendant,
+                ancestor_type,
+                ancestor,
+            } => {
+                let HierarchyMapping {
+                    table,
+                    descendant_type_column,
+                    descendant_column,
+                    ancestor_type_column,
+                    ancestor_column,
+                } = hierarchy;
+                let _ = write!(
+                    self.sql,
+                    "EXISTS (SELECT 1 FROM {table} WHERE {table}.{descendant_type_column} = "
+                );
+                self.render(descendant_type);
+                let _ = write!(self.sql, " AND {table}.{descendant_column} = ");
+                self.render(descendant);
+                let _ = write!(self.sql, " AND {table}.{ancestor_type_column} = ");
+                self.render(ancestor_type);
+                let _ = write!(self.sql, " AND {table}.{ancestor_column} = ");
+                self.render(ancestor);
+                self.sql.push(')');
+            }
+        }
+    }
+}
+
+/// Errors when translating residuals to SQL
+#[derive(Debug, Clone, PartialEq, Eq, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum SqlTranslationError {
+    /// A residual contains an expression which cannot be translated
+    #[error("cannot translate `{expr}` to SQL: {reason}")]
+    Unsupported {
+        /// The expression which cannot be translated
+        expr: String,
+        /// Why the expression cannot be translated
+        reason: String,
+    },
+    /// No table is mapped for the type of the resource
+    #[error("no table is mapped for entity type `{entity_type}`")]
+    UnmappedEntityType {
+        /// The entity type
+        entity_type: String,
+    },
+    /// No column is mapped for an attribute of the resource
+    #[error("no column is mapped for attribute `{attr}` of entity type `{entity_type}`")]
+    UnmappedAttribute {
+        /// The entity type
+        entity_type: String,
+        /// The attribute
+        attr: String,
+    },
+    /// The response was computed with an error-handling mode other than
+    /// [`ErrorHandling::Skip`]
+    #[error("only responses using the `Skip` error-handling mode can be translated, not `{error_handling:?}`")]
+    UnsupportedErrorHandling {
+        /// The error-handling mode of the response
+        error_handling: ErrorHandling,
+    },
+}
+
+impl SqlTranslationError {
+    fn unsupported(expr: &ast::Expr, reason: impl Into<String>) -> Self {
+        Self::Unsupported {
+            expr: expr.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Translate the residuals in `response` into a predicate which is true for
+/// exactly the rows of the resource table for which the request is allowed.
+///
+/// The resource of the request must be unknown, with a known entity type
+/// (see [`crate::RequestBuilder::unknown_resource_with_type`]); every other
+/// part of the request must be known.
+///
+/// # Errors
+///
+/// Returns an error if a residual contains an expression which cannot be
+/// translated, or which refers to an entity type or attribute which is not
+/// in `mapping`, or if the response has residuals and was computed with an
+/// error-handling mode other than [`ErrorHandling::Skip`], since whether a
+/// residual errors for a row cannot be expressed in SQL.
+pub fn residuals_to_sql(
+    response: &PartialResponse,
+    mapping: &SqlMapping,
+) -> Result<SqlExpr, SqlTranslationError> {
+    match response.decision() {
+        Some(Decision::Allow) => return Ok(SqlExpr::Constant(true)),
+        Some(Decision::Deny) => return Ok(SqlExpr::Constant(false)),
+        None => (),
+    }
+    let error_handling = response.error_handling();
+    if error_handling != ErrorHandling::Skip {
+        return Err(SqlTranslationError::UnsupportedErrorHandling { error_handling });
+    }
+    let mut permits = SqlExpr::Constant(false);
+    let mut forbids = SqlExpr::Constant(false);
+    for policy in response.definitely_satisfied() {
+        match policy.effect() {
+            Effect::Permit => permits = SqlExpr::Constant(true),
+            Effect::Forbid => forbids = SqlExpr::Constant(true),
+        }
+    }
+    let request = &response.request().0;
+    for policy in response.nontrivial_residuals() {
+        let condition = translate_condition(&policy, request, mapping)?;
+        match policy.effect() {
+            Effect::Permit => permits = permits.or(condition),
+            Effect::Forbid => forbids = forbids.or(condition),
+        }
+    }
+    Ok(permits.and(forbids.not()))
+}
+
+/// Translate the condition of the residual `policy` of a response to
+/// `request` into a predicate which is true for exactly the rows of the
+/// resource table which satisfy the policy
+fn translate_condition(
+    policy: &Policy,
+    request: &ast::Request,
+    mapping: &SqlMapping,
+) -> Result<SqlExpr, SqlTranslationError> {
+    let condition = policy.ast.condition();
+    Translator { request, mapping }
+        .translate(&condition)?
+        .into_sql(&condition)
+        .map(SqlExpr::truth)
+}
+
+/// Why an entity cannot be compared with a SQL expression
+const UNTYPED_ENTITY: &str = "the entity type of this value is unknown: attributes whose values are entities must be mapped with `TableMapping::with_entity_column`";
+
+/// Result of translating a Cedar expression
+#[derive(Clone)]
+enum Term<'a> {
+    /// A SQL expression
+    Sql(SqlExpr),
+    /// The resource, of the given type, stored in the given table
+    Resource(ast::EntityType, &'a TableMapping),
+    /// An entity of the given type, whose id is stored in a column
+    EntityColumn(ast::EntityType, SqlExpr),
+    /// An entity literal
+    Entity(ast::EntityUID),
+    /// A set literal
+    Set(Vec<Self>),
+}
+
+impl Term<'_> {
+    /// Use this term as a SQL value. Entities cannot be used as values, as
+    /// their id alone does not identify them.
+    fn into_sql(self, expr: &ast::Expr) -> Result<SqlExpr, SqlTranslationError> {
+        match self {
+            Term::Sql(e) => Ok(e),
+            Term::Resource(..) | Term::EntityColumn(..) | Term::Entity(_) => {
+                Err(SqlTranslationError::unsupported(
+                    expr,
+                    "entities can only be used with `==`, `contains`, `in` and `is`",
+                ))
+            }
+            Term::Set(_) => Err(SqlTranslationError::unsupported(
+                expr,
+                "sets can only be used with `contains` and `in`",
+            )),
+        }
+    }
+
+    /// The type and id of the entity this term refers to, if it is an entity
+    fn entity(&self) -> Option<(&ast::EntityType, SqlExpr)> {
+        match self {
+            Term::Resource(ty, table) => Some((ty, table.column(&table.id_column))),
+            Term::EntityColumn(ty, id) => Some((ty, id.clone())),
+            Term::Entity(uid) => Some((uid.entity_type(), entity_id(uid))),
+            Term::Sql(_) | Term::Set(_) => None,
+        }
+    }
+}
+
+/// The SQL expressions to compare to translate `left == right`, where `left`
+/// and `right` are the translations of `arg1` and `arg2`, or `None` if they
+/// are entities of different types, which are never equal
+fn operands(
+    expr: &ast::Expr,
+    (left, arg1): (&Term<'_>, &ast::Expr),
+    (right, arg2): (&Term<'_>, &ast::Expr),
+) -> Result<Option<(SqlExpr, SqlExpr)>, SqlTranslationError> {
+    match (left.entity(), right.entity()) {
+        (Some((left_ty, left)), Some((right_ty, right))) => {
+            Ok((left_ty == right_ty).then_some((left, right)))
+        }
+        (None, None) => Ok(Some((
+            left.clone().into_sql(arg1)?,
+            right.clone().into_sql(arg2)?,
+        ))),
+        _ => Err(SqlTranslationError::unsupported(expr, UNTYPED_ENTITY)),
+    }
+}
+
+struct Translator<'a> {
+    /// The request, which may be needed to evaluate variables left in the
+    /// residual, e.g. in operands which were not partially evaluated
+    request: &'a ast::Request,
+    mapping: &'a SqlMapping,
+}
+
+impl<'a> Translator<'a> {
+    #[allow(clippy::too_many_lines)]
+    fn translate(&self, expr: &ast::Expr) -> Result<Term<'a>, SqlTranslationError> {
+        let sql = |e: &ast::Expr| self.translate(e)?.into_sql(e);
+        Ok(match expr.expr_kind() {
+            ExprKind::Lit(lit) => literal(lit),
+            ExprKind::Var(var) => self.var(expr, *var)?,
+            ExprKind::Unknown(unknown) if unknown.name == "resource" => {
+                match &unknown.type_annotation {
+                    Some(ast::Type::Entity { ty }) => Term::Resource(ty.clone(), self.table(ty)?),
+                    _ => {
+                        return Err(SqlTranslationError::unsupported(
+                            expr,
+                            "the type of the resource is unknown",
+                        ))
+                    }
+                }
+            }
+            ExprKind::Unknown(_) => {
+                return Err(SqlTranslationError::unsupported(
+                    expr,
+                    "only the resource may be unknown",
+                ))
+            }
+            ExprKind::If {
+                test_expr,
+                then_expr,
+                else_expr,
+            } => {
+                let test = sql(test_expr)?;
+                let case = SqlExpr::Case {
+                    test: Box::new(test.clone().truth()),
+                    then_expr: Box::new(sql(then_expr)?),
+                    else_expr: Box::new(sql(else_expr)?),
+                };
+                Term::Sql(self.unless_null(&[test], case))
+            }
+            // SQL's `NULL AND FALSE` is `FALSE` and `NULL OR TRUE` is `TRUE`,
+            // but Cedar evaluates the left operand first, so an error there is
+            // an error whatever the right operand is
+            ExprKind::And { left, right } => {
+                let left = sql(left)?;
+                let expr = left.clone().and(sql(right)?);
+                Term::Sql(self.unless_null(std::slice::from_ref(&left), expr))
+            }
+            ExprKind::Or { left, right } => {
+                let left = sql(left)?;
+                let expr = left.clone().or(sql(right)?);
+                Term::Sql(self.unless_null(std::slice::from_ref(&left), expr))
+            }
+            ExprKind::UnaryApp { op, arg } => match op {
+                UnaryOp::Not => Term::Sql(sql(arg)?.not()),
+                UnaryOp::Neg => Term::Sql(SqlExpr::Neg(Box::new(sql(arg)?))),
+                UnaryOp::IsEmpty => {
+                    return Err(SqlTranslationError::unsupported(
+                        expr,
+                        "sets cannot be stored in columns",
+                    ))
+                }
+            },
+            ExprKind::BinaryApp { op, arg1, arg2 } => self.binary(expr, *op, arg1, arg2)?,
+            ExprKind::GetAttr { expr: e, attr } if is_context(e) => {
+                match self.context(expr)?.get(attr) {
+                    Some(value) => value_term(expr, value)?,
+                    None => {
+                        return Err(SqlTranslationError::unsupported(
+                            expr,
+                            "the context does not have this attribute",
+                        ))
+                    }
+                }
+            }
+            ExprKind::HasAttr { expr: e, attr } if is_context(e) => {
+                Term::Sql(SqlExpr::Constant(self.context(expr)?.contains_key(attr)))
+            }
+            ExprKind::GetAttr { expr: e, attr } => match self.translate(e)? {
+                Term::Resource(ty, table) => {
+                    let column = table.column(Self::attr(&ty, table, attr)?);
+                    match table.entity_types.get(attr) {
+                        Some(entity_type) => Term::EntityColumn(entity_type.clone(), column),
+                        None => Term::Sql(column),
+                    }
+                }
+                _ => {
+                    return Err(SqlTranslationError::unsupported(
+                        expr,
+                        "only attributes of the resource can be translated",
+                    ))
+                }
+            },
+            ExprKind::HasAttr { expr: e, attr } => match self.translate(e)? {
+                Term::Resource(ty, table) => Term::Sql(SqlExpr::IsNotNull(Box::new(
+                    table.column(Self::attr(&ty, table, attr)?),
+                ))),
+                _ => {
+                    return Err(SqlTranslationError::unsupported(
+                        expr,
+                        "only attributes of the resource can be translated",
+                    ))
+                }
+            },
+            ExprKind::Like { expr: e, pattern } => Term::Sql(SqlExpr::Like {
+                expr: Box::new(sql(e)?),
+                pattern: like_pattern(pattern.iter()),
+            }),
+            ExprKind::Is {
+                expr: e,
+                entity_type,
+            } => match self.translate(e)? {
+                Term::Resource(ty, _) => Term::Sql(SqlExpr::Constant(&ty == entity_type)),
+                Term::EntityColumn(ty, id) => {
+                    Term::Sql(self.unless_null(&[id], SqlExpr::Constant(&ty == entity_type)))
+                }
+                Term::Entity(uid) => Term::Sql(SqlExpr::Constant(uid.entity_type() == entity_type)),
+                _ => {
+                    return Err(SqlTranslationError::unsupported(
+                        expr,
+                        "the type of entities stored in columns is unknown",
+                    ))
+                }
+            },
+            ExprKind::Set(elems) => Term::Set(
+                elems
+                    .iter()
+                    .map(|e| self.translate(e))
+                    .collect::<Result<_, _>>()?,
+            ),
+            ExprKind::Slot(_) => {
+                return Err(SqlTranslationError::unsupported(
+                    expr,
+                    "templates must be linked",
+                ))
+            }
+            ExprKind::ExtensionFunctionApp { .. } => {
+                return Err(SqlTranslationError::unsupported(
+                    expr,
+                    "extension functions are not supported",
+                ))
+            }
+            ExprKind::Record(_) => {
+                return Err(SqlTranslationError::unsupported(
+                    expr,
+                    "records are not supported",
+                ))
+            }
+            #[cfg(feature = "tolerant-ast")]
+            ExprKind::Error { .. } => {
+                return Err(SqlTranslationError::unsupported(
+                    expr,
+                    "the expression failed to parse",
+                ))
+            }
+        })
+    }
+
+    fn binary(
+        &self,
+        expr: &ast::Expr,
+        op: BinaryOp,
+        arg1: &ast::Expr,
+        arg2: &ast::Expr,
+    ) -> Result<Term<'a>, SqlTranslationError> {
+        let left = self.translate(arg1)?;
+        let right = self.translate(arg2)?;
+        let compare = |op, left: Term<'a>, right: Term<'a>| -> Result<_, SqlTranslationError> {
+            Ok(Term::Sql(SqlExpr::compare(
+                op,
+                left.into_sql(arg1)?,
+                right.into_sql(arg2)?,
+            )))
+        };
+        match op {
+            BinaryOp::Eq => match (&left, &right) {
+                (Term::Resource(..), Term::Resource(..)) => Ok(Term::Sql(SqlExpr::Constant(true))),
+                (Term::Entity(a), Term::Entity(b)) => Ok(Term::Sql(SqlExpr::Constant(a == b))),
+                _ => Ok(Term::Sql(
+                    match operands(expr, (&left, arg1), (&right, arg2))? {
+                        Some((left, right)) => SqlExpr::compare(CompareOp::Eq, left, right),
+                        None => {
+                            self.unless_null(&entity_ids([&left, &right]), SqlExpr::Constant(false))
+                        }
+                    },
+                )),
+            },
+            BinaryOp::Less => compare(CompareOp::Less, left, right),
+            BinaryOp::LessEq => compare(CompareOp::LessEq, left, right),
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul => Ok(Term::Sql(SqlExpr::Arith {
+                op: match op {
+                    BinaryOp::Add => ArithOp::Add,
+                    BinaryOp::Sub => ArithOp::Sub,
+                    _ => ArithOp::Mul,
+                },
+                left: Box::new(left.into_sql(arg1)?),
+                right: Box::new(right.into_sql(arg2)?),
+            })),
+            BinaryOp::In => {
+                let ancestors = match right {
+                    Term::Set(elems) => elems,
+                    term => vec![term],
+                };
+                let mut result = SqlExpr::Constant(false);
+                for ancestor in ancestors {
+                    let Term::Entity(ancestor) = ancestor else {
+                        return Err(SqlTranslationError::unsupported(
+                            expr,
+                            "only entity literals can be ancestors",
+                        ));
+                    };
+                    result = result.or(self.descendant_of(expr, &left, arg1, &ancestor)?);
+                }
+                Ok(Term::Sql(result))
+            }
+            BinaryOp::Contains => match &left {
+                Term::Set(elems) => {
+                    // elements which are entities of another type than
+                    // `right` are left out of the list
+                    let mut needle = None;
+                    let mut list = Vec::new();
+                    for elem in elems {
+                        if let Some((value, elem)) = operands(expr, (&right, arg2), (elem, arg1))? {
+                            needle = Some(value);
+                            list.push(elem);
+                        }
+                    }
+                    // `IN` is true if any element is equal, even if another
+                    // is `NULL`, while Cedar fails on any erroring element
+                    let contains =
+                        needle.map_or(SqlExpr::Constant(false), |needle| SqlExpr::InList {
+                            expr: Box::new(needle),
+                            list,
+                        });
+                    let operands = entity_ids(elems.iter().chain([&right]))
+                        .into_iter()
+                        .chain(elems.iter().filter_map(|e| match e {
+                            Term::Sql(e) => Some(e.clone()),
+                            _ => None,
+                        }))
+                        .collect::<Vec<_>>();
+                    Ok(Term::Sql(self.unless_null(&operands, contains)))
+                }
+                _ => Err(SqlTranslationError::unsupported(
+                    expr,
+                    "sets cannot be stored in columns",
+                )),
+            },
+            BinaryOp::ContainsAll | BinaryOp::ContainsAny => Err(SqlTranslationError::unsupported(
+                expr,
+                "sets cannot be stored in columns",
+            )),
+            BinaryOp::GetTag | BinaryOp::HasTag => Err(SqlTranslationError::unsupported(
+                expr,
+                "tags are not supported",
+            )),
+        }
+    }
+
+    /// Translate `descendant in ancestor`, where `descendant` is the
+    /// translation of `arg`
+    fn descendant_of(
+        &self,
+        expr: &ast::Expr,
+        descendant: &Term<'a>,
+        arg: &ast::Expr,
+        ancestor: &ast::EntityUID,
+    ) -> Result<SqlExpr, SqlTranslationError> {
+        let (ty, id) = match descendant {
+            Term::Entity(uid) if uid == ancestor => return Ok(SqlExpr::Constant(true)),
+            Term::Resource(ty, table) => (ty, table.column(&table.id_column)),
+            Term::EntityColumn(ty, id) => (ty, id.clone()),
+            Term::Sql(_) => return Err(SqlTranslationError::unsupported(expr, UNTYPED_ENTITY)),
+            Term::Entity(_) | Term::Set(_) => {
+                return Err(SqlTranslationError::unsupported(
+                    expr,
+                    format!("`{arg}` cannot be looked up in the hierarchy"),
+                ))
+            }
+        };
+        let Some(hierarchy) = &self.mapping.hierarchy else {
+            return Err(SqlTranslationError::unsupported(
+                expr,
+                "no hierarchy table is mapped",
+            ));
+        };
+        let equal = if ty == ancestor.entity_type() {
+            SqlExpr::compare(CompareOp::Eq, id.clone(), entity_id(ancestor))
+        } else {
+            SqlExpr::Constant(false)
+        };
+        // `EXISTS` is false rather than `NULL` for a `NULL` descendant
+        let descendant_of = equal.or(SqlExpr::Ancestor {
+            hierarchy: hierarchy.clone(),
+            descendant_type: Box::new(entity_type(ty)),
+            descendant: Box::new(id.clone()),
+            ancestor_type: Box::new(entity_type(ancestor.entity_type())),
+            ancestor: Box::new(entity_id(ancestor)),
+        });
+        Ok(self.unless_null(&[id], descendant_of))
+    }
+
+    /// `expr`, or `NULL` if any of `operands` is `NULL`, i.e. would produce an
+    /// error in Cedar. `NULL` operands of SQL operators which propagate
+    /// `NULL` need not be checked.
+    fn unless_null(&self, operands: &[SqlExpr], expr: SqlExpr) -> SqlExpr {
+        let test = operands
+            .iter()
+            .filter(|op| **op != expr && self.nullable(op))
+            .fold(SqlExpr::Constant(true), |test, op| {
+                test.and(SqlExpr::IsNotNull(Box::new(op.clone())))
+            });
+        match test {
+            SqlExpr::Constant(true) => expr,
+            test => SqlExpr::Case {
+                test: Box::new(test),
+                then_expr: Box::new(expr),
+                else_expr: Box::new(SqlExpr::Null),
+            },
+        }
+    }
+
+    /// Whether `expr` may be `NULL`. Only the id columns of entity tables are
+    /// known not to be `NULL`.
+    fn nullable(&self, expr: &SqlExpr) -> bool {
+        match expr {
+            SqlExpr::Constant(_)
+            | SqlExpr::Param(_)
+            | SqlExpr::IsTrue(_)
+            | SqlExpr::IsNotNull(_)
+            | SqlExpr::Ancestor { .. } => false,
+            SqlExpr::Null => true,
+            SqlExpr::Column { table, column } => !self
+                .mapping
+                .tables
+                .values()
+                .any(|t| &t.table == table && &t.id_column == column),
+            SqlExpr::Not(e) | SqlExpr::Neg(e) | SqlExpr::Like { expr: e, .. } => self.nullable(e),
+            SqlExpr::And(l, r)
+            | SqlExpr::Or(l, r)
+            | SqlExpr::Compare {
+                left: l, right: r, ..
+            }
+            | SqlExpr::Arith {
+                left: l, right: r, ..
+            }
+            | SqlExpr::Case {
+                then_expr: l,
+                else_expr: r,
+                ..
+            } => self.nullable(l) || self.nullable(r),
+            SqlExpr::InList { expr, list } => {
+                self.nullable(expr) || list.iter().any(|e| self.nullable(e))
+            }
+        }
+    }
+
+    /// Translate a variable which the partial evaluator did not substitute
+    fn var(&self, expr: &ast::Expr, var: ast::Var) -> Result<Term<'a>, SqlTranslationError> {
+        let entry = match var {
+            ast::Var::Principal => self.request.principal(),
+            ast::Var::Action => self.request.action(),
+            ast::Var::Resource => self.request.resource(),
+            ast::Var::Context => {
+                return Err(SqlTranslationError::unsupported(
+                    expr,
+                    "records are not supported",
+                ))
+            }
+        };
+        match entry {
+            ast::EntityUIDEntry::Known { euid, .. } => Ok(Term::Entity(euid.as_ref().clone())),
+            ast::EntityUIDEntry::Unknown { ty: Some(ty), .. } if var == ast::Var::Resource => {
+                Ok(Term::Resource(ty.clone(), self.table(ty)?))
+            }
+            ast::EntityUIDEntry::Unknown { .. } => Err(SqlTranslationError::unsupported(
+                expr,
+                "only the resource may be unknown, and its type must be known",
+            )),
+        }
+    }
+
+    /// The attributes of the context, if it is known
+    fn context(
+        &self,
+        expr: &ast::Expr,
+    ) -> Result<&'a BTreeMap<SmolStr, ast::Value>, SqlTranslationError> {
+        match self.request.context() {
+            Some(ast::Context::Value(attrs)) => Ok(attrs),
+            _ => Err(SqlTranslationError::unsupported(
+                expr,
+                "only the resource may be unknown",
+            )),
+        }
+    }
+
+    fn table(&self, ty: &ast::EntityType) -> Result<&'a TableMapping, SqlTranslationError> {
+        self.mapping
+            .tables
+            .get(EntityTypeName::ref_cast(ty))
+            .ok_or_else(|| SqlTranslationError::UnmappedEntityType {
+                entity_type: ty.to_string(),
+            })
+    }
+
+    fn attr<'t>(
+        ty: &ast::EntityType,
+        table: &'t TableMapping,
+        attr: &str,
+    ) -> Result<&'t str, SqlTranslationError> {
+        table.columns.get(attr).map(String::as_str).ok_or_else(|| {
+            SqlTranslationError::UnmappedAttribute {
+                entity_type: ty.to_string(),
+                attr: attr.to_owned(),
+            }
+        })
+    }
+}
+
+/// Whether `expr` is the `context` variable
+fn is_context(expr: &ast::Expr) -> bool {
+    matches!(expr.expr_kind(), ExprKind::Var(ast::Var::Context))
+}
+
+fn literal<'a>(lit: &Literal) -> Term<'a> {
+    match lit {
+        Literal::Bool(b) => Term::Sql(SqlExpr::Constant(*b)),
+        Literal::Long(i) => Term::Sql(SqlExpr::Param(SqlValue::Long(*i))),
+        Literal::String(s) => Term::Sql(SqlExpr::Param(SqlValue::String(s.clone()))),
+        Literal::EntityUID(uid) => Term::Entity(uid.as_ref().clone()),
+    }
+}
+
+/// Translate a value from the context
+fn value_term<'a>(expr: &ast::Expr, value: &ast::Value) -> Result<Term<'a>, SqlTranslationError> {
+    match value.value_kind() {
+        ast::ValueKind::Lit(lit) => Ok(literal(lit)),
+        ast::ValueKind::Set(set) => Ok(Term::Set(
+            set.iter()
+                .map(|v| value_term(expr, v))
+                .collect::<Result<_, _>>()?,
+        )),
+        ast::ValueKind::Record(_) => Err(SqlTranslationError::unsupported(
+            expr,
+            "records are not supported",
+        )),
+        ast::ValueKind::ExtensionValue(_) => Err(SqlTranslationError::unsupported(
+            expr,
+            "extension values are not supported",
+        )),
+    }
+}
+
+/// The ids of those of `terms` which are entities
+fn entity_ids<'t, 'a: 't>(terms: impl IntoIterator<Item = &'t Term<'a>>) -> Vec<SqlExpr> {
+    terms
+        .into_iter()
+        .filter_map(|t| t.entity().map(|(_, id)| id))
+        .collect()
+}
+
+/// The id of `uid`, as stored in the database
+fn entity_id(uid: &ast::EntityUID) -> SqlExpr {
+    let eid: &str = uid.eid().as_ref();
+    SqlExpr::Param(SqlValue::String(eid.into()))
+}
+
+/// The entity type `ty`, as stored in the hierarchy table
+fn entity_type(ty: &ast::EntityType) -> SqlExpr {
+    SqlExpr::Param(SqlValue::String(ty.to_string().into()))
+}
+
+/// Translate a Cedar `like` pattern into a SQL `LIKE` pattern, using `\` as
+/// the escape character
+fn like_pattern<'p>(pattern: impl Iterator<Item = &'p PatternElem>) -> String {
+    let mut like = String::new();
+    for elem in pattern {
+        match elem {
+            PatternElem::Wildcard => like.push('%'),
+            PatternElem::Char(c @ ('%' | '_' | '\\')) => {
+                like.push('\\');
+                like.push(*c);
+            }
+            PatternElem::Char(c) => like.push(*c),
+        }
+    }
+    like
+}
+
+/// Convert a `LIKE` pattern with `\` as the escape character, as produced by
+/// `like_pattern`, into a SQLite `GLOB` pattern
+fn glob_pattern(like: &str) -> String {
+    let mut glob = String::new();
+    let mut chars = like.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '%' => {
+                glob.push('*');
+                continue;
+            }
+            '\\' => match chars.next() {
+                Some(c) => c,
+                None => break,
+            },
+            c => c,
+        };
+        match c {
+            '*' | '?' | '[' => {
+                glob.push('[');
+                glob.push(c);
+                glob.push(']');
+            }
+            c => glob.push(c),
+        }
+    }
+    glob
+}
+
+#[cfg(test)]
+// PANIC SAFETY: unit tests
+#[allow(clippy::indexing_slicing, clippy::panic)]
+mod test {
+    use super::*;
+    use crate::{Authorizer, Context, Entities, EntityUid, PolicySet, Request};
+    use cool_asserts::assert_matches;
+
+    /// A value stored in the [`Database`]
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Cell {
+        Null,
+        Bool(bool),
+        Long(i64),
+        Text(SmolStr),
+    }
+
+    impl From<SqlValue> for Cell {
+        fn from(value: SqlValue) -> Self {
+            match value {
+                SqlValue::Bool(b) => Self::Bool(b),
+                SqlValue::Long(i) => Self::Long(i),
+                SqlValue::String(s) => Self::Text(s),
+            }
+        }
+    }
+
+    type Row = HashMap<&'static str, Cell>;
+
+    /// A minimal in-memory stand-in for a SQL database such as `SQLite`, which
+    /// evaluates `SqlExpr`s using SQL's three-valued logic
+    struct Database {
+        tables: HashMap<&'static str, Vec<Row>>,
+    }
+
+    impl Database {
+        /// The `id` of each row of `table` for which `predicate`, rendered in
+        /// `dialect`, is true
+        fn select(&self, table: &str, predicate: &SqlExpr, dialect: SqlDialect) -> Vec<SmolStr> {
+            self.tables[table]
+                .iter()
+                .filter(|row| self.eval(predicate, table, row, dialect) == Cell::Bool(true))
+                .map(|row| assert_matches!(&row["id"], Cell::Text(id) => id.clone()))
+                .collect()
+        }
+
+        fn eval(&self, expr: &SqlExpr, table: &str, row: &Row, dialect: SqlDialect) -> Cell {
+            let eval = |e: &SqlExpr| self.eval(e, table, row, dialect);
+            let bool = |e: &SqlExpr| match eval(e) {
+                Cell::Bool(b) => Some(b),
+                Cell::Null => None,
+                c => panic!("expected a boolean, got {c:?}"),
+            };
+            let from_bool = |b: Option<bool>| b.map_or(Cell::Null, Cell::Bool);
+            match expr {
+                SqlExpr::Constant(b) => Cell::Bool(*b),
+                SqlExpr::Null => Cell::Null,
+                SqlExpr::Param(v) => v.clone().into(),
+                SqlExpr::Column { table: t, column } => {
+                    assert_eq!(t, table);
+                    row.get(column.as_str()).cloned().unwrap_or(Cell::Null)
+                }
+                SqlExpr::Not(e) => from_bool(bool(e).map(|b| !b)),
+                SqlExpr::And(l, r) => from_bool(match (bool(l), bool(r)) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                }),
+                SqlExpr::Or(l, r) => from_bool(match (bool(l), bool(r)) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                }),
+                SqlExpr::IsTrue(e) => Cell::Bool(bool(e) == Some(true)),
+                SqlExpr::IsNotNull(e) => Cell::Bool(eval(e) != Cell::Null),
+                SqlExpr::Compare { op, left, right } => match (eval(left), eval(right)) {
+                    (Cell::Null, _) | (_, Cell::Null) => Cell::Null,
+                    (l, r) => Cell::Bool(match op {
+                        CompareOp::Eq => l == r,
+                        CompareOp::Less => {
+                            assert_matches!((l, r), (Cell::Long(l), Cell::Long(r)) => l < r)
+                        }
+                        CompareOp::LessEq => {
+                            assert_matches!((l, r), (Cell::Long(l), Cell::Long(r)) => l <= r)
+                        }
+                    }),
+                },
+                SqlExpr::Arith { op, left, right } => match (eval(left), eval(right)) {
+                    (Cell::Long(l), Cell::Long(r)) => Cell::Long(match op {
+                        ArithOp::Add => l + r,
+                        ArithOp::Sub => l - r,
+                        ArithOp::Mul => l * r,
+                    }),
+                    _ => Cell::Null,
+                },
+                SqlExpr::Neg(e) => match eval(e) {
+                    Cell::Long(i) => Cell::Long(-i),
+                    _ => Cell::Null,
+                },
+                SqlExpr::Like { expr: e, .. } => match eval(e) {
+                    Cell::Text(s) => Cell::Bool(like_matches(expr, dialect, &s)),
+                    _ => Cell::Null,
+                },
+                SqlExpr::InList { expr, list } => {
+                    let value = eval(expr);
+                    let cells: Vec<_> = list.iter().map(eval).collect();
+                    if value == Cell::Null {
+                        Cell::Null
+                    } else if cells.contains(&value) {
+                        Cell::Bool(true)
+                    } else if cells.contains(&Cell::Null) {
+                        Cell::Null
+                    } else {
+                        Cell::Bool(false)
+                    }
+                }
+                SqlExpr::Case {
+                    test,
+                    then_expr,
+                    else_expr,
+                } => {
+                    if bool(test) == Some(true) {
+                        eval(then_expr)
+                    } else {
+                        eval(else_expr)
+                    }
+                }
+                SqlExpr::Ancestor {
+                    hierarchy,
+                    descendant_type,
+                    descendant,
+                    ancestor_type,
+                    ancestor,
+                } => {
+                    let columns = [
+                        (&hierarchy.descendant_type_column, eval(descendant_type)),
+                        (&hierarchy.descendant_column, eval(descendant)),
+                        (&hierarchy.ancestor_type_column, eval(ancestor_type)),
+                        (&hierarchy.ancestor_column, eval(ancestor)),
+                    ];
+                    Cell::Bool(
+                        self.tables[hierarchy.table.as_str()]
+                            .iter()
+                            .any(|r| columns.iter().all(|(c, v)| &r[c.as_str()] == v)),
+                    )
+                }
+            }
+        }
+    }
+
+    /// Whether `s` is matched by the SQL that the `Like` expression `like`
+    /// renders to in `dialect`, following that dialect's semantics: plain
+    /// `LIKE` ignores case in SQLite and MySQL
+    fn like_matches(like: &SqlExpr, dialect: SqlDialect, s: &str) -> bool {
+        let (sql, params) = like.to_sql(dialect);
+        let [SqlValue::String(pattern)] = params.as_slice() else {
+            panic!("expected a pattern, got {params:?}");
+        };
+        let pattern: Vec<char> = pattern.chars().collect();
+        let s: Vec<char> = s.chars().collect();
+        if sql.contains(" GLOB ") {
+            glob(&s, &pattern)
+        } else if sql.contains(" LIKE BINARY ") || dialect == SqlDialect::Postgres {
+            like_match(&s, &pattern)
+        } else if sql.contains(" LIKE ") {
+            let lower =
+                |cs: &[char]| -> Vec<char> { cs.iter().flat_map(|c| c.to_lowercase()).collect() };
+            like_match(&lower(&s), &lower(&pattern))
+        } else {
+            panic!("unexpected rendering of `like`: {sql}")
+        }
+    }
+
+    /// Whether `s` matches the SQL `LIKE` pattern `pattern`, with `\` as the
+    /// escape character
+    fn like_match(s: &[char], pattern: &[char]) -> bool {
+        match pattern {
+            [] => s.is_empty(),
+            ['%', rest @ ..] => (0..=s.len()).any(|i| like_match(&s[i..], rest)),
+            ['_', rest @ ..] => !s.is_empty() && like_match(&s[1..], rest),
+            ['\\', c, rest @ ..] | [c, rest @ ..] => {
+                s.first() == Some(c) && like_match(&s[1..], rest)
+            }
+        }
+    }
+
+    /// Whether `s` matches the SQLite `GLOB` pattern `pattern`, which may
+    /// only use `[...]` to match a single character
+    fn glob(s: &[char], pattern: &[char]) -> bool {
+        match pattern {
+            [] => s.is_empty(),
+            ['*', rest @ ..] => (0..=s.len()).any(|i| glob(&s[i..], rest)),
+            ['?', rest @ ..] => !s.is_empty() && glob(&s[1..], rest),
+            ['[', c, ']', rest @ ..] | [c, rest @ ..] => {
+                s.first() == Some(c) && glob(&s[1..], rest)
+            }
+        }
+    }
+
+    fn text(s: &str) -> Cell {
+        Cell::Text(s.into())
+    }
+
+    /// The same documents, as Cedar entities and as database rows
+    fn data() -> (Entities, Database) {
+        let entities = Entities::from_json_value(
+            serde_json::json!([
+                { "uid": { "type": "User", "id": "alice" }, "attrs": {}, "parents": [] },
+                { "uid": { "type": "User", "id": "bob" }, "attrs": {}, "parents": [] },
+                { "uid": { "type": "Folder", "id": "root" }, "attrs": {}, "parents": [] },
+                { "uid": { "type": "Folder", "id": "public" }, "attrs": {}, "parents": [] },
+                { "uid": { "type": "Folder", "id": "private" }, "attrs": {},
+                  "parents": [{ "type": "Folder", "id": "root" }] },
+                { "uid": { "type": "Doc", "id": "a" },
+                  "attrs": { "owner": { "__entity": { "type": "User", "id": "alice" } },
+                             "level": 1, "title": "Quarterly report" },
+                  "parents": [{ "type": "Folder", "id": "public" }] },
+                { "uid": { "type": "Doc", "id": "b" },
+                  "attrs": { "owner": { "__entity": { "type": "User", "id": "bob" } },
+                             "level": 5, "title": "50% off", "secret": true },
+                  "parents": [{ "type": "Folder", "id": "private" }] },
+                { "uid": { "type": "Doc", "id": "c" },
+                  "attrs": { "owner": { "__entity": { "type": "User", "id": "alice" } },
+                             "level": 3, "title": "notes_2024", "secret": false },
+                  "parents": [] },
+                { "uid": { "type": "Doc", "id": "d" },
+                  "attrs": { "owner": { "__entity": { "type": "User", "id": "carol" } },
+                             "title": "Plan" },
+                  "parents": [{ "type": "Folder", "id": "public" }] }
+            ]),
+            None,
+        )
+        .unwrap();
+        let doc = |id, owner, level: Option<i64>, title, secret: Option<bool>| {
+            Row::from([
+                ("id", text(id)),
+                ("owner", text(owner)),
+                ("level", level.map_or(Cell::Null, Cell::Long)),
+                ("title", text(title)),
+                ("secret", secret.map_or(Cell::Null, Cell::Bool)),
+            ])
+        };
+        let ancestor = |(dt, d), (at, a)| {
+            Row::from([
+                ("entity_type", text(dt)),
+                ("entity", text(d)),
+                ("ancestor_type", text(at)),
+                ("ancestor", text(a)),
+            ])
+        };
+        let db = Database {
+            tables: HashMap::from([
+                (
+                    "docs",
+                    vec![
+                        doc("a", "alice", Some(1), "Quarterly report", None),
+                        doc("b", "bob", Some(5), "50% off", Some(true)),
+                        doc("c", "alice", Some(3), "notes_2024", Some(false)),
+                        doc("d", "carol", None, "Plan", None),
+                    ],
+                ),
+                (
+                    "ancestors",
+                    vec![
+                        ancestor(("Doc", "a"), ("Folder", "public")),
+                        ancestor(("Doc", "b"), ("Folder", "private")),
+                        ancestor(("Doc", "b"), ("Folder", "root")),
+                        ancestor(("Doc", "d"), ("Folder", "public")),
+                        ancestor(("Folder", "private"), ("Folder", "root")),
+                    ],
+                ),
+            ]),
+        };
+        (entities, db)
+    }
+
+    fn mapping() -> SqlMapping {
+        SqlMapping::new()
+            .with_table(
+                "Doc".parse().unwrap(),
+                TableMapping::new("docs", "id")
+                    .with_entity_column("owner", "owner", "User".parse().unwrap())
+                    .with_column("level", "level")
+                    .with_column("title", "title")
+                    .with_column("secret", "secret"),
+            )
+            .with_hierarchy(HierarchyMapping::new(
+                "ancestors",
+                "entity_type",
+                "entity",
+                "ancestor_type",
+                "ancestor",
+            ))
+    }
+
+    fn partial_response(
+        policies: &PolicySet,
+        entities: &Entities,
+        principal: &EntityUid,
+    ) -> PartialResponse {
+        let request = Request::builder()
+            .principal(principal.clone())
+            .action(r#"Action::"read""#.parse().unwrap())
+            .unknown_resource_with_type("Doc".parse().unwrap())
+            .context(Context::empty())
+            .build();
+        Authorizer::new().is_authorized_partial(&request, policies, entities)
+    }
+
+    /// Check that the translated residuals select exactly the documents for
+    /// which the request is allowed
+    #[track_caller]
+    fn assert_matches_authorizer(policies: &str) {
+        let policies: PolicySet = policies.parse().unwrap();
+        let (entities, db) = data();
+        for principal in [r#"User::"alice""#, r#"User::"bob""#] {
+            let principal: EntityUid = principal.parse().unwrap();
+            let response = partial_response(&policies, &entities, &principal);
+            let predicate = residuals_to_sql(&response, &mapping()).unwrap();
+            let expected: Vec<SmolStr> = ["a", "b", "c", "d"]
+                .into_iter()
+                .filter(|id| {
+                    let request = Request::new(
+                        principal.clone(),
+                        r#"Action::"read""#.parse().unwrap(),
+                        format!(r#"Doc::"{id}""#).parse().unwrap(),
+                        Context::empty(),
+                        None,
+                    )
+                    .unwrap();
+                    Authorizer::new()
+                        .is_authorized(&request, &policies, &entities)
+                        .decision()
+                        == Decision::Allow
+                })
+                .map(SmolStr::from)
+                .collect();
+            for dialect in [SqlDialect::Postgres, SqlDialect::Sqlite, SqlDialect::MySql] {
+                assert_eq!(
+                    db.select("docs", &predicate, dialect),
+                    expected,
+                    "for {principal}, predicate: {}",
+                    predicate.to_sql(dialect).0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hierarchy() {
+        assert_matches_authorizer(
+            r#"permit(principal, action == Action::"read", resource in Folder::"public");"#,
+        );
+        assert_matches_authorizer(
+            r#"permit(principal, action, resource) when { resource in [Folder::"root", Doc::"c"] }
+               unless { resource has secret && resource.secret };"#,
+        );
+    }
+
+    #[test]
+    fn entity_types() {
+        // entities of other types with the same ids are not selected
+        assert_matches_authorizer(
+            r#"permit(principal, action, resource) when { [Folder::"a", User::"c"].contains(resource) };
+               permit(principal, action, resource) when { resource in Doc::"public" };
+               permit(principal, action, resource) when { resource.owner == Folder::"alice" };
+               permit(principal, action, resource) when { [Doc::"alice", principal].contains(resource.owner) };"#,
+        );
+        assert_matches_authorizer(
+            r#"permit(principal, action, resource) when { resource.owner is User && resource.owner in User::"bob" };"#,
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        assert_matches_authorizer(
+            r"permit(principal, action, resource is Doc) when { resource.owner == principal };",
+        );
+        assert_matches_authorizer(
+            r"permit(principal, action, resource) when { resource has level && resource.level + 1 <= 3 };",
+        );
+        assert_matches_authorizer(
+            r#"permit(principal, action, resource)
+               when { resource.title like "*%*" || resource.title like "notes_*" };"#,
+        );
+        // `like` is case-sensitive
+        assert_matches_authorizer(
+            r#"permit(principal, action, resource)
+               when { resource.title like "quarterly*" || resource.title like "PLAN" };"#,
+        );
+        assert_matches_authorizer(
+            r"permit(principal, action, resource) when {
+                   if resource.owner == principal then true
+                   else resource has level && -resource.level > -3
+               };",
+        );
+    }
+
+    #[test]
+    fn errors() {
+        // `resource.level` is missing for `Doc::"d"`, and `resource.secret`
+        // for `Doc::"a"` and `Doc::"d"`
+        assert_matches_authorizer(
+            r"permit(principal, action, resource) when { !(resource.level + 1 > 0 && false) };",
+        );
+        assert_matches_authorizer(
+            r"permit(principal, action, resource) when { !(resource.secret || true) || resource.level > 2 };",
+        );
+        assert_matches_authorizer(
+            r"permit(principal, action, resource) when { if resource.level > 2 then false else true };",
+        );
+        assert_matches_authorizer(
+            r#"permit(principal, action, resource) when { !(resource.secret && resource in Folder::"root") };
+               permit(principal, action, resource) when { !([resource.level, 1].contains(1) && false) };"#,
+        );
+        assert_matches_authorizer(
+            r"permit(principal, action, resource);
+               forbid(principal, action, resource) when { !(resource.secret == true && false) };",
+        );
+    }
+
+    #[test]
+    fn permits_and_forbids() {
+        assert_matches_authorizer(
+            r#"permit(principal, action, resource) when { [Doc::"a", Doc::"c"].contains(resource) };
+               forbid(principal, action, resource == Doc::"c");"#,
+        );
+        assert_matches_authorizer(
+            r"permit(principal, action, resource);
+               forbid(principal, action, resource) when { resource.owner != principal };",
+        );
+        assert_matches_authorizer(
+            r#"permit(principal == User::"alice", action, resource);
+               permit(principal, action, resource) when { resource.level == 5 };"#,
+        );
+        // erroring forbid policies are skipped
+        assert_matches_authorizer(
+            r"permit(principal, action, resource);
+               forbid(principal, action, resource) when { resource.secret };",
+        );
+    }
+
+    #[test]
+    fn known_decision() {
+        let (entities, _) = data();
+        let policies: PolicySet = r#"permit(principal == User::"alice", action, resource);"#
+            .parse()
+            .unwrap();
+        let alice = r#"User::"alice""#.parse().unwrap();
+        let bob = r#"User::"bob""#.parse().unwrap();
+        let response = partial_response(&policies, &entities, &alice);
+        assert_eq!(
+            residuals_to_sql(&response, &mapping()),
+            Ok(SqlExpr::Constant(true))
+        );
+        let response = partial_response(&policies, &entities, &bob);
+        assert_eq!(
+            residuals_to_sql(&response, &mapping()),
+            Ok(SqlExpr::Constant(false))
+        );
+    }
+
+    #[test]
+    fn render() {
+        let (entities, _) = data();
+        let policies: PolicySet = r#"
+            permit(principal, action, resource in Folder::"public") when { resource.level < 3 };
+            forbid(principal, action, resource) when { resource.title like "*_*" };
+        "#
+        .parse()
+        .unwrap();
+        let response = partial_response(&policies, &entities, &r#"User::"alice""#.parse().unwrap());
+        let predicate = residuals_to_sql(&response, &mapping()).unwrap();
+        assert_eq!(
+            predicate.to_sql_with(SqlDialect::Postgres, |i| format!("${}", i + 1)),
+            (
+                "(((EXISTS (SELECT 1 FROM ancestors WHERE ancestors.entity_type = $1 AND ancestors.entity = docs.id AND ancestors.ancestor_type = $2 AND ancestors.ancestor = $3) AND (docs.level < $4)) IS TRUE) AND (NOT ((docs.title LIKE $5 ESCAPE '\\') IS TRUE)))".to_owned(),
+                vec![
+                    SqlValue::String("Doc".into()),
+                    SqlValue::String("Folder".into()),
+                    SqlValue::String("public".into()),
+                    SqlValue::Long(3),
+                    SqlValue::String("%\\_%".into()),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn render_like() {
+        let (entities, _) = data();
+        let policies: PolicySet =
+            r#"permit(principal, action, resource) when { resource.title like "a\*?[%_*" };"#
+                .parse()
+                .unwrap();
+        let response = partial_response(&policies, &entities, &r#"User::"alice""#.parse().unwrap());
+        let predicate = residuals_to_sql(&response, &mapping()).unwrap();
+        let like = |dialect| {
+            let (sql, params) = predicate.to_sql(dialect);
+            (sql, params.into_iter().next().unwrap())
+        };
+        assert_eq!(
+            like(SqlDialect::Postgres),
+            (
+                "((docs.title LIKE ? ESCAPE '\\') IS TRUE)".to_owned(),
+                SqlValue::String("a*?[\\%\\_%".into())
+            )
+        );
+        assert_eq!(
+            like(SqlDialect::Sqlite),
+            (
+                "((docs.title GLOB ?) IS TRUE)".to_owned(),
+                SqlValue::String("a[*][?][[]%_*".into())
+            )
+        );
+        assert_eq!(
+            like(SqlDialect::MySql),
+            (
+                "((docs.title LIKE BINARY ?) IS TRUE)".to_owned(),
+                SqlValue::String("a*?[\\%\\_%".into())
+            )
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let (entities, _) = data();
+        let alice = r#"User::"alice""#.parse().unwrap();
+        let translate = |policies: &str| {
+            let policies: PolicySet = policies.parse().unwrap();
+            residuals_to_sql(&partial_response(&policies, &entities, &alice), &mapping())
+        };
+        assert_matches!(
+            translate(r#"permit(principal, action, resource) when { resource.owner.name == "x" };"#),
+            Err(SqlTranslationError::Unsupported { expr, reason }) => {
+                assert_eq!(expr, r#"((unknown("resource"))["owner"])["name"]"#);
+                assert_eq!(reason, "only attributes of the resource can be translated");
+            }
+        );
+        assert_matches!(
+            translate(r"permit(principal, action, resource) when { resource.size > 3 };"),
+            Err(SqlTranslationError::UnmappedAttribute { entity_type, attr }) => {
+                assert_eq!(entity_type, "Doc");
+                assert_eq!(attr, "size");
+            }
+        );
+        assert_matches!(
+            translate(r#"permit(principal, action, resource) when { resource.title.contains("x") };"#),
+            Err(SqlTranslationError::Unsupported { reason, .. }) => {
+                assert_eq!(reason, "sets cannot be stored in columns");
+            }
+        );
+        assert_matches!(
+            translate(
+                r#"permit(principal, action, resource) when { resource.level == 1 }; permit(principal, action, resource in Folder::"public");"#
+            ),
+            Ok(_)
+        );
+        let no_hierarchy =
+            SqlMapping::new().with_table("Doc".parse().unwrap(), TableMapping::new("docs", "id"));
+        let policies: PolicySet = r#"permit(principal, action, resource in Folder::"public");"#
+            .parse()
+            .unwrap();
+        assert_matches!(
+            residuals_to_sql(&partial_response(&policies, &entities, &alice), &no_hierarchy),
+            Err(SqlTranslationError::Unsupported { reason, .. }) => {
+                assert_eq!(reason, "no hierarchy table is mapped");
+            }
+        );
+        let untyped_owner = SqlMapping::new().with_table(
+            "Doc".parse().unwrap(),
+            TableMapping::new("docs", "id").with_column("owner", "owner"),
+        );
+        let policies: PolicySet =
+            r"permit(principal, action, resource) when { resource.owner == principal };"
+                .parse()
+                .unwrap();
+        assert_matches!(
+            residuals_to_sql(&partial_response(&policies, &entities, &alice), &untyped_owner),
+            Err(SqlTranslationError::Unsupported { reason, .. }) => {
+                assert_eq!(reason, UNTYPED_ENTITY);
+            }
+        );
+    }
+
+    #[test]
+    fn error_handling() {
+        let (entities, _) = data();
+        let alice = r#"User::"alice""#.parse().unwrap();
+        let policies: PolicySet =
+            r"permit(principal, action, resource) when { resource.level > 1 };"
+                .parse()
+                .unwrap();
+        let request = Request::builder()
+            .principal(alice)
+            .action(r#"Action::"read""#.parse().unwrap())
+            .unknown_resource_with_type("Doc".parse().unwrap())
+            .context(Context::empty())
+            .build();
+        let response = Authorizer::new()
+            .with_error_handling(ErrorHandling::DenyOnError)
+            .is_authorized_partial(&request, &policies, &entities);
+        assert_eq!(
+            residuals_to_sql(&response, &mapping()),
+            Err(SqlTranslationError::UnsupportedErrorHandling {
+                error_handling: ErrorHandling::DenyOnError
+            })
+        );
+    }
+}
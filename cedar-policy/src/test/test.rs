@@ -7962,11 +7962,14 @@ permit(
 }
 
 mod test_entities_api {
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
+    use std::str::FromStr;
 
     use super::Entities;
     use super::Entity;
+    use super::EntityTypeName;
     use super::EntityUid;
+    use super::RestrictedExpression;
 
     #[test]
     fn test_upsert_entities() {
@@ -7989,4 +7992,916 @@ mod test_entities_api {
         assert_eq!(entities.len(), 2);
         assert!(entities.is_ancestor_of(&e2_uid, &e1_uid));
     }
+
+    #[test]
+    fn test_queries() {
+        let user = |eid: &str, manager: &str, parents: &[&str]| {
+            Entity::new(
+                EntityUid::from_strs("User", eid),
+                HashMap::from([(
+                    "manager".to_string(),
+                    RestrictedExpression::new_entity_uid(EntityUid::from_strs("User", manager)),
+                )]),
+                parents
+                    .iter()
+                    .map(|parent| EntityUid::from_strs("Group", parent))
+                    .collect(),
+            )
+            .unwrap()
+        };
+        let sorted_ids = |entities: Vec<&Entity>| {
+            let mut ids: Vec<String> = entities
+                .into_iter()
+                .map(|e| e.uid().id().unescaped().to_string())
+                .collect();
+            ids.sort();
+            ids
+        };
+        let eng = EntityUid::from_strs("Group", "eng");
+        let all = EntityUid::from_strs("Group", "all");
+        let carol = RestrictedExpression::new_entity_uid(EntityUid::from_strs("User", "carol"));
+
+        let entities = Entities::from_entities(
+            [
+                user("alice", "carol", &["eng"]),
+                user("bob", "carol", &["all"]),
+                Entity::new_no_attrs(eng.clone(), HashSet::from([all.clone()])),
+            ],
+            None,
+        )
+        .unwrap();
+        let user_type = EntityTypeName::from_str("User").unwrap();
+        assert_eq!(
+            sorted_ids(entities.entities_of_type(&user_type).collect()),
+            ["alice", "bob"]
+        );
+        assert_eq!(
+            sorted_ids(entities.children(&all).collect()),
+            ["bob", "eng"]
+        );
+        assert_eq!(
+            sorted_ids(entities.descendants(&all).collect()),
+            ["alice", "bob", "eng"]
+        );
+        assert_eq!(
+            sorted_ids(
+                entities
+                    .entities_with_attr("manager", &carol)
+                    .unwrap()
+                    .collect()
+            ),
+            ["alice", "bob"]
+        );
+        assert!(entities
+            .entities_with_attr(
+                "manager",
+                &RestrictedExpression::new_decimal("not a decimal")
+            )
+            .is_err());
+
+        // the queries see updates and removals
+        let entities = entities
+            .upsert_entities([user("bob", "dave", &["eng"])], None)
+            .unwrap()
+            .remove_entities([EntityUid::from_strs("User", "alice")])
+            .unwrap();
+        assert_eq!(
+            sorted_ids(entities.entities_of_type(&user_type).collect()),
+            ["bob"]
+        );
+        assert_eq!(
+            sorted_ids(entities.descendants(&all).collect()),
+            ["bob", "eng"]
+        );
+        assert_eq!(sorted_ids(entities.children(&all).collect()), ["eng"]);
+        assert_eq!(
+            entities
+                .entities_with_attr("manager", &carol)
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+}
+
+mod entity_store_tests {
+    use super::*;
+    use crate::entities_errors::EntityStoreError;
+    use std::cell::RefCell;
+
+    /// `EntityStore` which fails to load `User::"broken"`, and records every
+    /// entity it is asked for
+    struct RecordingStore {
+        entities: Entities,
+        requested: RefCell<Vec<EntityUid>>,
+    }
+
+    impl EntityStore for RecordingStore {
+        fn entity(&self, uid: &EntityUid) -> Result<Option<Entity>, EntityStoreError> {
+            self.requested.borrow_mut().push(uid.clone());
+            if uid == &EntityUid::from_strs("User", "broken") {
+                Err(EntityStoreError::new("database unavailable"))
+            } else {
+                EntityStore::entity(&self.entities, uid)
+            }
+        }
+    }
+
+    fn store() -> RecordingStore {
+        let entities = Entities::from_json_str(
+            r#"[
+                {
+                    "uid": {"type": "User", "id": "alice"},
+                    "attrs": {"level": 5},
+                    "parents": [{"type": "Group", "id": "admins"}]
+                },
+                {
+                    "uid": {"type": "Group", "id": "admins"},
+                    "attrs": {},
+                    "parents": []
+                }
+            ]"#,
+            None,
+        )
+        .unwrap();
+        RecordingStore {
+            entities,
+            requested: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn request(principal: &str) -> Request {
+        Request::new(
+            EntityUid::from_strs("User", principal),
+            EntityUid::from_strs("Action", "view"),
+            EntityUid::from_strs("Doc", "d"),
+            Context::empty(),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn loads_entities_on_demand() {
+        let policies = PolicySet::from_str(
+            r#"
+            permit(principal in Group::"admins", action, resource);
+            permit(principal, action, resource) when { principal.level > 3 };
+            "#,
+        )
+        .unwrap();
+        let store = store();
+        let response =
+            Authorizer::new().is_authorized_with_store(&request("alice"), &policies, &store);
+        assert_eq!(response.decision(), Decision::Allow);
+        assert_eq!(response.diagnostics().errors().count(), 0);
+        // both policies dereference `principal`, but it is only loaded once
+        assert_eq!(
+            store.requested.take(),
+            vec![EntityUid::from_strs("User", "alice")]
+        );
+    }
+
+    #[test]
+    fn store_errors_are_evaluation_errors() {
+        let policies = PolicySet::from_str(
+            r#"permit(principal, action, resource) when { principal.level > 3 };"#,
+        )
+        .unwrap();
+        let store = store();
+        let response =
+            Authorizer::new().is_authorized_with_store(&request("broken"), &policies, &store);
+        assert_eq!(response.decision(), Decision::Deny);
+        let errs = response.diagnostics().errors().collect::<Vec<_>>();
+        assert_eq!(errs.len(), 1);
+        expect_err(
+            "",
+            &Report::new(errs[0].clone()),
+            &ExpectedErrorMessageBuilder::error(
+                r#"error while evaluating policy `policy0`: failed to load entity `User::"broken"`: database unavailable"#,
+            )
+            .build(),
+        );
+    }
+
+    #[test]
+    fn entities_is_a_store() {
+        let policies = PolicySet::from_str(
+            r#"permit(principal, action, resource) when { principal.level > 3 };"#,
+        )
+        .unwrap();
+        let entities = store().entities;
+        let response =
+            Authorizer::new().is_authorized_with_store(&request("alice"), &policies, &entities);
+        assert_eq!(response.decision(), Decision::Allow);
+        let response =
+            Authorizer::new().is_authorized_with_store(&request("bob"), &policies, &entities);
+        assert_eq!(response.decision(), Decision::Deny);
+        assert_eq!(response.diagnostics().errors().count(), 1);
+    }
+}
+
+#[cfg(feature = "entity-manifest")]
+mod entity_loader_tests {
+    use super::*;
+    use crate::entities_errors::EntityStoreError;
+
+    fn schema() -> Schema {
+        Schema::from_cedarschema_str(
+            r#"
+            entity Group;
+            entity User in [Group] { level: Long, manager: User };
+            entity Doc;
+            action view appliesTo { principal: [User], resource: [Doc] };
+            "#,
+        )
+        .unwrap()
+        .0
+    }
+
+    fn entities() -> Entities {
+        Entities::from_json_str(
+            r#"[
+                {
+                    "uid": {"type": "User", "id": "alice"},
+                    "attrs": {"level": 2, "manager": {"type": "User", "id": "bob"}},
+                    "parents": []
+                },
+                {
+                    "uid": {"type": "User", "id": "bob"},
+                    "attrs": {"level": 5, "manager": {"type": "User", "id": "bob"}},
+                    "parents": [{"type": "Group", "id": "admins"}]
+                },
+                {
+                    "uid": {"type": "Group", "id": "admins"},
+                    "attrs": {},
+                    "parents": []
+                }
+            ]"#,
+            Some(&schema()),
+        )
+        .unwrap()
+    }
+
+    fn manifest_and_request() -> (PolicySet, EntityManifest, Request) {
+        let policies = PolicySet::from_str(
+            r#"permit(principal, action == Action::"view", resource)
+               when { principal.manager.level > 3 && principal.manager in Group::"admins" };"#,
+        )
+        .unwrap();
+        let validator = Validator::new(schema());
+        let manifest = compute_entity_manifest(&validator, &policies).unwrap();
+        let request = Request::new(
+            EntityUid::from_strs("User", "alice"),
+            EntityUid::from_strs("Action", "view"),
+            EntityUid::from_strs("Doc", "d"),
+            Context::empty(),
+            None,
+        )
+        .unwrap();
+        (policies, manifest, request)
+    }
+
+    /// Loads whole entities from an `Entities`, recording the ids requested
+    struct WholeEntityLoader {
+        entities: Entities,
+        requested: Vec<EntityUid>,
+    }
+
+    impl SimpleEntityLoader for WholeEntityLoader {
+        fn load_entities(
+            &mut self,
+            to_load: &[EntityUid],
+        ) -> Result<Vec<Option<Entity>>, EntityStoreError> {
+            self.requested.extend(to_load.iter().cloned());
+            Ok(to_load
+                .iter()
+                .map(|uid| self.entities.get(uid).cloned())
+                .collect())
+        }
+    }
+
+    /// Loads only the requested fields and ancestors from an `Entities`
+    struct FieldLoader {
+        entities: Entities,
+    }
+
+    impl EntityLoader for FieldLoader {
+        fn load_entities(
+            &mut self,
+            to_load: &[EntityRequest],
+        ) -> Result<Vec<Option<Entity>>, EntityStoreError> {
+            Ok(to_load
+                .iter()
+                .map(|request| {
+                    let entity = self.entities.get(request.entity_id())?;
+                    let attrs = request
+                        .access_trie()
+                        .children()
+                        .keys()
+                        .filter_map(|attr| {
+                            let value = match entity.attr(attr)?.ok()? {
+                                EvalResult::Long(l) => RestrictedExpression::new_long(l),
+                                EvalResult::EntityUid(uid) => {
+                                    RestrictedExpression::new_entity_uid(uid)
+                                }
+                                v => panic!("unexpected attribute value {v}"),
+                            };
+                            Some((attr.to_string(), value))
+                        })
+                        .collect::<HashMap<_, _>>();
+                    Some(Entity::new(entity.uid(), attrs, HashSet::new()).unwrap())
+                })
+                .collect())
+        }
+
+        fn load_ancestors(
+            &mut self,
+            to_load: &[AncestorsRequest],
+        ) -> Result<Vec<HashSet<EntityUid>>, EntityStoreError> {
+            Ok(to_load
+                .iter()
+                .map(|request| {
+                    request
+                        .ancestors()
+                        .filter(|ancestor| {
+                            self.entities.is_ancestor_of(ancestor, request.entity_id())
+                        })
+                        .cloned()
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    /// Fails to load anything
+    struct FailingLoader;
+
+    impl SimpleEntityLoader for FailingLoader {
+        fn load_entities(
+            &mut self,
+            _to_load: &[EntityUid],
+        ) -> Result<Vec<Option<Entity>>, EntityStoreError> {
+            Err(EntityStoreError::new("database unavailable"))
+        }
+    }
+
+    #[test]
+    fn simple_loader() {
+        let (policies, manifest, request) = manifest_and_request();
+        let mut loader = WholeEntityLoader {
+            entities: entities(),
+            requested: vec![],
+        };
+        let loaded = load_entities(&manifest, &request, &mut loader).unwrap();
+        assert_eq!(
+            loader.requested,
+            vec![
+                EntityUid::from_strs("User", "alice"),
+                EntityUid::from_strs("User", "bob"),
+            ]
+        );
+        let response = Authorizer::new().is_authorized(&request, &policies, &loaded);
+        assert_eq!(response.decision(), Decision::Allow);
+    }
+
+    #[test]
+    fn field_loader() {
+        let (policies, manifest, request) = manifest_and_request();
+        let mut loader = FieldLoader {
+            entities: entities(),
+        };
+        let loaded = load_entities(&manifest, &request, &mut loader).unwrap();
+        let alice = loaded.get(&EntityUid::from_strs("User", "alice")).unwrap();
+        // `alice.level` is not needed by the policy
+        assert!(alice.attr("level").is_none());
+        assert!(alice.attr("manager").is_some());
+        assert!(loaded.is_ancestor_of(
+            &EntityUid::from_strs("Group", "admins"),
+            &EntityUid::from_strs("User", "bob")
+        ));
+        let response = Authorizer::new().is_authorized(&request, &policies, &loaded);
+        assert_eq!(response.decision(), Decision::Allow);
+    }
+
+    #[test]
+    fn loader_errors() {
+        let (_, manifest, request) = manifest_and_request();
+        let err = load_entities(&manifest, &request, &mut FailingLoader).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to load entities: database unavailable"
+        );
+    }
+}
+
+mod audit_tests {
+    use super::*;
+    use itertools::Itertools;
+    use std::sync::Arc;
+
+    fn policies() -> PolicySet {
+        PolicySet::from_str(
+            r#"
+            @id("alice") @ticket("SEC-1")
+            permit(principal == User::"alice", action, resource);
+            @id("broken")
+            forbid(principal, action, resource) when { principal.suspended };
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn entities() -> Entities {
+        Entities::from_json_str(
+            r#"[{ "uid": { "type": "User", "id": "alice" }, "attrs": { "suspended": false }, "parents": [] }]"#,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn request(principal: &str) -> Request {
+        Request::new(
+            EntityUid::from_strs("User", principal),
+            EntityUid::from_strs("Action", "view"),
+            EntityUid::from_strs("Doc", "a"),
+            Context::from_json_str(r#"{ "ip": "10.0.0.1" }"#, None).unwrap(),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn authorizer(sink: &Arc<MemoryAuditSink>) -> AuditedAuthorizer {
+        Authorizer::new()
+            .with_audit_log(AuditLog::new(sink.clone()).with_annotations(["ticket", "missing"]))
+    }
+
+    #[test]
+    fn records_decisions() {
+        let sink = Arc::new(MemoryAuditSink::new());
+        let authorizer = authorizer(&sink);
+        let (policies, entities) = (policies(), entities());
+        authorizer.is_authorized(&request("alice"), &policies, &entities);
+        authorizer.is_authorized(&request("bob"), &policies, &entities);
+
+        let records = sink.take();
+        assert_eq!(records.len(), 2);
+        let alice = &records[0];
+        assert_eq!(alice.principal(), Some(r#"User::"alice""#));
+        assert_eq!(alice.action(), Some(r#"Action::"view""#));
+        assert_eq!(alice.resource(), Some(r#"Doc::"a""#));
+        assert_eq!(
+            alice.context(),
+            Some(&serde_json::json!({ "ip": "10.0.0.1" }))
+        );
+        assert_eq!(alice.decision(), Decision::Allow);
+        let determining = alice.determining_policies().collect::<Vec<_>>();
+        assert_eq!(determining.len(), 1);
+        assert_eq!(determining[0].id(), &PolicyId::new("policy0"));
+        assert_eq!(determining[0].annotation("ticket"), Some("SEC-1"));
+        assert_eq!(determining[0].annotation("id"), None);
+        assert_eq!(determining[0].annotation("missing"), None);
+        assert_eq!(alice.errors().count(), 0);
+
+        // `bob` has no `suspended` attribute
+        let bob = &records[1];
+        assert_eq!(bob.decision(), Decision::Deny);
+        let errors = bob.errors().collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].policy_id(), &PolicyId::new("policy1"));
+        assert!(errors[0].message().contains("does not exist"));
+
+        assert_eq!(alice.policy_set_hash(), bob.policy_set_hash());
+        assert_eq!(alice.entities_hash(), bob.entities_hash());
+        assert!(sink.records().is_empty());
+    }
+
+    #[test]
+    fn records_batches() {
+        let sink = Arc::new(MemoryAuditSink::new());
+        let responses = authorizer(&sink).is_authorized_batch(
+            [request("alice"), request("bob")],
+            &policies(),
+            &entities(),
+        );
+        let records = sink.records();
+        assert_eq!(records.len(), 2);
+        for (record, response) in records.iter().zip(&responses) {
+            assert_eq!(record.decision(), response.decision());
+        }
+        assert_eq!(records[1].principal(), Some(r#"User::"bob""#));
+    }
+
+    #[test]
+    fn hashes_are_canonical() {
+        let sink = Arc::new(MemoryAuditSink::new());
+        let authorizer = authorizer(&sink);
+        // The same policies, added in the opposite order
+        let mut reordered = PolicySet::new();
+        for policy in policies()
+            .policies()
+            .sorted_by_key(|p| std::cmp::Reverse(p.id()))
+        {
+            reordered.add(policy.clone()).unwrap();
+        }
+        let changed = PolicySet::from_str(
+            r#"permit(principal == User::"alice", action, resource); permit(principal, action, resource);"#,
+        )
+        .unwrap();
+        let updated = Entities::from_json_str(
+            r#"[{ "uid": { "type": "User", "id": "alice" }, "attrs": { "suspended": true }, "parents": [] }]"#,
+            None,
+        )
+        .unwrap();
+        authorizer.is_authorized(&request("alice"), &policies(), &entities());
+        authorizer.is_authorized(&request("alice"), &reordered, &entities());
+        authorizer.is_authorized(&request("alice"), &changed, &updated);
+        let records = sink.records();
+        assert_eq!(records[0].policy_set_hash(), records[1].policy_set_hash());
+        assert_ne!(records[0].policy_set_hash(), records[2].policy_set_hash());
+        assert_eq!(records[0].entities_hash(), records[1].entities_hash());
+        assert_ne!(records[0].entities_hash(), records[2].entities_hash());
+        assert_eq!(records[0].policy_set_hash().len(), 64);
+    }
+
+    #[test]
+    fn cached_hashes_follow_changes() {
+        let sink = Arc::new(MemoryAuditSink::new());
+        let authorizer = authorizer(&sink);
+        let mut changed = policies();
+        changed
+            .add(
+                Policy::parse(
+                    Some(PolicyId::new("extra")),
+                    "forbid(principal, action, resource);",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        let updated = entities()
+            .add_entities(
+                [Entity::new_no_attrs(
+                    EntityUid::from_strs("User", "carol"),
+                    HashSet::new(),
+                )],
+                None,
+            )
+            .unwrap();
+        authorizer.is_authorized(&request("alice"), &policies(), &entities());
+        authorizer.is_authorized(&request("alice"), &changed, &updated);
+        authorizer.is_authorized(&request("alice"), &policies(), &entities());
+        let records = sink.records();
+        assert_ne!(records[0].policy_set_hash(), records[1].policy_set_hash());
+        assert_ne!(records[0].entities_hash(), records[1].entities_hash());
+        assert_eq!(records[0].policy_set_hash(), records[2].policy_set_hash());
+        assert_eq!(records[0].entities_hash(), records[2].entities_hash());
+    }
+
+    #[test]
+    fn json_lines_sink() {
+        let file = tempfile_path();
+        let sink = Arc::new(JsonLinesAuditSink::append_to_file(&file).unwrap());
+        let authorizer = Authorizer::new().with_audit_log(AuditLog::new(sink.clone()));
+        authorizer.is_authorized(&request("alice"), &policies(), &entities());
+        authorizer.is_authorized(&request("bob"), &policies(), &entities());
+        assert!(sink.take_error().is_none());
+
+        let contents = std::fs::read_to_string(&file).unwrap();
+        let records = contents
+            .lines()
+            .map(|line| serde_json::from_str::<AuditRecord>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].decision(), Decision::Allow);
+        assert_eq!(records[1].decision(), Decision::Deny);
+        let json: serde_json::Value =
+            serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+        assert_eq!(json["determiningPolicies"][0]["id"], "policy0");
+        std::fs::remove_file(file).unwrap();
+    }
+
+    /// A path in the temporary directory which is unique to this process
+    fn tempfile_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("cedar-audit-{}.jsonl", std::process::id()))
+    }
+
+    #[test]
+    fn failing_sink() {
+        struct Failing;
+        impl std::io::Write for Failing {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk full"))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let sink = Arc::new(JsonLinesAuditSink::new(Failing));
+        let response = Authorizer::new()
+            .with_audit_log(AuditLog::new(sink.clone()))
+            .is_authorized(&request("alice"), &policies(), &entities());
+        assert_eq!(response.decision(), Decision::Allow);
+        assert_eq!(sink.take_error().unwrap().to_string(), "disk full");
+        assert!(sink.take_error().is_none());
+    }
+}
+
+mod ancestor_mode_tests {
+    use super::*;
+    use crate::entities_errors::EntityStoreError;
+
+    fn entities(mode: AncestorMode) -> Entities {
+        Entities::empty()
+            .with_ancestor_mode(mode)
+            .unwrap()
+            .add_entities_from_json_value(
+                serde_json::json!([
+                    { "uid": { "type": "User", "id": "alice" }, "attrs": {}, "parents": [{ "type": "Team", "id": "compilers" }] },
+                    { "uid": { "type": "User", "id": "bob" }, "attrs": {}, "parents": [{ "type": "Team", "id": "contractors" }] },
+                    { "uid": { "type": "User", "id": "carol" }, "attrs": {}, "parents": [{ "type": "Team", "id": "sales" }] },
+                    { "uid": { "type": "Team", "id": "compilers" }, "attrs": {}, "parents": [{ "type": "Department", "id": "eng" }] },
+                    { "uid": { "type": "Team", "id": "contractors" }, "attrs": {}, "parents": [{ "type": "Department", "id": "eng" }] },
+                    { "uid": { "type": "Team", "id": "sales" }, "attrs": {}, "parents": [{ "type": "Org", "id": "acme" }] },
+                    { "uid": { "type": "Department", "id": "eng" }, "attrs": {}, "parents": [{ "type": "Org", "id": "acme" }] },
+                    { "uid": { "type": "Folder", "id": "secret" }, "attrs": {}, "parents": [{ "type": "Folder", "id": "root" }] },
+                ]),
+                None,
+            )
+            .unwrap()
+    }
+
+    fn policies() -> PolicySet {
+        PolicySet::from_str(
+            r#"
+            permit(principal in Department::"eng", action, resource);
+            permit(principal, action, resource) when { principal in Org::"acme" && resource == Folder::"root" };
+            forbid(principal in Team::"contractors", action, resource in Folder::"root") unless { principal in Team::"compilers" };
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn request(principal: &str, resource: &str) -> Request {
+        Request::new(
+            EntityUid::from_strs("User", principal),
+            EntityUid::from_strs("Action", "read"),
+            EntityUid::from_strs("Folder", resource),
+            Context::empty(),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn same_decisions() {
+        let precomputed = entities(AncestorMode::Precomputed);
+        let on_demand = entities(AncestorMode::OnDemand);
+        assert_eq!(on_demand.ancestor_mode(), AncestorMode::OnDemand);
+        let authorizer = Authorizer::new();
+        let policies = policies();
+        for principal in ["alice", "bob", "carol", "dave"] {
+            for resource in ["root", "secret", "other"] {
+                let request = request(principal, resource);
+                let expected = authorizer.is_authorized(&request, &policies, &precomputed);
+                let actual = authorizer.is_authorized(&request, &policies, &on_demand);
+                assert_eq!(
+                    actual.decision(),
+                    expected.decision(),
+                    "{principal}, {resource}"
+                );
+                assert_eq!(
+                    actual.diagnostics().reason().collect::<HashSet<_>>(),
+                    expected.diagnostics().reason().collect::<HashSet<_>>(),
+                    "{principal}, {resource}"
+                );
+            }
+        }
+        assert_eq!(
+            authorizer
+                .is_authorized(&request("bob", "secret"), &policies, &on_demand)
+                .decision(),
+            Decision::Deny
+        );
+    }
+
+    #[test]
+    fn queries() {
+        let on_demand = entities(AncestorMode::OnDemand);
+        let alice = EntityUid::from_strs("User", "alice");
+        let acme = EntityUid::from_strs("Org", "acme");
+        assert!(on_demand.is_ancestor_of(&acme, &alice));
+        assert_eq!(
+            on_demand.ancestors(&alice).unwrap().collect::<HashSet<_>>(),
+            entities(AncestorMode::Precomputed)
+                .ancestors(&alice)
+                .unwrap()
+                .collect::<HashSet<_>>()
+        );
+        // only the parents are stored
+        assert_eq!(
+            on_demand.get(&alice).unwrap().clone().into_inner().2,
+            HashSet::from([EntityUid::from_strs("Team", "compilers")])
+        );
+        // and converting computes the rest
+        let precomputed = on_demand
+            .with_ancestor_mode(AncestorMode::Precomputed)
+            .unwrap();
+        assert!(precomputed
+            .get(&alice)
+            .unwrap()
+            .clone()
+            .into_inner()
+            .2
+            .contains(&acme));
+    }
+
+    /// `EntityStore` which only has the parents of each entity
+    struct ParentsOnlyStore(Entities);
+
+    impl EntityStore for ParentsOnlyStore {
+        fn entity(&self, uid: &EntityUid) -> Result<Option<Entity>, EntityStoreError> {
+            Ok(self.0.get(uid).cloned())
+        }
+
+        fn ancestor_mode(&self) -> AncestorMode {
+            AncestorMode::OnDemand
+        }
+    }
+
+    #[test]
+    fn store_with_parents_only() {
+        let store = ParentsOnlyStore(entities(AncestorMode::OnDemand));
+        let authorizer = Authorizer::new();
+        let policies = policies();
+        let response =
+            authorizer.is_authorized_with_store(&request("alice", "secret"), &policies, &store);
+        assert_eq!(response.decision(), Decision::Allow);
+        let response =
+            authorizer.is_authorized_with_store(&request("bob", "secret"), &policies, &store);
+        assert_eq!(response.decision(), Decision::Deny);
+    }
+}
+
+mod entity_patch_tests {
+    use super::*;
+    use crate::entities_errors::EntitiesError;
+    use cool_asserts::assert_matches;
+
+    fn schema() -> Schema {
+        Schema::from_cedarschema_str(
+            r#"
+            entity Group;
+            entity User in [Group] { name: String, manager?: User } tags String;
+            action read appliesTo { principal: User, resource: Group };
+            "#,
+        )
+        .unwrap()
+        .0
+    }
+
+    fn entities() -> Entities {
+        Entities::from_json_value(
+            serde_json::json!([
+                { "uid": { "type": "User", "id": "alice" }, "attrs": { "name": "Alice" }, "parents": [] },
+                { "uid": { "type": "User", "id": "bob" }, "attrs": { "name": "Bob" }, "parents": [{ "type": "Group", "id": "eng" }] },
+                { "uid": { "type": "Group", "id": "eng" }, "attrs": {}, "parents": [] },
+            ]),
+            Some(&schema()),
+        )
+        .unwrap()
+    }
+
+    fn read_eng(principal: &str, entities: &Entities) -> Decision {
+        let policies =
+            PolicySet::from_str(r#"permit(principal in Group::"eng", action, resource);"#).unwrap();
+        let request = Request::new(
+            EntityUid::from_strs("User", principal),
+            EntityUid::from_strs("Action", "read"),
+            EntityUid::from_strs("Group", "eng"),
+            Context::empty(),
+            None,
+        )
+        .unwrap();
+        Authorizer::new()
+            .is_authorized(&request, &policies, entities)
+            .decision()
+    }
+
+    #[test]
+    fn apply_and_undo() {
+        let schema = schema();
+        let mut entities = entities();
+        // with the schema, the entity reference in `manager` doesn't need an
+        // explicit `__entity` escape
+        let patch = EntityPatch::from_json_value(
+            serde_json::json!([
+                { "op": "addParent", "uid": { "type": "User", "id": "alice" }, "parent": { "type": "Group", "id": "eng" } },
+                { "op": "removeParent", "uid": { "type": "User", "id": "bob" }, "parent": { "type": "Group", "id": "eng" } },
+                { "op": "setAttr", "uid": { "type": "User", "id": "alice" }, "attr": "manager", "value": { "type": "User", "id": "bob" } },
+                { "op": "setTag", "uid": { "type": "User", "id": "alice" }, "tag": "desk", "value": "4F" },
+            ]),
+            Some(&schema),
+        )
+        .unwrap();
+        let undo = entities.apply_patch(patch, Some(&schema)).unwrap();
+        assert_eq!(undo.len(), 4);
+        assert_eq!(read_eng("alice", &entities), Decision::Allow);
+        assert_eq!(read_eng("bob", &entities), Decision::Deny);
+        let alice = entities
+            .get(&EntityUid::from_strs("User", "alice"))
+            .unwrap();
+        assert_matches!(
+            alice.attr("manager"),
+            Some(Ok(EvalResult::EntityUid(uid))) => assert_eq!(uid, EntityUid::from_strs("User", "bob"))
+        );
+
+        entities.apply_patch(undo, Some(&schema)).unwrap();
+        assert_eq!(entities, self::entities());
+        assert_eq!(read_eng("alice", &entities), Decision::Deny);
+        assert_eq!(read_eng("bob", &entities), Decision::Allow);
+        let alice = entities
+            .get(&EntityUid::from_strs("User", "alice"))
+            .unwrap();
+        assert!(alice.attr("manager").is_none());
+        assert!(alice.tag("desk").is_none());
+    }
+
+    #[test]
+    fn invalid_patch_is_not_applied() {
+        let schema = schema();
+        let mut entities = entities();
+        // `name` is required, so the whole change-set is rejected
+        let patch = EntityPatch::from_json_value(
+            serde_json::json!([
+                { "op": "addParent", "uid": { "type": "User", "id": "alice" }, "parent": { "type": "Group", "id": "eng" } },
+                { "op": "removeAttr", "uid": { "type": "User", "id": "alice" }, "attr": "name" },
+            ]),
+            Some(&schema),
+        )
+        .unwrap();
+        assert_matches!(
+            entities.apply_patch(patch, Some(&schema)),
+            Err(EntitiesError::InvalidEntity(_))
+        );
+        assert_eq!(read_eng("alice", &entities), Decision::Deny);
+        let alice = entities
+            .get(&EntityUid::from_strs("User", "alice"))
+            .unwrap();
+        assert!(alice.attr("name").is_some());
+
+        // attribute values must have the types in the schema
+        let patch = EntityPatch::from_json_value(
+            serde_json::json!([
+                { "op": "setAttr", "uid": { "type": "User", "id": "alice" }, "attr": "name", "value": 3 },
+            ]),
+            Some(&schema),
+        )
+        .unwrap();
+        assert_matches!(
+            entities.apply_patch(patch, Some(&schema)),
+            Err(EntitiesError::InvalidEntity(_))
+        );
+    }
+
+    #[test]
+    fn diff_explains_flipped_decision() {
+        let schema = schema();
+        let old = entities();
+        let mut new = old.clone();
+        let patch = EntityPatch::from_json_value(
+            serde_json::json!([
+                { "op": "addParent", "uid": { "type": "User", "id": "alice" }, "parent": { "type": "Group", "id": "eng" } },
+                { "op": "setAttr", "uid": { "type": "User", "id": "alice" }, "attr": "name", "value": "Alice B." },
+                { "op": "remove", "uid": { "type": "User", "id": "bob" } },
+            ]),
+            Some(&schema),
+        )
+        .unwrap();
+        new.apply_patch(patch, Some(&schema)).unwrap();
+        assert_eq!(read_eng("alice", &old), Decision::Deny);
+        assert_eq!(read_eng("alice", &new), Decision::Allow);
+
+        assert!(Entities::diff(&old, &old).is_empty());
+        let diff = Entities::diff(&old, &new);
+        assert_eq!(diff.len(), 3);
+        assert!(diff
+            .changes()
+            .all(|change| change.uid().type_name().to_string() == "User"));
+        assert_eq!(
+            diff.changes().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                r#"attribute `name` of `User::"alice"` changed from "Alice" to "Alice B.""#,
+                r#"parent `Group::"eng"` was added to `User::"alice"`"#,
+                r#"entity `User::"bob"` was removed"#,
+            ]
+        );
+        assert_eq!(
+            diff.to_json_value().unwrap(),
+            serde_json::json!([
+                { "change": "attributeChanged", "uid": { "type": "User", "id": "alice" }, "attr": "name", "old": "Alice", "new": "Alice B." },
+                { "change": "parentAdded", "uid": { "type": "User", "id": "alice" }, "parent": { "type": "Group", "id": "eng" } },
+                { "change": "entityRemoved", "entity": { "uid": { "type": "User", "id": "bob" }, "attrs": { "name": "Bob" }, "parents": [{ "type": "Group", "id": "eng" }] } },
+            ])
+        );
+    }
 }
@@ -23,13 +23,14 @@ pub use cedar_policy::ffi;
 use cedar_policy_core::ast::{self, Expr, PolicySet, Request, Value};
 use cedar_policy_core::authorizer::Authorizer;
 use cedar_policy_core::entities::{Entities, TCComputation};
-use cedar_policy_core::evaluator::Evaluator;
+use cedar_policy_core::evaluator::{CoverageReport, Evaluator};
 use cedar_policy_core::extensions::Extensions;
 use cedar_policy_validator::{ValidationMode, Validator, ValidatorSchema};
 use core::panic;
 use miette::miette;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
 use std::time::{Duration, Instant};
 
 /// Return type for `CedarTestImplementation` methods
@@ -237,12 +238,33 @@ pub enum ValidationComparisonMode {
 
 /// Basic struct to support implementing the `CedarTestImplementation` trait
 #[derive(Debug, Default)]
-pub struct RustEngine {}
+pub struct RustEngine {
+    /// Coverage of the policies evaluated by `is_authorized`, if enabled.
+    /// A `Mutex` rather than a `RefCell`, so that a `RustEngine` can be
+    /// shared between threads.
+    coverage: Option<Mutex<CoverageReport>>,
+}
 
 impl RustEngine {
     /// Create a new `RustEngine`
     pub fn new() -> Self {
-        Self {}
+        Self::default()
+    }
+
+    /// Create a new `RustEngine` which records the coverage of the policies
+    /// evaluated by `is_authorized`
+    pub fn with_coverage() -> Self {
+        Self {
+            coverage: Some(Mutex::new(CoverageReport::new())),
+        }
+    }
+
+    /// Take the coverage recorded so far. Returns `None` if coverage is not
+    /// enabled.
+    pub fn take_coverage(&self) -> Option<CoverageReport> {
+        self.coverage.as_ref().map(|coverage| {
+            std::mem::take(&mut *coverage.lock().unwrap_or_else(PoisonError::into_inner))
+        })
     }
 }
 
@@ -266,8 +288,15 @@ impl CedarTestImplementation for RustEngine {
         entities: &Entities,
     ) -> TestResult<TestResponse> {
         let authorizer = Authorizer::new();
-        let (response, duration) =
-            time_function(|| authorizer.is_authorized(request.clone(), policies, entities));
+        let (response, duration) = time_function(|| match &self.coverage {
+            Some(coverage) => authorizer.is_authorized_with_coverage(
+                request.clone(),
+                policies,
+                entities,
+                &mut coverage.lock().unwrap_or_else(PoisonError::into_inner),
+            ),
+            None => authorizer.is_authorized(request.clone(), policies, entities),
+        });
         let response = cedar_policy::Response::from(response);
         let response = ffi::Response::new(
             response.decision(),
@@ -402,3 +431,14 @@ impl CedarTestImplementation for RustEngine {
         ValidationComparisonMode::AgreeOnAll
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rust_engine_is_sync() {
+        fn assert_sync<T: Sync + Send>() {}
+        assert_sync::<RustEngine>();
+    }
+}
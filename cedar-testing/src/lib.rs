@@ -16,3 +16,5 @@
 
 pub mod cedar_test_impl;
 pub mod integration_testing;
+pub mod mutation;
+pub mod test_suite;
@@ -0,0 +1,741 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Mutation testing for policy test suites.
+//!
+//! A mutant is a copy of the policy set in which one policy was changed in a
+//! small way that changes its meaning, for instance by turning a `permit`
+//! into a `forbid` or by dropping one of its `when` clauses. Running a test
+//! suite against every mutant measures how thoroughly the suite tests the
+//! policies: a mutant is killed when some test case of the suite fails
+//! against it, and a mutant which survives points at behavior of the
+//! policies that no test case checks.
+//!
+//! Only static policies are mutated. Templates, and the policies linked from
+//! them, are left unchanged.
+
+use crate::cedar_test_impl::CedarTestImplementation;
+use crate::test_suite::{LoadedTestSuite, TestOutcome, TestSuiteError, TestSuiteReport};
+use cedar_policy_core::ast::{
+    self, ActionConstraint, BinaryOp, Effect, Expr, ExprKind, PolicyID, PolicySet,
+    PrincipalConstraint, PrincipalOrResourceConstraint, ResourceConstraint, StaticPolicy, Template,
+    UnaryOp,
+};
+use cedar_policy_core::expr_builder::ExprBuilder as _;
+use cedar_policy_core::parser::Loc;
+use std::fmt::{self, Display};
+use std::path::Path;
+use std::sync::Arc;
+
+/// The kinds of change made to a policy to produce a mutant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MutationOperator {
+    /// Turn a `permit` into a `forbid` or vice versa
+    FlipEffect,
+    /// Remove the principal, action or resource constraint of the scope
+    RemoveScopeConstraint,
+    /// Remove a `when` or `unless` clause
+    DropClause,
+    /// Negate the condition of a `when` or `unless` clause
+    NegateClause,
+    /// Replace an `in` by `==`, in the scope or in a condition
+    InToEq,
+    /// Replace `<` by `<=` or vice versa. Since `>` and `>=` are defined in
+    /// terms of `<=` and `<`, this also replaces `>` by `>=` and vice versa.
+    ChangeComparison,
+}
+
+impl Display for MutationOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::FlipEffect => "flip effect",
+                Self::RemoveScopeConstraint => "remove scope constraint",
+                Self::DropClause => "drop clause",
+                Self::NegateClause => "negate clause",
+                Self::InToEq => "replace `in` by `==`",
+                Self::ChangeComparison => "change comparison",
+            }
+        )
+    }
+}
+
+/// A policy set in which one policy was mutated
+#[derive(Debug, Clone)]
+pub struct Mutant {
+    /// The mutated policy
+    pub policy_id: PolicyID,
+    /// The kind of change made to the policy
+    pub operator: MutationOperator,
+    /// Human-readable description of the change
+    pub description: String,
+    /// Source location of the changed part of the policy, if available
+    pub loc: Option<Loc>,
+    /// The policy set containing the mutated policy
+    pub policies: PolicySet,
+}
+
+impl Display for Mutant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_mutation(f, &self.policy_id, self.loc.as_ref(), &self.description)
+    }
+}
+
+/// Generate all mutants of `policies`, ordered by policy id.
+pub fn mutants(policies: &PolicySet) -> Vec<Mutant> {
+    let mut statics = policies.static_policies().collect::<Vec<_>>();
+    statics.sort_by_key(|p| p.id());
+    statics
+        .into_iter()
+        .flat_map(|p| policy_mutations(p.template()))
+        .filter_map(|mutation| {
+            let mut policies = policies.clone();
+            policies.remove_static(mutation.policy.id()).ok()?;
+            let policy_id = mutation.policy.id().clone();
+            policies.add_static(mutation.policy).ok()?;
+            Some(Mutant {
+                policy_id,
+                operator: mutation.operator,
+                description: mutation.description,
+                loc: mutation.loc,
+                policies,
+            })
+        })
+        .collect()
+}
+
+/// Whether a test suite detected a mutant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MutantStatus {
+    /// At least one test case failed against the mutant. Holds the name of
+    /// the first such test case.
+    Killed(String),
+    /// Every test case passed against the mutant
+    Survived,
+}
+
+/// The result of running a test suite against a mutant
+#[derive(Debug, Clone)]
+pub struct MutantReport {
+    /// The mutant, without its policy set
+    pub policy_id: PolicyID,
+    /// The kind of change made to the policy
+    pub operator: MutationOperator,
+    /// Human-readable description of the change
+    pub description: String,
+    /// Source location of the changed part of the policy, if available
+    pub loc: Option<Loc>,
+    /// Whether the mutant was killed
+    pub status: MutantStatus,
+}
+
+impl Display for MutantReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_mutation(f, &self.policy_id, self.loc.as_ref(), &self.description)
+    }
+}
+
+/// The result of mutation testing one test suite
+#[derive(Debug, Clone)]
+pub struct MutationReport {
+    /// Name of the test suite
+    pub name: String,
+    /// The result of running the suite against the unmutated policies.
+    /// Mutants are only run when every test case passes.
+    pub baseline: TestSuiteReport,
+    /// The result of running the suite against each mutant
+    pub mutants: Vec<MutantReport>,
+}
+
+impl MutationReport {
+    /// Number of mutants killed by the test suite
+    pub fn killed(&self) -> usize {
+        self.mutants.len() - self.survived().count()
+    }
+
+    /// The mutants no test case detected
+    pub fn survived(&self) -> impl Iterator<Item = &MutantReport> {
+        self.mutants
+            .iter()
+            .filter(|m| m.status == MutantStatus::Survived)
+    }
+
+    /// Percentage of the mutants killed by the test suite, or `None` if there
+    /// are no mutants
+    pub fn score(&self) -> Option<f64> {
+        #[allow(clippy::cast_precision_loss)]
+        (!self.mutants.is_empty()).then(|| self.killed() as f64 * 100.0 / self.mutants.len() as f64)
+    }
+
+    /// Did the test suite pass against the unmutated policies and kill every
+    /// mutant?
+    pub fn success(&self) -> bool {
+        self.baseline.success() && self.survived().next().is_none()
+    }
+}
+
+impl Display for MutationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.baseline.success() {
+            writeln!(
+                f,
+                "mutation testing `{}`: the test suite fails against the unmutated policies",
+                self.name
+            )?;
+            return write!(f, "{}", self.baseline);
+        }
+        write!(
+            f,
+            "mutation testing `{}`: {} mutants, {} killed, {} survived",
+            self.name,
+            self.mutants.len(),
+            self.killed(),
+            self.mutants.len() - self.killed()
+        )?;
+        if let Some(score) = self.score() {
+            write!(f, " (score {score:.1}%)")?;
+        }
+        for mutant in self.survived() {
+            write!(f, "\n  SURVIVED {mutant}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Load the test suite in `path`, then run its test cases against every
+/// mutant of the policies it names.
+///
+/// Returns an error only if the suite, or the policies, schema or entities it
+/// names, cannot be loaded.
+pub fn run_mutation_testing(
+    path: impl AsRef<Path>,
+    test_impl: &impl CedarTestImplementation,
+) -> Result<MutationReport, TestSuiteError> {
+    let loaded = LoadedTestSuite::load(path.as_ref())?;
+    let baseline = loaded.run(&loaded.policies, test_impl);
+    let mutants = if baseline.success() {
+        mutants(&loaded.policies)
+            .into_iter()
+            .map(|mutant| {
+                let report = loaded.run(&mutant.policies, test_impl);
+                let status = report
+                    .cases
+                    .into_iter()
+                    .find(|case| !matches!(case.outcome, TestOutcome::Passed))
+                    .map_or(MutantStatus::Survived, |case| {
+                        MutantStatus::Killed(case.name)
+                    });
+                MutantReport {
+                    policy_id: mutant.policy_id,
+                    operator: mutant.operator,
+                    description: mutant.description,
+                    loc: mutant.loc,
+                    status,
+                }
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Ok(MutationReport {
+        name: loaded.name,
+        baseline,
+        mutants,
+    })
+}
+
+/// A mutated version of a single policy
+struct PolicyMutation {
+    policy: StaticPolicy,
+    operator: MutationOperator,
+    description: String,
+    loc: Option<Loc>,
+}
+
+/// The parts of a policy a mutation may change
+#[derive(Clone)]
+struct PolicyParts {
+    effect: Effect,
+    principal: PrincipalConstraint,
+    action: ActionConstraint,
+    resource: ResourceConstraint,
+    condition: Expr,
+}
+
+impl PolicyParts {
+    fn of(t: &Template) -> Self {
+        Self {
+            effect: t.effect(),
+            principal: t.principal_constraint().clone(),
+            action: t.action_constraint().clone(),
+            resource: t.resource_constraint().clone(),
+            condition: t.non_scope_constraints().clone(),
+        }
+    }
+
+    /// Build a static policy with the id, annotations and location of `t`
+    fn build(self, t: &Template) -> Option<StaticPolicy> {
+        StaticPolicy::new(
+            t.id().clone(),
+            t.loc().cloned(),
+            t.annotations_arc().as_ref().clone(),
+            self.effect,
+            self.principal,
+            self.action,
+            self.resource,
+            self.condition,
+        )
+        .ok()
+    }
+}
+
+/// All mutations of the static policy `t`
+fn policy_mutations(t: &Template) -> Vec<PolicyMutation> {
+    let parts = PolicyParts::of(t);
+    let mut mutations = Vec::new();
+    let mut push = |operator, description: String, loc: Option<&Loc>, parts: PolicyParts| {
+        if let Some(policy) = parts.build(t) {
+            mutations.push(PolicyMutation {
+                policy,
+                operator,
+                description,
+                loc: loc.cloned(),
+            });
+        }
+    };
+
+    let flipped = match parts.effect {
+        Effect::Permit => Effect::Forbid,
+        Effect::Forbid => Effect::Permit,
+    };
+    push(
+        MutationOperator::FlipEffect,
+        format!("changed `{}` to `{flipped}`", parts.effect),
+        t.loc(),
+        PolicyParts {
+            effect: flipped,
+            ..parts.clone()
+        },
+    );
+
+    // scope constraints have no source locations of their own
+    if parts.principal.as_inner() != &PrincipalOrResourceConstraint::Any {
+        push(
+            MutationOperator::RemoveScopeConstraint,
+            format!("removed the constraint `{}`", parts.principal.as_expr()),
+            t.loc(),
+            PolicyParts {
+                principal: PrincipalConstraint::any(),
+                ..parts.clone()
+            },
+        );
+    }
+    if parts.action != ActionConstraint::Any {
+        push(
+            MutationOperator::RemoveScopeConstraint,
+            format!("removed the constraint `{}`", parts.action.as_expr()),
+            t.loc(),
+            PolicyParts {
+                action: ActionConstraint::any(),
+                ..parts.clone()
+            },
+        );
+    }
+    if parts.resource.as_inner() != &PrincipalOrResourceConstraint::Any {
+        push(
+            MutationOperator::RemoveScopeConstraint,
+            format!("removed the constraint `{}`", parts.resource.as_expr()),
+            t.loc(),
+            PolicyParts {
+                resource: ResourceConstraint::any(),
+                ..parts.clone()
+            },
+        );
+    }
+    if let PrincipalOrResourceConstraint::In(e) = parts.principal.as_inner() {
+        let principal = PrincipalConstraint::new(PrincipalOrResourceConstraint::Eq(e.clone()));
+        push(
+            MutationOperator::InToEq,
+            format!(
+                "changed `{}` to `{}`",
+                parts.principal.as_expr(),
+                principal.as_expr()
+            ),
+            t.loc(),
+            PolicyParts {
+                principal,
+                ..parts.clone()
+            },
+        );
+    }
+    if let ActionConstraint::In(euids) = &parts.action {
+        if let [euid] = euids.as_slice() {
+            let action = ActionConstraint::Eq(euid.clone());
+            push(
+                MutationOperator::InToEq,
+                format!(
+                    "changed `{}` to `{}`",
+                    parts.action.as_expr(),
+                    action.as_expr()
+                ),
+                t.loc(),
+                PolicyParts {
+                    action,
+                    ..parts.clone()
+                },
+            );
+        }
+    }
+    if let PrincipalOrResourceConstraint::In(e) = parts.resource.as_inner() {
+        let resource = ResourceConstraint::new(PrincipalOrResourceConstraint::Eq(e.clone()));
+        push(
+            MutationOperator::InToEq,
+            format!(
+                "changed `{}` to `{}`",
+                parts.resource.as_expr(),
+                resource.as_expr()
+            ),
+            t.loc(),
+            PolicyParts {
+                resource,
+                ..parts.clone()
+            },
+        );
+    }
+
+    // Without a source location, the clauses of the condition can't be told
+    // apart from `&&` expressions within a clause
+    let clauses = t
+        .loc()
+        .map(|policy_loc| ast::condition_clauses(&parts.condition, policy_loc))
+        .unwrap_or_default();
+    for (i, clause) in clauses.iter().enumerate() {
+        let others = clauses
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, c)| (*c).clone());
+        push(
+            MutationOperator::DropClause,
+            format!("dropped the condition `{clause}`"),
+            clause.source_loc(),
+            PolicyParts {
+                condition: join_clauses(others, t.loc()),
+                ..parts.clone()
+            },
+        );
+        let negated = match clause.expr_kind() {
+            ExprKind::UnaryApp {
+                op: UnaryOp::Not,
+                arg,
+            } => arg.as_ref().clone(),
+            _ => ast::ExprBuilder::new()
+                .with_same_source_loc(clause)
+                .not((*clause).clone()),
+        };
+        let replaced = clauses.iter().enumerate().map(|(j, c)| {
+            if j == i {
+                negated.clone()
+            } else {
+                (*c).clone()
+            }
+        });
+        push(
+            MutationOperator::NegateClause,
+            format!("negated the condition `{clause}`"),
+            clause.source_loc(),
+            PolicyParts {
+                condition: join_clauses(replaced, t.loc()),
+                ..parts.clone()
+            },
+        );
+    }
+
+    for rewrite in rewrites(&parts.condition, &mutate_operator) {
+        push(
+            rewrite.operator,
+            format!(
+                "changed `{}` to `{}`",
+                rewrite.original, rewrite.replacement
+            ),
+            rewrite.original.source_loc(),
+            PolicyParts {
+                condition: rewrite.result,
+                ..parts.clone()
+            },
+        );
+    }
+    mutations
+}
+
+/// Join clauses the way the parser does
+fn join_clauses(mut clauses: impl Iterator<Item = Expr>, policy_loc: Option<&Loc>) -> Expr {
+    let builder = ast::ExprBuilder::new().with_maybe_source_loc(policy_loc);
+    match clauses.next() {
+        Some(first) => builder.and_nary(first, clauses),
+        None => builder.val(true),
+    }
+}
+
+/// Mutation of a single operator node
+fn mutate_operator(e: &Expr) -> Option<(MutationOperator, Expr)> {
+    let ExprKind::BinaryApp { op, arg1, arg2 } = e.expr_kind() else {
+        return None;
+    };
+    let (operator, op) = match op {
+        BinaryOp::In => (MutationOperator::InToEq, BinaryOp::Eq),
+        BinaryOp::Less => (MutationOperator::ChangeComparison, BinaryOp::LessEq),
+        BinaryOp::LessEq => (MutationOperator::ChangeComparison, BinaryOp::Less),
+        _ => return None,
+    };
+    Some((
+        operator,
+        ast::ExprBuilder::new().with_same_source_loc(e).binary_app(
+            op,
+            arg1.as_ref().clone(),
+            arg2.as_ref().clone(),
+        ),
+    ))
+}
+
+/// An expression in which a single subexpression was replaced
+struct Rewrite {
+    operator: MutationOperator,
+    /// The replaced subexpression
+    original: Expr,
+    /// What it was replaced by
+    replacement: Expr,
+    /// The whole expression after the replacement
+    result: Expr,
+}
+
+/// All the ways of rewriting exactly one subexpression of `e` with `f`
+fn rewrites(e: &Expr, f: &impl Fn(&Expr) -> Option<(MutationOperator, Expr)>) -> Vec<Rewrite> {
+    let mut result = Vec::new();
+    if let Some((operator, replacement)) = f(e) {
+        result.push(Rewrite {
+            operator,
+            original: e.clone(),
+            replacement: replacement.clone(),
+            result: replacement,
+        });
+    }
+    let children = children(e);
+    for (i, child) in children.iter().enumerate() {
+        for rewrite in rewrites(child, f) {
+            let new_children = children
+                .iter()
+                .enumerate()
+                .map(|(j, c)| {
+                    if j == i {
+                        rewrite.result.clone()
+                    } else {
+                        (*c).clone()
+                    }
+                })
+                .collect();
+            result.push(Rewrite {
+                result: with_children(e, new_children),
+                ..rewrite
+            });
+        }
+    }
+    result
+}
+
+/// The immediate subexpressions of `e`, in the order `with_children` expects
+fn children(e: &Expr) -> Vec<&Expr> {
+    match e.expr_kind() {
+        ExprKind::If {
+            test_expr,
+            then_expr,
+            else_expr,
+        } => vec![test_expr, then_expr, else_expr],
+        ExprKind::And { left, right } | ExprKind::Or { left, right } => vec![left, right],
+        ExprKind::UnaryApp { arg, .. } => vec![arg],
+        ExprKind::BinaryApp { arg1, arg2, .. } => vec![arg1, arg2],
+        ExprKind::ExtensionFunctionApp { args, .. } => args.iter().collect(),
+        ExprKind::GetAttr { expr, .. }
+        | ExprKind::HasAttr { expr, .. }
+        | ExprKind::Like { expr, .. }
+        | ExprKind::Is { expr, .. } => vec![expr],
+        ExprKind::Set(elems) => elems.iter().collect(),
+        ExprKind::Record(fields) => fields.values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `e` with its immediate subexpressions replaced by `children`
+fn with_children(e: &Expr, children: Vec<Expr>) -> Expr {
+    let builder = ast::ExprBuilder::new().with_same_source_loc(e);
+    let mut children = children.into_iter();
+    // PANIC SAFETY: `children` has an element for each subexpression of `e`
+    #[allow(clippy::unwrap_used)]
+    let mut next = || children.next().unwrap();
+    match e.expr_kind() {
+        ExprKind::If { .. } => {
+            let (test_expr, then_expr, else_expr) = (next(), next(), next());
+            builder.ite(test_expr, then_expr, else_expr)
+        }
+        ExprKind::And { .. } => {
+            let (left, right) = (next(), next());
+            builder.and(left, right)
+        }
+        ExprKind::Or { .. } => {
+            let (left, right) = (next(), next());
+            builder.or(left, right)
+        }
+        ExprKind::UnaryApp { op, .. } => builder.unary_app(*op, next()),
+        ExprKind::BinaryApp { op, .. } => {
+            let (arg1, arg2) = (next(), next());
+            builder.binary_app(*op, arg1, arg2)
+        }
+        ExprKind::ExtensionFunctionApp { fn_name, args } => {
+            let args = args.iter().map(|_| next()).collect::<Vec<_>>();
+            builder.call_extension_fn(fn_name.clone(), args)
+        }
+        ExprKind::GetAttr { attr, .. } => builder.get_attr(next(), attr.clone()),
+        ExprKind::HasAttr { attr, .. } => builder.has_attr(next(), attr.clone()),
+        ExprKind::Like { pattern, .. } => builder.like(next(), pattern.clone()),
+        ExprKind::Is { entity_type, .. } => builder.is_entity_type(next(), entity_type.clone()),
+        ExprKind::Set(elems) => {
+            let elems = elems.iter().map(|_| next()).collect::<Vec<_>>();
+            builder.set(elems)
+        }
+        ExprKind::Record(fields) => builder.record_arc(Arc::new(
+            fields.keys().map(|k| (k.clone(), next())).collect(),
+        )),
+        _ => e.clone(),
+    }
+}
+
+fn fmt_mutation(
+    f: &mut fmt::Formatter<'_>,
+    policy_id: &PolicyID,
+    loc: Option<&Loc>,
+    description: &str,
+) -> fmt::Result {
+    write!(f, "policy `{policy_id}`")?;
+    if let Some(line) = loc.map(line_of) {
+        write!(f, " (line {line})")?;
+    }
+    write!(f, ": {description}")
+}
+
+/// 1-based line number on which `loc` starts
+fn line_of(loc: &Loc) -> usize {
+    loc.src
+        .get(..loc.start())
+        .map_or(0, |s| s.matches('\n').count())
+        + 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cedar_policy_core::parser::parse_policyset;
+
+    fn describe(policies: &str) -> Vec<String> {
+        mutants(&parse_policyset(policies).unwrap())
+            .into_iter()
+            .map(|m| format!("{}: {m}", m.operator))
+            .collect()
+    }
+
+    #[test]
+    fn scope_mutants() {
+        assert_eq!(
+            describe(
+                r#"permit(principal in Group::"staff", action in [Action::"view"], resource == Doc::"a");"#
+            ),
+            [
+                "flip effect: policy `policy0` (line 1): changed `permit` to `forbid`",
+                r#"remove scope constraint: policy `policy0` (line 1): removed the constraint `principal in Group::"staff"`"#,
+                r#"remove scope constraint: policy `policy0` (line 1): removed the constraint `action in [Action::"view"]`"#,
+                r#"remove scope constraint: policy `policy0` (line 1): removed the constraint `resource == Doc::"a"`"#,
+                r#"replace `in` by `==`: policy `policy0` (line 1): changed `principal in Group::"staff"` to `principal == Group::"staff"`"#,
+                r#"replace `in` by `==`: policy `policy0` (line 1): changed `action in [Action::"view"]` to `action == Action::"view"`"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn condition_mutants() {
+        assert_eq!(
+            describe(
+                "forbid(principal, action, resource)\nwhen { principal.level < 3 }\nunless { principal in resource.owners };"
+            ),
+            [
+                "flip effect: policy `policy0` (line 1): changed `forbid` to `permit`",
+                "drop clause: policy `policy0` (line 2): dropped the condition `(principal[\"level\"]) < 3`",
+                "negate clause: policy `policy0` (line 2): negated the condition `(principal[\"level\"]) < 3`",
+                "drop clause: policy `policy0` (line 3): dropped the condition `!(principal in (resource[\"owners\"]))`",
+                "negate clause: policy `policy0` (line 3): negated the condition `!(principal in (resource[\"owners\"]))`",
+                "change comparison: policy `policy0` (line 2): changed `(principal[\"level\"]) < 3` to `(principal[\"level\"]) <= 3`",
+                "replace `in` by `==`: policy `policy0` (line 3): changed `principal in (resource[\"owners\"])` to `principal == (resource[\"owners\"])`",
+            ]
+        );
+    }
+
+    #[test]
+    fn unlocated_clauses_are_not_mutated() {
+        let context = |attr: &str| Expr::get_attr(Expr::var(ast::Var::Context), attr.into());
+        let policy = ast::Policy::from_when_clause(
+            Effect::Permit,
+            Expr::and(context("a"), context("b")),
+            PolicyID::from_string("unlocated"),
+            None,
+        );
+        let mut policies = PolicySet::new();
+        policies.add(policy).unwrap();
+        let operators = mutants(&policies)
+            .into_iter()
+            .map(|m| m.operator)
+            .collect::<Vec<_>>();
+        assert_eq!(operators, [MutationOperator::FlipEffect]);
+    }
+
+    #[test]
+    fn mutated_policies() {
+        let policies = parse_policyset(
+            "permit(principal, action, resource) when { context.a } when { context.b && 1 <= 2 };",
+        )
+        .unwrap();
+        let conditions = mutants(&policies)
+            .into_iter()
+            .map(|m| {
+                m.policies
+                    .get(&PolicyID::from_string("policy0"))
+                    .unwrap()
+                    .non_scope_constraints()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            conditions,
+            [
+                "(context[\"a\"]) && ((context[\"b\"]) && (1 <= 2))",
+                "(context[\"b\"]) && (1 <= 2)",
+                "(!(context[\"a\"])) && ((context[\"b\"]) && (1 <= 2))",
+                "context[\"a\"]",
+                "(context[\"a\"]) && (!((context[\"b\"]) && (1 <= 2)))",
+                "(context[\"a\"]) && ((context[\"b\"]) && (1 < 2))",
+            ]
+        );
+    }
+}
@@ -0,0 +1,750 @@
+/*
+ * Copyright Cedar Contributors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Runner for policy test suites.
+//!
+//! A test suite is a JSON file naming a policy set, a schema and an entities
+//! file, along with a list of requests and the decisions expected for them.
+//! Unlike [`crate::integration_testing`], the runner does not panic when a
+//! test fails: it collects the outcome of every test case in a
+//! [`TestSuiteReport`], which can be rendered for humans (via `Display`) or as
+//! JUnit XML (via [`junit_xml`]).
+//!
+//! Policies are identified by their `@id` annotation if they have one, and by
+//! their position in the policy file (`policy0`, `policy1`, ...) otherwise.
+//!
+//! Example suite file:
+//!
+//! ```json
+//! {
+//!     "name": "photo sharing",
+//!     "policies": "policies.cedar",
+//!     "schema": "schema.cedarschema",
+//!     "entities": "entities.json",
+//!     "shouldValidate": true,
+//!     "tests": [
+//!         {
+//!             "description": "alice can view the photo",
+//!             "principal": { "type": "User", "id": "alice" },
+//!             "action": { "type": "Action", "id": "view" },
+//!             "resource": { "type": "Photo", "id": "vacation.jpg" },
+//!             "decision": "allow",
+//!             "reason": ["policy0"]
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! File names are relative to the directory containing the suite file. A
+//! schema file whose name ends in `.json` is parsed as a JSON schema, any
+//! other schema file is parsed as a Cedar schema.
+
+use crate::cedar_test_impl::*;
+use cedar_policy::{Decision, PolicyId, ValidationMode};
+use cedar_policy_core::ast::{EntityUID, PolicySet, Request};
+use cedar_policy_core::entities::{self, json::err::JsonDeserializationErrorContext, Entities};
+use cedar_policy_core::extensions::Extensions;
+use cedar_policy_core::jsonvalue::JsonValueWithNoDuplicateKeys;
+use cedar_policy_core::xml::xml_escape;
+use cedar_policy_validator::ValidatorSchema;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// JSON representation of a policy test suite
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonTestSuite {
+    /// Name of the suite. Defaults to the name of the suite file.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Filename of the policy set (in Cedar syntax)
+    pub policies: String,
+    /// Filename of the schema (in Cedar syntax, or in JSON syntax if the
+    /// filename ends in `.json`)
+    pub schema: String,
+    /// Filename of a JSON file representing the entity hierarchy
+    pub entities: String,
+    /// If present, whether the policies are expected to pass the validator
+    /// with this schema. Checked as an additional test case.
+    #[serde(default)]
+    pub should_validate: Option<bool>,
+    /// Requests to perform, along with their expected results
+    pub tests: Vec<JsonTestCase>,
+}
+
+/// JSON representation of a single test case in a [`JsonTestSuite`]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonTestCase {
+    /// Description for the test case, used as its name in reports
+    pub description: String,
+    /// Principal for the request, in either explicit or implicit `__entity` form
+    pub principal: JsonValueWithNoDuplicateKeys,
+    /// Action for the request, in either explicit or implicit `__entity` form
+    pub action: JsonValueWithNoDuplicateKeys,
+    /// Resource for the request, in either explicit or implicit `__entity` form
+    pub resource: JsonValueWithNoDuplicateKeys,
+    /// Context for the request. Defaults to the empty record.
+    #[serde(default)]
+    pub context: Option<JsonValueWithNoDuplicateKeys>,
+    /// Whether to validate the request against the schema
+    #[serde(default = "constant_true")]
+    pub validate_request: bool,
+    /// Expected decision for the request
+    pub decision: Decision,
+    /// If present, the policies expected to determine the decision
+    #[serde(default)]
+    pub reason: Option<Vec<PolicyId>>,
+    /// If present, the policies expected to produce errors
+    #[serde(default)]
+    pub errors: Option<Vec<PolicyId>>,
+}
+
+fn constant_true() -> bool {
+    true
+}
+
+/// Errors preventing a test suite from running at all
+#[derive(Debug, Diagnostic, Error)]
+pub enum TestSuiteError {
+    /// A file could not be read
+    #[error("failed to read `{}`: {source}", path.display())]
+    Io {
+        /// The file which could not be read
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// A file could not be parsed
+    #[error("failed to parse `{}`: {message}", path.display())]
+    Parse {
+        /// The file which could not be parsed
+        path: PathBuf,
+        /// Description of the parse error
+        message: String,
+    },
+}
+
+/// The outcome of a single test case
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TestOutcome {
+    /// The response matched all expectations
+    Passed,
+    /// The response did not match some expectations, described by the
+    /// contained messages
+    Failed(Vec<String>),
+    /// The test case could not be run, e.g., because its request is invalid
+    Errored(String),
+}
+
+/// Result of running a single test case
+#[derive(Debug, Clone)]
+pub struct TestCaseReport {
+    /// Name of the test case
+    pub name: String,
+    /// Outcome of the test case
+    pub outcome: TestOutcome,
+    /// Time taken to run the test case
+    pub duration: Duration,
+}
+
+/// Result of running a test suite
+#[derive(Debug, Clone)]
+pub struct TestSuiteReport {
+    /// Name of the suite
+    pub name: String,
+    /// The policies file of the suite
+    pub policies: PathBuf,
+    /// Results of the test cases, in the order they appear in the suite
+    pub cases: Vec<TestCaseReport>,
+}
+
+impl TestSuiteReport {
+    /// Number of test cases which passed
+    pub fn passed(&self) -> usize {
+        self.count(|o| matches!(o, TestOutcome::Passed))
+    }
+
+    /// Number of test cases which failed
+    pub fn failed(&self) -> usize {
+        self.count(|o| matches!(o, TestOutcome::Failed(_)))
+    }
+
+    /// Number of test cases which could not be run
+    pub fn errored(&self) -> usize {
+        self.count(|o| matches!(o, TestOutcome::Errored(_)))
+    }
+
+    /// Did every test case pass?
+    pub fn success(&self) -> bool {
+        self.passed() == self.cases.len()
+    }
+
+    fn count(&self, f: impl Fn(&TestOutcome) -> bool) -> usize {
+        self.cases.iter().filter(|c| f(&c.outcome)).count()
+    }
+
+    fn duration(&self) -> Duration {
+        self.cases.iter().map(|c| c.duration).sum()
+    }
+}
+
+impl Display for TestSuiteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "suite {}", self.name)?;
+        for case in &self.cases {
+            match &case.outcome {
+                TestOutcome::Passed => writeln!(f, "  PASS  {}", case.name)?,
+                TestOutcome::Failed(msgs) => {
+                    writeln!(f, "  FAIL  {}", case.name)?;
+                    for msg in msgs {
+                        writeln!(f, "        {msg}")?;
+                    }
+                }
+                TestOutcome::Errored(msg) => {
+                    writeln!(f, "  ERROR {}", case.name)?;
+                    writeln!(f, "        {msg}")?;
+                }
+            }
+        }
+        write!(
+            f,
+            "{} passed, {} failed, {} errored",
+            self.passed(),
+            self.failed(),
+            self.errored()
+        )
+    }
+}
+
+/// Render `reports` as a JUnit XML document, with one `testsuite` element
+/// per report
+pub fn junit_xml(reports: &[TestSuiteReport]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let tests: usize = reports.iter().map(|r| r.cases.len()).sum();
+    let failures: usize = reports.iter().map(TestSuiteReport::failed).sum();
+    let errors: usize = reports.iter().map(TestSuiteReport::errored).sum();
+    let time: Duration = reports.iter().map(TestSuiteReport::duration).sum();
+    // `write!` to a `String` cannot fail
+    let _ = writeln!(
+        out,
+        "<testsuites tests=\"{tests}\" failures=\"{failures}\" errors=\"{errors}\" time=\"{:.6}\">",
+        time.as_secs_f64()
+    );
+    for report in reports {
+        let _ = writeln!(
+            out,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.6}\">",
+            xml_escape(&report.name),
+            report.cases.len(),
+            report.failed(),
+            report.errored(),
+            report.duration().as_secs_f64()
+        );
+        for case in &report.cases {
+            let _ = write!(
+                out,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.6}\"",
+                xml_escape(&case.name),
+                xml_escape(&report.name),
+                case.duration.as_secs_f64()
+            );
+            match &case.outcome {
+                TestOutcome::Passed => out.push_str("/>\n"),
+                TestOutcome::Failed(msgs) => {
+                    let _ = writeln!(
+                        out,
+                        ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                        xml_escape(msgs.first().map_or("", String::as_str)),
+                        xml_escape(&msgs.join("\n"))
+                    );
+                }
+                TestOutcome::Errored(msg) => {
+                    let _ = writeln!(
+                        out,
+                        ">\n      <error message=\"{}\"/>\n    </testcase>",
+                        xml_escape(msg)
+                    );
+                }
+            }
+        }
+        out.push_str("  </testsuite>\n");
+    }
+    out.push_str("</testsuites>\n");
+    out
+}
+
+/// Load the test suite in `path` and run its test cases against `test_impl`.
+///
+/// Returns an error only if the suite, or the policies, schema or entities it
+/// names, cannot be loaded. Failing test cases are recorded in the report.
+pub fn run_test_suite(
+    path: impl AsRef<Path>,
+    test_impl: &impl CedarTestImplementation,
+) -> Result<TestSuiteReport, TestSuiteError> {
+    let loaded = LoadedTestSuite::load(path.as_ref())?;
+    Ok(loaded.run(&loaded.policies, test_impl))
+}
+
+/// Run the test cases of `suite` against already loaded policies, schema and
+/// entities. The policies file in the report is the one named in `suite`.
+pub fn run_loaded_test_suite(
+    name: String,
+    suite: &JsonTestSuite,
+    policies: &PolicySet,
+    schema: &ValidatorSchema,
+    entities: &Entities,
+    test_impl: &impl CedarTestImplementation,
+) -> TestSuiteReport {
+    let mut cases = Vec::with_capacity(suite.tests.len() + 1);
+    if let Some(should_validate) = suite.should_validate {
+        let start = Instant::now();
+        let outcome = check_validation(policies, schema, should_validate, test_impl);
+        cases.push(TestCaseReport {
+            name: "policies validate against the schema".into(),
+            outcome,
+            duration: start.elapsed(),
+        });
+    }
+    for case in &suite.tests {
+        let start = Instant::now();
+        let outcome = match parse_request(case, schema) {
+            Ok(request) => match test_impl.is_authorized(&request, policies, entities) {
+                TestResult::Success(response) => {
+                    check_response(&response, case, &test_impl.error_comparison_mode())
+                }
+                TestResult::Failure(e) => TestOutcome::Errored(e),
+            },
+            Err(e) => TestOutcome::Errored(e),
+        };
+        cases.push(TestCaseReport {
+            name: case.description.clone(),
+            outcome,
+            duration: start.elapsed(),
+        });
+    }
+    TestSuiteReport {
+        name,
+        policies: PathBuf::from(&suite.policies),
+        cases,
+    }
+}
+
+/// A test suite along with the policies, schema and entities it names
+pub(crate) struct LoadedTestSuite {
+    pub(crate) name: String,
+    pub(crate) suite: JsonTestSuite,
+    /// Path of the policies file, relative to the working directory
+    pub(crate) policies_path: PathBuf,
+    pub(crate) policies: PolicySet,
+    pub(crate) schema: ValidatorSchema,
+    pub(crate) entities: Entities,
+}
+
+impl LoadedTestSuite {
+    /// Load the test suite in `path` and the files it names
+    pub(crate) fn load(path: &Path) -> Result<Self, TestSuiteError> {
+        let suite: JsonTestSuite =
+            serde_json::from_str(&read_file(path)?).map_err(|e| TestSuiteError::Parse {
+                path: path.into(),
+                message: e.to_string(),
+            })?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let policies_path = dir.join(&suite.policies);
+        let policies = load_policies(&policies_path)?;
+        let schema = load_schema(&dir.join(&suite.schema))?;
+        let entities = load_entities(&dir.join(&suite.entities), &schema)?;
+        let name = suite
+            .name
+            .clone()
+            .unwrap_or_else(|| path.display().to_string());
+        Ok(Self {
+            name,
+            suite,
+            policies_path,
+            policies,
+            schema,
+            entities,
+        })
+    }
+
+    /// Run the test cases of the suite against `policies` instead of the
+    /// policies it names
+    pub(crate) fn run(
+        &self,
+        policies: &PolicySet,
+        test_impl: &impl CedarTestImplementation,
+    ) -> TestSuiteReport {
+        TestSuiteReport {
+            policies: self.policies_path.clone(),
+            ..run_loaded_test_suite(
+                self.name.clone(),
+                &self.suite,
+                policies,
+                &self.schema,
+                &self.entities,
+                test_impl,
+            )
+        }
+    }
+}
+
+fn check_validation(
+    policies: &PolicySet,
+    schema: &ValidatorSchema,
+    should_validate: bool,
+    test_impl: &impl CedarTestImplementation,
+) -> TestOutcome {
+    let result = match test_impl.validate(schema, policies, ValidationMode::default().into()) {
+        TestResult::Success(result) => result,
+        TestResult::Failure(e) => return TestOutcome::Errored(e),
+    };
+    if should_validate && !result.validation_passed() {
+        TestOutcome::Failed(
+            std::iter::once("expected policies to validate, but got errors:".to_string())
+                .chain(result.errors)
+                .collect(),
+        )
+    } else if !should_validate
+        && result.validation_passed()
+        && test_impl.validation_comparison_mode() == ValidationComparisonMode::AgreeOnAll
+    {
+        TestOutcome::Failed(vec![
+            "expected policies to fail validation, but they validated".into(),
+        ])
+    } else {
+        TestOutcome::Passed
+    }
+}
+
+fn check_response(
+    response: &TestResponse,
+    case: &JsonTestCase,
+    error_comparison_mode: &ErrorComparisonMode,
+) -> TestOutcome {
+    let mut failures = Vec::new();
+    let decision = response.response.decision();
+    if decision != case.decision {
+        failures.push(format!(
+            "expected decision {}, got {}",
+            decision_str(case.decision),
+            decision_str(decision)
+        ));
+    }
+    if let Some(expected) = &case.reason {
+        let actual = response.response.diagnostics().reason().cloned().collect();
+        check_policy_ids("determining policies", expected, &actual, &mut failures);
+    }
+    // like the integration tests, errors can only be compared by policy id
+    if let (Some(expected), ErrorComparisonMode::PolicyIds) = (&case.errors, error_comparison_mode)
+    {
+        let actual = response
+            .response
+            .diagnostics()
+            .errors()
+            .map(|err| err.policy_id.clone())
+            .collect();
+        check_policy_ids("erroring policies", expected, &actual, &mut failures);
+    }
+    if failures.is_empty() {
+        TestOutcome::Passed
+    } else {
+        TestOutcome::Failed(failures)
+    }
+}
+
+fn check_policy_ids(
+    what: &str,
+    expected: &[PolicyId],
+    actual: &BTreeSet<PolicyId>,
+    failures: &mut Vec<String>,
+) {
+    let expected = expected.iter().cloned().collect::<BTreeSet<_>>();
+    if &expected != actual {
+        failures.push(format!(
+            "expected {what} [{}], got [{}]",
+            join_ids(&expected),
+            join_ids(actual)
+        ));
+    }
+}
+
+fn join_ids(ids: &BTreeSet<PolicyId>) -> String {
+    ids.iter()
+        .map(|id| format!("`{id}`"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn decision_str(decision: Decision) -> &'static str {
+    match decision {
+        Decision::Allow => "allow",
+        Decision::Deny => "deny",
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, TestSuiteError> {
+    std::fs::read_to_string(path).map_err(|source| TestSuiteError::Io {
+        path: path.into(),
+        source,
+    })
+}
+
+/// Build a parse error for `path`, describing `e` along with its sources
+fn parse_error(path: &Path, e: impl std::error::Error) -> TestSuiteError {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(e) = source {
+        let _ = write!(message, ": {e}");
+        source = e.source();
+    }
+    TestSuiteError::Parse {
+        path: path.into(),
+        message,
+    }
+}
+
+/// Parse the policies in `path`, using their `@id` annotations as policy ids
+/// like the CLI does
+fn load_policies(path: &Path) -> Result<PolicySet, TestSuiteError> {
+    let parsed: cedar_policy::PolicySet =
+        read_file(path)?.parse().map_err(|e| parse_error(path, e))?;
+    let mut policies = cedar_policy::PolicySet::new();
+    for template in parsed.templates() {
+        let template = match template.annotation("id") {
+            Some(id) => template.new_id(PolicyId::new(id)),
+            None => template.clone(),
+        };
+        policies
+            .add_template(template)
+            .map_err(|e| parse_error(path, e))?;
+    }
+    for policy in parsed.policies() {
+        let policy = match policy.annotation("id") {
+            Some(id) => policy.new_id(PolicyId::new(id)),
+            None => policy.clone(),
+        };
+        policies.add(policy).map_err(|e| parse_error(path, e))?;
+    }
+    Ok(policies.as_ref().clone())
+}
+
+fn load_schema(path: &Path) -> Result<ValidatorSchema, TestSuiteError> {
+    let text = read_file(path)?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        ValidatorSchema::from_json_str(&text, Extensions::all_available())
+            .map_err(|e| parse_error(path, e))
+    } else {
+        ValidatorSchema::from_cedarschema_str(&text, Extensions::all_available())
+            .map(|(schema, _)| schema)
+            .map_err(|e| parse_error(path, e))
+    }
+}
+
+fn load_entities(path: &Path, schema: &ValidatorSchema) -> Result<Entities, TestSuiteError> {
+    let text = read_file(path)?;
+    let schema = cedar_policy_validator::CoreSchema::new(schema);
+    entities::EntityJsonParser::new(
+        Some(&schema),
+        Extensions::all_available(),
+        entities::TCComputation::ComputeNow,
+    )
+    .from_json_str(&text)
+    .map_err(|e| parse_error(path, e))
+}
+
+fn parse_entity_uid(json: &JsonValueWithNoDuplicateKeys, what: &str) -> Result<EntityUID, String> {
+    let parsed: entities::EntityUidJson = serde_json::from_value(json.clone().into())
+        .map_err(|e| format!("failed to parse {what}: {e}"))?;
+    parsed
+        .into_euid(|| JsonDeserializationErrorContext::EntityUid)
+        .map_err(|e| format!("failed to parse {what}: {e}"))
+}
+
+fn parse_request(case: &JsonTestCase, schema: &ValidatorSchema) -> Result<Request, String> {
+    let principal = parse_entity_uid(&case.principal, "principal")?;
+    let action = parse_entity_uid(&case.action, "action")?;
+    let resource = parse_entity_uid(&case.resource, "resource")?;
+    let context_schema = cedar_policy_validator::context_schema_for_action(schema, &action)
+        .ok_or_else(|| format!("unknown action {action}"))?;
+    let context_json = case
+        .context
+        .clone()
+        .map_or_else(|| serde_json::json!({}), Into::into);
+    let context =
+        entities::ContextJsonParser::new(Some(&context_schema), Extensions::all_available())
+            .from_json_value(context_json)
+            .map_err(|e| format!("failed to parse context: {e}"))?;
+    Request::new(
+        (principal, None),
+        (action, None),
+        (resource, None),
+        context,
+        case.validate_request.then_some(schema),
+        Extensions::all_available(),
+    )
+    .map_err(|e| format!("invalid request: {e}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(tests: &str) -> TestSuiteReport {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("policies.cedar"),
+            r#"
+            @id("view")
+            permit(principal in Group::"friends", action == Action::"view", resource);
+            @id("no-bob")
+            forbid(principal == User::"bob", action, resource);
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("schema.cedarschema"),
+            r#"
+            entity Group;
+            entity User in [Group];
+            entity Photo;
+            action view appliesTo { principal: User, resource: Photo };
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("entities.json"),
+            r#"[
+                { "uid": { "type": "User", "id": "alice" }, "attrs": {}, "parents": [{ "type": "Group", "id": "friends" }] },
+                { "uid": { "type": "User", "id": "bob" }, "attrs": {}, "parents": [{ "type": "Group", "id": "friends" }] }
+            ]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("suite.json"),
+            format!(
+                r#"{{
+                    "name": "photos",
+                    "policies": "policies.cedar",
+                    "schema": "schema.cedarschema",
+                    "entities": "entities.json",
+                    "shouldValidate": true,
+                    "tests": {tests}
+                }}"#
+            ),
+        )
+        .unwrap();
+        run_test_suite(dir.path().join("suite.json"), &RustEngine::new()).unwrap()
+    }
+
+    fn request(description: &str, principal: &str, expected: &str) -> String {
+        format!(
+            r#"{{
+                "description": "{description}",
+                "principal": {{ "type": "User", "id": "{principal}" }},
+                "action": {{ "type": "Action", "id": "view" }},
+                "resource": {{ "type": "Photo", "id": "p" }},
+                {expected}
+            }}"#
+        )
+    }
+
+    #[test]
+    fn passing_suite() {
+        let report = run(&format!(
+            "[{}, {}]",
+            request(
+                "alice",
+                "alice",
+                r#""decision": "allow", "reason": ["view"], "errors": []"#
+            ),
+            request("bob", "bob", r#""decision": "deny", "reason": ["no-bob"]"#),
+        ));
+        assert_eq!(report.name, "photos");
+        assert_eq!(report.cases.len(), 3);
+        assert!(report.success(), "{report}");
+    }
+
+    #[test]
+    fn failing_cases() {
+        let report = run(&format!(
+            "[{}, {}, {}]",
+            request("wrong decision", "alice", r#""decision": "deny""#),
+            request(
+                "wrong reason",
+                "bob",
+                r#""decision": "deny", "reason": ["view"]"#
+            ),
+            request("unknown principal type", "carol", r#""decision": "deny""#)
+                .replace("\"User\"", "\"Admin\""),
+        ));
+        assert_eq!(
+            report.cases[1].outcome,
+            TestOutcome::Failed(vec!["expected decision deny, got allow".into()])
+        );
+        assert_eq!(
+            report.cases[2].outcome,
+            TestOutcome::Failed(vec![
+                "expected determining policies [`view`], got [`no-bob`]".into()
+            ])
+        );
+        assert!(matches!(report.cases[3].outcome, TestOutcome::Errored(_)));
+        assert_eq!(
+            (report.passed(), report.failed(), report.errored()),
+            (1, 2, 1)
+        );
+    }
+
+    #[test]
+    fn junit() {
+        let report = TestSuiteReport {
+            name: "a<b".into(),
+            policies: PathBuf::from("policies.cedar"),
+            cases: vec![
+                TestCaseReport {
+                    name: "ok".into(),
+                    outcome: TestOutcome::Passed,
+                    duration: Duration::ZERO,
+                },
+                TestCaseReport {
+                    name: "\"bad\"".into(),
+                    outcome: TestOutcome::Failed(vec!["expected decision allow, got deny".into()]),
+                    duration: Duration::ZERO,
+                },
+            ],
+        };
+        assert_eq!(
+            junit_xml(&[report]),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="2" failures="1" errors="0" time="0.000000">
+  <testsuite name="a&lt;b" tests="2" failures="1" errors="0" time="0.000000">
+    <testcase name="ok" classname="a&lt;b" time="0.000000"/>
+    <testcase name="&quot;bad&quot;" classname="a&lt;b" time="0.000000">
+      <failure message="expected decision allow, got deny">expected decision allow, got deny</failure>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}